@@ -25,8 +25,12 @@ pub mod echo;
 pub mod ollama;
 pub mod policy;
 pub mod registry;
+pub mod resume;
 pub mod router;
+pub mod structured;
+pub mod vision;
 pub mod worker_llama;
+pub mod worker_openai;
 
 // LUCID M2: the production inference worker. Shells out to `llama-server`,
 // streams tokens back through the protocol, and signs receipts. Exported at
@@ -34,6 +38,10 @@ pub mod worker_llama;
 // required, used by CI) and LlamaCppWorker (production path) via CLI flag.
 pub use worker_llama::{LlamaCppConfig, LlamaCppWorker};
 
+// Proxy worker for operators who already run an OpenAI-compatible server
+// (vLLM, llama-swap, …). Same receipts, no subprocess management.
+pub use worker_openai::{OpenAiUpstreamConfig, OpenAiUpstreamWorker};
+
 // Public re-exports for the M6 model registry. Downstream code (the
 // router in M5, the Ollama `/api/tags` handler in M4) consumes these as
 // `lucidd::ModelRegistry` etc. without having to know about the module
@@ -59,6 +67,6 @@ pub use policy::{
 // `/phase/job-relay/1.0.0`, or refuse.
 pub use dht_transport::PhaseNetDhtTransport;
 pub use router::{
-    make_inbound_relay_handler, ReceiptVerification, RouteDecision, RouteVia, Router, RouterError,
-    RELAY_TIMEOUT,
+    make_inbound_relay_handler, ReceiptVerification, RouteDecision, RouteRequirements, RouteVia,
+    Router, RouterError, RELAY_TIMEOUT,
 };
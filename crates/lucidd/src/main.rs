@@ -10,7 +10,7 @@
 //!    request/response protocol.
 //! 3. `PhaseNetDhtTransport` + `ModelRegistry` for the model index.
 //! 4. `PolicyEngine` for operator-controlled gating.
-//! 5. Optional local `Worker` (Echo or LlamaCpp). With `--no-local-worker`
+//! 5. Optional local `Worker` (Echo, LlamaCpp or an OpenAI upstream). With `--no-local-worker`
 //!    the daemon is consume-only: every request goes to a peer or refuses.
 //! 6. `Router` glues 1–5 and exposes a per-request decision API the
 //!    Ollama HTTP layer wraps.
@@ -22,12 +22,17 @@ use std::sync::Arc;
 use clap::{Parser, ValueEnum};
 use lucidd::echo::EchoWorker;
 use lucidd::ollama::{router as ollama_router, AppState};
-use lucidd::registry::DhtTransport;
+use lucidd::registry::{DhtTransport, ModelAdvertisementValidator, MODEL_KEY_PREFIX};
 use lucidd::router::{make_inbound_relay_handler, Router as LucidRouter};
-use lucidd::{LlamaCppConfig, LlamaCppWorker, ModelRegistry, PhaseNetDhtTransport, PolicyEngine};
+use lucidd::{
+    LlamaCppConfig, LlamaCppWorker, ModelRegistry, OpenAiUpstreamConfig, OpenAiUpstreamWorker,
+    PhaseNetDhtTransport, PolicyEngine,
+};
 use phase_identity::{default_identity_path, NodeIdentity};
-use phase_net::{Discovery, DiscoveryConfig};
-use phase_protocol::DynWorker;
+use phase_net::{
+    Discovery, DiscoveryConfig, NetworkKey, PeerCapabilities, PeerFilter, ValidatorRegistry,
+};
+use phase_protocol::{DynWorker, JobSpecKind};
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 enum WorkerChoice {
@@ -35,6 +40,10 @@ enum WorkerChoice {
     Echo,
     /// LlamaCppWorker, shells out to `llama-server`.
     LlamaCpp,
+    /// OpenAiUpstreamWorker, proxies to an OpenAI-compatible server
+    /// (vLLM, llama-swap, …) at `--upstream-url`.
+    #[value(name = "openai-upstream")]
+    OpenAiUpstream,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
@@ -43,9 +52,10 @@ enum NodeMode {
     /// inbound job-relay handler if --no-local-worker is not set.
     Worker,
     /// Consume-only / relay role: no local worker is loaded, every chat
-    /// request is routed to a peer (or refused). Sets the same internal
-    /// flag as --no-local-worker. Foundation relay-server protocol
-    /// (libp2p `relay::server::Behaviour`, DCUtR, etc.) lands in v0.2.
+    /// request is routed to a peer (or refused), and the node runs a
+    /// libp2p circuit relay v2 server so NATed peers can reserve a slot
+    /// on it. Sets the same internal flag as --no-local-worker. Run it on
+    /// a publicly reachable host, ideally with `--external-addr`.
     Relay,
 }
 
@@ -56,7 +66,7 @@ enum NodeMode {
 )]
 struct Cli {
     /// Run as a worker (default) or as a consume-only relay node.
-    /// `--mode relay` is equivalent to `--no-local-worker`.
+    /// `--mode relay` is `--no-local-worker` plus a circuit relay server.
     #[arg(long, value_enum, default_value_t = NodeMode::Worker)]
     mode: NodeMode,
 
@@ -67,8 +77,7 @@ struct Cli {
 
     /// Run without any local worker — every request gets routed to a
     /// peer over the Phase DHT or refused. Useful on GPU-less laptops
-    /// that still want to be useful clients. Same effect as
-    /// `--mode relay`.
+    /// that still want to be useful clients. `--mode relay` implies it.
     #[arg(long, default_value_t = false)]
     no_local_worker: bool,
 
@@ -91,6 +100,29 @@ struct Cli {
     #[arg(long, default_value_t = 8192)]
     llama_ctx_size: usize,
 
+    /// How long llama-server keeps a model resident after its last
+    /// request when the request sets no `keep_alive`. Ollama syntax:
+    /// `5m`, `1h`, `0` (unload when idle), `-1` (keep until evicted).
+    #[arg(long, default_value = "5m")]
+    llama_keep_alive: String,
+
+    /// Base URL of the OpenAI-compatible API, version prefix included.
+    /// Required when `--worker openai-upstream`. The bearer token, if the
+    /// upstream wants one, is read from `LUCIDD_UPSTREAM_API_KEY` so it
+    /// stays out of `ps` output.
+    #[arg(long, value_name = "URL")]
+    upstream_url: Option<String>,
+
+    /// Model id to serve from the upstream. Repeatable. Default: every
+    /// model the upstream lists at `GET /models` on startup.
+    #[arg(long = "upstream-model", value_name = "MODEL")]
+    upstream_models: Vec<String>,
+
+    /// Context length to advertise for upstream models. The upstream
+    /// enforces its own; this only informs peers choosing a route.
+    #[arg(long, default_value_t = 8192)]
+    upstream_ctx_size: u32,
+
     /// Override the policy config path. Default:
     /// `~/.config/lucidd/policy.toml` (with the platform's XDG / AppSupport
     /// resolution). `lucidd` seeds a fully-commented default if absent.
@@ -133,6 +165,36 @@ struct Cli {
     #[arg(long = "bootstrap-dns", value_name = "DOMAIN")]
     bootstrap_dns: Vec<String>,
 
+    /// Circuit relays to reserve a slot on, so peers can reach this node
+    /// behind NAT via `<relay>/p2p-circuit/p2p/<our-peer-id>`. Repeatable.
+    /// Same format as `--bootstrap-peer`; the `/p2p/<peer-id>` is
+    /// required. Once a relayed connection is up, DCUtR tries to upgrade
+    /// it to a direct one.
+    #[arg(long = "relay", value_name = "MULTIADDR")]
+    relays: Vec<String>,
+
+    /// Publicly reachable multiaddr of this node (e.g. a VPS address or a
+    /// forwarded port), e.g. `/ip4/203.0.113.7/tcp/4001`. Repeatable.
+    /// Otherwise AutoNAT has to confirm one first, and a `--mode relay`
+    /// node hands out no reservations until it does.
+    #[arg(long = "external-addr", value_name = "MULTIADDR")]
+    external_addrs: Vec<String>,
+
+    /// Private-network key file (go-ipfs `swarm.key` format). Only nodes
+    /// holding the same key can connect; QUIC is disabled while it is set.
+    #[arg(long = "network-key", value_name = "PATH")]
+    network_key: Option<PathBuf>,
+
+    /// Only talk to these peer-ids. Repeatable. Bootstrap peers and relays
+    /// must be listed too.
+    #[arg(long = "allow-peer", value_name = "PEER_ID", conflicts_with = "deny_peers")]
+    allow_peers: Vec<String>,
+
+    /// Refuse connections and job requests from these peer-ids.
+    /// Repeatable.
+    #[arg(long = "deny-peer", value_name = "PEER_ID")]
+    deny_peers: Vec<String>,
+
     /// Opt in to falling back to public DNS resolvers (Cloudflare 1.1.1.1
     /// / Google 8.8.8.8) when the system resolver config can't be loaded.
     /// SEC-09: this widens the set of resolvers you trust for bootstrap
@@ -334,8 +396,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
     }
 
-    // --mode relay collapses into --no-local-worker for the v0.1 binary.
-    // The real libp2p relay::server::Behaviour wiring lands in v0.2.
+    // --mode relay serves no local worker; the circuit relay server is
+    // switched on in the DiscoveryConfig below.
     let no_local_worker = cli.no_local_worker || cli.mode == NodeMode::Relay;
 
     // Persistent identity: libp2p peer-id + receipt signing key derive
@@ -373,9 +435,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Build the phase-net discovery layer. mDNS may be denied in
     // restricted CI envs — that's expected; the daemon still serves
     // local requests in that case.
+    //
+    // Model advertisements carry their own signed envelope; the registry
+    // checks it before any peer's PUT lands in our record store.
+    let mut validators = ValidatorRegistry::default();
+    validators.register(MODEL_KEY_PREFIX, Arc::new(ModelAdvertisementValidator));
     let disc_config = DiscoveryConfig {
         identity: Some(node_identity.clone()),
         bootstrap_peers,
+        relay_server: cli.mode == NodeMode::Relay,
+        relays: cli.relays.clone(),
+        external_addrs: cli.external_addrs.clone(),
+        // DHT records and the routing table survive restarts next to the
+        // identity, so the node rejoins via last run's peers.
+        state_dir: Some(phase_net::state_dir_for_identity(&identity_path)),
+        validators,
+        network_key: cli
+            .network_key
+            .as_deref()
+            .map(NetworkKey::load)
+            .transpose()?,
+        peer_filter: PeerFilter::from_lists(&cli.allow_peers, &cli.deny_peers)?,
+        capabilities: PeerCapabilities {
+            supported_kinds: vec![JobSpecKind::Inference],
+            ..PeerCapabilities::default()
+        },
         ..DiscoveryConfig::default()
     };
     let discovery = Arc::new(Discovery::new(disc_config)?);
@@ -399,6 +483,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if let Err(e) = discovery.bootstrap().await {
         tracing::warn!(error = %e, "discovery bootstrap failed (continuing)");
     }
+    // The driver keeps the record fresh from here on, measurements and
+    // in-flight relay count included.
+    if let Err(e) = discovery.advertise_capabilities().await {
+        tracing::warn!(error = %e, "capability advertisement failed (continuing)");
+    }
 
     // Model registry, backed by phase-net's Kademlia DHT.
     let transport: Arc<dyn DhtTransport> =
@@ -486,6 +575,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         let Some(model_id) = path.file_stem().and_then(|s| s.to_str()) else {
                             continue;
                         };
+                        // `<model>.mmproj.gguf` is the vision projector for
+                        // `<model>`, not a model of its own.
+                        if path
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .is_some_and(|n| n.ends_with(lucidd::worker_llama::PROJECTOR_SUFFIX))
+                        {
+                            continue;
+                        }
                         // Deterministic placeholder CID derived from the
                         // name via SHA-256 with domain separation. Two
                         // peers see the same CID for the same model_id, so
@@ -494,7 +592,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         // content-hashed CIDs land in v0.2.
                         let cid = lucidd::ModelCid::from_model_id(model_id);
 
-                        let caps = lucidd::ModelCapabilities::now(
+                        let mut caps = lucidd::ModelCapabilities::now(
                             model_id,
                             cid,
                             "unknown",
@@ -502,6 +600,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             1,
                             "llama.cpp",
                         );
+                        caps.vision = lucidd::worker_llama::resolve_projector_path(
+                            &model_dir, model_id,
+                        )
+                        .is_ok();
                         if let Err(e) = registry.advertise_loaded(caps).await {
                             tracing::warn!(model = %model_id, error = %e, "failed to advertise");
                         } else {
@@ -512,16 +614,67 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     tracing::info!(count = advertised, dir = ?model_dir, "advertised local models");
                 }
 
+                let default_keep_alive =
+                    lucidd::ollama::parse_keep_alive_str(&cli.llama_keep_alive)
+                        .map_err(|e| format!("--llama-keep-alive: {e}"))?;
                 let config = LlamaCppConfig {
                     server_binary_path,
                     model_dir,
                     default_n_gpu_layers: n_gpu_layers,
                     default_context_size: cli.llama_ctx_size,
+                    default_keep_alive,
                     ..Default::default()
                 };
                 tracing::info!(?config, "worker: llama-cpp");
-                let worker_identity = NodeIdentity::generate();
-                Some(Arc::new(LlamaCppWorker::new(worker_identity, config)) as Arc<dyn DynWorker>)
+                // The node identity, so receipts and resumption tokens name
+                // the PeerId peers actually dial.
+                Some(Arc::new(LlamaCppWorker::new(node_identity.clone(), config)) as Arc<dyn DynWorker>)
+            }
+            WorkerChoice::OpenAiUpstream => {
+                let base_url = cli
+                    .upstream_url
+                    .clone()
+                    .ok_or("--upstream-url is required with --worker openai-upstream")?;
+                let config = OpenAiUpstreamConfig {
+                    base_url,
+                    api_key: std::env::var("LUCIDD_UPSTREAM_API_KEY")
+                        .ok()
+                        .filter(|k| !k.is_empty()),
+                    models: cli.upstream_models.clone(),
+                    ..Default::default()
+                };
+                tracing::info!(?config, "worker: openai-upstream");
+                // Receipts are signed with the node identity so relaying
+                // peers can bind them to our PeerId.
+                let worker = OpenAiUpstreamWorker::new(node_identity.clone(), config);
+
+                // Advertise like local GGUFs. An unreachable upstream is
+                // not fatal — it may come up after us — but nothing is
+                // advertised (or served) until the next restart.
+                match worker.list_models().await {
+                    Ok(models) => {
+                        for model_id in &models {
+                            let caps = lucidd::ModelCapabilities::now(
+                                model_id.as_str(),
+                                lucidd::ModelCid::from_model_id(model_id),
+                                "unknown",
+                                cli.upstream_ctx_size,
+                                1,
+                                "openai-upstream",
+                            );
+                            if let Err(e) = registry.advertise_loaded(caps).await {
+                                tracing::warn!(model = %model_id, error = %e, "failed to advertise");
+                            } else {
+                                tracing::info!(model = %model_id, "advertised upstream model");
+                            }
+                        }
+                        tracing::info!(count = models.len(), "advertised upstream models");
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "could not list upstream models; advertising none");
+                    }
+                }
+                Some(Arc::new(worker) as Arc<dyn DynWorker>)
             }
         }
     };
@@ -544,6 +697,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         node_identity.clone(),
         discovery.clone(),
     ));
+    router.watch_peers();
 
     let client_identity = NodeIdentity::generate();
     let state = AppState {
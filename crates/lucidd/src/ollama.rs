@@ -32,12 +32,17 @@ use futures::StreamExt;
 use phase_identity::NodeIdentity;
 use phase_manifest::ManifestBuilder;
 use phase_protocol::{
-    ChatMessage as PhaseChatMessage, ChatRole as PhaseChatRole, InferenceJobSpec, JobEvent,
-    JobSpec, SamplingParams,
+    ChatMessage as PhaseChatMessage, ChatRole as PhaseChatRole, ConversationToken,
+    InferenceJobSpec, JobEvent, JobSpec, KeepAlive, OutputConstraint, SamplingParams, ToolCall,
+    ToolDefinition,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::router::{RouteDecision, RouteVia, Router as LucidRouter, RouterError};
+use crate::router::{
+    RouteDecision, RouteRequirements, RouteVia, Router as LucidRouter, RouterError,
+};
+use crate::resume;
+use crate::vision;
 
 // ---------------------------------------------------------------------------
 // Wire types
@@ -58,6 +63,11 @@ pub struct ChatRequest {
     pub format: Option<serde_json::Value>,
     #[serde(default)]
     pub tools: Option<serde_json::Value>,
+    /// Not part of Ollama's chat API: the `context` a previous final
+    /// response carried, accepted here too so chat clients can resume the
+    /// way `/api/generate` clients do.
+    #[serde(default)]
+    pub context: Option<Vec<i64>>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -67,6 +77,32 @@ pub struct WireMessage {
     pub content: String,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub images: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<WireToolCall>,
+    /// Ollama names the tool a `role: "tool"` message answers here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// The id of the call a `role: "tool"` message answers, for clients
+    /// that echo back the `id` of a returned tool call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+/// Ollama's `message.tool_calls[]` entry. `arguments` is a JSON object on
+/// the wire (not a string, unlike OpenAI).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct WireToolCall {
+    /// The call's id, when the serving worker has one (OpenAI upstreams).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub function: WireToolCallFunction,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct WireToolCallFunction {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
 }
 
 #[derive(Debug, Serialize)]
@@ -90,12 +126,17 @@ struct ChatFinalResponse<'a> {
     prompt_eval_duration: u64,
     eval_count: u64,
     eval_duration: u64,
+    /// Resumption token for the next turn; see [`HEADER_RESUME_TOKEN`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    context: Vec<u8>,
 }
 
 #[derive(Debug, Serialize)]
 struct ChatChunkMessage<'a> {
     role: &'a str,
     content: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tool_calls: &'a [WireToolCall],
 }
 
 #[derive(Debug, Serialize)]
@@ -175,6 +216,12 @@ pub const HEADER_ROUTED_VIA: &str = "x-lucid-routed-via";
 /// `unverifiable`; omitted on the local path (no peer receipt to assert).
 pub const HEADER_RECEIPT_VERIFIED: &str = "x-lucid-receipt-verified";
 
+/// HTTP request header carrying a conversation resumption token (URL-safe
+/// base64), for clients that would rather not echo Ollama's `context`
+/// array. Either one works; the final response of a turn hands the next
+/// token back in `context`.
+pub const HEADER_RESUME_TOKEN: &str = "x-lucid-resume-token";
+
 /// Parse `X-Lucid-Local-Only`. Anything that looks truthy ("1", "true",
 /// "yes", case-insensitive) flips the flag. Absent / empty → false.
 fn parse_local_only(headers: &HeaderMap) -> bool {
@@ -190,6 +237,28 @@ fn parse_local_only(headers: &HeaderMap) -> bool {
         .unwrap_or(false)
 }
 
+/// The resumption token for this turn, from the request's `context` or,
+/// failing that, [`HEADER_RESUME_TOKEN`]. A context that isn't one of our
+/// tokens (e.g. real Ollama token ids) is ignored.
+fn parse_resume_token(headers: &HeaderMap, context: Option<&[i64]>) -> Option<ConversationToken> {
+    context.and_then(resume::from_context).or_else(|| {
+        headers
+            .get(HEADER_RESUME_TOKEN)
+            .and_then(|v| v.to_str().ok())
+            .and_then(resume::from_header)
+    })
+}
+
+/// `context` for a final response: the next turn's resumption token, or
+/// empty when the worker didn't issue one.
+fn context_of(result: &phase_protocol::JobResult) -> Vec<u8> {
+    result
+        .resumption
+        .as_ref()
+        .map(resume::to_context)
+        .unwrap_or_default()
+}
+
 /// Build a 503 response carrying the human-readable refusal reason.
 fn refused_response(reason: &str) -> Response {
     (
@@ -199,6 +268,18 @@ fn refused_response(reason: &str) -> Response {
         .into_response()
 }
 
+/// The serving peer rate-limited us. 429 with `Retry-After` (whole
+/// seconds, rounded up) so Ollama clients back off instead of hammering.
+fn rate_limited_response(retry_after: std::time::Duration) -> Response {
+    let secs = retry_after.as_millis().div_ceil(1000).max(1);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(axum::http::header::RETRY_AFTER, secs.to_string())],
+        format!("peer rate limited; retry in {secs}s"),
+    )
+        .into_response()
+}
+
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/api/chat", post(handle_chat))
@@ -323,6 +404,11 @@ pub struct GenerateRequest {
     pub images: Option<Vec<String>>,
     #[serde(default)]
     pub template: Option<String>,
+    #[serde(default)]
+    pub format: Option<serde_json::Value>,
+    /// What the previous turn's final response returned as `context`.
+    #[serde(default)]
+    pub context: Option<Vec<i64>>,
 }
 
 /// `/api/generate` — same streaming machinery as `/api/chat`, but the
@@ -336,27 +422,69 @@ async fn handle_generate(
     let model = req.model.clone();
     let stream_mode = req.stream.unwrap_or(true);
     let prompt = req.prompt.clone().unwrap_or_default();
+    let keep_alive = match parse_keep_alive(req.keep_alive.as_ref()) {
+        Ok(k) => k,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let images = req.images.clone().unwrap_or_default();
+    // Ollama: an empty prompt just loads (or, with `keep_alive: 0`,
+    // unloads) the model. The worker sees an empty spec as a load request.
+    let load_only = prompt.is_empty() && images.is_empty();
+    // Images ride on a single user message, as on /api/chat: that is where
+    // the manifest binds their digests and where the worker reads them.
+    let mut messages = Vec::new();
+    if !images.is_empty() {
+        let mut message = PhaseChatMessage {
+            role: PhaseChatRole::User,
+            content: prompt.clone(),
+            images,
+            image_digests: Vec::new(),
+            tool_calls: Vec::new(),
+            tool_name: None,
+            tool_call_id: None,
+        };
+        if let Err(e) = vision::attach_image_digests(&mut message) {
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+        messages.push(message);
+    }
 
     let local_only = parse_local_only(&headers);
+    let resume_from = parse_resume_token(&headers, req.context.as_deref());
+    let requirements = RouteRequirements {
+        vision: !messages.is_empty(),
+        prefer_peer: resume_from
+            .as_ref()
+            .and_then(|t| resume::preferred_peer(t, resume::unix_ms_now())),
+    };
 
     // Route decision. Refusals short-circuit to 503 without ever
     // touching the worker.
-    let decision = state.router.route(&model, local_only).await;
+    let decision = state
+        .router
+        .route_with(&model, local_only, requirements)
+        .await;
     if let RouteVia::Refused { reason } = &decision.via {
         // SEC-10: model is attacker-controlled (request body); sanitize.
         tracing::info!(model = %sanitize_for_log(&model), reason = %reason, "router refused /api/generate");
         return refused_response(reason);
     }
     let routed_via = decision.header_value();
+    // Only the issuer can continue the transcript; anywhere else the
+    // prompt runs on its own.
+    let resume_from = resume_from.filter(|t| state.router.reaches_issuer(&decision, t));
 
     let job_spec = JobSpec::Inference(InferenceJobSpec {
         model_cid: req.model.clone(),
-        messages: Vec::new(),
-        prompt: Some(prompt),
-        resume_from: None,
+        prompt: if messages.is_empty() { Some(prompt) } else { None },
+        messages,
+        resume_from,
         sampling: SamplingParams::default(),
         max_tokens: None,
         stream: stream_mode,
+        tools: Vec::new(),
+        output_constraint: parse_format(req.format.as_ref()),
+        keep_alive,
     });
 
     // Sign with the AppState identity. Each call's `created_at` differs by
@@ -378,6 +506,9 @@ async fn handle_generate(
         match state.router.execute(&decision, manifest).await {
             Ok(t) => t,
             Err(RouterError::Refused { reason }) => return refused_response(&reason),
+            Err(RouterError::RateLimited { retry_after, .. }) => {
+                return rate_limited_response(retry_after)
+            }
             Err(e) => {
                 tracing::error!(error = %e, "router dispatch failed (/api/generate)");
                 return (
@@ -391,11 +522,36 @@ async fn handle_generate(
     let job_id = handle.job_id().clone();
     let started_at = std::time::Instant::now();
 
+    if load_only {
+        while job_stream.next().await.is_some() {}
+        let done_reason = if keep_alive == Some(KeepAlive::Duration { ms: 0 }) {
+            "unload"
+        } else {
+            "load"
+        };
+        let body = serde_json::json!({
+            "model": model,
+            "created_at": rfc3339_now(),
+            "response": "",
+            "done": true,
+            "done_reason": done_reason,
+        });
+        let mut resp = (StatusCode::OK, Json(body)).into_response();
+        if let Some(rv) = routed_via.as_deref() {
+            if let Ok(hv) = rv.parse() {
+                resp.headers_mut().insert(HEADER_ROUTED_VIA, hv);
+            }
+        }
+        tracing::info!(%job_id, done_reason, "generate load request complete");
+        return resp;
+    }
+
     if !stream_mode {
         let mut acc = String::new();
         let mut done_reason = "stop";
         let mut prompt_tokens = 0u64;
         let mut completion_tokens = 0u64;
+        let mut context = Vec::new();
         while let Some(ev) = job_stream.next().await {
             match ev {
                 JobEvent::Output(chunk) => {
@@ -404,11 +560,13 @@ async fn handle_generate(
                     }
                 }
                 JobEvent::Final { result, .. } => {
+                    context = context_of(&result);
                     done_reason = match result.completion {
                         phase_protocol::Completion::Stop => "stop",
                         phase_protocol::Completion::Length => "length",
                         phase_protocol::Completion::Cancelled => "cancelled",
                         phase_protocol::Completion::Error => "error",
+                        phase_protocol::Completion::ConstraintViolation => "constraint_violation",
                         _ => "unknown",
                     };
                     prompt_tokens = result.metrics.prompt_tokens;
@@ -429,7 +587,7 @@ async fn handle_generate(
             "response": acc,
             "done": true,
             "done_reason": done_reason,
-            "context": [],
+            "context": context,
             "total_duration": total_duration,
             "load_duration": 0u64,
             "prompt_eval_count": prompt_tokens,
@@ -464,6 +622,7 @@ async fn handle_generate(
         let mut completion_tokens = 0u64;
         let mut done_reason = "stop";
         let mut commitment: Option<[u8; 32]> = None;
+        let mut context = Vec::new();
 
         while let Some(ev) = job_stream.next().await {
             match ev {
@@ -489,11 +648,13 @@ async fn handle_generate(
                         phase_protocol::Completion::Length => "length",
                         phase_protocol::Completion::Cancelled => "cancelled",
                         phase_protocol::Completion::Error => "error",
+                        phase_protocol::Completion::ConstraintViolation => "constraint_violation",
                         _ => "unknown",
                     };
                     prompt_tokens = result.metrics.prompt_tokens;
                     completion_tokens = result.metrics.completion_tokens;
                     commitment = Some(result.output_commitment);
+                    context = context_of(&result);
                 }
                 JobEvent::Progress(_) => {}
                 _ => {}
@@ -513,7 +674,7 @@ async fn handle_generate(
             "response": "",
             "done": true,
             "done_reason": done_reason,
-            "context": [],
+            "context": context,
             "total_duration": total_duration,
             "load_duration": 0,
             "prompt_eval_count": prompt_tokens,
@@ -560,37 +721,70 @@ async fn handle_chat(
     let model = req.model.clone();
     let stream_mode = req.stream.unwrap_or(true);
 
-    let local_only = parse_local_only(&headers);
+    let keep_alive = match parse_keep_alive(req.keep_alive.as_ref()) {
+        Ok(k) => k,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
 
-    // Route decision (M5). Refusals short-circuit to 503 before we
-    // build a manifest or touch a worker.
-    let decision: RouteDecision = state.router.route(&model, local_only).await;
-    if let RouteVia::Refused { reason } = &decision.via {
-        // SEC-10: model is attacker-controlled (request body); sanitize.
-        tracing::info!(model = %sanitize_for_log(&model), reason = %reason, "router refused /api/chat");
-        return refused_response(reason);
-    }
-    let routed_via = decision.header_value();
+    let local_only = parse_local_only(&headers);
 
-    // Translate wire → JobSpec.
-    let messages: Vec<PhaseChatMessage> = req
+    // Translate wire → JobSpec. Done before routing because the messages
+    // decide what the serving peer needs (vision).
+    let mut messages: Vec<PhaseChatMessage> = req
         .messages
         .iter()
         .map(|m| PhaseChatMessage {
             role: parse_role(&m.role),
             content: m.content.clone(),
             images: m.images.clone(),
+            image_digests: Vec::new(),
+            tool_calls: m.tool_calls.iter().map(tool_call_from_wire).collect(),
+            tool_name: m.tool_name.clone(),
+            tool_call_id: m.tool_call_id.clone(),
         })
         .collect();
+    // Bind each image's decoded bytes into the signed manifest.
+    for message in &mut messages {
+        if let Err(e) = vision::attach_image_digests(message) {
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    }
+    let tools = parse_tools(req.tools.as_ref());
+    let resume_from = parse_resume_token(&headers, req.context.as_deref());
+    let requirements = RouteRequirements {
+        vision: messages.iter().any(|m| !m.images.is_empty()),
+        prefer_peer: resume_from
+            .as_ref()
+            .and_then(|t| resume::preferred_peer(t, resume::unix_ms_now())),
+    };
+
+    // Route decision (M5). Refusals short-circuit to 503 before we
+    // build a manifest or touch a worker.
+    let decision: RouteDecision = state
+        .router
+        .route_with(&model, local_only, requirements)
+        .await;
+    if let RouteVia::Refused { reason } = &decision.via {
+        // SEC-10: model is attacker-controlled (request body); sanitize.
+        tracing::info!(model = %sanitize_for_log(&model), reason = %reason, "router refused /api/chat");
+        return refused_response(reason);
+    }
+    let routed_via = decision.header_value();
+    // The messages carry the full history, so a peer other than the
+    // issuer just replays it from a cold cache.
+    let resume_from = resume_from.filter(|t| state.router.reaches_issuer(&decision, t));
 
     let job_spec = JobSpec::Inference(InferenceJobSpec {
         model_cid: req.model.clone(),
         messages,
         prompt: None,
-        resume_from: None,
+        resume_from,
         sampling: SamplingParams::default(),
         max_tokens: None,
         stream: stream_mode,
+        tools,
+        output_constraint: parse_format(req.format.as_ref()),
+        keep_alive,
     });
 
     // Real signed manifest. M5 swapped the pseudo-manifest UUID for a
@@ -612,6 +806,7 @@ async fn handle_chat(
         match state.router.execute(&decision, manifest).await {
         Ok(t) => t,
         Err(RouterError::Refused { reason }) => return refused_response(&reason),
+        Err(RouterError::RateLimited { retry_after, .. }) => return rate_limited_response(retry_after),
         Err(e) => {
             tracing::error!(error = %e, "router dispatch failed");
             return (
@@ -628,11 +823,18 @@ async fn handle_chat(
     // ----- non-streaming path: collect everything, send a single JSON ----
     if !stream_mode {
         let mut acc = String::new();
+        let mut tool_calls: Vec<WireToolCall> = Vec::new();
         let mut done_reason = "stop";
         let mut prompt_tokens = 0u64;
         let mut completion_tokens = 0u64;
+        let mut context = Vec::new();
         while let Some(ev) = job_stream.next().await {
             match ev {
+                JobEvent::Output(chunk) if chunk.kind == "tool_call" => {
+                    if let Some(call) = tool_call_chunk_to_wire(&chunk.data) {
+                        tool_calls.push(call);
+                    }
+                }
                 JobEvent::Output(chunk) => {
                     if let Ok(s) = std::str::from_utf8(&chunk.data) {
                         acc.push_str(s);
@@ -644,10 +846,12 @@ async fn handle_chat(
                         phase_protocol::Completion::Length => "length",
                         phase_protocol::Completion::Cancelled => "cancelled",
                         phase_protocol::Completion::Error => "error",
+                        phase_protocol::Completion::ConstraintViolation => "constraint_violation",
                         _ => "unknown",
                     };
                     prompt_tokens = result.metrics.prompt_tokens;
                     completion_tokens = result.metrics.completion_tokens;
+                    context = context_of(&result);
                 }
                 JobEvent::Progress(_) => {}
                 _ => {}
@@ -661,10 +865,14 @@ async fn handle_chat(
         };
 
         let total_duration = started_at.elapsed().as_nanos() as u64;
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "model": model,
             "created_at": rfc3339_now(),
-            "message": { "role": "assistant", "content": acc },
+            "message": ChatChunkMessage {
+                role: "assistant",
+                content: &acc,
+                tool_calls: &tool_calls,
+            },
             "done": true,
             "done_reason": done_reason,
             "total_duration": total_duration,
@@ -674,6 +882,11 @@ async fn handle_chat(
             "eval_count": completion_tokens,
             "eval_duration": total_duration,
         });
+        if !context.is_empty() {
+            if let Some(map) = body.as_object_mut() {
+                map.insert("context".to_string(), serde_json::json!(context));
+            }
+        }
 
         let mut resp = (StatusCode::OK, Json(body)).into_response();
         if let Some(v) = receipt_header {
@@ -703,13 +916,24 @@ async fn handle_chat(
         let mut completion_tokens = 0u64;
         let mut done_reason = "stop";
         let mut commitment: Option<[u8; 32]> = None;
+        let mut context = Vec::new();
 
         while let Some(ev) = job_stream.next().await {
             match ev {
                 JobEvent::Output(chunk) => {
-                    let text = match std::str::from_utf8(&chunk.data) {
-                        Ok(s) => s.to_string(),
-                        Err(_) => base64::engine::general_purpose::STANDARD.encode(&chunk.data),
+                    // A tool call goes out as its own frame with empty
+                    // content, which is how Ollama itself streams them.
+                    let (text, tool_calls) = if chunk.kind == "tool_call" {
+                        match tool_call_chunk_to_wire(&chunk.data) {
+                            Some(call) => (String::new(), vec![call]),
+                            None => continue,
+                        }
+                    } else {
+                        let text = match std::str::from_utf8(&chunk.data) {
+                            Ok(s) => s.to_string(),
+                            Err(_) => base64::engine::general_purpose::STANDARD.encode(&chunk.data),
+                        };
+                        (text, Vec::new())
                     };
                     let payload = ChatChunkResponse {
                         model: &model_for_body,
@@ -717,6 +941,7 @@ async fn handle_chat(
                         message: ChatChunkMessage {
                             role: "assistant",
                             content: &text,
+                            tool_calls: &tool_calls,
                         },
                         done: false,
                     };
@@ -736,11 +961,13 @@ async fn handle_chat(
                         phase_protocol::Completion::Length => "length",
                         phase_protocol::Completion::Cancelled => "cancelled",
                         phase_protocol::Completion::Error => "error",
+                        phase_protocol::Completion::ConstraintViolation => "constraint_violation",
                         _ => "unknown",
                     };
                     prompt_tokens = result.metrics.prompt_tokens;
                     completion_tokens = result.metrics.completion_tokens;
                     commitment = Some(result.output_commitment);
+                    context = context_of(&result);
                 }
                 JobEvent::Progress(_) => {}
                 _ => {}
@@ -765,7 +992,7 @@ async fn handle_chat(
         let final_payload = ChatFinalResponse {
             model: &model_for_body,
             created_at: rfc3339_now(),
-            message: ChatChunkMessage { role: "assistant", content: "" },
+            message: ChatChunkMessage { role: "assistant", content: "", tool_calls: &[] },
             done: true,
             done_reason,
             total_duration,
@@ -774,6 +1001,7 @@ async fn handle_chat(
             prompt_eval_duration: 0,
             eval_count: completion_tokens,
             eval_duration: total_duration,
+            context,
         };
         let mut final_value = serde_json::to_value(&final_payload).unwrap_or(serde_json::json!({}));
         if let Some(c) = commitment.as_ref() {
@@ -823,6 +1051,156 @@ fn parse_role(s: &str) -> PhaseChatRole {
     }
 }
 
+/// Translate Ollama's `format` into an [`OutputConstraint`]: the string
+/// `"json"` selects JSON mode, an object is taken as a JSON Schema.
+/// Anything else (absent, `""`, other strings) means unconstrained.
+fn parse_format(format: Option<&serde_json::Value>) -> Option<OutputConstraint> {
+    match format? {
+        serde_json::Value::String(s) if s == "json" => Some(OutputConstraint::Json),
+        schema @ serde_json::Value::Object(_) => Some(OutputConstraint::JsonSchema {
+            schema: schema.to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Translate Ollama's `keep_alive`. A number is seconds; a string is a Go
+/// duration (`"5m"`, `"1h30m"`, `"300ms"`) or, leniently, a bare number of
+/// seconds. Negative values pin the model, as in Ollama. Absent / `null`
+/// defers to the worker default.
+pub fn parse_keep_alive(value: Option<&serde_json::Value>) -> Result<Option<KeepAlive>, String> {
+    match value {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::Number(n)) => n
+            .as_f64()
+            .ok_or_else(|| format!("invalid keep_alive: {n}"))
+            .and_then(keep_alive_from_secs)
+            .map(Some),
+        Some(serde_json::Value::String(s)) => parse_keep_alive_str(s).map(Some),
+        Some(other) => Err(format!("invalid keep_alive: {other}")),
+    }
+}
+
+/// The string form of [`parse_keep_alive`]; also backs `--llama-keep-alive`.
+pub fn parse_keep_alive_str(s: &str) -> Result<KeepAlive, String> {
+    let s = s.trim();
+    let secs = match s.parse::<f64>() {
+        Ok(secs) => secs,
+        Err(_) => parse_go_duration(s)?,
+    };
+    keep_alive_from_secs(secs)
+}
+
+fn keep_alive_from_secs(secs: f64) -> Result<KeepAlive, String> {
+    if !secs.is_finite() {
+        return Err("invalid keep_alive: not finite".to_string());
+    }
+    if secs < 0.0 {
+        return Ok(KeepAlive::Forever);
+    }
+    Ok(KeepAlive::Duration {
+        ms: (secs * 1000.0).min(u64::MAX as f64) as u64,
+    })
+}
+
+/// Go's `time.ParseDuration`, returning seconds: an optional sign, then
+/// one or more `<decimal><unit>` terms with unit `ns`, `us`/`µs`, `ms`,
+/// `s`, `m` or `h`.
+fn parse_go_duration(s: &str) -> Result<f64, String> {
+    let invalid = || format!("invalid keep_alive duration: {s:?}");
+    let (sign, mut rest) = match s.strip_prefix('-') {
+        Some(r) => (-1.0, r),
+        None => (1.0, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if rest == "0" {
+        return Ok(0.0);
+    }
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .ok_or_else(invalid)?;
+        let value: f64 = rest[..num_len].parse().map_err(|_| invalid())?;
+        rest = &rest[num_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let scale = match &rest[..unit_len] {
+            "ns" => 1e-9,
+            "us" | "µs" | "μs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return Err(invalid()),
+        };
+        total += value * scale;
+        rest = &rest[unit_len..];
+    }
+    Ok(sign * total)
+}
+
+/// Translate Ollama's `tools[]` into protocol [`ToolDefinition`]s. Entries
+/// that aren't `{"type": "function", "function": {"name": …}}` are
+/// skipped — Ollama only defines function tools today.
+fn parse_tools(tools: Option<&serde_json::Value>) -> Vec<ToolDefinition> {
+    let Some(serde_json::Value::Array(entries)) = tools else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter(|t| t.get("type").and_then(|v| v.as_str()).unwrap_or("function") == "function")
+        .filter_map(|t| {
+            let f = t.get("function")?;
+            Some(ToolDefinition {
+                name: f.get("name")?.as_str()?.to_string(),
+                description: f
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .map(str::to_string),
+                parameters: f.get("parameters").map(|p| p.to_string()),
+            })
+        })
+        .collect()
+}
+
+fn tool_call_from_wire(call: &WireToolCall) -> ToolCall {
+    ToolCall {
+        id: call.id.clone(),
+        name: call.function.name.clone(),
+        arguments: match &call.function.arguments {
+            serde_json::Value::Null => "{}".to_string(),
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        },
+    }
+}
+
+/// Decode a `"tool_call"` chunk's JSON-encoded [`ToolCall`] into the Ollama
+/// wire shape. `None` for a payload that doesn't decode — logged, and the
+/// frame is dropped rather than shipped as garbage text.
+fn tool_call_chunk_to_wire(data: &[u8]) -> Option<WireToolCall> {
+    let call: ToolCall = match serde_json::from_slice(data) {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::warn!(error = %e, "undecodable tool_call chunk");
+            return None;
+        }
+    };
+    let arguments = serde_json::from_str(&call.arguments)
+        .unwrap_or(serde_json::Value::String(call.arguments));
+    Some(WireToolCall {
+        id: call.id,
+        function: WireToolCallFunction {
+            name: call.name,
+            arguments,
+        },
+    })
+}
+
 fn rfc3339_now() -> String {
     // Hand-roll an RFC3339-shaped timestamp so we don't drag in `chrono`
     // or `time` just for one field. Ollama clients accept any RFC3339-ish
@@ -912,4 +1290,119 @@ mod tests {
         let clean = "/api/chat";
         assert_eq!(sanitize_for_log(clean), clean);
     }
+
+    #[test]
+    fn parse_tools_reads_ollama_function_tools() {
+        let tools = serde_json::json!([
+            {
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather",
+                    "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+                }
+            },
+            {"type": "retrieval"},
+            {"type": "function", "function": {"description": "nameless"}}
+        ]);
+        let parsed = parse_tools(Some(&tools));
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].name, "get_weather");
+        assert_eq!(parsed[0].description.as_deref(), Some("Current weather"));
+        let schema: serde_json::Value =
+            serde_json::from_str(parsed[0].parameters.as_deref().unwrap()).unwrap();
+        assert_eq!(schema["properties"]["city"]["type"], "string");
+        assert!(parse_tools(None).is_empty());
+    }
+
+    #[test]
+    fn tool_calls_round_trip_wire_to_chunk() {
+        let wire = WireToolCall {
+            id: Some("call_a".to_string()),
+            function: WireToolCallFunction {
+                name: "get_weather".to_string(),
+                arguments: serde_json::json!({"city": "Paris"}),
+            },
+        };
+        let call = tool_call_from_wire(&wire);
+        assert_eq!(call.arguments, r#"{"city":"Paris"}"#);
+        let data = serde_json::to_vec(&call).unwrap();
+        assert_eq!(tool_call_chunk_to_wire(&data), Some(wire));
+        assert_eq!(tool_call_chunk_to_wire(b"not json"), None);
+    }
+
+    #[test]
+    fn chat_message_omits_empty_tool_calls() {
+        let plain = ChatChunkMessage {
+            role: "assistant",
+            content: "hi",
+            tool_calls: &[],
+        };
+        let v = serde_json::to_value(&plain).unwrap();
+        assert!(v.get("tool_calls").is_none());
+    }
+
+    #[test]
+    fn parse_format_maps_json_and_schema() {
+        assert_eq!(
+            parse_format(Some(&serde_json::json!("json"))),
+            Some(OutputConstraint::Json)
+        );
+        let schema = serde_json::json!({"type": "object", "required": ["a"]});
+        match parse_format(Some(&schema)) {
+            Some(OutputConstraint::JsonSchema { schema: s }) => {
+                assert_eq!(serde_json::from_str::<serde_json::Value>(&s).unwrap(), schema);
+            }
+            other => panic!("expected schema constraint, got {other:?}"),
+        }
+        assert_eq!(parse_format(Some(&serde_json::json!(""))), None);
+        assert_eq!(parse_format(None), None);
+    }
+
+    #[test]
+    fn parse_keep_alive_accepts_ollama_forms() {
+        use serde_json::json;
+        let ms = |ms| Ok(Some(KeepAlive::Duration { ms }));
+        assert_eq!(parse_keep_alive(Some(&json!(30))), ms(30_000));
+        assert_eq!(parse_keep_alive(Some(&json!(0))), ms(0));
+        assert_eq!(parse_keep_alive(Some(&json!(-1))), Ok(Some(KeepAlive::Forever)));
+        assert_eq!(parse_keep_alive(Some(&json!("5m"))), ms(300_000));
+        assert_eq!(parse_keep_alive(Some(&json!("1h30m"))), ms(5_400_000));
+        assert_eq!(parse_keep_alive(Some(&json!("300ms"))), ms(300));
+        assert_eq!(parse_keep_alive(Some(&json!("0"))), ms(0));
+        assert_eq!(parse_keep_alive(Some(&json!("-1"))), Ok(Some(KeepAlive::Forever)));
+        assert_eq!(parse_keep_alive(Some(&json!("-1m"))), Ok(Some(KeepAlive::Forever)));
+        assert_eq!(parse_keep_alive(Some(&json!(null))), Ok(None));
+        assert_eq!(parse_keep_alive(None), Ok(None));
+    }
+
+    #[test]
+    fn parse_keep_alive_rejects_garbage() {
+        use serde_json::json;
+        for bad in [json!("soon"), json!("10x"), json!("1h-5m"), json!("NaN"), json!(""), json!(true)] {
+            assert!(parse_keep_alive(Some(&bad)).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn resume_token_comes_from_context_or_header() {
+        let token = resume::issue(
+            &NodeIdentity::generate(),
+            "qwen3",
+            resume::SlotRef {
+                generation: 1,
+                slot: 0,
+                nonce: 2,
+            },
+            u64::MAX,
+        );
+        let context: Vec<i64> = resume::to_context(&token).into_iter().map(i64::from).collect();
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_resume_token(&headers, Some(&context)), Some(token.clone()));
+        // A real Ollama context is token ids, not ours; no token.
+        assert_eq!(parse_resume_token(&headers, Some(&[128006, 882])), None);
+
+        headers.insert(HEADER_RESUME_TOKEN, resume::to_header(&token).parse().unwrap());
+        assert_eq!(parse_resume_token(&headers, None), Some(token));
+    }
 }
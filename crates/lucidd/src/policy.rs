@@ -32,6 +32,7 @@ use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use chrono::{Local, Timelike};
+use phase_protocol::KeepAlive;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
@@ -110,6 +111,12 @@ pub struct PolicyConfig {
     /// allowlist is what pins the key to an authorized identity.
     pub authorized_submitters: Vec<String>,
 
+    /// Succession chains (hex, as printed by `plasmd identity rotate`) for
+    /// submitters that have rotated their key. A key that a chain shows
+    /// succeeding an `authorized_submitters` entry is authorized too, so
+    /// a rotation doesn't need the allowlist re-edited.
+    pub submitter_successions: Vec<String>,
+
     /// SEC-01 escape hatch: when `true`, skip the authorization gate
     /// entirely and accept any manifest that passes `verify()`. This
     /// restores the pre-SEC-01 open behavior and is **insecure** — it lets
@@ -122,6 +129,11 @@ pub struct PolicyConfig {
     /// what the (untrusted) client asked for. Protects against a peer
     /// requesting an enormous generation to exhaust GPU time.
     pub max_tokens_ceiling: u32,
+
+    /// Longest a peer's relayed job may keep its model resident here, in
+    /// seconds. A relayed `keep_alive` above this (including "forever") is
+    /// clamped to it; residency beyond that is the operator's call.
+    pub max_relay_keep_alive_secs: u64,
 }
 
 impl Default for PolicyConfig {
@@ -137,10 +149,13 @@ impl Default for PolicyConfig {
             // SEC-01: default-deny. Operator must explicitly list keys, or
             // flip `allow_unauthenticated_jobs` for local dev.
             authorized_submitters: Vec::new(),
+            submitter_successions: Vec::new(),
             allow_unauthenticated_jobs: false,
             // 8192 tokens is a generous default ceiling; operators can raise
             // it. Clamps a hostile manifest's `max_tokens` server-side.
             max_tokens_ceiling: 8192,
+            // Ollama's default keep_alive.
+            max_relay_keep_alive_secs: 300,
         }
     }
 }
@@ -164,7 +179,8 @@ impl PolicyConfig {
     /// Order of decisions:
     /// 1. `allow_unauthenticated_jobs == true` → accept anything (insecure
     ///    local-dev mode).
-    /// 2. Otherwise the key must appear in `authorized_submitters`.
+    /// 2. Otherwise the key must appear in `authorized_submitters`, or
+    ///    succeed a key that does by one of `submitter_successions`.
     ///
     /// NOTE (SEC-06 / PeerID-bind): the relay handler (`router.rs`) now ALSO
     /// accepts a signer whose Ed25519 key derives to the delivering libp2p
@@ -175,9 +191,11 @@ impl PolicyConfig {
         if self.allow_unauthenticated_jobs {
             return true;
         }
-        self.authorized_submitters
-            .iter()
-            .any(|k| k.eq_ignore_ascii_case(pubkey_hex))
+        phase_identity::allowlist_permits(
+            &self.authorized_submitters,
+            &self.submitter_successions,
+            pubkey_hex,
+        )
     }
 
     /// SEC-01: clamp a manifest-supplied `max_tokens` to the operator
@@ -186,6 +204,16 @@ impl PolicyConfig {
     pub fn clamp_max_tokens(&self, requested: Option<u32>) -> Option<u32> {
         requested.map(|n| n.min(self.max_tokens_ceiling))
     }
+
+    /// Clamp a relayed `keep_alive` to `max_relay_keep_alive_secs`.
+    /// `None` (worker default) stays `None`; `Forever` becomes the maximum.
+    pub fn clamp_relay_keep_alive(&self, requested: Option<KeepAlive>) -> Option<KeepAlive> {
+        let max_ms = self.max_relay_keep_alive_secs.saturating_mul(1000);
+        requested.map(|keep_alive| match keep_alive {
+            KeepAlive::Duration { ms } => KeepAlive::Duration { ms: ms.min(max_ms) },
+            KeepAlive::Forever => KeepAlive::Duration { ms: max_ms },
+        })
+    }
 }
 
 impl TimeWindow {
@@ -340,6 +368,14 @@ impl PolicyEngine {
             .clamp_max_tokens(requested)
     }
 
+    /// Clamp a relayed `keep_alive` to the live operator maximum.
+    pub fn clamp_relay_keep_alive(&self, requested: Option<KeepAlive>) -> Option<KeepAlive> {
+        self.config
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clamp_relay_keep_alive(requested)
+    }
+
     /// Snapshot of the current state. Cheap (`Clone`).
     pub fn state(&self) -> PolicyState {
         self.state
@@ -576,6 +612,11 @@ max_concurrent_remote_jobs = 4
 #   ]
 authorized_submitters = []
 
+# Succession chains for listed submitters that rotated their key, exactly as
+# printed by `plasmd identity rotate`. A key a chain shows succeeding one of
+# the keys above is trusted like it.
+submitter_successions = []
+
 # INSECURE escape hatch for local development only. When true, the signer
 # allowlist is bypassed and ANY peer whose manifest verifies can use this
 # node's GPU. Never enable on an internet-exposed node.
@@ -585,6 +626,10 @@ allow_unauthenticated_jobs = false
 # can ask for an enormous generation; this clamps it regardless of what the
 # manifest claims.
 max_tokens_ceiling = 8192
+
+# Longest, in seconds, a peer's relayed job may keep its model loaded here.
+# Longer requests, including "keep forever", are clamped to this.
+max_relay_keep_alive_secs = 300
 "#;
 
 // ---------------------------------------------------------------------------
@@ -1045,14 +1090,35 @@ mod tests {
             manual_pause: false,
             max_concurrent_remote_jobs: 2,
             authorized_submitters: vec!["aa".repeat(32), "bb".repeat(32)],
+            submitter_successions: vec!["cc".repeat(144)],
             allow_unauthenticated_jobs: false,
             max_tokens_ceiling: 4096,
+            max_relay_keep_alive_secs: 60,
         };
         let serialized = toml::to_string(&original).expect("serialize");
         let parsed: PolicyConfig = toml::from_str(&serialized).expect("parse back");
         assert_eq!(parsed, original);
     }
 
+    #[test]
+    fn relay_keep_alive_is_clamped_to_the_maximum() {
+        let config = PolicyConfig {
+            max_relay_keep_alive_secs: 60,
+            ..PolicyConfig::default()
+        };
+        let max = Some(KeepAlive::Duration { ms: 60_000 });
+        assert_eq!(config.clamp_relay_keep_alive(None), None);
+        assert_eq!(config.clamp_relay_keep_alive(Some(KeepAlive::Forever)), max);
+        assert_eq!(
+            config.clamp_relay_keep_alive(Some(KeepAlive::Duration { ms: u64::MAX })),
+            max
+        );
+        assert_eq!(
+            config.clamp_relay_keep_alive(Some(KeepAlive::Duration { ms: 0 })),
+            Some(KeepAlive::Duration { ms: 0 })
+        );
+    }
+
     // --- PolicyEngine surface ---------------------------------------------
 
     #[tokio::test]
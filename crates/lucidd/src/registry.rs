@@ -66,7 +66,7 @@ use anyhow::{anyhow, bail, Context, Result};
 use async_trait::async_trait;
 use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
 use phase_identity::NodeIdentity;
-use phase_net::PeerId;
+use phase_net::{PeerId, RecordError, RecordValidator};
 use serde::{Deserialize, Serialize};
 use tokio::sync::{Mutex, RwLock};
 use tokio::task::JoinHandle;
@@ -92,7 +92,13 @@ use tracing::{debug, warn};
 /// would mis-verify each other's advertisements. The network is tiny (v0.1),
 /// so this is a deliberate clean break: a v2 reader rejects v1 records on the
 /// schema-version check before even reaching signature verification.
-pub const ADVERTISEMENT_SCHEMA_VERSION: u32 = 2;
+///
+/// ## v3 — vision capability
+///
+/// Adds [`ModelCapabilities::vision`]. postcard is positional, so an extra
+/// field changes both the signed bytes and the wire layout; v2 records are
+/// rejected on the version check the same way v1 records were.
+pub const ADVERTISEMENT_SCHEMA_VERSION: u32 = 3;
 
 /// DHT key prefix for model advertisements. Final key shape:
 /// `b"phase/model/" || model_cid` — exactly 12 + 32 = 44 bytes.
@@ -187,6 +193,11 @@ pub struct ModelCapabilities {
     /// Backend that loaded the model, e.g. `"llama.cpp"` / `"mlx"`.
     pub backend: String,
 
+    /// Whether the peer can take image input for this model (a multimodal
+    /// projector is installed alongside the weights). The router only
+    /// sends image-bearing requests to peers that set this.
+    pub vision: bool,
+
     /// Unix millisecond timestamp the advertisement was produced.
     pub advertised_at: u64,
 
@@ -197,8 +208,9 @@ pub struct ModelCapabilities {
 
 impl ModelCapabilities {
     /// Build an advertisement with `advertised_at = now` and
-    /// `valid_until = now + ADVERTISEMENT_TTL`. Callers can override
-    /// either field before passing to `advertise_loaded`.
+    /// `valid_until = now + ADVERTISEMENT_TTL` and `vision = false`.
+    /// Callers can override any of these before passing to
+    /// `advertise_loaded`.
     pub fn now(
         model_id: impl Into<String>,
         model_cid: ModelCid,
@@ -215,6 +227,7 @@ impl ModelCapabilities {
             context_length,
             max_concurrent,
             backend: backend.into(),
+            vision: false,
             advertised_at: now,
             valid_until: now + ADVERTISEMENT_TTL.as_millis() as u64,
         }
@@ -348,6 +361,48 @@ impl SignedModelAdvertisement {
     }
 }
 
+/// Largest advertisement a node will store or accept from the DHT. Real
+/// ones are a few hundred bytes; this leaves room for long model ids.
+pub const MAX_ADVERTISEMENT_BYTES: usize = 8 * 1024;
+
+/// Admission rule for the `phase/model/` namespace, registered with
+/// phase-net's [`phase_net::ValidatorRegistry`] so forged, stale or
+/// misfiled advertisements never enter (or leave) a node's record store.
+///
+/// Checks the signature, that the key is the advertised model's own
+/// `dht_key()`, and that `valid_until` is still in the future; the
+/// store then drops the record at `valid_until`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ModelAdvertisementValidator;
+
+impl RecordValidator for ModelAdvertisementValidator {
+    fn validate(
+        &self,
+        key: &[u8],
+        value: &[u8],
+        now_unix_ms: u64,
+    ) -> std::result::Result<Option<u64>, RecordError> {
+        if value.len() > MAX_ADVERTISEMENT_BYTES {
+            return Err(RecordError::TooLarge {
+                size: value.len(),
+                limit: MAX_ADVERTISEMENT_BYTES,
+            });
+        }
+        let ad = SignedModelAdvertisement::decode(value)
+            .map_err(|e| RecordError::Invalid(format!("{e:#}")))?;
+        if ad.caps.model_cid.dht_key() != key {
+            return Err(RecordError::KeyMismatch);
+        }
+        if ad.caps.valid_until <= now_unix_ms {
+            return Err(RecordError::Expired {
+                expires_unix_ms: ad.caps.valid_until,
+                now_unix_ms,
+            });
+        }
+        Ok(Some(ad.caps.valid_until))
+    }
+}
+
 // ---------------------------------------------------------------------------
 // DhtTransport — small abstraction over what the registry needs from the DHT.
 // ---------------------------------------------------------------------------
@@ -898,6 +953,27 @@ mod tests {
         assert_eq!(back.pubkey, identity.verifying_key().to_bytes());
     }
 
+    #[test]
+    fn model_validator_checks_key_signature_and_expiry() {
+        let identity = NodeIdentity::generate();
+        let caps = sample_caps();
+        let key = caps.model_cid.dht_key();
+        let bytes = SignedModelAdvertisement::sign(caps.clone(), &identity)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let v = ModelAdvertisementValidator;
+
+        assert_eq!(v.validate(&key, &bytes, caps.valid_until - 1), Ok(Some(caps.valid_until)));
+        assert!(matches!(
+            v.validate(&key, &bytes, caps.valid_until),
+            Err(RecordError::Expired { .. })
+        ));
+        let other_key = ModelCid([0xee; 32]).dht_key();
+        assert_eq!(v.validate(&other_key, &bytes, 0), Err(RecordError::KeyMismatch));
+        assert!(matches!(v.validate(&key, b"unsigned", 0), Err(RecordError::Invalid(_))));
+    }
+
     #[test]
     fn tamper_with_caps_breaks_signature() {
         let identity = NodeIdentity::generate();
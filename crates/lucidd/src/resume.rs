@@ -0,0 +1,285 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Conversation resumption tokens.
+//!
+//! After a turn, [`LlamaCppWorker`](crate::LlamaCppWorker) hands back a
+//! [`ConversationToken`] naming the `llama-server` slot that now holds the
+//! conversation's KV cache. The client returns it on the next turn (as
+//! Ollama's `context` array, or the [`HEADER_RESUME_TOKEN`] header), the
+//! router sends that turn back to the issuing peer while the token is
+//! fresh, and the worker pins the request to the same slot so only the
+//! new suffix is prefilled.
+//!
+//! The token's `state` is a [`SlotRef`] plus an Ed25519 signature by the
+//! issuing node over the slot, the issuer, the model and the expiry. Only
+//! the issuer ever interprets it; any other peer — or the issuer after the
+//! model was reloaded — treats it as absent and prefills from the full
+//! history. A bad token makes a turn slower, never wrong, so every failure
+//! here is an `Option`, not an error.
+//!
+//! [`HEADER_RESUME_TOKEN`]: crate::ollama::HEADER_RESUME_TOKEN
+
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine as _;
+use bytes::Bytes;
+use ed25519_dalek::{Signature, Signer, Verifier};
+use phase_identity::NodeIdentity;
+use phase_net::PeerId;
+use phase_protocol::{should_resume_on_same_peer, ConversationToken, KeepAlive};
+use serde::{Deserialize, Serialize};
+
+/// Version byte at the front of every token `state`. Bumped if the layout
+/// of [`SlotRef`] or the signed payload changes; older tokens then just
+/// stop resuming.
+pub const TOKEN_VERSION: u8 = 1;
+
+/// Domain separator so a token signature can't be replayed as any other
+/// signature the node identity produces (receipts, advertisements).
+const SIGNING_DOMAIN: &str = "lucid/resume-token/v1";
+
+/// Where a conversation's KV cache lives on the issuing worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotRef {
+    /// Random per model load; a token from an earlier load of the same
+    /// model (whose cache died with the subprocess) won't match.
+    pub generation: u64,
+    /// `llama-server` slot id (`id_slot`).
+    pub slot: u32,
+    /// Random per turn. The slot may have served another conversation
+    /// since; the worker only resumes if the nonce is still the latest.
+    pub nonce: u64,
+}
+
+/// The bytes in [`ConversationToken::state`].
+#[derive(Serialize, Deserialize)]
+struct TokenState {
+    version: u8,
+    slot: SlotRef,
+    signature: Vec<u8>,
+}
+
+/// Canonical form covered by [`TokenState::signature`].
+#[derive(Serialize)]
+struct SigningPayload<'a> {
+    domain: &'a str,
+    version: u8,
+    issuer: &'a str,
+    model_id: &'a str,
+    valid_until_unix_ms: u64,
+    slot: &'a SlotRef,
+}
+
+fn signed_bytes(issuer: &str, model_id: &str, valid_until_unix_ms: u64, slot: &SlotRef) -> Vec<u8> {
+    postcard::to_allocvec(&SigningPayload {
+        domain: SIGNING_DOMAIN,
+        version: TOKEN_VERSION,
+        issuer,
+        model_id,
+        valid_until_unix_ms,
+        slot,
+    })
+    .expect("postcard serialization of plain fields is infallible")
+}
+
+/// The libp2p `PeerId` an identity appears as on the network — the same
+/// derivation the registry and the relay receipt check use.
+pub fn peer_id_of(identity: &NodeIdentity) -> PeerId {
+    use phase_net::libp2p_identity::{ed25519, PublicKey};
+    let ed = ed25519::PublicKey::try_from_bytes(&identity.verifying_key().to_bytes())
+        .expect("a NodeIdentity verifying key is a valid ed25519 point");
+    PeerId::from(PublicKey::from(ed))
+}
+
+/// Sign a token for `slot`, resumable on this node until
+/// `valid_until_unix_ms`.
+pub fn issue(
+    identity: &NodeIdentity,
+    model_id: &str,
+    slot: SlotRef,
+    valid_until_unix_ms: u64,
+) -> ConversationToken {
+    let issuer = peer_id_of(identity).to_string();
+    let bytes = signed_bytes(&issuer, model_id, valid_until_unix_ms, &slot);
+    let state = TokenState {
+        version: TOKEN_VERSION,
+        slot,
+        signature: identity.signing_key().sign(&bytes).to_bytes().to_vec(),
+    };
+    ConversationToken {
+        issuer: phase_protocol::PeerId(issuer),
+        state: Bytes::from(
+            postcard::to_allocvec(&state).expect("postcard serialization of plain fields is infallible"),
+        ),
+        valid_until_unix_ms,
+    }
+}
+
+/// Recover the slot a token points at, if this node issued it for
+/// `model_id`, it hasn't expired, and the signature holds.
+pub fn open(
+    token: &ConversationToken,
+    identity: &NodeIdentity,
+    model_id: &str,
+    now_unix_ms: u64,
+) -> Option<SlotRef> {
+    if !should_resume_on_same_peer(token, now_unix_ms) {
+        return None;
+    }
+    let issuer = peer_id_of(identity).to_string();
+    if token.issuer.0 != issuer {
+        return None;
+    }
+    let state: TokenState = postcard::from_bytes(&token.state).ok()?;
+    if state.version != TOKEN_VERSION {
+        return None;
+    }
+    let sig_bytes: &[u8; 64] = state.signature.as_slice().try_into().ok()?;
+    let bytes = signed_bytes(&issuer, model_id, token.valid_until_unix_ms, &state.slot);
+    identity
+        .verifying_key()
+        .verify(&bytes, &Signature::from_bytes(sig_bytes))
+        .ok()?;
+    Some(state.slot)
+}
+
+/// The peer a follow-up turn should go back to, or `None` once the token
+/// is past its grace window (or names something that isn't a `PeerId`).
+pub fn preferred_peer(token: &ConversationToken, now_unix_ms: u64) -> Option<PeerId> {
+    if !should_resume_on_same_peer(token, now_unix_ms) {
+        return None;
+    }
+    PeerId::from_str(&token.issuer.0).ok()
+}
+
+/// How long the worker promises to keep a slot for: the model's own
+/// keep-alive, capped at [`DEFAULT_RESUMPTION_GRACE`]. `None` when the
+/// model unloads as soon as the turn ends, since there is nothing to come
+/// back to.
+///
+/// [`DEFAULT_RESUMPTION_GRACE`]: phase_protocol::DEFAULT_RESUMPTION_GRACE
+pub fn retention(keep_alive: KeepAlive) -> Option<Duration> {
+    let grace = phase_protocol::DEFAULT_RESUMPTION_GRACE;
+    match keep_alive {
+        KeepAlive::Duration { ms: 0 } => None,
+        KeepAlive::Duration { ms } => Some(Duration::from_millis(ms).min(grace)),
+        KeepAlive::Forever => Some(grace),
+    }
+}
+
+/// Encode a token as an Ollama `context` array: one entry per byte.
+/// Clients store and echo `context` verbatim without interpreting it.
+pub fn to_context(token: &ConversationToken) -> Vec<u8> {
+    postcard::to_allocvec(token).expect("postcard serialization of plain fields is infallible")
+}
+
+/// Decode an Ollama `context` array. A context produced by real Ollama
+/// (token ids, mostly > 255) or by anything else decodes to `None`.
+pub fn from_context(context: &[i64]) -> Option<ConversationToken> {
+    if context.is_empty() {
+        return None;
+    }
+    let bytes = context
+        .iter()
+        .map(|&v| u8::try_from(v).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    postcard::from_bytes(&bytes).ok()
+}
+
+/// Encode a token for the resume header (unpadded URL-safe base64).
+pub fn to_header(token: &ConversationToken) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(to_context(token))
+}
+
+/// Decode the resume header; `None` for anything malformed.
+pub fn from_header(value: &str) -> Option<ConversationToken> {
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(value.trim().trim_end_matches('='))
+        .ok()?;
+    postcard::from_bytes(&bytes).ok()
+}
+
+/// Milliseconds since the Unix epoch, the clock tokens are stamped in.
+pub fn unix_ms_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOT: SlotRef = SlotRef {
+        generation: 7,
+        slot: 2,
+        nonce: 42,
+    };
+    const NOW: u64 = 1_700_000_000_000;
+
+    #[test]
+    fn issued_token_opens_only_for_issuer_model_and_window() {
+        let issuer = NodeIdentity::generate();
+        let token = issue(&issuer, "qwen3", SLOT, NOW + 60_000);
+        assert!(token.state.len() <= 256, "state is {} bytes", token.state.len());
+
+        assert_eq!(open(&token, &issuer, "qwen3", NOW), Some(SLOT));
+        assert_eq!(open(&token, &issuer, "llama3", NOW), None);
+        assert_eq!(open(&token, &NodeIdentity::generate(), "qwen3", NOW), None);
+        assert_eq!(open(&token, &issuer, "qwen3", NOW + 60_000), None);
+    }
+
+    #[test]
+    fn tampered_token_does_not_open() {
+        let issuer = NodeIdentity::generate();
+        let token = issue(&issuer, "qwen3", SLOT, NOW + 60_000);
+
+        let mut extended = token.clone();
+        extended.valid_until_unix_ms += 1;
+        assert_eq!(open(&extended, &issuer, "qwen3", NOW), None);
+
+        let mut state = token.state.to_vec();
+        // Byte 2 is the slot id: postcard lays out version, generation and
+        // slot as one-byte varints for these values.
+        state[2] ^= 1;
+        let flipped = ConversationToken {
+            state: Bytes::from(state),
+            ..token
+        };
+        assert_eq!(open(&flipped, &issuer, "qwen3", NOW), None);
+    }
+
+    #[test]
+    fn token_round_trips_through_context_and_header() {
+        let issuer = NodeIdentity::generate();
+        let token = issue(&issuer, "qwen3", SLOT, NOW + 60_000);
+
+        let context: Vec<i64> = to_context(&token).into_iter().map(i64::from).collect();
+        assert_eq!(from_context(&context), Some(token.clone()));
+        assert_eq!(from_header(&to_header(&token)), Some(token.clone()));
+        assert_eq!(preferred_peer(&token, NOW), Some(peer_id_of(&issuer)));
+        assert_eq!(preferred_peer(&token, NOW + 60_000), None);
+    }
+
+    #[test]
+    fn foreign_context_is_ignored() {
+        assert_eq!(from_context(&[]), None);
+        assert_eq!(from_context(&[128006, 882, 128007, 271]), None);
+        assert_eq!(from_context(&[1, 2, 3]), None);
+        assert_eq!(from_header("not a token"), None);
+    }
+
+    #[test]
+    fn retention_follows_keep_alive_up_to_grace() {
+        let grace = phase_protocol::DEFAULT_RESUMPTION_GRACE;
+        assert_eq!(retention(KeepAlive::Duration { ms: 0 }), None);
+        assert_eq!(
+            retention(KeepAlive::Duration { ms: 30_000 }),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(retention(KeepAlive::Duration { ms: u64::MAX }), Some(grace));
+        assert_eq!(retention(KeepAlive::Forever), Some(grace));
+    }
+}
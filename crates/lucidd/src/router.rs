@@ -19,9 +19,11 @@
 //!    is non-negotiable.
 //! 2. Operator policy ([`PolicyEngine::should_serve`]) says pause →
 //!    `Refused(PauseReason)`.
-//! 3. Local worker has the model loaded → `Local`.
-//! 4. Otherwise: DHT lookup; first valid peer → `Peer { peer_id }`.
-//! 5. No peers → `Refused("no peers serving model X")`.
+//! 3. The request resumes a conversation whose token names another peer
+//!    that still advertises the model → `Peer { issuer }`.
+//! 4. Local worker has the model loaded → `Local`.
+//! 5. Otherwise: DHT lookup; first valid peer → `Peer { peer_id }`.
+//! 6. No peers → `Refused("no peers serving model X")`.
 //!
 //! ## v0.1 limitations (documented, not bugs)
 //!
@@ -36,18 +38,19 @@
 //!   its own admission control via `WorkerError::Capacity`. The router
 //!   surfaces that as a 503 to the client.
 
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use async_stream::stream;
 use phase_identity::NodeIdentity;
-use phase_net::{Discovery, JobRelayRequest, JobRelayResponse, PeerId};
+use phase_net::{Discovery, JobRelayRequest, JobRelayResponse, NetworkEvent, PeerId};
 use phase_protocol::{
-    CommitmentAccumulator, DynWorker, JobEvent, JobHandle, JobId, JobResult, JobSpec, JobStream,
-    SignedManifest, SignedReceipt, WorkerError,
+    CommitmentAccumulator, ConversationToken, DynWorker, JobEvent, JobHandle, JobId, JobResult, JobSpec, JobStream,
+    OutputConstraint, SignedManifest, SignedReceipt, WorkerError,
 };
 use thiserror::Error;
-use tokio::sync::Semaphore;
+use tokio::sync::{broadcast, Semaphore};
 use tokio::time::timeout;
 use tracing::{debug, info, warn};
 
@@ -67,6 +70,11 @@ use crate::policy::{PauseReason, PolicyDecision, PolicyEngine};
 /// covers a long generation on a slow GPU before we give up.
 pub const RELAY_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
+/// How long a peer that dropped off stays out of routing. Once the mark
+/// lapses the peer is a candidate again, and relaying to it redials it
+/// through the addresses Kademlia still holds.
+const DEPARTED_TTL: Duration = Duration::from_secs(5 * 60);
+
 // ---------------------------------------------------------------------------
 // Public API types
 // ---------------------------------------------------------------------------
@@ -82,6 +90,26 @@ pub enum RouteVia {
     Refused { reason: String },
 }
 
+/// What a request needs from whoever serves it, beyond having the model.
+/// `Default` is a plain text request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteRequirements {
+    /// The request carries images; only a server advertising
+    /// [`ModelCapabilities::vision`](crate::ModelCapabilities::vision) can
+    /// take it.
+    pub vision: bool,
+    /// Send the request to this peer if it still serves the model — the
+    /// issuer of the conversation's resumption token, which holds its KV
+    /// cache. Anyone else can serve it too, just from a cold prefill.
+    pub prefer_peer: Option<PeerId>,
+}
+
+impl RouteRequirements {
+    fn satisfied_by(&self, caps: &crate::ModelCapabilities) -> bool {
+        !self.vision || caps.vision
+    }
+}
+
 /// Outcome of a routing decision.
 #[derive(Debug, Clone)]
 pub struct RouteDecision {
@@ -149,6 +177,10 @@ pub enum RouterError {
     Worker(#[from] WorkerError),
     #[error("peer relay error: {0}")]
     Relay(String),
+    /// The serving peer's per-peer rate limit refused the job before it
+    /// ran; it is safe to retry after `retry_after`.
+    #[error("peer {peer} is rate limiting us; retry in {retry_after:?}")]
+    RateLimited { peer: PeerId, retry_after: Duration },
     #[error("router has no local worker")]
     NoLocalWorker,
 }
@@ -162,9 +194,14 @@ pub struct Router {
     local_worker: Option<Arc<dyn DynWorker>>,
     registry: Arc<ModelRegistry>,
     policy: Arc<PolicyEngine>,
-    #[allow(dead_code)] // surfaces in receipts/logs in a future milestone
     identity: NodeIdentity,
     phase_net: Arc<Discovery>,
+    /// Peers that expired from mDNS or whose last connection failed, and
+    /// when. Their model adverts stay in the DHT for up to
+    /// `ADVERTISEMENT_TTL`, so without this we'd keep routing to them until
+    /// the records expire. Fed by [`Router::watch_peers`]; marks lapse
+    /// after [`DEPARTED_TTL`].
+    departed: Arc<Mutex<HashMap<PeerId, Instant>>>,
 }
 
 impl Router {
@@ -187,15 +224,86 @@ impl Router {
             policy,
             identity,
             phase_net,
+            departed: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Track peer connections from the network event stream so routing
+    /// skips peers that have gone away. Call once at startup.
+    pub fn watch_peers(&self) -> tokio::task::JoinHandle<()> {
+        let mut events = self.phase_net.subscribe();
+        let departed = Arc::clone(&self.departed);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    // A clean close (the idle timeout, mostly) says nothing
+                    // about the peer; the next relay just redials it.
+                    Ok(NetworkEvent::PeerDisconnected { peer, error: true })
+                    | Ok(NetworkEvent::PeerExpired { peer, .. }) => {
+                        debug!(peer = %peer, "peer left; skipping it for routing");
+                        departed.lock().unwrap().insert(peer, Instant::now());
+                    }
+                    Ok(NetworkEvent::PeerConnected { peer, .. }) => {
+                        departed.lock().unwrap().remove(&peer);
+                    }
+                    Ok(_) => {}
+                    // Missed events could include a reconnect; forget
+                    // everything rather than shun a peer that is back.
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(skipped = n, "network event stream lagged; clearing departed peers");
+                        departed.lock().unwrap().clear();
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    fn has_departed(&self, peer: &PeerId) -> bool {
+        let mut departed = self.departed.lock().unwrap();
+        match departed.get(peer) {
+            Some(at) if at.elapsed() < DEPARTED_TTL => true,
+            Some(_) => {
+                departed.remove(peer);
+                false
+            }
+            None => false,
         }
     }
 
     /// Choose where to serve `model_id`. Pure decision step — no side
     /// effects, no worker dispatch.
     pub async fn route(&self, model_id: &str, local_only: bool) -> RouteDecision {
+        self.route_with(model_id, local_only, RouteRequirements::default())
+            .await
+    }
+
+    /// Whether `decision` dispatches to the node that issued `token`, i.e.
+    /// whether forwarding the token can do any good. Everyone else would
+    /// just ignore it, so the HTTP layer drops it instead.
+    pub fn reaches_issuer(&self, decision: &RouteDecision, token: &ConversationToken) -> bool {
+        let target = match &decision.via {
+            RouteVia::Local => crate::resume::peer_id_of(&self.identity),
+            RouteVia::Peer { peer_id } => *peer_id,
+            RouteVia::Refused { .. } => return false,
+        };
+        target.to_string() == token.issuer.0
+    }
+
+    /// [`route`](Self::route) for a request with extra [`RouteRequirements`].
+    /// Local and peer candidates whose advertised capabilities don't meet
+    /// them are skipped as if they didn't have the model.
+    pub async fn route_with(
+        &self,
+        model_id: &str,
+        local_only: bool,
+        requirements: RouteRequirements,
+    ) -> RouteDecision {
         let has_local_worker = self.local_worker.is_some();
         let local_models = self.registry.local_models_async().await;
-        let local_has_model = local_models.iter().any(|c| c.model_id == model_id);
+        let local_has_model = local_models
+            .iter()
+            .any(|c| c.model_id == model_id && requirements.satisfied_by(c));
 
         // 1. Local-only privacy posture wins over everything else.
         if local_only && !(has_local_worker && local_has_model) {
@@ -230,7 +338,37 @@ impl Router {
             }
         }
 
-        // 3. Local has the model — fast path.
+        // 3. Resumption affinity: a follow-up turn goes back to the peer
+        //    holding its KV cache while that peer still advertises the
+        //    model. Local-only requests never leave the node, and when we
+        //    are the issuer the local fast path below already applies.
+        let preferred = requirements
+            .prefer_peer
+            .filter(|p| !local_only && *p != crate::resume::peer_id_of(&self.identity));
+        let mut peers = None;
+        if let Some(preferred) = preferred {
+            let found = match self.registry.find_peers_by_model_id(model_id).await {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!(error = %e, "registry lookup failed");
+                    Vec::new()
+                }
+            };
+            if !self.has_departed(&preferred)
+                && found
+                    .iter()
+                    .any(|(peer_id, caps)| *peer_id == preferred && requirements.satisfied_by(caps))
+            {
+                debug!(model = %model_id, peer = %preferred, "resuming on issuing peer");
+                return RouteDecision {
+                    via: RouteVia::Peer { peer_id: preferred },
+                    model_id: model_id.to_string(),
+                };
+            }
+            peers = Some(found);
+        }
+
+        // 4. Local has the model — fast path.
         if has_local_worker && local_has_model {
             return RouteDecision {
                 via: RouteVia::Local,
@@ -238,15 +376,21 @@ impl Router {
             };
         }
 
-        // 4. Look up peers on the DHT.
-        let peers = match self.registry.find_peers_by_model_id(model_id).await {
-            Ok(p) => p,
-            Err(e) => {
-                warn!(error = %e, "registry lookup failed");
-                Vec::new()
-            }
+        // 5. Look up peers on the DHT (unless step 3 already did).
+        let peers = match peers {
+            Some(p) => p,
+            None => match self.registry.find_peers_by_model_id(model_id).await {
+                Ok(p) => p,
+                Err(e) => {
+                    warn!(error = %e, "registry lookup failed");
+                    Vec::new()
+                }
+            },
         };
-        if let Some((peer_id, caps)) = peers.into_iter().next() {
+        if let Some((peer_id, caps)) = peers
+            .into_iter()
+            .find(|(peer_id, caps)| !self.has_departed(peer_id) && requirements.satisfied_by(caps))
+        {
             debug!(
                 model = %model_id,
                 peer = %peer_id,
@@ -259,11 +403,14 @@ impl Router {
             };
         }
 
-        // 5. Nobody can serve.
+        // 6. Nobody can serve.
+        let reason = if requirements.vision {
+            format!("no peers serving model '{model_id}' with vision support")
+        } else {
+            format!("no peers serving model '{model_id}'")
+        };
         RouteDecision {
-            via: RouteVia::Refused {
-                reason: format!("no peers serving model '{model_id}'"),
-            },
+            via: RouteVia::Refused { reason },
             model_id: model_id.to_string(),
         }
     }
@@ -341,6 +488,15 @@ impl Router {
             JobRelayResponse::Err { reason } => {
                 return Err(RouterError::Relay(format!("peer refused: {reason}")));
             }
+            JobRelayResponse::RateLimited { retry_after_ms } => {
+                return Err(RouterError::RateLimited {
+                    peer: peer_id,
+                    retry_after: Duration::from_millis(retry_after_ms),
+                });
+            }
+            JobRelayResponse::Busy => {
+                return Err(RouterError::Relay(format!("peer {peer_id} is busy")));
+            }
         };
 
         let events: Vec<JobEvent> = serde_json::from_slice(&events_bytes)
@@ -655,14 +811,62 @@ pub fn make_inbound_relay_handler(
                     );
                     spec.max_tokens = clamped;
                 }
+                // A peer may ask us to keep its model warm for a while,
+                // but not to pin it: residency on this node is the
+                // operator's call, so the request is capped.
+                let keep_alive = policy.clamp_relay_keep_alive(spec.keep_alive);
+                if keep_alive != spec.keep_alive {
+                    debug!(
+                        requested = ?spec.keep_alive,
+                        clamped = ?keep_alive,
+                        "relay: clamped keep_alive to operator maximum"
+                    );
+                    spec.keep_alive = keep_alive;
+                }
+                // An empty spec only asks for a model load. Which models
+                // sit in this node's memory is not a peer's decision.
+                if spec.messages.is_empty() && spec.prompt.as_deref().is_none_or(str::is_empty) {
+                    return JobRelayResponse::Err {
+                        reason: "load-only requests are not accepted from peers".to_string(),
+                    };
+                }
             }
 
             // 1d. SEC-06: bound total prompt/message length BEFORE dispatch.
             //     `max_tokens` caps *output*; this caps *input* so a peer
-            //     can't exhaust context memory with a giant prompt.
+            //     can't exhaust context memory with a giant prompt. Tool
+            //     schemas and replayed call arguments are rendered into the
+            //     prompt too, an output schema is compiled into a grammar
+            //     server-side, and images are decoded and encoded into
+            //     context, so they all count against the same cap (images
+            //     by their base64 length).
             if let JobSpec::Inference(spec) = &job.payload {
                 let prompt_chars: usize = spec.prompt.as_ref().map(|p| p.len()).unwrap_or(0)
-                    + spec.messages.iter().map(|m| m.content.len()).sum::<usize>();
+                    + spec
+                        .messages
+                        .iter()
+                        .map(|m| {
+                            m.content.len()
+                                + m.images.iter().map(String::len).sum::<usize>()
+                                + m.tool_calls
+                                    .iter()
+                                    .map(|c| c.name.len() + c.arguments.len())
+                                    .sum::<usize>()
+                        })
+                        .sum::<usize>()
+                    + spec
+                        .tools
+                        .iter()
+                        .map(|t| {
+                            t.name.len()
+                                + t.description.as_ref().map_or(0, |d| d.len())
+                                + t.parameters.as_ref().map_or(0, |p| p.len())
+                        })
+                        .sum::<usize>()
+                    + match &spec.output_constraint {
+                        Some(OutputConstraint::JsonSchema { schema }) => schema.len(),
+                        _ => 0,
+                    };
                 if prompt_chars > MAX_PROMPT_CHARS {
                     warn!(
                         prompt_chars,
@@ -1004,6 +1208,222 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn route_with_vision_skips_text_only_peers() {
+        // Two peers serve "llava"; only the second advertises a projector.
+        // Plain requests take the first, image requests must skip it. The
+        // text-only peer alone also serves "phi", so image requests for it
+        // have nowhere to go.
+        let identity = NodeIdentity::generate();
+        let transport = Arc::new(MockDht::default());
+        let text_only = NodeIdentity::generate();
+        let vision = NodeIdentity::generate();
+        for (model, signer, has_vision) in [
+            ("llava", &text_only, false),
+            ("llava", &vision, true),
+            ("phi", &text_only, false),
+        ] {
+            let cid = ModelCid::from_model_id(model);
+            let mut caps = sample_caps(model, 0);
+            caps.model_cid = cid;
+            caps.vision = has_vision;
+            let ad = crate::registry::SignedModelAdvertisement::sign(caps, signer).unwrap();
+            transport
+                .store
+                .lock()
+                .unwrap()
+                .entry(cid.dht_key())
+                .or_default()
+                .push(ad.encode().unwrap());
+        }
+        let registry = Arc::new(ModelRegistry::new(identity.clone(), transport as _));
+        let policy = Arc::new(PolicyEngine::new_for_tests(
+            PolicyConfig::default(),
+            PolicyState::default(),
+        ));
+        let router = Router::new(None, registry, policy, identity, build_test_discovery());
+
+        let peer_of = |id: &NodeIdentity| {
+            use phase_net::libp2p_identity::{ed25519, PublicKey};
+            let ed = ed25519::PublicKey::try_from_bytes(&id.verifying_key().to_bytes()).unwrap();
+            PeerId::from(PublicKey::from(ed))
+        };
+        let needs_vision = RouteRequirements {
+            vision: true,
+            ..Default::default()
+        };
+
+        match router.route("llava", false).await.via {
+            RouteVia::Peer { peer_id } => assert_eq!(peer_id, peer_of(&text_only)),
+            other => panic!("expected Peer, got {other:?}"),
+        }
+        match router.route_with("llava", false, needs_vision).await.via {
+            RouteVia::Peer { peer_id } => assert_eq!(peer_id, peer_of(&vision)),
+            other => panic!("expected Peer, got {other:?}"),
+        }
+        match router.route("phi", false).await.via {
+            RouteVia::Peer { peer_id } => assert_eq!(peer_id, peer_of(&text_only)),
+            other => panic!("expected Peer, got {other:?}"),
+        }
+        match router.route_with("phi", false, needs_vision).await.via {
+            RouteVia::Refused { reason } => assert!(reason.contains("vision"), "reason: {reason}"),
+            other => panic!("expected Refused, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn route_skips_departed_peers() {
+        // Two peers advertise "llava". Once the first disconnects its
+        // advert lingers in the DHT, but routing moves on to the second.
+        let identity = NodeIdentity::generate();
+        let transport = Arc::new(MockDht::default());
+        let cid = ModelCid::from_model_id("llava");
+        let first = NodeIdentity::generate();
+        let second = NodeIdentity::generate();
+        for signer in [&first, &second] {
+            let caps = sample_caps("llava", 0);
+            let ad = crate::registry::SignedModelAdvertisement::sign(caps, signer).unwrap();
+            transport
+                .store
+                .lock()
+                .unwrap()
+                .entry(cid.dht_key())
+                .or_default()
+                .push(ad.encode().unwrap());
+        }
+        let registry = Arc::new(ModelRegistry::new(identity.clone(), transport as _));
+        let policy = Arc::new(PolicyEngine::new_for_tests(
+            PolicyConfig::default(),
+            PolicyState::default(),
+        ));
+        let router = Router::new(None, registry, policy, identity, build_test_discovery());
+        let first_peer = crate::resume::peer_id_of(&first);
+        let second_peer = crate::resume::peer_id_of(&second);
+
+        match router.route("llava", false).await.via {
+            RouteVia::Peer { peer_id } => assert_eq!(peer_id, first_peer),
+            other => panic!("expected Peer, got {other:?}"),
+        }
+        router.departed.lock().unwrap().insert(first_peer, Instant::now());
+        match router.route("llava", false).await.via {
+            RouteVia::Peer { peer_id } => assert_eq!(peer_id, second_peer),
+            other => panic!("expected Peer, got {other:?}"),
+        }
+        router.departed.lock().unwrap().insert(second_peer, Instant::now());
+        assert!(matches!(
+            router.route("llava", false).await.via,
+            RouteVia::Refused { .. }
+        ));
+
+        // A lapsed mark puts the peer back in the running.
+        let lapsed = Instant::now() - DEPARTED_TTL - Duration::from_secs(1);
+        router.departed.lock().unwrap().insert(first_peer, lapsed);
+        match router.route("llava", false).await.via {
+            RouteVia::Peer { peer_id } => assert_eq!(peer_id, first_peer),
+            other => panic!("expected Peer, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn route_with_prefers_resumption_issuer() {
+        // We serve "llava" locally and two peers advertise it too. A
+        // follow-up turn goes to whichever peer issued its token; anything
+        // that can't be honoured falls back to the normal order.
+        let identity = NodeIdentity::generate();
+        let transport = Arc::new(MockDht::default());
+        let cid = ModelCid::from_model_id("llava");
+        let first = NodeIdentity::generate();
+        let issuer = NodeIdentity::generate();
+        for signer in [&first, &issuer] {
+            let mut caps = sample_caps("llava", 0);
+            caps.model_cid = cid;
+            let ad = crate::registry::SignedModelAdvertisement::sign(caps, signer).unwrap();
+            transport
+                .store
+                .lock()
+                .unwrap()
+                .entry(cid.dht_key())
+                .or_default()
+                .push(ad.encode().unwrap());
+        }
+        let registry = Arc::new(ModelRegistry::new(identity.clone(), transport as _));
+        let mut local_caps = sample_caps("llava", 0);
+        local_caps.model_cid = cid;
+        registry
+            .advertise_loaded(local_caps)
+            .await
+            .expect("advertise");
+        let policy = Arc::new(PolicyEngine::new_for_tests(
+            PolicyConfig::default(),
+            PolicyState::default(),
+        ));
+        let worker: Arc<dyn DynWorker> = Arc::new(EchoWorker::new());
+        let router = Router::new(
+            Some(worker),
+            registry,
+            policy,
+            identity.clone(),
+            build_test_discovery(),
+        );
+        let prefer = |id: &NodeIdentity| RouteRequirements {
+            prefer_peer: Some(crate::resume::peer_id_of(id)),
+            ..Default::default()
+        };
+
+        let decision = router.route_with("llava", false, prefer(&issuer)).await;
+        match &decision.via {
+            RouteVia::Peer { peer_id } => {
+                assert_eq!(*peer_id, crate::resume::peer_id_of(&issuer))
+            }
+            other => panic!("expected Peer, got {other:?}"),
+        }
+        let token = crate::resume::issue(
+            &issuer,
+            "llava",
+            crate::resume::SlotRef {
+                generation: 1,
+                slot: 0,
+                nonce: 1,
+            },
+            u64::MAX,
+        );
+        assert!(router.reaches_issuer(&decision, &token));
+
+        for (requirements, local_only) in [
+            (prefer(&identity), false),
+            (prefer(&NodeIdentity::generate()), false),
+            (prefer(&issuer), true),
+        ] {
+            let decision = router.route_with("llava", local_only, requirements).await;
+            assert!(
+                matches!(decision.via, RouteVia::Local),
+                "expected Local, got {:?}",
+                decision.via
+            );
+            assert!(!router.reaches_issuer(&decision, &token));
+        }
+    }
+
+    #[tokio::test]
+    async fn route_with_vision_refuses_text_only_local_model() {
+        let (router, _registry) = make_router_with_local_model().await;
+        let decision = router
+            .route_with(
+                "qwen3-mini",
+                true,
+                RouteRequirements {
+                    vision: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert!(
+            matches!(decision.via, RouteVia::Refused { .. }),
+            "expected Refused, got {:?}",
+            decision.via
+        );
+    }
+
     #[tokio::test]
     async fn route_refused_when_no_peers_and_not_local() {
         let identity = NodeIdentity::generate();
@@ -1049,12 +1469,19 @@ mod tests {
                 role: ChatRole::User,
                 content: "abc".to_string(),
                 images: vec![],
+                image_digests: vec![],
+                tool_calls: vec![],
+                tool_name: None,
+                tool_call_id: None,
             }],
             prompt: None,
             resume_from: None,
             sampling: SamplingParams::default(),
             max_tokens: None,
             stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
         });
         let manifest = ManifestBuilder::new(spec).sign_with(&client).unwrap();
         let (_handle, mut stream, verification) =
@@ -1119,12 +1546,19 @@ mod tests {
                 role: ChatRole::User,
                 content: "hi".to_string(),
                 images: vec![],
+                image_digests: vec![],
+                tool_calls: vec![],
+                tool_name: None,
+                tool_call_id: None,
             }],
             prompt: None,
             resume_from: None,
             sampling: SamplingParams::default(),
             max_tokens,
             stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
         });
         ManifestBuilder::new(spec).sign_with(client).unwrap()
     }
@@ -1503,6 +1937,9 @@ mod tests {
             sampling: SamplingParams::default(),
             max_tokens: None,
             stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
         });
         let manifest = ManifestBuilder::new(spec).sign_with(&client).unwrap();
         let bytes = serde_json::to_vec(&manifest).unwrap();
@@ -1512,9 +1949,72 @@ mod tests {
             JobRelayResponse::Err { reason } => assert!(reason.contains("prompt too large")),
             other => panic!("expected Err, got {other:?}"),
         }
+
+        // Images count too, by their base64 length.
+        let spec = JobSpec::Inference(InferenceJobSpec {
+            model_cid: "qwen3-mini".to_string(),
+            messages: vec![phase_protocol::ChatMessage {
+                role: phase_protocol::ChatRole::User,
+                content: "what is this?".to_string(),
+                images: vec!["A".repeat(MAX_PROMPT_CHARS)],
+                image_digests: vec![],
+                tool_calls: vec![],
+                tool_name: None,
+                tool_call_id: None,
+            }],
+            prompt: None,
+            resume_from: None,
+            sampling: SamplingParams::default(),
+            max_tokens: None,
+            stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
+        });
+        let manifest = ManifestBuilder::new(spec).sign_with(&client).unwrap();
+        let bytes = serde_json::to_vec(&manifest).unwrap();
+        match handler(PeerId::random(), bytes).await {
+            JobRelayResponse::Err { reason } => assert!(reason.contains("prompt too large")),
+            other => panic!("expected Err, got {other:?}"),
+        }
         assert_eq!(spy.call_count(), 0, "oversized prompt must not reach the worker");
     }
 
+    #[tokio::test]
+    async fn relay_refuses_load_only_specs() {
+        let spy = SpyWorker::new();
+        let worker: Arc<dyn DynWorker> = Arc::new(spy.clone());
+        let registry = registry_with_model("qwen3-mini").await;
+        let config = PolicyConfig {
+            allow_unauthenticated_jobs: true,
+            ..PolicyConfig::default()
+        };
+        let policy = Arc::new(PolicyEngine::new_for_tests(config, PolicyState::default()));
+        let handler = make_inbound_relay_handler(worker, registry, policy);
+
+        use phase_manifest::ManifestBuilder;
+        use phase_protocol::{InferenceJobSpec, KeepAlive, SamplingParams};
+        let spec = JobSpec::Inference(InferenceJobSpec {
+            model_cid: "qwen3-mini".to_string(),
+            messages: vec![],
+            prompt: None,
+            resume_from: None,
+            sampling: SamplingParams::default(),
+            max_tokens: None,
+            stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: Some(KeepAlive::Forever),
+        });
+        let manifest = ManifestBuilder::new(spec).sign_with(&NodeIdentity::generate()).unwrap();
+        let bytes = serde_json::to_vec(&manifest).unwrap();
+        match handler(PeerId::random(), bytes).await {
+            JobRelayResponse::Err { reason } => assert!(reason.contains("load-only"), "{reason}"),
+            other => panic!("expected Err, got {other:?}"),
+        }
+        assert_eq!(spy.call_count(), 0, "a peer must not trigger a model load");
+    }
+
     #[tokio::test]
     async fn sec06_concurrency_cap_rejects_n_plus_one() {
         // A worker that blocks until released, so we can hold N permits and
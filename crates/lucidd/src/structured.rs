@@ -0,0 +1,374 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Structured output — enforcing an [`OutputConstraint`] on generated text.
+//!
+//! Two layers, deliberately redundant:
+//!
+//! 1. **Constrained decoding.** [`apply_to_completion_body`] adds the
+//!    constraint to the llama-server `/completion` request: JSON mode ships
+//!    a GBNF grammar for "any JSON object", schema mode ships the schema as
+//!    llama-server's `json_schema` parameter (which llama.cpp converts to a
+//!    grammar itself). Sampling can then only produce conforming tokens.
+//!    OpenAI-compatible upstreams get the equivalent `response_format`
+//!    ([`apply_to_chat_completions_body`]).
+//! 2. **Post-hoc validation.** [`check_output`] re-parses the finished text
+//!    and validates it before the worker emits `Final`. Grammar sampling
+//!    can't help with truncation, a backend that silently ignores the
+//!    grammar, or a schema keyword the converter doesn't support — and the
+//!    receipt is supposed to attest to what came out, not to what we asked
+//!    for.
+//!
+//! ## Why a hand-rolled validator?
+//!
+//! The full JSON Schema spec (`$ref`, `$dynamicRef`, formats, remote
+//! documents) is a large dependency for what is a belt-and-braces check.
+//! [`validate`] covers the keywords that structured-output schemas in
+//! practice use — `type`, `enum`, `const`, `properties`, `required`,
+//! `additionalProperties`, `items`, `anyOf` / `oneOf` / `allOf`, and the
+//! numeric / length / item-count bounds. Unknown keywords are ignored
+//! (JSON Schema's own rule), so a schema we don't fully understand can only
+//! make validation more permissive, never reject conforming output.
+
+use phase_protocol::OutputConstraint;
+use serde_json::{Map, Value};
+
+/// GBNF for a single JSON object. Adapted from llama.cpp's
+/// `grammars/json.gbnf`; matches Ollama's `format: "json"` behaviour, which
+/// always yields an object at the top level.
+pub const JSON_OBJECT_GBNF: &str = r#"root   ::= object
+value  ::= object | array | string | number | ("true" | "false" | "null") ws
+object ::= "{" ws ( string ":" ws value ("," ws string ":" ws value)* )? "}" ws
+array  ::= "[" ws ( value ("," ws value)* )? "]" ws
+string ::= "\"" ( [^"\\\x7F\x00-\x1F] | "\\" (["\\bfnrt] | "u" [0-9a-fA-F]{4}) )* "\"" ws
+number ::= ("-"? ([0-9] | [1-9] [0-9]{0,15})) ("." [0-9]+)? ([eE] [-+]? [0-9] [1-9]{0,15})? ws
+ws     ::= | " " | "\n" [ \t]{0,20}
+"#;
+
+/// Reject constraints the worker can't act on before any GPU time is
+/// spent. Today that's only an unparseable schema.
+pub fn check_constraint(constraint: &OutputConstraint) -> Result<(), String> {
+    match constraint {
+        OutputConstraint::Json => Ok(()),
+        OutputConstraint::JsonSchema { schema } => match serde_json::from_str::<Value>(schema) {
+            Ok(Value::Object(_)) | Ok(Value::Bool(_)) => Ok(()),
+            Ok(_) => Err("json schema must be an object or boolean".to_string()),
+            Err(e) => Err(format!("json schema does not parse: {e}")),
+        },
+    }
+}
+
+/// Add the constraint to a llama-server `/completion` request body.
+pub fn apply_to_completion_body(constraint: &OutputConstraint, body: &mut Map<String, Value>) {
+    match constraint {
+        OutputConstraint::Json => {
+            body.insert(
+                "grammar".to_string(),
+                Value::String(JSON_OBJECT_GBNF.to_string()),
+            );
+        }
+        OutputConstraint::JsonSchema { schema } => {
+            // `check_constraint` ran at dispatch; a parse failure here is
+            // unreachable in practice, and dropping the parameter still
+            // leaves post-hoc validation in force.
+            if let Ok(v) = serde_json::from_str::<Value>(schema) {
+                body.insert("json_schema".to_string(), v);
+            }
+        }
+    }
+}
+
+/// Add the constraint to an OpenAI `/v1/chat/completions` request body as
+/// `response_format`. Upstreams that ignore it are still caught by
+/// [`check_output`].
+pub fn apply_to_chat_completions_body(constraint: &OutputConstraint, body: &mut Map<String, Value>) {
+    let format = match constraint {
+        OutputConstraint::Json => serde_json::json!({"type": "json_object"}),
+        OutputConstraint::JsonSchema { schema } => match serde_json::from_str::<Value>(schema) {
+            Ok(schema) => serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {"name": "output", "schema": schema, "strict": true},
+            }),
+            // Same reasoning as `apply_to_completion_body`.
+            Err(_) => return,
+        },
+    };
+    body.insert("response_format".to_string(), format);
+}
+
+/// Validate finished output against the constraint. `Err` carries a short
+/// human-readable reason (with a JSON-path-ish location for schema
+/// failures) that goes into the `Final` event's error field.
+pub fn check_output(constraint: &OutputConstraint, text: &str) -> Result<(), String> {
+    let value: Value =
+        serde_json::from_str(text.trim()).map_err(|e| format!("output is not valid JSON: {e}"))?;
+    match constraint {
+        OutputConstraint::Json => {
+            if value.is_object() {
+                Ok(())
+            } else {
+                Err("output is not a JSON object".to_string())
+            }
+        }
+        OutputConstraint::JsonSchema { schema } => {
+            let schema: Value = serde_json::from_str(schema)
+                .map_err(|e| format!("json schema does not parse: {e}"))?;
+            validate(&value, &schema, "$")
+        }
+    }
+}
+
+/// Validate `value` against `schema` (see module docs for the supported
+/// keyword subset). `path` is the location reported on failure.
+pub fn validate(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{path}: schema `false` admits nothing")),
+        Value::Object(s) => s,
+        // Not a schema; nothing to enforce.
+        _ => return Ok(()),
+    };
+
+    if let Some(ty) = schema.get("type") {
+        let matches = match ty {
+            Value::String(t) => type_matches(value, t),
+            Value::Array(ts) => ts
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|t| type_matches(value, t)),
+            _ => true,
+        };
+        if !matches {
+            return Err(format!("{path}: expected type {ty}"));
+        }
+    }
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            return Err(format!("{path}: value not in enum"));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(format!("{path}: value does not equal const"));
+        }
+    }
+
+    if let Some(Value::Array(subs)) = schema.get("allOf") {
+        for sub in subs {
+            validate(value, sub, path)?;
+        }
+    }
+    if let Some(Value::Array(subs)) = schema.get("anyOf") {
+        if !subs.iter().any(|sub| validate(value, sub, path).is_ok()) {
+            return Err(format!("{path}: matches none of anyOf"));
+        }
+    }
+    if let Some(Value::Array(subs)) = schema.get("oneOf") {
+        let n = subs
+            .iter()
+            .filter(|sub| validate(value, sub, path).is_ok())
+            .count();
+        if n != 1 {
+            return Err(format!("{path}: matches {n} of oneOf, expected exactly 1"));
+        }
+    }
+
+    match value {
+        Value::Object(obj) => validate_object(obj, schema, path)?,
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                if (items.len() as u64) < min {
+                    return Err(format!("{path}: fewer than {min} items"));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                if (items.len() as u64) > max {
+                    return Err(format!("{path}: more than {max} items"));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate(item, item_schema, &format!("{path}[{i}]"))?;
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    return Err(format!("{path}: shorter than {min} chars"));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    return Err(format!("{path}: longer than {max} chars"));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let x = n.as_f64().unwrap_or(f64::NAN);
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if x < min {
+                    return Err(format!("{path}: below minimum {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if x > max {
+                    return Err(format!("{path}: above maximum {max}"));
+                }
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+    Ok(())
+}
+
+fn validate_object(
+    obj: &Map<String, Value>,
+    schema: &Map<String, Value>,
+    path: &str,
+) -> Result<(), String> {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for key in required.iter().filter_map(Value::as_str) {
+            if !obj.contains_key(key) {
+                return Err(format!("{path}: missing required property {key:?}"));
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (key, v) in obj {
+        let child = format!("{path}.{key}");
+        match properties.and_then(|p| p.get(key)) {
+            Some(prop_schema) => validate(v, prop_schema, &child)?,
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    return Err(format!("{path}: unexpected property {key:?}"));
+                }
+                Some(extra @ Value::Object(_)) => validate(v, extra, &child)?,
+                _ => {}
+            },
+        }
+    }
+    Ok(())
+}
+
+fn type_matches(value: &Value, ty: &str) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => match value {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        // Unknown type name — don't reject on something we can't judge.
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema(v: Value) -> OutputConstraint {
+        OutputConstraint::JsonSchema {
+            schema: v.to_string(),
+        }
+    }
+
+    #[test]
+    fn json_mode_requires_an_object() {
+        assert!(check_output(&OutputConstraint::Json, " {\"a\": 1}\n").is_ok());
+        assert!(check_output(&OutputConstraint::Json, "[1, 2]").is_err());
+        assert!(check_output(&OutputConstraint::Json, "{\"a\": ").is_err());
+    }
+
+    #[test]
+    fn schema_accepts_conforming_output() {
+        let c = schema(json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        }));
+        assert!(check_output(&c, r#"{"name": "Ada", "age": 36, "tags": ["a"]}"#).is_ok());
+    }
+
+    #[test]
+    fn schema_reports_location_of_violation() {
+        let c = schema(json!({
+            "type": "object",
+            "properties": {
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["tags"],
+            "additionalProperties": false
+        }));
+        let err = check_output(&c, r#"{"tags": ["ok", 7]}"#).unwrap_err();
+        assert!(err.starts_with("$.tags[1]:"), "{err}");
+        let err = check_output(&c, r#"{}"#).unwrap_err();
+        assert!(err.contains("missing required property \"tags\""), "{err}");
+        let err = check_output(&c, r#"{"tags": [], "extra": 1}"#).unwrap_err();
+        assert!(err.contains("unexpected property \"extra\""), "{err}");
+    }
+
+    #[test]
+    fn combinators_and_unknown_keywords() {
+        let c = schema(json!({
+            "anyOf": [{"type": "string"}, {"type": "number"}],
+            "format": "whatever-we-do-not-implement"
+        }));
+        assert!(check_output(&c, "3").is_ok());
+        assert!(check_output(&c, "\"x\"").is_ok());
+        assert!(check_output(&c, "true").is_err());
+        let one_of = schema(json!({"oneOf": [{"type": "integer"}, {"type": "number"}]}));
+        // 3 is both an integer and a number → matches two branches.
+        assert!(check_output(&one_of, "3").is_err());
+        assert!(check_output(&one_of, "3.5").is_ok());
+    }
+
+    #[test]
+    fn check_constraint_rejects_unparseable_schema() {
+        assert!(check_constraint(&OutputConstraint::Json).is_ok());
+        assert!(check_constraint(&schema(json!({"type": "object"}))).is_ok());
+        let bad = OutputConstraint::JsonSchema {
+            schema: "{not json".to_string(),
+        };
+        assert!(check_constraint(&bad).is_err());
+        let not_obj = OutputConstraint::JsonSchema {
+            schema: "42".to_string(),
+        };
+        assert!(check_constraint(&not_obj).is_err());
+    }
+
+    #[test]
+    fn request_body_carries_grammar_or_schema() {
+        let mut body = Map::new();
+        apply_to_completion_body(&OutputConstraint::Json, &mut body);
+        assert!(body["grammar"].as_str().unwrap().starts_with("root"));
+        let mut body = Map::new();
+        apply_to_completion_body(&schema(json!({"type": "object"})), &mut body);
+        assert_eq!(body["json_schema"], json!({"type": "object"}));
+        assert!(!body.contains_key("grammar"));
+    }
+
+    #[test]
+    fn chat_completions_body_carries_response_format() {
+        let mut body = Map::new();
+        apply_to_chat_completions_body(&OutputConstraint::Json, &mut body);
+        assert_eq!(body["response_format"], json!({"type": "json_object"}));
+        let mut body = Map::new();
+        apply_to_chat_completions_body(&schema(json!({"type": "object"})), &mut body);
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["schema"], json!({"type": "object"}));
+    }
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Image input for vision (llava / mmproj-style) models.
+//!
+//! Images travel in [`ChatMessage::images`] as base64, the way Ollama
+//! clients send them. Base64 is not canonical — padding, line wrapping and
+//! `data:` URI prefixes all yield different strings for the same bytes —
+//! so the edge also records the SHA-256 of each *decoded* image in
+//! [`ChatMessage::image_digests`]. Both fields are part of the signed
+//! manifest, and the receipt's `job_spec_hash` is the manifest hash, so a
+//! receipt commits to the exact pixels the model saw.
+//!
+//! The worker re-derives every digest before spending GPU time
+//! ([`verify_image_digests`]); a relaying peer that swaps an image has to
+//! re-sign the manifest, which breaks the client's signature.
+
+use base64::Engine as _;
+use phase_protocol::{ChatMessage, InferenceJobSpec};
+use sha2::{Digest, Sha256};
+
+/// Placeholder llama-server's multimodal (`mtmd`) prompt parser replaces
+/// with the embedding of the next entry in `multimodal_data`.
+pub const MEDIA_MARKER: &str = "<__media__>";
+
+/// Decode a client-supplied image. Accepts standard base64 with or without
+/// padding and tolerates embedded whitespace (some clients wrap at 76
+/// columns), plus an optional `data:<mime>;base64,` prefix.
+pub fn decode_image(b64: &str) -> Result<Vec<u8>, String> {
+    let body = match b64.split_once(";base64,") {
+        Some((prefix, rest)) if prefix.starts_with("data:") => rest,
+        _ => b64,
+    };
+    let compact: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let trimmed = compact.trim_end_matches('=');
+    base64::engine::general_purpose::STANDARD_NO_PAD
+        .decode(trimmed)
+        .map_err(|e| format!("image is not valid base64: {e}"))
+}
+
+/// Lowercase hex SHA-256 of decoded image bytes.
+pub fn image_digest(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let mut s = String::with_capacity(64);
+    for byte in digest {
+        s.push_str(&format!("{byte:02x}"));
+    }
+    s
+}
+
+/// Fill in `image_digests` for a message about to be signed. Overwrites
+/// whatever the client sent — the edge is the party that signs, so it
+/// computes the digests itself.
+pub fn attach_image_digests(message: &mut ChatMessage) -> Result<(), String> {
+    message.image_digests = message
+        .images
+        .iter()
+        .map(|b64| decode_image(b64).map(|bytes| image_digest(&bytes)))
+        .collect::<Result<_, _>>()?;
+    Ok(())
+}
+
+/// True if any message in the spec carries an image.
+pub fn has_images(spec: &InferenceJobSpec) -> bool {
+    spec.messages.iter().any(|m| !m.images.is_empty())
+}
+
+/// Check every image against its signed digest and rewrite it in place as
+/// canonical padded base64, the form llama-server's `multimodal_data`
+/// accepts. Operates on the worker's copy of the spec; the signed manifest
+/// is untouched.
+pub fn verify_image_digests(spec: &mut InferenceJobSpec) -> Result<(), String> {
+    for (m_idx, message) in spec.messages.iter_mut().enumerate() {
+        if message.images.len() != message.image_digests.len() {
+            return Err(format!(
+                "message {m_idx}: {} images but {} image digests",
+                message.images.len(),
+                message.image_digests.len()
+            ));
+        }
+        for (i_idx, (b64, expected)) in
+            message.images.iter_mut().zip(&message.image_digests).enumerate()
+        {
+            let bytes = decode_image(b64).map_err(|e| format!("message {m_idx} image {i_idx}: {e}"))?;
+            if !image_digest(&bytes).eq_ignore_ascii_case(expected) {
+                return Err(format!("message {m_idx} image {i_idx}: digest mismatch"));
+            }
+            *b64 = base64::engine::general_purpose::STANDARD.encode(&bytes);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phase_protocol::{ChatRole, SamplingParams};
+
+    const PIXELS: &[u8] = b"\x89PNG\r\n\x1a\n not really a png";
+
+    fn message(images: Vec<String>) -> ChatMessage {
+        ChatMessage {
+            role: ChatRole::User,
+            content: "what is this?".into(),
+            images,
+            image_digests: vec![],
+            tool_calls: vec![],
+            tool_name: None,
+            tool_call_id: None,
+        }
+    }
+
+    fn spec(messages: Vec<ChatMessage>) -> InferenceJobSpec {
+        InferenceJobSpec {
+            model_cid: "llava".into(),
+            messages,
+            prompt: None,
+            resume_from: None,
+            sampling: SamplingParams::default(),
+            max_tokens: None,
+            stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
+        }
+    }
+
+    #[test]
+    fn digest_ignores_base64_presentation() {
+        let padded = base64::engine::general_purpose::STANDARD.encode(PIXELS);
+        let unpadded = padded.trim_end_matches('=').to_string();
+        let wrapped = format!("data:image/png;base64,{}\n{}", &padded[..8], &padded[8..]);
+        let want = image_digest(PIXELS);
+        for form in [padded, unpadded, wrapped] {
+            assert_eq!(image_digest(&decode_image(&form).unwrap()), want, "{form}");
+        }
+    }
+
+    #[test]
+    fn attached_digests_verify_and_reencode() {
+        let b64 = base64::engine::general_purpose::STANDARD.encode(PIXELS);
+        let mut msg = message(vec![b64.trim_end_matches('=').to_string()]);
+        attach_image_digests(&mut msg).unwrap();
+        assert_eq!(msg.image_digests, vec![image_digest(PIXELS)]);
+        let mut spec = spec(vec![msg]);
+        verify_image_digests(&mut spec).unwrap();
+        assert_eq!(spec.messages[0].images, vec![b64]);
+    }
+
+    #[test]
+    fn swapped_or_missing_digest_is_rejected() {
+        let b64 = base64::engine::general_purpose::STANDARD.encode(PIXELS);
+        let mut msg = message(vec![b64]);
+        let err = verify_image_digests(&mut spec(vec![msg.clone()])).unwrap_err();
+        assert!(err.contains("1 images but 0"), "{err}");
+
+        msg.image_digests = vec![image_digest(b"other pixels")];
+        let err = verify_image_digests(&mut spec(vec![msg])).unwrap_err();
+        assert!(err.contains("digest mismatch"), "{err}");
+    }
+
+    #[test]
+    fn invalid_base64_is_rejected() {
+        let mut msg = message(vec!["not base64!".into()]);
+        assert!(attach_image_digests(&mut msg).is_err());
+    }
+}
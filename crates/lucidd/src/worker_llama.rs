@@ -25,6 +25,13 @@
 //!    Three failures within the rolling 60 s window stop the loop and emit
 //!    a "failed" sentinel.
 //!
+//! A fourth arm enforces **keep-alive**: once no job holds the model, the
+//! supervisor sleeps until the residency deadline set by the last job's
+//! [`KeepAlive`] and then unloads the model — kills the child, drops the
+//! map entry and frees the port. `KeepAlive::Forever` pins the model
+//! (only SEC-07 LRU eviction can unload it); a zero duration unloads as
+//! soon as it goes idle.
+//!
 //! Per-request hang detection lives in [`stream_completion`]: if no SSE
 //! frame arrives for 30 s the request is aborted and the underlying model
 //! is signalled as suspect (next request triggers a health check).
@@ -41,19 +48,42 @@
 //! would handle the template — but rendering ourselves keeps the worker
 //! deterministic across llama-server versions.
 //!
+//! ## Vision models
+//!
+//! A model ships its multimodal projector next to the weights as
+//! `<model_id>.mmproj.gguf`. When that file exists it is passed to
+//! `llama-server --mmproj`, and image-bearing requests send a
+//! `{prompt_string, multimodal_data}` prompt object with one
+//! [`vision::MEDIA_MARKER`] per image. Images are checked against their
+//! signed digests before dispatch (see [`crate::vision`]).
+//!
+//! ## Conversation resumption
+//!
+//! Every turn that ends in `Stop` or `Length` records what the serving
+//! slot now holds (prompt plus output) and returns a signed
+//! [`ConversationToken`](phase_protocol::ConversationToken) pointing at it
+//! in `JobResult::resumption`, valid for the model's keep-alive capped at
+//! [`DEFAULT_RESUMPTION_GRACE`](phase_protocol::DEFAULT_RESUMPTION_GRACE).
+//! A follow-up carrying a token that still matches the slot is sent with
+//! `id_slot` so llama-server reuses the cached prefix; a raw-prompt
+//! follow-up is also prefixed with the recorded transcript, which is what
+//! Ollama's `context` means. Anything stale or forged just prefills cold
+//! (see [`crate::resume`]).
+//!
 //! ## What this file deliberately does NOT do
 //!
-//! - Eviction policy beyond crash handling (LUCID M6 — model registry +
-//!   DHT-aware eviction).
+//! - Eviction policy beyond crash handling, keep-alive expiry and the
+//!   resident-model cap (LUCID M6 — model registry + DHT-aware eviction).
 //! - Model downloads (artifact-server's job; we expect GGUFs to already be
 //!   in `model_dir`).
 //! - Quantization or backend-selection logic — those are flag-string
 //!   knobs on [`LlamaCppConfig`] that callers populate.
 
+use std::collections::HashMap;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
 use async_stream::stream;
@@ -63,8 +93,8 @@ use futures::StreamExt;
 use phase_identity::NodeIdentity;
 use phase_protocol::{
     ChatRole, CommitmentAccumulator, Completion, InferenceJobSpec, JobEvent, JobHandle,
-    JobHandleProducer, JobId, JobMetrics, JobResult, JobSpec, JobSpecKind, JobStream, OutputChunk,
-    SignedManifest, Worker, WorkerError,
+    JobHandleProducer, JobId, JobMetrics, JobResult, JobSpec, JobSpecKind, JobStream, KeepAlive,
+    OutputChunk, SignedManifest, ToolCall, ToolDefinition, Worker, WorkerError,
 };
 use phase_receipt::ReceiptBuilder;
 use serde::Deserialize;
@@ -73,6 +103,10 @@ use tokio::process::{Child, Command};
 use tokio::sync::{Mutex, Notify};
 use tokio::time::timeout;
 
+use crate::resume::{self, SlotRef};
+use crate::structured;
+use crate::vision;
+
 /// Configuration for [`LlamaCppWorker`].
 ///
 /// Carriers (CLI flags, env vars, config files) populate this; the worker
@@ -118,6 +152,10 @@ pub struct LlamaCppConfig {
     /// suspect. 30 s default matches the research brief's hang guidance.
     pub per_request_idle_timeout: Duration,
 
+    /// Residency after a job whose spec carries no `keep_alive`. 5 min
+    /// default, matching Ollama's `OLLAMA_KEEP_ALIVE`.
+    pub default_keep_alive: KeepAlive,
+
     /// Extra environment variables to set on the `llama-server` child.
     /// Production callers typically leave this empty; the test fixture
     /// uses it to configure the in-tree `fake-llama-server` per-spawn
@@ -138,6 +176,7 @@ impl Default for LlamaCppConfig {
             max_loaded_models: 3,
             model_load_timeout: Duration::from_secs(60),
             per_request_idle_timeout: Duration::from_secs(30),
+            default_keep_alive: KeepAlive::Duration { ms: 5 * 60 * 1000 },
             extra_env: Vec::new(),
         }
     }
@@ -153,9 +192,11 @@ impl Default for LlamaCppConfig {
 struct LoadedModel {
     /// Bound port — used to construct `http://127.0.0.1:{port}/completion`.
     port: u16,
+    /// Whether `llama-server` was started with a multimodal projector.
+    /// Image-bearing requests against a text-only load are refused.
+    vision: bool,
     /// Model alias the caller used to request this load. Stable for the
     /// life of the LoadedModel; eviction creates a new entry.
-    #[allow(dead_code)] // surfaced via metrics in LUCID M6
     model_id: String,
     /// First time the worker saw this model. Useful for "uptime since
     /// load" telemetry and stale-state debugging.
@@ -172,11 +213,144 @@ struct LoadedModel {
     /// extra retry that will hit `failed.notified()` immediately.
     failed_flag: Arc<std::sync::atomic::AtomicBool>,
     /// Join handle for the supervisor task. Held so we can abort it on
-    /// `Drop` of the [`LlamaCppWorker`] or on LRU eviction.
-    supervisor: tokio::task::JoinHandle<()>,
+    /// `Drop` of the [`LlamaCppWorker`] or on LRU eviction; taken by
+    /// [`LoadedModel::shutdown_and_wait`].
+    supervisor: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    /// Keep-alive state, shared with the supervisor that enforces it.
+    residency: Arc<Residency>,
+    /// Random per load, bound into resumption tokens so one issued
+    /// against a previous subprocess (whose KV cache is gone) is ignored.
+    generation: u64,
+    /// What each llama-server slot last served, keyed by `id_slot`. A
+    /// token resumes only while its nonce is still the slot's latest.
+    sessions: std::sync::Mutex<HashMap<u32, SlotSession>>,
+}
+
+/// The conversation a slot's KV cache currently holds.
+struct SlotSession {
+    nonce: u64,
+    /// Prompt plus output of the turn that filled the slot.
+    transcript: String,
+}
+
+/// Slot ids above this aren't tracked. llama-server's `--parallel` is in
+/// the single digits in practice; the cap keeps a misbehaving server from
+/// growing the session map without bound.
+const MAX_TRACKED_SLOTS: u32 = 64;
+
+/// Keep-alive bookkeeping for one [`LoadedModel`]. The std `Mutex` is
+/// never held across an `.await` — every critical section is a couple of
+/// field updates.
+struct Residency {
+    state: std::sync::Mutex<ResidencyState>,
+    /// Poked whenever the deadline may have moved so the supervisor
+    /// re-arms its timer. `notify_one`, so a poke that lands before the
+    /// supervisor starts waiting isn't lost.
+    changed: Notify,
+}
+
+struct ResidencyState {
+    /// Jobs currently holding a [`ModelLease`]. Never expires while > 0.
+    in_flight: usize,
+    /// Idle deadline; `None` = pinned.
+    expires_at: Option<Instant>,
+    /// Set by the supervisor, under the lock, when it commits to
+    /// unloading. No lease can be taken afterwards.
+    expired: bool,
+}
+
+impl Residency {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            state: std::sync::Mutex::new(ResidencyState {
+                in_flight: 0,
+                expires_at: Some(Instant::now()),
+                expired: false,
+            }),
+            changed: Notify::new(),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ResidencyState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// When the supervisor should next try to unload, or `None` while a
+    /// job is running or the model is pinned.
+    fn deadline(&self) -> Option<Instant> {
+        let state = self.lock();
+        if state.in_flight > 0 {
+            return None;
+        }
+        state.expires_at
+    }
+
+    /// Commit to unloading if the model is idle and past its deadline.
+    /// Atomic with [`LoadedModel::lease`], so a job either gets its lease
+    /// first or sees the model as gone.
+    fn try_expire(&self) -> bool {
+        let mut state = self.lock();
+        let due = state.in_flight == 0
+            && state.expires_at.is_some_and(|at| at <= Instant::now());
+        if due {
+            state.expired = true;
+        }
+        due
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.lock().expires_at.is_none()
+    }
+}
+
+/// A job's hold on a [`LoadedModel`]. While any lease is alive the model
+/// can't expire; dropping the last one starts the idle clock from this
+/// job's [`KeepAlive`] — the most recent request wins, as in Ollama.
+/// Dropped when the job's stream finishes *or* is dropped by the caller.
+struct ModelLease {
+    model: Arc<LoadedModel>,
+    keep_alive: KeepAlive,
+}
+
+impl std::ops::Deref for ModelLease {
+    type Target = LoadedModel;
+    fn deref(&self) -> &LoadedModel {
+        &self.model
+    }
+}
+
+impl Drop for ModelLease {
+    fn drop(&mut self) {
+        let mut state = self.model.residency.lock();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        state.expires_at = match self.keep_alive {
+            KeepAlive::Duration { ms } => Some(Instant::now() + Duration::from_millis(ms)),
+            KeepAlive::Forever => None,
+        };
+        drop(state);
+        self.model.residency.changed.notify_one();
+    }
 }
 
 impl LoadedModel {
+    /// Take a lease for a job, or `None` if the model has been (or is
+    /// being) unloaded.
+    fn lease(self: &Arc<Self>, keep_alive: KeepAlive) -> Option<ModelLease> {
+        if self.failed_flag.load(std::sync::atomic::Ordering::Acquire) {
+            return None;
+        }
+        let mut state = self.residency.lock();
+        if state.expired {
+            return None;
+        }
+        state.in_flight += 1;
+        drop(state);
+        Some(ModelLease {
+            model: self.clone(),
+            keep_alive,
+        })
+    }
+
     /// Tear this model down: mark it failed (so any in-flight request
     /// bails and a concurrent `ensure_loaded` won't hand it out), then
     /// abort the supervisor task. The supervisor owns the `llama-server`
@@ -187,7 +361,60 @@ impl LoadedModel {
         self.failed_flag
             .store(true, std::sync::atomic::Ordering::Release);
         self.failed.notify_waiters();
-        self.supervisor.abort();
+        if let Some(supervisor) = self.supervisor_handle().as_ref() {
+            supervisor.abort();
+        }
+    }
+
+    /// [`shutdown`](Self::shutdown), then wait until the aborted
+    /// supervisor has actually been dropped — and with it the `Child`.
+    /// `abort()` only takes effect the next time the runtime polls the
+    /// task, so a caller that frees the port without waiting can hand it
+    /// to a new `llama-server` while the old one is still listening.
+    async fn shutdown_and_wait(&self) {
+        self.shutdown();
+        let supervisor = self.supervisor_handle().take();
+        if let Some(supervisor) = supervisor {
+            let _ = supervisor.await;
+        }
+    }
+
+    fn supervisor_handle(
+        &self,
+    ) -> std::sync::MutexGuard<'_, Option<tokio::task::JoinHandle<()>>> {
+        self.supervisor.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn session_map(&self) -> std::sync::MutexGuard<'_, HashMap<u32, SlotSession>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The transcript a token's slot still holds, or `None` if the token
+    /// is from another load or the slot has moved on to another turn.
+    fn resumable(&self, slot: &SlotRef) -> Option<String> {
+        if slot.generation != self.generation {
+            return None;
+        }
+        self.session_map()
+            .get(&slot.slot)
+            .filter(|session| session.nonce == slot.nonce)
+            .map(|session| session.transcript.clone())
+    }
+
+    /// Note that `slot` now holds `transcript`, superseding any token
+    /// issued for it earlier.
+    fn record_session(&self, slot: u32, transcript: String) -> Option<SlotRef> {
+        if slot >= MAX_TRACKED_SLOTS {
+            return None;
+        }
+        let nonce = uuid::Uuid::new_v4().as_u64_pair().0;
+        self.session_map()
+            .insert(slot, SlotSession { nonce, transcript });
+        Some(SlotRef {
+            generation: self.generation,
+            slot,
+            nonce,
+        })
     }
 }
 
@@ -235,24 +462,40 @@ impl LlamaCppWorker {
         }
     }
 
-    /// Ensure a model is loaded. Idempotent — if the model is already
-    /// loaded, returns the existing entry. If not, spawns a new
-    /// `llama-server` subprocess and waits for `/health` to go green
-    /// before returning.
-    async fn ensure_loaded(&self, model_id: &str) -> Result<Arc<LoadedModel>, WorkerError> {
-        if let Some(existing) = self.inner.loaded_models.get(model_id) {
-            if !existing
-                .failed_flag
-                .load(std::sync::atomic::Ordering::Acquire)
-            {
-                return Ok(existing.clone());
-            }
-            // The previous load has been declared dead; drop it and try
-            // again. The supervisor's `kill()` already ran.
-            let stale_port = existing.port;
-            drop(existing);
-            self.inner.loaded_models.remove(model_id);
-            self.release_port(stale_port).await;
+    /// Lease an already-loaded model, clearing out a dead or expired
+    /// entry if that's what the map holds. Never spawns.
+    async fn lease_loaded(&self, model_id: &str, keep_alive: KeepAlive) -> Option<ModelLease> {
+        let existing = self.inner.loaded_models.get(model_id)?.clone();
+        if let Some(lease) = existing.lease(keep_alive) {
+            return Some(lease);
+        }
+        // The previous load has been declared dead (or is expiring); drop
+        // it. The supervisor's `kill()` already ran or is about to. Only
+        // whoever removes the entry frees the port — an expiring
+        // supervisor may have beaten us to both, and the port may since
+        // belong to a fresh load.
+        if self
+            .inner
+            .loaded_models
+            .remove_if(model_id, |_, m| Arc::ptr_eq(m, &existing))
+            .is_some()
+        {
+            self.release_port(existing.port).await;
+        }
+        None
+    }
+
+    /// Ensure a model is loaded and lease it for one job. Idempotent — if
+    /// the model is already loaded, leases the existing entry. If not,
+    /// spawns a new `llama-server` subprocess and waits for `/health` to
+    /// go green before returning.
+    async fn ensure_loaded(
+        &self,
+        model_id: &str,
+        keep_alive: KeepAlive,
+    ) -> Result<ModelLease, WorkerError> {
+        if let Some(lease) = self.lease_loaded(model_id, keep_alive).await {
+            return Ok(lease);
         }
 
         // SEC-04: confine the resolved path to `model_dir`. Any traversal
@@ -275,6 +518,12 @@ impl LlamaCppWorker {
                 }
             };
 
+        // The projector is optional — most models are text-only — so a
+        // resolution failure just means "no vision". It goes through the
+        // same SEC-04 confinement as the weights.
+        let projector_path =
+            resolve_projector_path(&self.inner.config.model_dir, model_id).ok();
+
         // SEC-07: enforce the resident-model cap before spawning. If we're
         // at the cap, evict the least-recently-used model first. Done
         // before `allocate_port` so the freed port is available to the new
@@ -285,6 +534,7 @@ impl LlamaCppWorker {
         let child = match spawn_llama_server(
             &self.inner.config.server_binary_path,
             &model_path,
+            projector_path.as_deref(),
             port,
             self.inner.config.default_n_gpu_layers,
             self.inner.config.default_context_size,
@@ -309,6 +559,10 @@ impl LlamaCppWorker {
 
         let failed = Arc::new(Notify::new());
         let failed_flag = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let residency = Residency::new();
+        // Count this job in before the supervisor can look at the
+        // deadline; the matching `ModelLease` is built below.
+        residency.lock().in_flight = 1;
 
         let model_id_owned = model_id.to_string();
         let supervisor_input = SupervisorInput {
@@ -319,6 +573,9 @@ impl LlamaCppWorker {
             client: self.inner.client.clone(),
             config: self.inner.config.clone(),
             model_path: model_path.clone(),
+            projector_path: projector_path.clone(),
+            residency: residency.clone(),
+            worker: Arc::downgrade(&self.inner),
         };
 
         // The supervisor task gets the child handle (so it can wait/kill).
@@ -329,12 +586,16 @@ impl LlamaCppWorker {
 
         let loaded = Arc::new(LoadedModel {
             port,
+            vision: projector_path.is_some(),
             model_id: model_id_owned.clone(),
             loaded_at: Instant::now(),
             last_used: Mutex::new(Instant::now()),
             failed,
             failed_flag,
-            supervisor,
+            supervisor: std::sync::Mutex::new(Some(supervisor)),
+            residency,
+            generation: uuid::Uuid::new_v4().as_u64_pair().0,
+            sessions: std::sync::Mutex::new(HashMap::new()),
         });
         // A concurrent `ensure_loaded` for the same id could have raced us
         // to a winning load; if `insert` replaces a live entry, shut the
@@ -345,11 +606,14 @@ impl LlamaCppWorker {
             .insert(model_id_owned, loaded.clone())
         {
             if prev.port != port {
-                prev.shutdown();
+                prev.shutdown_and_wait().await;
                 self.release_port(prev.port).await;
             }
         }
-        Ok(loaded)
+        Ok(ModelLease {
+            model: loaded,
+            keep_alive,
+        })
     }
 
     /// SEC-07: allocate a port not currently bound by a live child. Scans
@@ -381,9 +645,11 @@ impl LlamaCppWorker {
 
     /// SEC-07: if the worker is already at `max_loaded_models`, evict the
     /// least-recently-used model (by `last_used`) to make room for a new
-    /// one. The model currently being (re)loaded — `incoming` — is never a
+    /// one. Models pinned with `KeepAlive::Forever` are only chosen when
+    /// every candidate is pinned — the cap is a hard bound, a pin is not.
+    /// The model currently being (re)loaded — `incoming` — is never a
     /// candidate. The evicted model's subprocess is killed via
-    /// [`LoadedModel::shutdown`] and its port released.
+    /// [`LoadedModel::shutdown_and_wait`] and its port released.
     async fn evict_lru_if_at_cap(&self, incoming: &str) {
         let cap = self.inner.config.max_loaded_models.max(1);
         // Evict in a loop in case we're over cap (e.g. cap was lowered or
@@ -395,7 +661,7 @@ impl LlamaCppWorker {
             // Find the LRU victim. `last_used` is a `Mutex<Instant>`; read
             // each under its lock. We hold no DashMap shard lock across the
             // await by collecting candidates first.
-            let mut victim: Option<(String, Instant)> = None;
+            let mut victim: Option<(String, (bool, Instant))> = None;
             let candidates: Vec<(String, Arc<LoadedModel>)> = self
                 .inner
                 .loaded_models
@@ -404,10 +670,10 @@ impl LlamaCppWorker {
                 .map(|e| (e.key().clone(), e.value().clone()))
                 .collect();
             for (id, model) in &candidates {
-                let used = *model.last_used.lock().await;
+                let rank = (model.residency.is_pinned(), *model.last_used.lock().await);
                 match &victim {
-                    Some((_, t)) if *t <= used => {}
-                    _ => victim = Some((id.clone(), used)),
+                    Some((_, best)) if *best <= rank => {}
+                    _ => victim = Some((id.clone(), rank)),
                 }
             }
             let Some((victim_id, _)) = victim else {
@@ -418,7 +684,7 @@ impl LlamaCppWorker {
             };
             if let Some((_, model)) = self.inner.loaded_models.remove(&victim_id) {
                 tracing::info!(model = %victim_id, "evicting LRU model to honour max_loaded_models");
-                model.shutdown();
+                model.shutdown_and_wait().await;
                 self.release_port(model.port).await;
             }
             // Re-check the cap; another concurrent loader may have changed
@@ -437,7 +703,7 @@ impl Worker for LlamaCppWorker {
         &self,
         job: SignedManifest<JobSpec>,
     ) -> Result<(JobHandle, JobStream), WorkerError> {
-        let inference = match &job.payload {
+        let mut inference = match &job.payload {
             JobSpec::Inference(spec) => spec.clone(),
             other => {
                 return Err(WorkerError::Unsupported {
@@ -446,16 +712,60 @@ impl Worker for LlamaCppWorker {
             }
         };
 
+        if let Some(constraint) = &inference.output_constraint {
+            structured::check_constraint(constraint).map_err(WorkerError::BadManifest)?;
+        }
+        vision::verify_image_digests(&mut inference).map_err(WorkerError::BadManifest)?;
+
         let manifest_hash = job
             .manifest_hash()
             .map_err(|e| WorkerError::BadManifest(e.to_string()))?;
         let job_id = JobId(manifest_hash);
+        let keep_alive = inference
+            .keep_alive
+            .unwrap_or(self.inner.config.default_keep_alive);
+
+        if is_load_request(&inference) {
+            // Ollama semantics: an empty request with `keep_alive: 0` is
+            // an unload, and must not load the model first.
+            let lease = if keep_alive == (KeepAlive::Duration { ms: 0 }) {
+                self.lease_loaded(&inference.model_cid, keep_alive).await
+            } else {
+                Some(self.ensure_loaded(&inference.model_cid, keep_alive).await?)
+            };
+            let (handle, producer) = JobHandle::new(job_id);
+            let identity = self.inner.identity.clone();
+            let stream: JobStream =
+                Box::pin(run_load(lease, manifest_hash, producer, identity));
+            return Ok((handle, stream));
+        }
+
+        // Refuse images for a model without a projector before loading
+        // it, so a request that can't run never spawns or evicts anything.
+        // Another peer may have the projector for this model, so this is
+        // the retryable artifact error rather than a bad manifest.
+        let no_projector = || {
+            WorkerError::ArtifactUnavailable(format!(
+                "model '{}' has no vision projector",
+                inference.model_cid
+            ))
+        };
+        let has_images = vision::has_images(&inference);
+        if has_images
+            && resolve_projector_path(&self.inner.config.model_dir, &inference.model_cid).is_err()
+        {
+            return Err(no_projector());
+        }
 
         // Load the model up front so dispatch-time errors are returned
         // through `WorkerError` rather than as a single `Final::Error`
         // event with no chunks. Once we get past this point the only
         // failure mode is in-stream.
-        let model = self.ensure_loaded(&inference.model_cid).await?;
+        let model = self.ensure_loaded(&inference.model_cid, keep_alive).await?;
+        if has_images && !model.vision {
+            // Loaded before its projector was added.
+            return Err(no_projector());
+        }
 
         let (handle, producer) = JobHandle::new(job_id);
         let identity = self.inner.identity.clone();
@@ -505,6 +815,21 @@ impl Worker for LlamaCppWorker {
 /// to the same generic client-facing error (oracle closed) and logs the
 /// detail server-side.
 fn resolve_model_path(model_dir: &Path, model_id: &str) -> Result<PathBuf, String> {
+    resolve_in_model_dir(model_dir, model_id, ".gguf")
+}
+
+/// Resolve the multimodal projector that pairs with `model_id`:
+/// `model_dir/<model_id>.mmproj.gguf`, under the same SEC-04 shape checks
+/// and canonicalize-and-confine rule as [`resolve_model_path`]. `Err` when
+/// the model has no projector (the common, text-only case).
+pub fn resolve_projector_path(model_dir: &Path, model_id: &str) -> Result<PathBuf, String> {
+    resolve_in_model_dir(model_dir, model_id, PROJECTOR_SUFFIX)
+}
+
+/// File-name suffix of a model's multimodal projector in `model_dir`.
+pub const PROJECTOR_SUFFIX: &str = ".mmproj.gguf";
+
+fn resolve_in_model_dir(model_dir: &Path, model_id: &str, suffix: &str) -> Result<PathBuf, String> {
     if model_id.is_empty()
         || model_id.contains('\0')
         || model_id.contains('/')
@@ -515,7 +840,7 @@ fn resolve_model_path(model_dir: &Path, model_id: &str) -> Result<PathBuf, Strin
         return Err(format!("invalid model id: {model_id:?}"));
     }
 
-    let candidate = model_dir.join(format!("{model_id}.gguf"));
+    let candidate = model_dir.join(format!("{model_id}{suffix}"));
     let canon = candidate
         .canonicalize()
         .map_err(|e| format!("model not found ({}): {e}", candidate.display()))?;
@@ -537,6 +862,7 @@ fn resolve_model_path(model_dir: &Path, model_id: &str) -> Result<PathBuf, Strin
 fn spawn_llama_server(
     binary: &Path,
     model: &Path,
+    projector: Option<&Path>,
     port: u16,
     n_gpu_layers: i32,
     ctx_size: usize,
@@ -568,6 +894,9 @@ fn spawn_llama_server(
     cmd.env("PATH", "/usr/bin:/bin:/usr/local/bin");
 
     cmd.arg("--model").arg(model);
+    if let Some(projector) = projector {
+        cmd.arg("--mmproj").arg(projector);
+    }
     cmd.arg("--host").arg("127.0.0.1");
     cmd.arg("--port").arg(port.to_string());
     cmd.arg("--ctx-size").arg(ctx_size.to_string());
@@ -641,11 +970,17 @@ struct SupervisorInput {
     client: reqwest::Client,
     config: LlamaCppConfig,
     model_path: PathBuf,
+    projector_path: Option<PathBuf>,
+    residency: Arc<Residency>,
+    /// Back-reference for keep-alive unloads, which remove the model's
+    /// own map entry and port. Weak so supervisors don't keep a dropped
+    /// worker alive.
+    worker: Weak<Inner>,
 }
 
 /// Long-running task: watch the child, restart on crash up to 3 times in
-/// 60 s, periodically poll `/health`. Owns the `Child` for the lifetime
-/// of the loaded model.
+/// 60 s, periodically poll `/health`, unload on keep-alive expiry. Owns
+/// the `Child` for the lifetime of the loaded model.
 async fn run_supervisor(input: SupervisorInput, initial_child: Child) {
     let SupervisorInput {
         model_id,
@@ -655,6 +990,9 @@ async fn run_supervisor(input: SupervisorInput, initial_child: Child) {
         client,
         config,
         model_path,
+        projector_path,
+        residency,
+        worker,
     } = input;
 
     // Sliding window of recent crash timestamps. If we accumulate three
@@ -701,6 +1039,13 @@ async fn run_supervisor(input: SupervisorInput, initial_child: Child) {
                         }
                     }
                 }
+                _ = sleep_until_deadline(residency.deadline()) => {
+                    if residency.try_expire() {
+                        break ChildExit::Expired;
+                    }
+                }
+                // A job started or finished; re-read the deadline.
+                _ = residency.changed.notified() => {}
                 _ = health_timer.tick() => {
                     match client.get(&health_url).timeout(Duration::from_secs(5)).send().await {
                         Ok(r) if r.status().is_success() => {
@@ -723,6 +1068,22 @@ async fn run_supervisor(input: SupervisorInput, initial_child: Child) {
         };
 
         match exit_kind {
+            ChildExit::Expired => {
+                tracing::info!(model = %model_id, "keep-alive expired; unloading");
+                failed_flag.store(true, std::sync::atomic::Ordering::Release);
+                failed.notify_waiters();
+                let _ = child.kill().await;
+                if let Some(inner) = worker.upgrade() {
+                    let removed = inner
+                        .loaded_models
+                        .remove_if(&model_id, |_, m| Arc::ptr_eq(&m.residency, &residency))
+                        .is_some();
+                    if removed {
+                        inner.ports_in_use.lock().await.remove(&port);
+                    }
+                }
+                return;
+            }
             ChildExit::CleanExit => {
                 // Caller explicitly killed it via Drop or unload. Don't
                 // restart.
@@ -753,6 +1114,7 @@ async fn run_supervisor(input: SupervisorInput, initial_child: Child) {
                 let respawned = spawn_llama_server(
                     &config.server_binary_path,
                     &model_path,
+                    projector_path.as_deref(),
                     port,
                     config.default_n_gpu_layers,
                     config.default_context_size,
@@ -807,6 +1169,15 @@ enum ChildExit {
     CleanExit,
     Crash,
     HealthDead,
+    Expired,
+}
+
+/// Sleep until `deadline`, or forever if there isn't one.
+async fn sleep_until_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(at) => tokio::time::sleep_until(tokio::time::Instant::from_std(at)).await,
+        None => std::future::pending().await,
+    }
 }
 
 /// Drain a child's stdout/stderr in the background so a chatty subprocess
@@ -838,12 +1209,19 @@ fn drain_child_io(child: &mut Option<Child>) {
 // Inference path
 // ---------------------------------------------------------------------------
 
+/// `/completion` keys the worker owns. A sampling param can't set these:
+/// `id_slot` must stay the slot a verified resumption token named (or be
+/// left to the server), `cache_prompt` and `stream` keep the slot reuse and
+/// SSE decoding we rely on, and `n_predict` holds generation to the
+/// clamped `max_tokens`.
+const RESERVED_KEYS: &[&str] = &["prompt", "stream", "cache_prompt", "id_slot", "n_predict"];
+
 /// Drive a single inference: render the prompt, fire `POST /completion`
 /// with `stream: true`, decode SSE frames into [`JobEvent::Output`], and
 /// produce a signed receipt at the end.
 fn run_inference(
     client: reqwest::Client,
-    model: Arc<LoadedModel>,
+    model: ModelLease,
     inference: InferenceJobSpec,
     manifest_hash: [u8; 32],
     mut producer: JobHandleProducer,
@@ -852,27 +1230,67 @@ fn run_inference(
 ) -> impl futures::Stream<Item = JobEvent> + Send + 'static {
     stream! {
         let started_at = Instant::now();
-        let prompt = render_prompt(&inference);
+        // A token that still matches its slot pins this turn there. Chat
+        // turns already replay the whole history, so llama-server finds
+        // the shared prefix itself; a raw prompt continues the transcript.
+        let resumed = inference.resume_from.as_ref().and_then(|token| {
+            let slot = resume::open(token, &identity, &model.model_id, resume::unix_ms_now())?;
+            model.resumable(&slot).map(|transcript| (slot.slot, transcript))
+        });
+        if inference.resume_from.is_some() && resumed.is_none() {
+            tracing::debug!(model = %model.model_id, "resumption token not honoured; prefilling cold");
+        }
+        let prompt = match &resumed {
+            Some((_, transcript)) if inference.messages.is_empty() => {
+                format!("{transcript}{}", render_prompt(&inference))
+            }
+            _ => render_prompt(&inference),
+        };
         let prompt_chars = prompt.chars().count() as u64;
         let url = format!("http://127.0.0.1:{}/completion", model.port);
+        // With images, llama-server takes a prompt object: each
+        // `MEDIA_MARKER` in the string is replaced by the next image.
+        let images: Vec<&String> =
+            inference.messages.iter().flat_map(|m| &m.images).collect();
+        let prompt_field = if images.is_empty() {
+            serde_json::json!(prompt)
+        } else {
+            serde_json::json!({
+                "prompt_string": prompt,
+                "multimodal_data": images,
+            })
+        };
         let mut body = serde_json::json!({
-            "prompt": prompt,
+            "prompt": prompt_field,
             "stream": true,
             "cache_prompt": true,
         });
         if let Some(map) = body.as_object_mut() {
+            if let Some((slot, _)) = &resumed {
+                map.insert("id_slot".to_string(), serde_json::json!(slot));
+            }
             if let Some(n_predict) = inference.max_tokens {
                 map.insert("n_predict".to_string(), serde_json::json!(n_predict));
             }
             // Pass-through of sampling params. We only forward keys with
             // numeric/string values that JSON-decode cleanly; anything we
             // can't parse is silently dropped (server tolerates unknown
-            // sampler names but not malformed JSON).
+            // sampler names but not malformed JSON). Reserved keys are
+            // skipped.
             for (k, v) in &inference.sampling.params {
+                if RESERVED_KEYS.contains(&k.as_str()) {
+                    continue;
+                }
                 if let Ok(json_v) = serde_json::from_str::<serde_json::Value>(v) {
                     map.insert(k.clone(), json_v);
                 }
             }
+            // Applied after sampling params so a stray `grammar` /
+            // `json_schema` sampling key can't override the signed
+            // constraint.
+            if let Some(constraint) = &inference.output_constraint {
+                structured::apply_to_completion_body(constraint, map);
+            }
         }
 
         let response = client.post(&url).json(&body).send().await;
@@ -916,6 +1334,19 @@ fn run_inference(
         let mut completion_tokens: u64 = 0;
         let mut cancelled = false;
         let mut final_stop_type: Option<String> = None;
+        // Reported on the final frame; the slot is what a resumption
+        // token points at.
+        let mut served_slot: Option<i64> = None;
+        let mut tokens_cached: Option<u64> = None;
+        // Raw model output, tool-call markup included — the slot's cache
+        // holds exactly this after the prompt.
+        let mut generated = String::new();
+        // Only jobs that declared tools get their text scanned for
+        // `<tool_call>` blocks; everything else streams tokens verbatim.
+        let mut splitter = (!inference.tools.is_empty()).then(ToolCallSplitter::default);
+        // Plain-text output, kept only when there's a constraint to check
+        // it against before `Final`.
+        let mut constrained_text = inference.output_constraint.as_ref().map(|_| String::new());
 
         'outer: loop {
             if producer.is_cancelled() {
@@ -976,20 +1407,30 @@ fn run_inference(
                     match serde_json::from_slice::<CompletionFrame>(json_part) {
                         Ok(f) => {
                             if !f.content.is_empty() {
-                                let chunk = OutputChunk {
-                                    kind: "token".to_string(),
-                                    data: Bytes::copy_from_slice(f.content.as_bytes()),
-                                    seq,
-                                };
-                                acc.update(&chunk);
-                                seq += 1;
                                 completion_tokens += 1;
-                                yield JobEvent::Output(chunk);
+                                generated.push_str(&f.content);
+                                let segments = match splitter.as_mut() {
+                                    Some(sp) => sp.push(&f.content),
+                                    None => vec![Segment::Text(f.content)],
+                                };
+                                for segment in segments {
+                                    let chunk = segment.into_chunk(seq);
+                                    if let (Some(text), "token") =
+                                        (constrained_text.as_mut(), chunk.kind.as_str())
+                                    {
+                                        text.push_str(&String::from_utf8_lossy(&chunk.data));
+                                    }
+                                    acc.update(&chunk);
+                                    seq += 1;
+                                    yield JobEvent::Output(chunk);
+                                }
                             }
                             if f.stop {
                                 if let Some(st) = f.stop_type {
                                     final_stop_type = Some(st);
                                 }
+                                served_slot = f.id_slot;
+                                tokens_cached = f.tokens_cached;
                                 break 'outer;
                             }
                         }
@@ -1001,6 +1442,17 @@ fn run_inference(
             }
         }
 
+        // Flush whatever the splitter was holding back (a partial marker,
+        // or an unterminated call that never closed) as plain text.
+        if let Some(segment) = splitter.and_then(ToolCallSplitter::finish) {
+            let chunk = segment.into_chunk(seq);
+            if let (Some(text), "token") = (constrained_text.as_mut(), chunk.kind.as_str()) {
+                text.push_str(&String::from_utf8_lossy(&chunk.data));
+            }
+            acc.update(&chunk);
+            yield JobEvent::Output(chunk);
+        }
+
         {
             let mut last = model.last_used.lock().await;
             *last = Instant::now();
@@ -1016,17 +1468,56 @@ fn run_inference(
             }
         };
 
+        // Only a natural stop is checked: a cancelled or length-capped run
+        // is already reported as such, and its truncated JSON would fail
+        // for the wrong reason.
+        let mut error = None;
+        let completion = match (completion, &inference.output_constraint, constrained_text) {
+            (Completion::Stop, Some(constraint), Some(text)) => {
+                match structured::check_output(constraint, &text) {
+                    Ok(()) => Completion::Stop,
+                    Err(reason) => {
+                        tracing::debug!(model = %model.model_id, %reason, "output failed constraint");
+                        error = Some(format!("output violates constraint: {reason}"));
+                        Completion::ConstraintViolation
+                    }
+                }
+            }
+            (completion, _, _) => completion,
+        };
+
+        // Only a turn that ran to its end leaves a usable cache behind.
+        let resumption = match (&completion, served_slot, resume::retention(model.keep_alive)) {
+            (Completion::Stop | Completion::Length, Some(slot), Some(retention)) => {
+                u32::try_from(slot)
+                    .ok()
+                    .and_then(|slot| model.record_session(slot, format!("{prompt}{generated}")))
+                    .map(|slot| {
+                        let valid_until = resume::unix_ms_now() + retention.as_millis() as u64;
+                        resume::issue(&identity, &model.model_id, slot, valid_until)
+                    })
+            }
+            _ => None,
+        };
+
+        let mut extra = std::collections::BTreeMap::new();
+        if let Some(slot) = served_slot {
+            extra.insert("slot_id".to_string(), slot.to_string());
+        }
+        if let Some(cached) = tokens_cached {
+            extra.insert("tokens_cached".to_string(), cached.to_string());
+        }
         let result = JobResult {
             job_spec_hash: manifest_hash,
             output_commitment: commitment,
             output_chunk_count: count,
             completion,
-            resumption: None,
+            resumption,
             metrics: JobMetrics {
                 total_duration_ms: started_at.elapsed().as_millis() as u64,
                 prompt_tokens: prompt_chars,
                 completion_tokens,
-                ..Default::default()
+                extra,
             },
         };
 
@@ -1035,6 +1526,39 @@ fn run_inference(
             .expect("sign receipt (Serialize impls are infallible)");
         producer.deliver_receipt(receipt);
 
+        yield JobEvent::Final { result, error };
+    }
+}
+
+/// An empty spec — no messages, no prompt text — asks only for residency.
+fn is_load_request(spec: &InferenceJobSpec) -> bool {
+    spec.messages.is_empty() && spec.prompt.as_deref().is_none_or(str::is_empty)
+}
+
+/// Stream for a load request: a single `Final` over the empty commitment,
+/// with a signed receipt like any other job. The lease (absent when an
+/// unload found nothing loaded) is held until the event is taken, so the
+/// idle clock starts from this request's `keep_alive`.
+fn run_load(
+    model: Option<ModelLease>,
+    manifest_hash: [u8; 32],
+    mut producer: JobHandleProducer,
+    identity: NodeIdentity,
+) -> impl futures::Stream<Item = JobEvent> + Send + 'static {
+    stream! {
+        let _model = model;
+        let (commitment, count) = CommitmentAccumulator::new().finalize();
+        let result = JobResult {
+            job_spec_hash: manifest_hash,
+            output_commitment: commitment,
+            output_chunk_count: count,
+            completion: Completion::Stop,
+            resumption: None,
+            metrics: JobMetrics::default(),
+        };
+        if let Ok(receipt) = ReceiptBuilder::new(result.clone(), manifest_hash).sign_with(&identity) {
+            producer.deliver_receipt(receipt);
+        }
         yield JobEvent::Final { result, error: None };
     }
 }
@@ -1042,7 +1566,7 @@ fn run_inference(
 /// Build the terminal `JobEvent::Final` for an error path and deliver the
 /// signed receipt out-of-band. Pure side-effecting helper so the happy
 /// and sad paths in `run_inference` look the same.
-fn emit_final_error(
+pub(crate) fn emit_final_error(
     producer: &mut JobHandleProducer,
     identity: &NodeIdentity,
     manifest_hash: [u8; 32],
@@ -1074,8 +1598,9 @@ fn emit_final_error(
     }
 }
 
-/// One streamed frame on `POST /completion`. We only care about three
+/// One streamed frame on `POST /completion`. We only care about these
 /// fields — anything else (timings, generation_settings, etc.) we skip.
+/// `id_slot` and `tokens_cached` only appear on the final frame.
 #[derive(Debug, Deserialize)]
 struct CompletionFrame {
     #[serde(default)]
@@ -1084,16 +1609,20 @@ struct CompletionFrame {
     stop: bool,
     #[serde(default)]
     stop_type: Option<String>,
+    #[serde(default)]
+    id_slot: Option<i64>,
+    #[serde(default)]
+    tokens_cached: Option<u64>,
 }
 
 /// Find the first `\n\n` separator in a buffer (the SSE record boundary).
-fn find_double_newline(buf: &[u8]) -> Option<usize> {
+pub(crate) fn find_double_newline(buf: &[u8]) -> Option<usize> {
     buf.windows(2).position(|w| w == b"\n\n")
 }
 
 /// Strip the `data: ` SSE prefix. Returns `None` for non-data frames
 /// (comments, retries, named events) — we don't care about any of them.
-fn strip_sse_data_prefix(frame: &[u8]) -> Option<&[u8]> {
+pub(crate) fn strip_sse_data_prefix(frame: &[u8]) -> Option<&[u8]> {
     // Skip leading whitespace / stray CR.
     let mut start = 0;
     while start < frame.len() && (frame[start] == b'\r' || frame[start] == b'\n') {
@@ -1124,20 +1653,91 @@ fn strip_sse_data_prefix(frame: &[u8]) -> Option<&[u8]> {
 /// <|user|>Hello.
 /// <|assistant|>
 /// ```
+///
+/// When the spec declares tools, a leading `<|system|>` block lists them
+/// and asks the model to answer with `<tool_call>{json}</tool_call>` —
+/// the Hermes/Qwen convention most tool-tuned open models already emit.
+/// Earlier assistant calls and `tool` results are replayed in the same
+/// shape so multi-step exchanges stay coherent.
+///
+/// Each image on a message becomes a [`vision::MEDIA_MARKER`] ahead of its
+/// text, matching the order `run_inference` sends them in.
 fn render_prompt(spec: &InferenceJobSpec) -> String {
     if spec.messages.is_empty() {
         return spec.prompt.clone().unwrap_or_default();
     }
     let mut out = String::new();
+    if !spec.tools.is_empty() {
+        out.push_str(role_tag(&ChatRole::System));
+        out.push_str(&render_tool_preamble(&spec.tools));
+        out.push('\n');
+    }
     for msg in &spec.messages {
         out.push_str(role_tag(&msg.role));
-        out.push_str(&msg.content);
+        if msg.role == ChatRole::Tool {
+            match &msg.tool_name {
+                Some(name) => out.push_str(&format!("<tool_response name={name:?}>")),
+                None => out.push_str("<tool_response>"),
+            }
+            out.push_str(&msg.content);
+            out.push_str("</tool_response>");
+        } else {
+            for _ in &msg.images {
+                out.push_str(vision::MEDIA_MARKER);
+            }
+            out.push_str(&msg.content);
+        }
+        for call in &msg.tool_calls {
+            out.push_str(TOOL_CALL_OPEN);
+            out.push_str(&tool_call_json(call));
+            out.push_str(TOOL_CALL_CLOSE);
+        }
         out.push('\n');
     }
     out.push_str("<|assistant|>\n");
     out
 }
 
+/// Instructions + one JSON line per tool. `parameters` is re-parsed so the
+/// model sees a schema object rather than a string-escaped blob; anything
+/// that doesn't parse is passed through as the raw string.
+fn render_tool_preamble(tools: &[ToolDefinition]) -> String {
+    let mut out = String::from(
+        "You may call the following tools. To call one, reply with \
+         <tool_call>{\"name\": <tool name>, \"arguments\": <JSON object>}</tool_call> \
+         and nothing else.\nAvailable tools:",
+    );
+    for tool in tools {
+        let parameters = tool
+            .parameters
+            .as_deref()
+            .map(|p| {
+                serde_json::from_str(p).unwrap_or_else(|_| serde_json::Value::String(p.into()))
+            })
+            .unwrap_or_else(|| serde_json::json!({}));
+        let line = serde_json::json!({
+            "name": tool.name,
+            "description": tool.description.as_deref().unwrap_or(""),
+            "parameters": parameters,
+        });
+        out.push('\n');
+        out.push_str(&line.to_string());
+    }
+    out
+}
+
+/// `{"name": …, "arguments": …}` with `name` first regardless of
+/// serde_json's map ordering — the same shape the preamble asks for.
+fn tool_call_json(call: &ToolCall) -> String {
+    let arguments = serde_json::from_str::<serde_json::Value>(&call.arguments)
+        .unwrap_or_else(|_| serde_json::Value::String(call.arguments.clone()));
+    format!(
+        "{{\"name\":{},\"arguments\":{}}}",
+        serde_json::Value::String(call.name.clone()),
+        arguments
+    )
+}
+
 fn role_tag(role: &ChatRole) -> &'static str {
     match role {
         ChatRole::System => "<|system|>",
@@ -1147,6 +1747,128 @@ fn role_tag(role: &ChatRole) -> &'static str {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Tool-call extraction
+// ---------------------------------------------------------------------------
+
+const TOOL_CALL_OPEN: &str = "<tool_call>";
+const TOOL_CALL_CLOSE: &str = "</tool_call>";
+
+/// One piece of model output after tool-call extraction.
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Call(ToolCall),
+}
+
+impl Segment {
+    /// `"token"` for text, `"tool_call"` (JSON-encoded [`ToolCall`]) for a
+    /// call — distinct kinds so the commitment separates them.
+    fn into_chunk(self, seq: u64) -> OutputChunk {
+        match self {
+            Segment::Text(text) => OutputChunk {
+                kind: "token".to_string(),
+                data: Bytes::from(text),
+                seq,
+            },
+            Segment::Call(call) => OutputChunk {
+                kind: "tool_call".to_string(),
+                data: Bytes::from(serde_json::to_vec(&call).unwrap_or_default()),
+                seq,
+            },
+        }
+    }
+}
+
+/// Incremental splitter over the SSE token stream. Text outside
+/// `<tool_call>…</tool_call>` passes through as soon as it can't be the
+/// start of a marker; text inside is buffered until the close marker and
+/// then parsed. A block that isn't valid call JSON is surfaced verbatim as
+/// text — the client sees exactly what the model produced.
+#[derive(Debug, Default)]
+struct ToolCallSplitter {
+    buf: String,
+    in_call: bool,
+}
+
+impl ToolCallSplitter {
+    fn push(&mut self, text: &str) -> Vec<Segment> {
+        self.buf.push_str(text);
+        let mut out = Vec::new();
+        loop {
+            if self.in_call {
+                let Some(end) = self.buf.find(TOOL_CALL_CLOSE) else {
+                    break;
+                };
+                let body: String = self.buf.drain(..end).collect();
+                self.buf.drain(..TOOL_CALL_CLOSE.len());
+                self.in_call = false;
+                out.push(match parse_tool_call(&body) {
+                    Some(call) => Segment::Call(call),
+                    None => Segment::Text(format!("{TOOL_CALL_OPEN}{body}{TOOL_CALL_CLOSE}")),
+                });
+            } else if let Some(start) = self.buf.find(TOOL_CALL_OPEN) {
+                if start > 0 {
+                    out.push(Segment::Text(self.buf.drain(..start).collect()));
+                }
+                self.buf.drain(..TOOL_CALL_OPEN.len());
+                self.in_call = true;
+            } else {
+                // Hold back a tail that could still grow into the open
+                // marker; release the rest.
+                let emit = self.buf.len() - partial_marker_len(&self.buf, TOOL_CALL_OPEN);
+                if emit > 0 {
+                    out.push(Segment::Text(self.buf.drain(..emit).collect()));
+                }
+                break;
+            }
+        }
+        out
+    }
+
+    /// End of stream. Anything still held back is returned as text.
+    fn finish(self) -> Option<Segment> {
+        let rest = if self.in_call {
+            format!("{TOOL_CALL_OPEN}{}", self.buf)
+        } else {
+            self.buf
+        };
+        (!rest.is_empty()).then_some(Segment::Text(rest))
+    }
+}
+
+/// Length of the longest proper prefix of `marker` that `buf` ends with.
+/// `marker` is ASCII, so the returned length is always a char boundary.
+fn partial_marker_len(buf: &str, marker: &str) -> usize {
+    (1..marker.len())
+        .rev()
+        .find(|&n| buf.ends_with(&marker[..n]))
+        .unwrap_or(0)
+}
+
+/// Parse the body of a `<tool_call>` block. Models emit `arguments` either
+/// as an object or as a JSON-encoded string; both normalise to the string
+/// form [`ToolCall::arguments`] carries.
+fn parse_tool_call(body: &str) -> Option<ToolCall> {
+    #[derive(Deserialize)]
+    struct RawToolCall {
+        name: String,
+        #[serde(default)]
+        arguments: serde_json::Value,
+    }
+    let raw: RawToolCall = serde_json::from_str(body.trim()).ok()?;
+    let arguments = match raw.arguments {
+        serde_json::Value::Null => "{}".to_string(),
+        serde_json::Value::String(s) => s,
+        other => other.to_string(),
+    };
+    Some(ToolCall {
+        id: None,
+        name: raw.name,
+        arguments,
+    })
+}
+
 // ---------------------------------------------------------------------------
 // Cleanup
 // ---------------------------------------------------------------------------
@@ -1158,7 +1880,9 @@ impl Drop for Inner {
         // aborting the supervisor task suppresses log noise from the
         // about-to-be-killed children.
         for entry in self.loaded_models.iter() {
-            entry.value().supervisor.abort();
+            if let Some(supervisor) = entry.value().supervisor_handle().as_ref() {
+                supervisor.abort();
+            }
             entry
                 .value()
                 .failed_flag
@@ -1167,11 +1891,6 @@ impl Drop for Inner {
     }
 }
 
-// We never let `ChatMessage`'s images field bleed into the prompt — text
-// only for the native `/completion` path. Multimodal would route through
-// `/v1/chat/completions` with explicit `multimodal_data` once we wire
-// vision models in a later milestone.
-
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -1276,6 +1995,9 @@ mod tests {
             sampling: Default::default(),
             max_tokens: None,
             stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
         };
         assert_eq!(render_prompt(&spec), "Hello.");
     }
@@ -1289,11 +2011,19 @@ mod tests {
                     role: ChatRole::System,
                     content: "Be helpful.".to_string(),
                     images: vec![],
+                    image_digests: vec![],
+                    tool_calls: vec![],
+                    tool_name: None,
+                    tool_call_id: None,
                 },
                 ChatMessage {
                     role: ChatRole::User,
                     content: "Hi.".to_string(),
                     images: vec![],
+                    image_digests: vec![],
+                    tool_calls: vec![],
+                    tool_name: None,
+                    tool_call_id: None,
                 },
             ],
             prompt: None,
@@ -1301,10 +2031,188 @@ mod tests {
             sampling: Default::default(),
             max_tokens: None,
             stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
         };
         let rendered = render_prompt(&spec);
         assert!(rendered.contains("<|system|>Be helpful."));
         assert!(rendered.contains("<|user|>Hi."));
         assert!(rendered.ends_with("<|assistant|>\n"));
     }
+
+    fn tool_spec(messages: Vec<ChatMessage>) -> InferenceJobSpec {
+        InferenceJobSpec {
+            model_cid: "x".to_string(),
+            messages,
+            prompt: None,
+            resume_from: None,
+            sampling: Default::default(),
+            max_tokens: None,
+            stream: true,
+            tools: vec![ToolDefinition {
+                name: "get_weather".to_string(),
+                description: Some("Current weather for a city.".to_string()),
+                parameters: Some(r#"{"type":"object","properties":{"city":{"type":"string"}}}"#.to_string()),
+            }],
+            output_constraint: None,
+            keep_alive: None,
+        }
+    }
+
+    #[test]
+    fn render_prompt_lists_tools_and_replays_calls() {
+        let spec = tool_spec(vec![
+            ChatMessage {
+                role: ChatRole::User,
+                content: "Weather in Paris?".to_string(),
+                images: vec![],
+                image_digests: vec![],
+                tool_calls: vec![],
+                tool_name: None,
+                tool_call_id: None,
+            },
+            ChatMessage {
+                role: ChatRole::Assistant,
+                content: String::new(),
+                images: vec![],
+                image_digests: vec![],
+                tool_calls: vec![ToolCall {
+                    id: None,
+                    name: "get_weather".to_string(),
+                    arguments: r#"{"city":"Paris"}"#.to_string(),
+                }],
+                tool_name: None,
+                tool_call_id: None,
+            },
+            ChatMessage {
+                role: ChatRole::Tool,
+                content: "18C, clear".to_string(),
+                images: vec![],
+                image_digests: vec![],
+                tool_calls: vec![],
+                tool_name: Some("get_weather".to_string()),
+                tool_call_id: None,
+            },
+        ]);
+        let rendered = render_prompt(&spec);
+        assert!(rendered.starts_with("<|system|>You may call the following tools."));
+        assert!(rendered.contains(r#""name":"get_weather""#));
+        assert!(rendered.contains(r#""properties":{"city""#));
+        assert!(rendered.contains(
+            r#"<|assistant|><tool_call>{"name":"get_weather","arguments":{"city":"Paris"}}</tool_call>"#
+        ));
+        assert!(rendered
+            .contains(r#"<|tool|><tool_response name="get_weather">18C, clear</tool_response>"#));
+        assert!(rendered.ends_with("<|assistant|>\n"));
+    }
+
+    #[test]
+    fn render_prompt_places_a_media_marker_per_image() {
+        let mut spec = tool_spec(vec![ChatMessage {
+            role: ChatRole::User,
+            content: "Compare these.".to_string(),
+            images: vec!["AAAA".to_string(), "BBBB".to_string()],
+            image_digests: vec![],
+            tool_calls: vec![],
+            tool_name: None,
+            tool_call_id: None,
+        }]);
+        spec.tools.clear();
+        assert_eq!(
+            render_prompt(&spec),
+            "<|user|><__media__><__media__>Compare these.\n<|assistant|>\n"
+        );
+    }
+
+    #[test]
+    fn resolve_projector_path_finds_sibling_mmproj() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("llava.gguf"), b"gguf").expect("touch model");
+        assert!(resolve_projector_path(dir.path(), "llava").is_err());
+        let projector = dir.path().join("llava.mmproj.gguf");
+        std::fs::write(&projector, b"gguf").expect("touch projector");
+        assert_eq!(
+            resolve_projector_path(dir.path(), "llava").expect("resolve ok"),
+            projector.canonicalize().unwrap()
+        );
+        // Same SEC-04 confinement as the weights.
+        assert!(resolve_projector_path(dir.path(), "../llava").is_err());
+    }
+
+    #[test]
+    fn splitter_passes_plain_text_through() {
+        let mut sp = ToolCallSplitter::default();
+        assert_eq!(sp.push("Hello"), vec![Segment::Text("Hello".into())]);
+        assert_eq!(sp.push(", world"), vec![Segment::Text(", world".into())]);
+        assert_eq!(sp.finish(), None);
+    }
+
+    #[test]
+    fn splitter_extracts_call_split_across_tokens() {
+        let mut sp = ToolCallSplitter::default();
+        let mut out = Vec::new();
+        for tok in [
+            "Sure. <tool",
+            "_call>{\"name\": \"get_weather\", ",
+            "\"arguments\": {\"city\": \"Paris\"}}",
+            "</tool_call>",
+            " done",
+        ] {
+            out.extend(sp.push(tok));
+        }
+        assert_eq!(
+            out,
+            vec![
+                Segment::Text("Sure. ".into()),
+                Segment::Call(ToolCall {
+                    id: None,
+                    name: "get_weather".into(),
+                    arguments: r#"{"city":"Paris"}"#.into(),
+                }),
+                Segment::Text(" done".into()),
+            ]
+        );
+        assert_eq!(sp.finish(), None);
+    }
+
+    #[test]
+    fn splitter_surfaces_malformed_and_unterminated_blocks_as_text() {
+        let mut sp = ToolCallSplitter::default();
+        assert_eq!(
+            sp.push("<tool_call>not json</tool_call>"),
+            vec![Segment::Text("<tool_call>not json</tool_call>".into())]
+        );
+        assert!(sp.push("<tool_call>{\"name\":").is_empty());
+        assert_eq!(
+            sp.finish(),
+            Some(Segment::Text("<tool_call>{\"name\":".into()))
+        );
+    }
+
+    #[test]
+    fn tool_call_chunks_commit_differently_from_tokens() {
+        // Same bytes, different kind → different commitment, so a relay
+        // can't downgrade a call into plain text without breaking the
+        // receipt.
+        let call = ToolCall {
+            id: None,
+            name: "f".into(),
+            arguments: "{}".into(),
+        };
+        let as_call = Segment::Call(call.clone()).into_chunk(0);
+        let as_text =
+            Segment::Text(String::from_utf8(as_call.data.to_vec()).unwrap()).into_chunk(0);
+        assert_eq!(as_call.kind, "tool_call");
+        assert_eq!(as_text.kind, "token");
+        let mut a = CommitmentAccumulator::new();
+        a.update(&as_call);
+        let mut b = CommitmentAccumulator::new();
+        b.update(&as_text);
+        assert_ne!(a.finalize().0, b.finalize().0);
+        assert_eq!(
+            serde_json::from_slice::<ToolCall>(&as_call.data).unwrap(),
+            call
+        );
+    }
 }
@@ -0,0 +1,939 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! `OpenAiUpstreamWorker` — a [`Worker`] that proxies inference to an
+//! already-running OpenAI-compatible HTTP server (vLLM, llama-swap, a
+//! `llama-server` someone else manages, …) instead of spawning its own.
+//!
+//! Each job becomes one `POST {base_url}/chat/completions` with
+//! `stream: true`. The `choices[0].delta` of every SSE frame is re-emitted
+//! as [`JobEvent::Output`] and folded into a [`CommitmentAccumulator`];
+//! the receipt is signed with this node's identity exactly as
+//! [`LlamaCppWorker`](crate::LlamaCppWorker) signs its own, so a relaying
+//! peer verifies it the same way. The receipt attests to what this node
+//! relayed from its upstream — the upstream itself signs nothing.
+//!
+//! ## Mapping
+//!
+//! - Messages, tools and assistant tool calls map one-to-one onto the
+//!   OpenAI request shape. Streamed `delta.tool_calls` fragments are
+//!   reassembled by `index` and emitted as `"tool_call"` chunks once the
+//!   stream ends, matching the llama worker's chunk kinds.
+//! - An [`OutputConstraint`](phase_protocol::OutputConstraint) becomes
+//!   `response_format` and is re-checked post hoc (see [`crate::structured`]).
+//! - `finish_reason: "length"` maps to [`Completion::Length`].
+//! - Residency is the upstream's business: `keep_alive` is ignored and a
+//!   load request (empty prompt) completes immediately.
+//!
+//! ## What this file deliberately does NOT do
+//!
+//! - Image input. Upstream models are advertised without vision, so the
+//!   router never sends image-bearing jobs here; one that arrives anyway
+//!   is refused at dispatch.
+//! - Retries or failover between upstreams — run one worker per upstream.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_stream::stream;
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use phase_identity::NodeIdentity;
+use phase_protocol::{
+    ChatMessage, ChatRole, CommitmentAccumulator, Completion, InferenceJobSpec, JobEvent, JobHandle,
+    JobHandleProducer, JobId, JobMetrics, JobResult, JobSpec, JobSpecKind, JobStream,
+    OutputChunk, SignedManifest, ToolCall, ToolDefinition, Worker, WorkerError,
+};
+use phase_receipt::ReceiptBuilder;
+use serde::Deserialize;
+use tokio::time::timeout;
+
+use crate::structured;
+use crate::vision;
+use crate::worker_llama::{emit_final_error, find_double_newline, strip_sse_data_prefix};
+
+/// Configuration for [`OpenAiUpstreamWorker`].
+#[derive(Clone)]
+pub struct OpenAiUpstreamConfig {
+    /// Base URL of the OpenAI API, including the version prefix —
+    /// `http://127.0.0.1:8000/v1`. `/chat/completions` and `/models` are
+    /// appended to it.
+    pub base_url: String,
+
+    /// Sent as `Authorization: Bearer …` when set.
+    pub api_key: Option<String>,
+
+    /// Model ids this worker serves. Jobs for anything else are refused
+    /// with the same generic error as a missing GGUF. Empty = ask the
+    /// upstream via [`OpenAiUpstreamWorker::list_models`] and serve
+    /// whatever it reports.
+    pub models: Vec<String>,
+
+    /// Inter-frame hang threshold, as
+    /// [`LlamaCppConfig::per_request_idle_timeout`](crate::LlamaCppConfig::per_request_idle_timeout).
+    pub per_request_idle_timeout: Duration,
+}
+
+impl Default for OpenAiUpstreamConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://127.0.0.1:8000/v1".to_string(),
+            api_key: None,
+            models: Vec::new(),
+            per_request_idle_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+// Hand-written so `tracing::info!(?config)` never logs the API key.
+impl std::fmt::Debug for OpenAiUpstreamConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenAiUpstreamConfig")
+            .field("base_url", &self.base_url)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .field("models", &self.models)
+            .field("per_request_idle_timeout", &self.per_request_idle_timeout)
+            .finish()
+    }
+}
+
+/// Proxies inference to an OpenAI-compatible upstream. Cheaply cloneable.
+#[derive(Clone)]
+pub struct OpenAiUpstreamWorker {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    identity: NodeIdentity,
+    config: OpenAiUpstreamConfig,
+    client: reqwest::Client,
+    /// Served model ids: the configured list, or whatever the upstream
+    /// reported the last time [`OpenAiUpstreamWorker::list_models`] ran.
+    models: std::sync::RwLock<Vec<String>>,
+}
+
+impl OpenAiUpstreamWorker {
+    /// Construct a worker. Nothing is sent upstream until the first job
+    /// (or [`list_models`](Self::list_models)).
+    pub fn new(identity: NodeIdentity, config: OpenAiUpstreamConfig) -> Self {
+        let client = reqwest::Client::builder()
+            // No overall request timeout: streams legitimately stay open
+            // for minutes. Idleness is enforced per frame instead.
+            .connect_timeout(Duration::from_secs(10))
+            .pool_idle_timeout(Some(Duration::from_secs(90)))
+            .build()
+            .expect("reqwest client (rustls-tls) builds with default config");
+        let models = std::sync::RwLock::new(config.models.clone());
+        Self {
+            inner: Arc::new(Inner {
+                identity,
+                config,
+                client,
+                models,
+            }),
+        }
+    }
+
+    /// The model ids to advertise. Returns the configured list as-is;
+    /// otherwise queries `GET {base_url}/models` and remembers the answer
+    /// as the set of servable models.
+    pub async fn list_models(&self) -> Result<Vec<String>, WorkerError> {
+        if !self.inner.config.models.is_empty() {
+            return Ok(self.inner.config.models.clone());
+        }
+        #[derive(Deserialize)]
+        struct ModelList {
+            data: Vec<ModelEntry>,
+        }
+        #[derive(Deserialize)]
+        struct ModelEntry {
+            id: String,
+        }
+        let resp = self
+            .authorized(self.inner.client.get(self.url("models")))
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await
+            .map_err(|e| WorkerError::Other(format!("upstream /models: {e}")))?;
+        if !resp.status().is_success() {
+            return Err(WorkerError::Other(format!(
+                "upstream /models returned {}",
+                resp.status()
+            )));
+        }
+        let list: ModelList = resp
+            .json()
+            .await
+            .map_err(|e| WorkerError::Other(format!("upstream /models: {e}")))?;
+        let ids: Vec<String> = list.data.into_iter().map(|m| m.id).collect();
+        *self.models_mut() = ids.clone();
+        Ok(ids)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.inner.config.base_url.trim_end_matches('/'))
+    }
+
+    fn authorized(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.inner.config.api_key {
+            Some(key) => req.bearer_auth(key),
+            None => req,
+        }
+    }
+
+    fn serves(&self, model_id: &str) -> bool {
+        self.inner
+            .models
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .any(|m| m == model_id)
+    }
+
+    fn models_mut(&self) -> std::sync::RwLockWriteGuard<'_, Vec<String>> {
+        self.inner.models.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Worker for OpenAiUpstreamWorker {
+    fn supported_kinds(&self) -> &[JobSpecKind] {
+        &[JobSpecKind::Inference]
+    }
+
+    async fn execute(
+        &self,
+        job: SignedManifest<JobSpec>,
+    ) -> Result<(JobHandle, JobStream), WorkerError> {
+        let mut inference = match &job.payload {
+            JobSpec::Inference(spec) => spec.clone(),
+            other => {
+                return Err(WorkerError::Unsupported {
+                    kind: other.kind(),
+                });
+            }
+        };
+
+        if !self.serves(&inference.model_cid) {
+            tracing::warn!(model = %inference.model_cid, "model not served by upstream");
+            return Err(WorkerError::ArtifactUnavailable("model unavailable".into()));
+        }
+        if let Some(constraint) = &inference.output_constraint {
+            structured::check_constraint(constraint).map_err(WorkerError::BadManifest)?;
+        }
+        vision::verify_image_digests(&mut inference).map_err(WorkerError::BadManifest)?;
+        if vision::has_images(&inference) {
+            return Err(WorkerError::ArtifactUnavailable(format!(
+                "model '{}' has no vision support on this upstream",
+                inference.model_cid
+            )));
+        }
+
+        let manifest_hash = job
+            .manifest_hash()
+            .map_err(|e| WorkerError::BadManifest(e.to_string()))?;
+        let (handle, producer) = JobHandle::new(JobId(manifest_hash));
+        let identity = self.inner.identity.clone();
+
+        if inference.messages.is_empty() && inference.prompt.as_deref().is_none_or(str::is_empty) {
+            let stream: JobStream = Box::pin(run_noop(manifest_hash, producer, identity));
+            return Ok((handle, stream));
+        }
+
+        let request = self
+            .authorized(self.inner.client.post(self.url("chat/completions")))
+            .json(&request_body(&inference));
+        let stream: JobStream = Box::pin(run_upstream(
+            request,
+            inference,
+            manifest_hash,
+            producer,
+            identity,
+            self.inner.config.per_request_idle_timeout,
+        ));
+        Ok((handle, stream))
+    }
+}
+
+/// Request keys the worker owns. A sampling param can't set these: they
+/// pick the model the `serves()` allowlist admitted, keep the response an
+/// SSE stream we can parse, and hold generation to the clamped
+/// `max_tokens`.
+const RESERVED_KEYS: &[&str] = &[
+    "model",
+    "messages",
+    "stream",
+    "stream_options",
+    "max_tokens",
+    "max_completion_tokens",
+    "n",
+    "tools",
+];
+
+/// How much of an upstream error body goes into the job's final error. The
+/// error ends up in logs and client responses; a proxy's HTML error page
+/// shouldn't.
+const MAX_ERROR_BODY_CHARS: usize = 512;
+
+/// Bytes of an upstream error body read before giving up on the rest:
+/// enough for one character past [`MAX_ERROR_BODY_CHARS`] at four bytes
+/// each, so a cut body is always marked as truncated.
+const MAX_ERROR_BODY_BYTES: usize = 4 * (MAX_ERROR_BODY_CHARS + 1);
+
+/// The start of an upstream error body, truncated for the final error.
+async fn error_body(mut response: reqwest::Response) -> String {
+    let mut body = Vec::new();
+    while let Ok(Some(chunk)) = response.chunk().await {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_ERROR_BODY_BYTES {
+            body.truncate(MAX_ERROR_BODY_BYTES);
+            break;
+        }
+    }
+    truncate_chars(&String::from_utf8_lossy(&body), MAX_ERROR_BODY_CHARS)
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+/// Translate a spec into an OpenAI chat-completions request body.
+fn request_body(spec: &InferenceJobSpec) -> serde_json::Value {
+    let messages: Vec<serde_json::Value> = if spec.messages.is_empty() {
+        vec![serde_json::json!({
+            "role": "user",
+            "content": spec.prompt.clone().unwrap_or_default(),
+        })]
+    } else {
+        messages_json(&spec.messages)
+    };
+    let mut body = serde_json::json!({
+        "model": spec.model_cid,
+        "messages": messages,
+        "stream": true,
+        // Ask for a trailing usage frame; servers that don't know the
+        // option ignore it and we fall back to counting frames.
+        "stream_options": {"include_usage": true},
+    });
+    if let Some(map) = body.as_object_mut() {
+        if let Some(max_tokens) = spec.max_tokens {
+            map.insert("max_tokens".to_string(), serde_json::json!(max_tokens));
+        }
+        // Same pass-through rule as the llama worker: forward every
+        // sampling key whose value decodes as JSON, except reserved ones.
+        for (k, v) in &spec.sampling.params {
+            if RESERVED_KEYS.contains(&k.as_str()) {
+                continue;
+            }
+            if let Ok(json_v) = serde_json::from_str::<serde_json::Value>(v) {
+                map.insert(k.clone(), json_v);
+            }
+        }
+        if !spec.tools.is_empty() {
+            map.insert(
+                "tools".to_string(),
+                spec.tools.iter().map(tool_json).collect(),
+            );
+        }
+        // After sampling params, so a stray `response_format` sampling key
+        // can't override the signed constraint.
+        if let Some(constraint) = &spec.output_constraint {
+            structured::apply_to_chat_completions_body(constraint, map);
+        }
+    }
+    body
+}
+
+/// Translate the history. Strict OpenAI-compatible servers want every
+/// `tool` message to carry the `tool_call_id` of an earlier assistant call.
+/// Ollama-shaped history has neither ids nor that link, so a call without
+/// an id gets a synthetic one from its position, and a tool message
+/// without one answers the oldest unanswered call of its `tool_name` (or
+/// simply the oldest unanswered call).
+fn messages_json(messages: &[ChatMessage]) -> Vec<serde_json::Value> {
+    let mut unanswered: VecDeque<(String, &str)> = VecDeque::new();
+    let mut out = Vec::with_capacity(messages.len());
+    for (m, msg) in messages.iter().enumerate() {
+        let call_ids: Vec<String> = msg
+            .tool_calls
+            .iter()
+            .enumerate()
+            .map(|(i, call)| call.id.clone().unwrap_or_else(|| format!("call_{m}_{i}")))
+            .collect();
+        let tool_call_id = if msg.role == ChatRole::Tool {
+            match &msg.tool_call_id {
+                Some(id) => {
+                    unanswered.retain(|(pending, _)| pending != id);
+                    Some(id.clone())
+                }
+                None => unanswered
+                    .iter()
+                    .position(|(_, name)| msg.tool_name.as_deref() == Some(*name))
+                    .or((!unanswered.is_empty()).then_some(0))
+                    .and_then(|i| unanswered.remove(i))
+                    .map(|(id, _)| id),
+            }
+        } else {
+            None
+        };
+        out.push(message_json(msg, &call_ids, tool_call_id));
+        unanswered.extend(
+            call_ids
+                .into_iter()
+                .zip(msg.tool_calls.iter().map(|call| call.name.as_str())),
+        );
+    }
+    out
+}
+
+fn message_json(
+    msg: &ChatMessage,
+    call_ids: &[String],
+    tool_call_id: Option<String>,
+) -> serde_json::Value {
+    let mut out = serde_json::json!({
+        "role": msg.role,
+        "content": msg.content,
+    });
+    if let Some(map) = out.as_object_mut() {
+        if !msg.tool_calls.is_empty() {
+            let calls: Vec<serde_json::Value> = msg
+                .tool_calls
+                .iter()
+                .zip(call_ids)
+                .map(|(call, id)| {
+                    serde_json::json!({
+                        "id": id,
+                        "type": "function",
+                        "function": {"name": call.name, "arguments": call.arguments},
+                    })
+                })
+                .collect();
+            map.insert("tool_calls".to_string(), calls.into());
+        }
+        if let Some(id) = tool_call_id {
+            map.insert("tool_call_id".to_string(), id.into());
+        }
+        if let Some(name) = &msg.tool_name {
+            map.insert("name".to_string(), name.clone().into());
+        }
+    }
+    out
+}
+
+fn tool_json(tool: &ToolDefinition) -> serde_json::Value {
+    let parameters = tool
+        .parameters
+        .as_deref()
+        .and_then(|p| serde_json::from_str::<serde_json::Value>(p).ok())
+        .unwrap_or_else(|| serde_json::json!({"type": "object", "properties": {}}));
+    serde_json::json!({
+        "type": "function",
+        "function": {
+            "name": tool.name,
+            "description": tool.description.as_deref().unwrap_or(""),
+            "parameters": parameters,
+        },
+    })
+}
+
+/// One streamed `chat.completion.chunk`. Only the fields we use.
+#[derive(Debug, Deserialize)]
+struct ChunkFrame {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<DeltaToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaToolCall {
+    #[serde(default)]
+    index: usize,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    function: Option<DeltaFunction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeltaFunction {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+/// Tool calls under reassembly, keyed by the upstream's `index`. `id` and
+/// `name` arrive once; `arguments` arrives in fragments.
+#[derive(Debug, Default)]
+struct PendingToolCalls(BTreeMap<usize, ToolCall>);
+
+impl PendingToolCalls {
+    fn push(&mut self, fragment: DeltaToolCall) {
+        let call = self.0.entry(fragment.index).or_insert_with(|| ToolCall {
+            id: None,
+            name: String::new(),
+            arguments: String::new(),
+        });
+        if fragment.id.is_some() {
+            call.id = fragment.id;
+        }
+        if let Some(f) = fragment.function {
+            if let Some(name) = f.name {
+                call.name.push_str(&name);
+            }
+            if let Some(arguments) = f.arguments {
+                call.arguments.push_str(&arguments);
+            }
+        }
+    }
+
+    fn finish(self) -> impl Iterator<Item = ToolCall> {
+        self.0.into_values().map(|mut call| {
+            if call.arguments.trim().is_empty() {
+                call.arguments = "{}".to_string();
+            }
+            call
+        })
+    }
+}
+
+/// Drive one upstream request: send it, decode SSE frames into
+/// [`JobEvent::Output`], and finish with a signed receipt.
+fn run_upstream(
+    request: reqwest::RequestBuilder,
+    inference: InferenceJobSpec,
+    manifest_hash: [u8; 32],
+    mut producer: JobHandleProducer,
+    identity: NodeIdentity,
+    idle_timeout: Duration,
+) -> impl futures::Stream<Item = JobEvent> + Send + 'static {
+    stream! {
+        let started_at = Instant::now();
+        let prompt_chars: u64 = match &inference.prompt {
+            Some(p) if inference.messages.is_empty() => p.chars().count() as u64,
+            _ => inference.messages.iter().map(|m| m.content.chars().count() as u64).sum(),
+        };
+
+        let resp = match request.send().await {
+            Ok(r) if r.status().is_success() => r,
+            Ok(r) => {
+                let status = r.status();
+                let body = error_body(r).await;
+                yield emit_final_error(
+                    &mut producer,
+                    &identity,
+                    manifest_hash,
+                    prompt_chars,
+                    0,
+                    started_at,
+                    format!("upstream returned {status}: {body}"),
+                );
+                return;
+            }
+            Err(e) => {
+                yield emit_final_error(
+                    &mut producer,
+                    &identity,
+                    manifest_hash,
+                    prompt_chars,
+                    0,
+                    started_at,
+                    format!("request to upstream failed: {e}"),
+                );
+                return;
+            }
+        };
+
+        let mut bytes = resp.bytes_stream();
+        let mut buf = BytesMut::with_capacity(4096);
+        let mut acc = CommitmentAccumulator::new();
+        let mut seq: u64 = 0;
+        let mut completion_tokens: u64 = 0;
+        let mut usage: Option<Usage> = None;
+        let mut cancelled = false;
+        let mut finish_reason: Option<String> = None;
+        let mut tool_calls = PendingToolCalls::default();
+        let mut constrained_text = inference.output_constraint.as_ref().map(|_| String::new());
+
+        'outer: loop {
+            if producer.is_cancelled() {
+                cancelled = true;
+                break;
+            }
+            let chunk = match timeout(idle_timeout, bytes.next()).await {
+                Ok(Some(Ok(c))) => c,
+                Ok(Some(Err(e))) => {
+                    yield emit_final_error(
+                        &mut producer,
+                        &identity,
+                        manifest_hash,
+                        prompt_chars,
+                        completion_tokens,
+                        started_at,
+                        format!("SSE stream broke: {e}"),
+                    );
+                    return;
+                }
+                // Some servers close without `[DONE]`; treat it the same.
+                Ok(None) => break,
+                Err(_elapsed) => {
+                    yield emit_final_error(
+                        &mut producer,
+                        &identity,
+                        manifest_hash,
+                        prompt_chars,
+                        completion_tokens,
+                        started_at,
+                        format!("no frame within {idle_timeout:?} (hang detected)"),
+                    );
+                    return;
+                }
+            };
+            buf.extend_from_slice(&chunk);
+            while let Some(pos) = find_double_newline(&buf) {
+                let frame_bytes = buf.split_to(pos + 2);
+                let frame = &frame_bytes[..frame_bytes.len().saturating_sub(2)];
+                let Some(json_part) = strip_sse_data_prefix(frame) else {
+                    continue;
+                };
+                if json_part.trim_ascii() == b"[DONE]" {
+                    break 'outer;
+                }
+                let f = match serde_json::from_slice::<ChunkFrame>(json_part) {
+                    Ok(f) => f,
+                    Err(e) => {
+                        tracing::debug!(error = %e, "skipping malformed SSE frame");
+                        continue;
+                    }
+                };
+                if f.usage.is_some() {
+                    usage = f.usage;
+                }
+                let Some(choice) = f.choices.into_iter().next() else {
+                    continue;
+                };
+                for fragment in choice.delta.tool_calls {
+                    tool_calls.push(fragment);
+                }
+                if let Some(content) = choice.delta.content.filter(|c| !c.is_empty()) {
+                    completion_tokens += 1;
+                    if let Some(text) = constrained_text.as_mut() {
+                        text.push_str(&content);
+                    }
+                    let chunk = OutputChunk {
+                        kind: "token".to_string(),
+                        data: Bytes::from(content),
+                        seq,
+                    };
+                    acc.update(&chunk);
+                    seq += 1;
+                    yield JobEvent::Output(chunk);
+                }
+                if choice.finish_reason.is_some() {
+                    // Keep reading: the usage frame and `[DONE]` follow.
+                    finish_reason = choice.finish_reason;
+                }
+            }
+        }
+
+        if !cancelled {
+            for call in tool_calls.finish() {
+                let chunk = OutputChunk {
+                    kind: "tool_call".to_string(),
+                    data: Bytes::from(serde_json::to_vec(&call).unwrap_or_default()),
+                    seq,
+                };
+                acc.update(&chunk);
+                seq += 1;
+                yield JobEvent::Output(chunk);
+            }
+        }
+
+        let (commitment, count) = acc.finalize();
+        let completion = if cancelled {
+            Completion::Cancelled
+        } else if finish_reason.as_deref() == Some("length") {
+            Completion::Length
+        } else {
+            Completion::Stop
+        };
+
+        let mut error = None;
+        let completion = match (completion, &inference.output_constraint, constrained_text) {
+            (Completion::Stop, Some(constraint), Some(text)) => {
+                match structured::check_output(constraint, &text) {
+                    Ok(()) => Completion::Stop,
+                    Err(reason) => {
+                        error = Some(format!("output violates constraint: {reason}"));
+                        Completion::ConstraintViolation
+                    }
+                }
+            }
+            (completion, _, _) => completion,
+        };
+
+        let (prompt_tokens, completion_tokens) = match usage {
+            Some(u) => (u.prompt_tokens, u.completion_tokens),
+            None => (prompt_chars, completion_tokens),
+        };
+        let result = JobResult {
+            job_spec_hash: manifest_hash,
+            output_commitment: commitment,
+            output_chunk_count: count,
+            completion,
+            resumption: None,
+            metrics: JobMetrics {
+                total_duration_ms: started_at.elapsed().as_millis() as u64,
+                prompt_tokens,
+                completion_tokens,
+                ..Default::default()
+            },
+        };
+        let receipt = ReceiptBuilder::new(result.clone(), manifest_hash)
+            .sign_with(&identity)
+            .expect("sign receipt (Serialize impls are infallible)");
+        producer.deliver_receipt(receipt);
+
+        yield JobEvent::Final { result, error };
+    }
+}
+
+/// Load requests have nothing to do here; answer with the same signed,
+/// empty `Final` the llama worker gives.
+fn run_noop(
+    manifest_hash: [u8; 32],
+    mut producer: JobHandleProducer,
+    identity: NodeIdentity,
+) -> impl futures::Stream<Item = JobEvent> + Send + 'static {
+    stream! {
+        let (commitment, count) = CommitmentAccumulator::new().finalize();
+        let result = JobResult {
+            job_spec_hash: manifest_hash,
+            output_commitment: commitment,
+            output_chunk_count: count,
+            completion: Completion::Stop,
+            resumption: None,
+            metrics: JobMetrics::default(),
+        };
+        if let Ok(receipt) = ReceiptBuilder::new(result.clone(), manifest_hash).sign_with(&identity) {
+            producer.deliver_receipt(receipt);
+        }
+        yield JobEvent::Final { result, error: None };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phase_protocol::SamplingParams;
+
+    fn frame(json: &str) -> DeltaToolCall {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn tool_call_fragments_reassemble_by_index() {
+        let mut pending = PendingToolCalls::default();
+        pending.push(frame(r#"{"index":0,"id":"call_a","function":{"name":"get_weather","arguments":""}}"#));
+        pending.push(frame(r#"{"index":1,"id":"call_b","function":{"name":"get_time"}}"#));
+        pending.push(frame(r#"{"index":0,"function":{"arguments":"{\"city\":"}}"#));
+        pending.push(frame(r#"{"index":0,"function":{"arguments":"\"Paris\"}"}}"#));
+        let calls: Vec<ToolCall> = pending.finish().collect();
+        assert_eq!(
+            calls,
+            vec![
+                ToolCall {
+                    id: Some("call_a".into()),
+                    name: "get_weather".into(),
+                    arguments: r#"{"city":"Paris"}"#.into(),
+                },
+                ToolCall {
+                    id: Some("call_b".into()),
+                    name: "get_time".into(),
+                    arguments: "{}".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn request_body_maps_messages_tools_and_sampling() {
+        let mut sampling = SamplingParams::default();
+        sampling.params.insert("temperature".into(), "0.2".into());
+        let spec = InferenceJobSpec {
+            model_cid: "qwen3".into(),
+            messages: vec![
+                ChatMessage {
+                    role: ChatRole::Assistant,
+                    content: String::new(),
+                    images: vec![],
+                    image_digests: vec![],
+                    tool_calls: vec![ToolCall {
+                        id: None,
+                        name: "get_weather".into(),
+                        arguments: "{}".into(),
+                    }],
+                    tool_name: None,
+                    tool_call_id: None,
+                },
+                ChatMessage {
+                    role: ChatRole::Tool,
+                    content: "sunny".into(),
+                    images: vec![],
+                    image_digests: vec![],
+                    tool_calls: vec![],
+                    tool_name: Some("get_weather".into()),
+                    tool_call_id: None,
+                },
+            ],
+            prompt: None,
+            resume_from: None,
+            sampling,
+            max_tokens: Some(64),
+            stream: true,
+            tools: vec![ToolDefinition {
+                name: "get_weather".into(),
+                description: None,
+                parameters: Some(r#"{"type":"object"}"#.into()),
+            }],
+            output_constraint: None,
+            keep_alive: None,
+        };
+        let body = request_body(&spec);
+        assert_eq!(body["model"], "qwen3");
+        assert_eq!(body["max_tokens"], 64);
+        assert_eq!(body["temperature"], 0.2);
+        assert_eq!(body["messages"][0]["role"], "assistant");
+        assert_eq!(body["messages"][0]["tool_calls"][0]["function"]["name"], "get_weather");
+        assert_eq!(body["messages"][1]["role"], "tool");
+        assert_eq!(body["messages"][1]["name"], "get_weather");
+        // The synthetic call id is echoed on the result that answers it.
+        assert_eq!(body["messages"][0]["tool_calls"][0]["id"], "call_0_0");
+        assert_eq!(body["messages"][1]["tool_call_id"], "call_0_0");
+        assert_eq!(body["tools"][0]["function"]["parameters"]["type"], "object");
+    }
+
+    #[test]
+    fn sampling_params_cannot_override_reserved_keys() {
+        let mut sampling = SamplingParams::default();
+        for (k, v) in [
+            ("model", r#""other-model""#),
+            ("stream", "false"),
+            ("stream_options", "{}"),
+            ("max_tokens", "100000"),
+            ("max_completion_tokens", "100000"),
+            ("top_p", "0.9"),
+        ] {
+            sampling.params.insert(k.into(), v.into());
+        }
+        let spec = InferenceJobSpec {
+            model_cid: "qwen3".into(),
+            messages: vec![],
+            prompt: Some("hi".into()),
+            resume_from: None,
+            sampling,
+            max_tokens: None,
+            stream: true,
+            tools: vec![],
+            output_constraint: None,
+            keep_alive: None,
+        };
+        let body = request_body(&spec);
+        assert_eq!(body["model"], "qwen3");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("max_completion_tokens").is_none());
+        assert_eq!(body["top_p"], 0.9);
+    }
+
+    #[test]
+    fn tool_results_are_matched_to_their_calls() {
+        let message = |role, tool_calls: Vec<ToolCall>, tool_name: Option<&str>, tool_call_id: Option<&str>| {
+            ChatMessage {
+                role,
+                content: String::new(),
+                images: vec![],
+                image_digests: vec![],
+                tool_calls,
+                tool_name: tool_name.map(Into::into),
+                tool_call_id: tool_call_id.map(Into::into),
+            }
+        };
+        let call = |id: Option<&str>, name: &str| ToolCall {
+            id: id.map(Into::into),
+            name: name.into(),
+            arguments: "{}".into(),
+        };
+        let messages = messages_json(&[
+            message(
+                ChatRole::Assistant,
+                vec![call(Some("call_w"), "get_weather"), call(None, "get_time"), call(None, "get_time")],
+                None,
+                None,
+            ),
+            // Ollama-style: answered by name, out of order.
+            message(ChatRole::Tool, vec![], Some("get_time"), None),
+            // OpenAI-style: answered by id.
+            message(ChatRole::Tool, vec![], Some("get_weather"), Some("call_w")),
+            message(ChatRole::Tool, vec![], None, None),
+        ]);
+        assert_eq!(messages[0]["tool_calls"][0]["id"], "call_w");
+        assert_eq!(messages[0]["tool_calls"][1]["id"], "call_0_1");
+        assert_eq!(messages[1]["tool_call_id"], "call_0_1");
+        assert_eq!(messages[2]["tool_call_id"], "call_w");
+        assert_eq!(messages[3]["tool_call_id"], "call_0_2");
+        assert!(messages[0].get("tool_call_id").is_none());
+    }
+
+    #[test]
+    fn error_bodies_are_truncated() {
+        assert_eq!(truncate_chars("short", 8), "short");
+        assert_eq!(truncate_chars("ééééé", 2), "éé…");
+    }
+
+    #[tokio::test]
+    async fn long_error_bodies_are_cut_while_reading() {
+        let page = "🔥".repeat(10 * MAX_ERROR_BODY_CHARS);
+        let body = error_body(axum::http::Response::new(page).into()).await;
+        assert_eq!(body.chars().count(), MAX_ERROR_BODY_CHARS + 1);
+        assert!(body.ends_with('…'));
+    }
+
+    #[test]
+    fn debug_redacts_api_key() {
+        let config = OpenAiUpstreamConfig {
+            api_key: Some("sk-secret".into()),
+            ..Default::default()
+        };
+        assert!(!format!("{config:?}").contains("sk-secret"));
+    }
+}
@@ -12,6 +12,15 @@
 //! - Serves `GET /health` → `{"status":"ok"}` (or 503 for a configurable
 //!   warmup period).
 //! - Serves `POST /completion` returning SSE frames the worker can decode.
+//!   A multimodal prompt object (`{prompt_string, multimodal_data}`) is
+//!   rejected with 400 unless `--mmproj` was given and the number of
+//!   `<__media__>` markers matches the number of images, as llama-server
+//!   does.
+//! - Keeps a per-slot "KV cache" (the last prompt + output each slot
+//!   served). A request's `id_slot` picks the slot, otherwise slots are
+//!   handed out round-robin; the final frame reports `id_slot` and
+//!   `tokens_cached` (characters shared with the slot's cache), like
+//!   llama-server's slot reuse.
 //!
 //! Behaviour knobs (env vars, picked up at fixture spawn time):
 //!
@@ -23,12 +32,14 @@
 //! | `FAKE_LLAMA_HANG_AFTER=N` | Stop emitting after N tokens, hold socket. |
 //! | `FAKE_LLAMA_CRASH_AFTER_MS=N` | `std::process::exit(2)` after N ms. |
 //! | `FAKE_LLAMA_FAIL_HEALTH=1` | Always return 500 from `/health`. |
+//! | `FAKE_LLAMA_SLOTS=2` | Number of slots (llama-server's `--parallel`). |
 //!
 //! The worker only ever cares about the SSE chunk shape and the `/health`
 //! semantics, so the fixture stays small.
 
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use axum::{
@@ -49,7 +60,11 @@ struct Config {
     warmup: Duration,
     hang_after: Option<usize>,
     fail_health: bool,
+    mmproj: bool,
     boot_at: std::time::Instant,
+    /// What each slot last held, prompt + output.
+    slots: Arc<Mutex<Vec<String>>>,
+    next_slot: Arc<AtomicUsize>,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -57,6 +72,7 @@ async fn main() {
     let args: Vec<String> = std::env::args().collect();
     let mut port: u16 = 8080;
     let mut host: String = "127.0.0.1".to_string();
+    let mut mmproj = false;
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
@@ -67,6 +83,11 @@ async fn main() {
                     continue;
                 }
             }
+            "--mmproj" => {
+                mmproj = true;
+                i += 2;
+                continue;
+            }
             "--host" => {
                 if let Some(v) = args.get(i + 1) {
                     host = v.clone();
@@ -124,7 +145,17 @@ async fn main() {
             .ok()
             .and_then(|s| s.parse().ok()),
         fail_health: std::env::var("FAKE_LLAMA_FAIL_HEALTH").is_ok(),
+        mmproj,
         boot_at: std::time::Instant::now(),
+        slots: Arc::new(Mutex::new(vec![
+            String::new();
+            std::env::var("FAKE_LLAMA_SLOTS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|&n: &usize| n > 0)
+                .unwrap_or(2)
+        ])),
+        next_slot: Arc::new(AtomicUsize::new(0)),
     };
 
     // Optional self-destruct used to simulate a crash mid-stream.
@@ -173,8 +204,7 @@ async fn handle_health(State(cfg): State<Arc<Config>>) -> Response {
 #[derive(Deserialize)]
 struct CompletionRequest {
     #[serde(default)]
-    #[allow(dead_code)]
-    prompt: Option<String>,
+    prompt: Option<serde_json::Value>,
     #[serde(default)]
     #[allow(dead_code)]
     stream: Option<bool>,
@@ -184,6 +214,8 @@ struct CompletionRequest {
     #[serde(default)]
     #[allow(dead_code)]
     messages: Option<serde_json::Value>,
+    #[serde(default)]
+    id_slot: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -199,12 +231,56 @@ struct FinalCompletionChunk<'a> {
     stop_type: &'a str,
     tokens_predicted: usize,
     tokens_evaluated: usize,
+    id_slot: usize,
+    tokens_cached: usize,
 }
 
 async fn handle_completion(
     State(cfg): State<Arc<Config>>,
-    Json(_req): Json<CompletionRequest>,
+    Json(req): Json<CompletionRequest>,
 ) -> Response {
+    if let Some(serde_json::Value::Object(prompt)) = &req.prompt {
+        if !cfg.mmproj {
+            return (StatusCode::BAD_REQUEST, "multimodal prompt without --mmproj")
+                .into_response();
+        }
+        let markers = prompt
+            .get("prompt_string")
+            .and_then(|v| v.as_str())
+            .map(|p| p.matches("<__media__>").count())
+            .unwrap_or(0);
+        let images = prompt
+            .get("multimodal_data")
+            .and_then(|v| v.as_array())
+            .map(|a| a.len())
+            .unwrap_or(0);
+        if markers != images {
+            return (StatusCode::BAD_REQUEST, "media marker count mismatch").into_response();
+        }
+    }
+    let prompt_text = match &req.prompt {
+        Some(serde_json::Value::String(p)) => p.clone(),
+        Some(serde_json::Value::Object(o)) => o
+            .get("prompt_string")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        _ => String::new(),
+    };
+    let (id_slot, tokens_cached) = {
+        let mut slots = cfg.slots.lock().unwrap();
+        let id_slot = match req.id_slot {
+            Some(id) if id >= 0 && (id as usize) < slots.len() => id as usize,
+            _ => cfg.next_slot.fetch_add(1, Ordering::Relaxed) % slots.len(),
+        };
+        let cached = slots[id_slot]
+            .chars()
+            .zip(prompt_text.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        slots[id_slot] = format!("{prompt_text}{}", cfg.tokens.concat());
+        (id_slot, cached)
+    };
     let cfg = cfg.clone();
     let stream = async_stream::stream! {
         for (idx, tok) in cfg.tokens.iter().enumerate() {
@@ -230,6 +306,8 @@ async fn handle_completion(
             stop_type: "eos",
             tokens_predicted: cfg.tokens.len(),
             tokens_evaluated: 1,
+            id_slot,
+            tokens_cached,
         };
         let mut frame = b"data: ".to_vec();
         frame.extend_from_slice(&serde_json::to_vec(&final_chunk).unwrap());
@@ -26,7 +26,8 @@ use lucidd::{LlamaCppConfig, LlamaCppWorker};
 use phase_identity::NodeIdentity;
 use phase_manifest::ManifestBuilder;
 use phase_protocol::{
-    Completion, InferenceJobSpec, JobEvent, JobSpec, SamplingParams, SignedManifest, Worker,
+    ChatMessage, ChatRole, Completion, ConversationToken, InferenceJobSpec, JobEvent, JobResult,
+    JobSpec, KeepAlive, OutputConstraint, SamplingParams, SignedManifest, Worker,
 };
 
 /// Pick a port that's free *right now*. The fake binary will re-bind it
@@ -53,6 +54,9 @@ fn make_manifest(model_id: &str, prompt: &str) -> SignedManifest<JobSpec> {
         sampling: SamplingParams::default(),
         max_tokens: Some(32),
         stream: true,
+        tools: vec![],
+        output_constraint: None,
+        keep_alive: None,
     });
     ManifestBuilder::new(job_spec)
         .sign_with(&id)
@@ -79,6 +83,7 @@ fn setup(model_id: &str) -> TestModel {
         max_loaded_models: 3,
         model_load_timeout: Duration::from_secs(10),
         per_request_idle_timeout: Duration::from_secs(5),
+        default_keep_alive: KeepAlive::Forever,
         extra_env: Vec::new(),
     };
     TestModel {
@@ -126,6 +131,7 @@ fn multi_model_worker(
         max_loaded_models,
         model_load_timeout: Duration::from_secs(10),
         per_request_idle_timeout: Duration::from_secs(5),
+        default_keep_alive: KeepAlive::Forever,
         extra_env: vec![
             ("FAKE_LLAMA_TOKENS".to_string(), "a,b".to_string()),
             ("FAKE_LLAMA_DELAY_MS".to_string(), "1".to_string()),
@@ -368,6 +374,7 @@ async fn real_llama_server_smoke_test_when_env_set() {
         max_loaded_models: 3,
         model_load_timeout: Duration::from_secs(120),
         per_request_idle_timeout: Duration::from_secs(60),
+        default_keep_alive: KeepAlive::Forever,
         extra_env: Vec::new(),
     };
     let worker = LlamaCppWorker::new(NodeIdentity::generate(), config);
@@ -388,3 +395,445 @@ async fn real_llama_server_smoke_test_when_env_set() {
     assert!(got_token, "expected at least one token from real server");
     assert!(got_final, "expected Final from real server");
 }
+
+fn constrained_manifest(model_id: &str, constraint: OutputConstraint) -> SignedManifest<JobSpec> {
+    let id = NodeIdentity::generate();
+    let job_spec = JobSpec::Inference(InferenceJobSpec {
+        model_cid: model_id.to_string(),
+        messages: vec![],
+        prompt: Some("Reply in JSON.".to_string()),
+        resume_from: None,
+        sampling: SamplingParams::default(),
+        max_tokens: Some(32),
+        stream: true,
+        tools: vec![],
+        output_constraint: Some(constraint),
+        keep_alive: None,
+    });
+    ManifestBuilder::new(job_spec)
+        .sign_with(&id)
+        .expect("sign manifest")
+}
+
+/// Drive a manifest to `Final` and return its completion, error, and the
+/// signed receipt's completion.
+async fn final_of(
+    worker: &LlamaCppWorker,
+    manifest: SignedManifest<JobSpec>,
+) -> (Completion, Option<String>, Completion) {
+    let (handle, mut stream) = worker.execute(manifest).await.expect("dispatch");
+    let mut out = None;
+    while let Some(ev) = stream.next().await {
+        if let JobEvent::Final { result, error } = ev {
+            out = Some((result.completion, error));
+        }
+    }
+    let (completion, error) = out.expect("Final event");
+    let receipt = handle.finish().await.expect("receipt");
+    (completion, error, receipt.result.completion)
+}
+
+#[tokio::test]
+async fn json_constraint_passes_conforming_output() {
+    let mut setup = setup("json-ok");
+    setup.config.extra_env = vec![(
+        "FAKE_LLAMA_TOKENS".to_string(),
+        r#"{"ok": ,true}"#.to_string(),
+    )];
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    let schema = r#"{"type":"object","properties":{"ok":{"type":"boolean"}},"required":["ok"]}"#;
+    let manifest = constrained_manifest(
+        &setup.model_id,
+        OutputConstraint::JsonSchema {
+            schema: schema.to_string(),
+        },
+    );
+    let (completion, error, signed) = final_of(&worker, manifest).await;
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    assert_eq!(signed, Completion::Stop);
+}
+
+#[tokio::test]
+async fn json_constraint_violation_is_a_distinct_signed_completion() {
+    // Default fake tokens ("Hello, world!") are not JSON.
+    let setup = setup("json-bad");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    let manifest = constrained_manifest(&setup.model_id, OutputConstraint::Json);
+    let (completion, error, signed) = final_of(&worker, manifest).await;
+    assert_eq!(completion, Completion::ConstraintViolation);
+    assert_eq!(signed, Completion::ConstraintViolation);
+    assert!(
+        error.unwrap_or_default().contains("not valid JSON"),
+        "expected a validation reason"
+    );
+}
+
+#[tokio::test]
+async fn unparseable_schema_is_rejected_at_dispatch() {
+    let setup = setup("json-schema-bad");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    let manifest = constrained_manifest(
+        &setup.model_id,
+        OutputConstraint::JsonSchema {
+            schema: "{nope".to_string(),
+        },
+    );
+    match worker.execute(manifest).await {
+        Err(phase_protocol::WorkerError::BadManifest(msg)) => {
+            assert!(msg.contains("schema"), "unexpected message: {msg}")
+        }
+        Err(other) => panic!("expected BadManifest, got {other:?}"),
+        Ok(_) => panic!("expected BadManifest, got Ok"),
+    }
+}
+
+/// A chat manifest with one image, its digest filled in the way the Ollama
+/// edge does it.
+fn image_manifest(model_id: &str) -> SignedManifest<JobSpec> {
+    let id = NodeIdentity::generate();
+    let mut message = ChatMessage {
+        role: ChatRole::User,
+        content: "What is in this picture?".to_string(),
+        images: vec!["iVBORw0KGgo=".to_string()],
+        image_digests: vec![],
+        tool_calls: vec![],
+        tool_name: None,
+        tool_call_id: None,
+    };
+    lucidd::vision::attach_image_digests(&mut message).expect("valid base64");
+    let job_spec = JobSpec::Inference(InferenceJobSpec {
+        model_cid: model_id.to_string(),
+        messages: vec![message],
+        prompt: None,
+        resume_from: None,
+        sampling: SamplingParams::default(),
+        max_tokens: Some(32),
+        stream: true,
+        tools: vec![],
+        output_constraint: None,
+        keep_alive: None,
+    });
+    ManifestBuilder::new(job_spec)
+        .sign_with(&id)
+        .expect("sign manifest")
+}
+
+#[tokio::test]
+async fn image_request_runs_when_projector_is_present() {
+    let setup = setup("llava");
+    std::fs::write(setup._dir.path().join("llava.mmproj.gguf"), b"fake").expect("touch mmproj");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    // The fixture 400s a multimodal prompt unless it was started with
+    // `--mmproj` and the media markers line up with the images.
+    let (completion, error, signed) = final_of(&worker, image_manifest(&setup.model_id)).await;
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    assert_eq!(signed, Completion::Stop);
+}
+
+#[tokio::test]
+async fn image_request_without_projector_is_refused_at_dispatch() {
+    let setup = setup("text-only");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    match worker.execute(image_manifest(&setup.model_id)).await {
+        Err(phase_protocol::WorkerError::ArtifactUnavailable(msg)) => {
+            assert!(msg.contains("vision"), "unexpected message: {msg}")
+        }
+        Err(other) => panic!("expected ArtifactUnavailable, got {other:?}"),
+        Ok(_) => panic!("expected ArtifactUnavailable, got Ok"),
+    }
+}
+
+#[tokio::test]
+async fn image_request_without_projector_loads_nothing() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    assert!(matches!(
+        worker.execute(image_manifest("ka-a")).await,
+        Err(phase_protocol::WorkerError::ArtifactUnavailable(_))
+    ));
+    // The only port is still free: the refused model was never spawned.
+    assert!(second_model_loads(&worker).await);
+}
+
+#[tokio::test]
+async fn tampered_image_digest_is_rejected_at_dispatch() {
+    let setup = setup("llava-tampered");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    let mut manifest = image_manifest(&setup.model_id);
+    if let JobSpec::Inference(spec) = &mut manifest.payload {
+        spec.messages[0].image_digests[0] = "00".repeat(32);
+    }
+    match worker.execute(manifest).await {
+        Err(phase_protocol::WorkerError::BadManifest(msg)) => {
+            assert!(msg.contains("digest mismatch"), "unexpected message: {msg}")
+        }
+        Err(other) => panic!("expected BadManifest, got {other:?}"),
+        Ok(_) => panic!("expected BadManifest, got Ok"),
+    }
+}
+
+fn keep_alive_manifest(
+    model_id: &str,
+    prompt: &str,
+    keep_alive: Option<KeepAlive>,
+) -> SignedManifest<JobSpec> {
+    let id = NodeIdentity::generate();
+    let job_spec = JobSpec::Inference(InferenceJobSpec {
+        model_cid: model_id.to_string(),
+        messages: vec![],
+        prompt: Some(prompt.to_string()),
+        resume_from: None,
+        sampling: SamplingParams::default(),
+        max_tokens: Some(32),
+        stream: true,
+        tools: vec![],
+        output_constraint: None,
+        keep_alive,
+    });
+    ManifestBuilder::new(job_spec)
+        .sign_with(&id)
+        .expect("sign manifest")
+}
+
+/// Residency is observed through the port pool: these workers have a
+/// single port and room for two models, so a second model only loads once
+/// the first has actually been unloaded (otherwise: `Capacity`).
+async fn second_model_loads(worker: &LlamaCppWorker) -> bool {
+    match run_to_final(worker, "ka-b").await {
+        Ok((Completion::Stop, _)) => true,
+        Err(e) if e.contains("capacity") => false,
+        other => panic!("unexpected outcome loading ka-b: {other:?}"),
+    }
+}
+
+async fn eventually_second_model_loads(worker: &LlamaCppWorker) -> bool {
+    for _ in 0..50 {
+        if second_model_loads(worker).await {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn keep_alive_zero_unloads_once_idle() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    let manifest = keep_alive_manifest("ka-a", "Hello.", Some(KeepAlive::Duration { ms: 0 }));
+    let (completion, error, _) = final_of(&worker, manifest).await;
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    assert!(
+        eventually_second_model_loads(&worker).await,
+        "ka-a should have been unloaded, freeing the only port"
+    );
+}
+
+#[tokio::test]
+async fn keep_alive_duration_holds_then_expires() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    let manifest = keep_alive_manifest("ka-a", "Hello.", Some(KeepAlive::Duration { ms: 1500 }));
+    let (completion, error, _) = final_of(&worker, manifest).await;
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    assert!(!second_model_loads(&worker).await, "ka-a unloaded before its keep_alive");
+    assert!(eventually_second_model_loads(&worker).await, "ka-a never expired");
+}
+
+#[tokio::test]
+async fn pinned_model_stays_resident() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    let manifest = keep_alive_manifest("ka-a", "Hello.", Some(KeepAlive::Forever));
+    let (completion, error, _) = final_of(&worker, manifest).await;
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!second_model_loads(&worker).await, "pinned model was unloaded");
+}
+
+#[tokio::test]
+async fn empty_prompt_preloads_model() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    let load = keep_alive_manifest("ka-a", "", None);
+    let (handle, mut stream) = worker.execute(load).await.expect("dispatch");
+    let mut events = Vec::new();
+    while let Some(ev) = stream.next().await {
+        events.push(ev);
+    }
+    assert_eq!(events.len(), 1, "a load request yields only Final");
+    let receipt = handle.finish().await.expect("receipt");
+    assert_eq!(receipt.result.completion, Completion::Stop);
+    assert_eq!(receipt.result.output_chunk_count, 0);
+    assert!(!second_model_loads(&worker).await, "preload did not keep ka-a resident");
+}
+
+#[tokio::test]
+async fn empty_prompt_with_keep_alive_zero_unloads() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    let (completion, error) = run_to_final(&worker, "ka-a").await.expect("load ka-a");
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    let unload = keep_alive_manifest("ka-a", "", Some(KeepAlive::Duration { ms: 0 }));
+    let (completion, _, _) = final_of(&worker, unload).await;
+    assert_eq!(completion, Completion::Stop);
+    assert!(eventually_second_model_loads(&worker).await, "ka-a was not unloaded");
+}
+
+#[tokio::test]
+async fn unload_of_model_not_loaded_does_not_spawn() {
+    let (_dir, worker) = multi_model_worker(&["ka-a", "ka-b"], 2, 1);
+    let unload = keep_alive_manifest("ka-a", "", Some(KeepAlive::Duration { ms: 0 }));
+    let (completion, _, _) = final_of(&worker, unload).await;
+    assert_eq!(completion, Completion::Stop);
+    // The only port is still free, with no unload to wait for.
+    assert!(second_model_loads(&worker).await);
+}
+
+fn resume_manifest(
+    model_id: &str,
+    prompt: &str,
+    resume_from: Option<ConversationToken>,
+) -> SignedManifest<JobSpec> {
+    sampled_resume_manifest(model_id, prompt, resume_from, SamplingParams::default())
+}
+
+fn sampled_resume_manifest(
+    model_id: &str,
+    prompt: &str,
+    resume_from: Option<ConversationToken>,
+    sampling: SamplingParams,
+) -> SignedManifest<JobSpec> {
+    let id = NodeIdentity::generate();
+    let job_spec = JobSpec::Inference(InferenceJobSpec {
+        model_cid: model_id.to_string(),
+        messages: vec![],
+        prompt: Some(prompt.to_string()),
+        resume_from,
+        sampling,
+        max_tokens: Some(32),
+        stream: true,
+        tools: vec![],
+        output_constraint: None,
+        keep_alive: None,
+    });
+    ManifestBuilder::new(job_spec)
+        .sign_with(&id)
+        .expect("sign manifest")
+}
+
+/// Run one turn and return its `JobResult`, checking it matches the
+/// signed receipt's.
+async fn turn(
+    worker: &LlamaCppWorker,
+    model_id: &str,
+    prompt: &str,
+    resume_from: Option<ConversationToken>,
+) -> JobResult {
+    let manifest = resume_manifest(model_id, prompt, resume_from);
+    let (handle, mut stream) = worker.execute(manifest).await.expect("dispatch");
+    let mut out = None;
+    while let Some(ev) = stream.next().await {
+        if let JobEvent::Final { result, error } = ev {
+            assert!(error.is_none(), "unexpected error: {error:?}");
+            out = Some(result);
+        }
+    }
+    let result = out.expect("Final event");
+    let receipt = handle.finish().await.expect("receipt");
+    assert_eq!(receipt.result.resumption, result.resumption);
+    result
+}
+
+fn extra<'a>(result: &'a JobResult, key: &str) -> &'a str {
+    result.metrics.extra.get(key).map(String::as_str).unwrap_or("")
+}
+
+#[tokio::test]
+async fn resumption_token_returns_to_its_slot_with_the_transcript() {
+    let setup = setup("resume");
+    let identity = NodeIdentity::generate();
+    let worker = LlamaCppWorker::new(identity.clone(), setup.config);
+
+    let first = turn(&worker, &setup.model_id, "Once", None).await;
+    let token = first.resumption.clone().expect("a finished turn issues a token");
+    assert_eq!(token.issuer.0, lucidd::resume::peer_id_of(&identity).to_string());
+    // Another conversation takes the other slot in between.
+    let other = turn(&worker, &setup.model_id, "Elsewhere", None).await;
+    assert_ne!(extra(&other, "slot_id"), extra(&first, "slot_id"));
+
+    // The follow-up lands on the first slot, and its prompt extends the
+    // first turn's prompt + output, so all of that is served from cache.
+    let follow_up = turn(&worker, &setup.model_id, " more", Some(token)).await;
+    assert_eq!(extra(&follow_up, "slot_id"), extra(&first, "slot_id"));
+    let transcript = "OnceHello, world!";
+    assert_eq!(extra(&follow_up, "tokens_cached"), transcript.len().to_string());
+    assert_eq!(
+        follow_up.metrics.prompt_tokens,
+        (transcript.len() + " more".len()) as u64
+    );
+    assert!(follow_up.resumption.is_some());
+}
+
+#[tokio::test]
+async fn sampling_params_cannot_redirect_the_slot() {
+    let setup = setup("resume-slot");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+
+    let first = turn(&worker, &setup.model_id, "Once", None).await;
+    let token = first.resumption.clone().expect("token");
+    let other = turn(&worker, &setup.model_id, "Elsewhere", None).await;
+    let other_slot = extra(&other, "slot_id").to_string();
+
+    // A client-supplied `id_slot` would land the turn on someone else's
+    // cache; the token's slot wins.
+    let mut sampling = SamplingParams::default();
+    sampling.params.insert("id_slot".into(), other_slot.clone());
+    sampling.params.insert("cache_prompt".into(), "false".into());
+    let manifest = sampled_resume_manifest(&setup.model_id, " more", Some(token), sampling);
+    let (_handle, mut stream) = worker.execute(manifest).await.expect("dispatch");
+    let mut follow_up = None;
+    while let Some(ev) = stream.next().await {
+        if let JobEvent::Final { result, .. } = ev {
+            follow_up = Some(result);
+        }
+    }
+    let follow_up = follow_up.expect("Final event");
+    assert_eq!(extra(&follow_up, "slot_id"), extra(&first, "slot_id"));
+    assert_ne!(extra(&follow_up, "slot_id"), other_slot);
+}
+
+#[tokio::test]
+async fn stale_or_foreign_tokens_prefill_cold() {
+    let setup = setup("resume-cold");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+
+    let first = turn(&worker, &setup.model_id, "Once", None).await;
+    let token = first.resumption.clone().expect("token");
+    // Resuming supersedes the token: the slot now holds a newer turn.
+    turn(&worker, &setup.model_id, " more", Some(token.clone())).await;
+    let stale = turn(&worker, &setup.model_id, "Again", Some(token)).await;
+    assert_eq!(extra(&stale, "tokens_cached"), "0");
+    assert_eq!(stale.metrics.prompt_tokens, "Again".len() as u64);
+
+    // A token signed by some other node is ignored the same way.
+    let foreign = lucidd::resume::issue(
+        &NodeIdentity::generate(),
+        &setup.model_id,
+        lucidd::resume::SlotRef {
+            generation: 0,
+            slot: 0,
+            nonce: 0,
+        },
+        lucidd::resume::unix_ms_now() + 60_000,
+    );
+    let cold = turn(&worker, &setup.model_id, "Fresh", Some(foreign)).await;
+    assert_eq!(extra(&cold, "tokens_cached"), "0");
+    assert_eq!(cold.metrics.prompt_tokens, "Fresh".len() as u64);
+}
+
+#[tokio::test]
+async fn no_token_when_the_model_unloads_after_the_turn() {
+    let setup = setup("resume-unload");
+    let worker = LlamaCppWorker::new(NodeIdentity::generate(), setup.config);
+    let manifest = keep_alive_manifest(&setup.model_id, "Hello.", Some(KeepAlive::Duration { ms: 0 }));
+    let (handle, mut stream) = worker.execute(manifest).await.expect("dispatch");
+    while stream.next().await.is_some() {}
+    let receipt = handle.finish().await.expect("receipt");
+    assert_eq!(receipt.result.completion, Completion::Stop);
+    assert_eq!(receipt.result.resumption, None);
+}
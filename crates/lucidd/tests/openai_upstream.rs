@@ -0,0 +1,372 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+
+//! Integration tests for `OpenAiUpstreamWorker` against an in-process
+//! stand-in for an OpenAI-compatible server.
+//!
+//! Unlike `llama_worker.rs` there's no subprocess to manage — the worker
+//! only speaks HTTP — so the fake is a small axum app bound to an
+//! ephemeral port inside the test. It serves `GET /v1/models` and streams
+//! `POST /v1/chat/completions` as `chat.completion.chunk` SSE frames
+//! terminated by `data: [DONE]`, the way vLLM and llama-swap do.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::StreamExt;
+use lucidd::{OpenAiUpstreamConfig, OpenAiUpstreamWorker};
+use phase_identity::NodeIdentity;
+use phase_manifest::ManifestBuilder;
+use phase_protocol::{
+    ChatMessage, ChatRole, Completion, InferenceJobSpec, JobEvent, JobResult, JobSpec,
+    OutputChunk, SamplingParams, SignedManifest, ToolCall, ToolDefinition, Worker, WorkerError,
+};
+use phase_receipt::SignedReceipt;
+
+/// What the fake streams back for each request.
+#[derive(Clone)]
+enum Reply {
+    /// Content deltas, then `finish_reason`, a usage frame and `[DONE]`.
+    Text { deltas: Vec<&'static str>, finish: &'static str },
+    /// One tool call split across several argument fragments.
+    ToolCall,
+    /// Non-2xx with an error body.
+    Status(StatusCode),
+}
+
+#[derive(Clone)]
+struct Fake {
+    reply: Reply,
+    api_key: Option<&'static str>,
+    last_body: Arc<Mutex<Option<serde_json::Value>>>,
+}
+
+async fn spawn_fake(reply: Reply, api_key: Option<&'static str>) -> (String, Fake) {
+    let fake = Fake {
+        reply,
+        api_key,
+        last_body: Arc::new(Mutex::new(None)),
+    };
+    let app = Router::new()
+        .route("/v1/models", get(handle_models))
+        .route("/v1/chat/completions", post(handle_chat))
+        .with_state(fake.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("bind ephemeral");
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    (format!("http://{addr}/v1"), fake)
+}
+
+fn authorized(fake: &Fake, headers: &HeaderMap) -> bool {
+    match fake.api_key {
+        None => true,
+        Some(key) => headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            == Some(format!("Bearer {key}").as_str()),
+    }
+}
+
+async fn handle_models(State(fake): State<Fake>, headers: HeaderMap) -> Response {
+    if !authorized(&fake, &headers) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(serde_json::json!({
+        "object": "list",
+        "data": [
+            {"id": "qwen3-8b", "object": "model"},
+            {"id": "llama3.1-70b", "object": "model"},
+        ],
+    }))
+    .into_response()
+}
+
+fn sse(value: serde_json::Value) -> String {
+    format!("data: {value}\n\n")
+}
+
+fn delta(delta: serde_json::Value, finish: Option<&str>) -> String {
+    sse(serde_json::json!({
+        "object": "chat.completion.chunk",
+        "choices": [{"index": 0, "delta": delta, "finish_reason": finish}],
+    }))
+}
+
+async fn handle_chat(
+    State(fake): State<Fake>,
+    headers: HeaderMap,
+    Json(body): Json<serde_json::Value>,
+) -> Response {
+    if !authorized(&fake, &headers) {
+        return (StatusCode::UNAUTHORIZED, "bad key").into_response();
+    }
+    *fake.last_body.lock().unwrap() = Some(body);
+    let mut frames = vec![delta(serde_json::json!({"role": "assistant"}), None)];
+    match fake.reply {
+        Reply::Status(status) => return (status, "upstream exploded").into_response(),
+        Reply::Text { deltas, finish } => {
+            for d in deltas {
+                frames.push(delta(serde_json::json!({"content": d}), None));
+            }
+            frames.push(delta(serde_json::json!({}), Some(finish)));
+        }
+        Reply::ToolCall => {
+            frames.push(delta(
+                serde_json::json!({"tool_calls": [{
+                    "index": 0, "id": "call_1", "type": "function",
+                    "function": {"name": "get_weather", "arguments": ""},
+                }]}),
+                None,
+            ));
+            for fragment in ["{\"city\"", ":\"Paris\"}"] {
+                frames.push(delta(
+                    serde_json::json!({"tool_calls": [{
+                        "index": 0, "function": {"arguments": fragment},
+                    }]}),
+                    None,
+                ));
+            }
+            frames.push(delta(serde_json::json!({}), Some("tool_calls")));
+        }
+    }
+    frames.push(sse(serde_json::json!({
+        "object": "chat.completion.chunk",
+        "choices": [],
+        "usage": {"prompt_tokens": 7, "completion_tokens": 3, "total_tokens": 10},
+    })));
+    frames.push("data: [DONE]\n\n".to_string());
+    let stream = futures::stream::iter(
+        frames
+            .into_iter()
+            .map(|f| Ok::<bytes::Bytes, std::io::Error>(bytes::Bytes::from(f))),
+    );
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/event-stream")
+        .body(Body::from_stream(stream))
+        .unwrap()
+}
+
+fn worker(base_url: String, api_key: Option<&str>, models: &[&str]) -> OpenAiUpstreamWorker {
+    worker_with_identity(NodeIdentity::generate(), base_url, api_key, models)
+}
+
+fn worker_with_identity(
+    identity: NodeIdentity,
+    base_url: String,
+    api_key: Option<&str>,
+    models: &[&str],
+) -> OpenAiUpstreamWorker {
+    OpenAiUpstreamWorker::new(
+        identity,
+        OpenAiUpstreamConfig {
+            base_url,
+            api_key: api_key.map(str::to_string),
+            models: models.iter().map(|m| m.to_string()).collect(),
+            per_request_idle_timeout: Duration::from_secs(5),
+        },
+    )
+}
+
+fn chat_manifest(model_id: &str, tools: Vec<ToolDefinition>) -> SignedManifest<JobSpec> {
+    let job_spec = JobSpec::Inference(InferenceJobSpec {
+        model_cid: model_id.to_string(),
+        messages: vec![ChatMessage {
+            role: ChatRole::User,
+            content: "Hello.".into(),
+            images: vec![],
+            image_digests: vec![],
+            tool_calls: vec![],
+            tool_name: None,
+            tool_call_id: None,
+        }],
+        prompt: None,
+        resume_from: None,
+        sampling: SamplingParams::default(),
+        max_tokens: Some(32),
+        stream: true,
+        tools,
+        output_constraint: None,
+        keep_alive: None,
+    });
+    ManifestBuilder::new(job_spec)
+        .sign_with(&NodeIdentity::generate())
+        .expect("sign manifest")
+}
+
+/// Run a job to completion. Returns the output chunks, the terminal
+/// completion + error, and the receipt the handle received.
+async fn run(
+    worker: &OpenAiUpstreamWorker,
+    manifest: SignedManifest<JobSpec>,
+) -> (Vec<OutputChunk>, Completion, Option<String>, Option<SignedReceipt<JobResult>>) {
+    let (handle, mut stream) = worker.execute(manifest).await.expect("execute");
+    let mut chunks = Vec::new();
+    while let Some(ev) = stream.next().await {
+        match ev {
+            JobEvent::Output(chunk) => chunks.push(chunk),
+            JobEvent::Final { result, error } => {
+                return (chunks, result.completion, error, handle.finish().await.ok());
+            }
+            _ => {}
+        }
+    }
+    panic!("stream ended without Final");
+}
+
+fn text(chunks: &[OutputChunk]) -> String {
+    chunks
+        .iter()
+        .filter(|c| c.kind == "token")
+        .map(|c| String::from_utf8_lossy(&c.data).into_owned())
+        .collect()
+}
+
+#[tokio::test]
+async fn streams_deltas_and_signs_receipt() {
+    let (url, fake) = spawn_fake(
+        Reply::Text {
+            deltas: vec!["Hel", "lo", "!"],
+            finish: "stop",
+        },
+        Some("sk-test"),
+    )
+    .await;
+    let identity = NodeIdentity::generate();
+    let worker = worker_with_identity(identity.clone(), url, Some("sk-test"), &["qwen3-8b"]);
+    let (chunks, completion, error, receipt) = run(&worker, chat_manifest("qwen3-8b", vec![])).await;
+    assert_eq!(completion, Completion::Stop, "error: {error:?}");
+    assert_eq!(text(&chunks), "Hello!");
+    assert_eq!(chunks.iter().map(|c| c.seq).collect::<Vec<_>>(), vec![0, 1, 2]);
+    // Signed by the identity the worker was built with, over the
+    // upstream's reported usage.
+    let receipt = receipt.expect("receipt");
+    receipt.verify().expect("receipt verifies");
+    assert_eq!(receipt.verifying_key(), Some(identity.verifying_key()));
+    assert_eq!(receipt.result.metrics.prompt_tokens, 7);
+    assert_eq!(receipt.result.metrics.completion_tokens, 3);
+
+    let body = fake.last_body.lock().unwrap().clone().expect("request seen");
+    assert_eq!(body["model"], "qwen3-8b");
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["max_tokens"], 32);
+    assert_eq!(body["messages"][0]["content"], "Hello.");
+}
+
+#[tokio::test]
+async fn finish_reason_length_maps_to_length() {
+    let (url, _fake) = spawn_fake(
+        Reply::Text {
+            deltas: vec!["trunc"],
+            finish: "length",
+        },
+        None,
+    )
+    .await;
+    let worker = worker(url, None, &["qwen3-8b"]);
+    let (_, completion, _, _) = run(&worker, chat_manifest("qwen3-8b", vec![])).await;
+    assert_eq!(completion, Completion::Length);
+}
+
+#[tokio::test]
+async fn streamed_tool_call_is_reassembled() {
+    let (url, fake) = spawn_fake(Reply::ToolCall, None).await;
+    let worker = worker(url, None, &["qwen3-8b"]);
+    let tools = vec![ToolDefinition {
+        name: "get_weather".into(),
+        description: Some("Current weather".into()),
+        parameters: Some(r#"{"type":"object","properties":{"city":{"type":"string"}}}"#.into()),
+    }];
+    let (chunks, completion, _, _) = run(&worker, chat_manifest("qwen3-8b", tools)).await;
+    assert_eq!(completion, Completion::Stop);
+    let calls: Vec<ToolCall> = chunks
+        .iter()
+        .filter(|c| c.kind == "tool_call")
+        .map(|c| serde_json::from_slice(&c.data).unwrap())
+        .collect();
+    assert_eq!(
+        calls,
+        vec![ToolCall {
+            id: Some("call_1".into()),
+            name: "get_weather".into(),
+            arguments: r#"{"city":"Paris"}"#.into(),
+        }]
+    );
+    let body = fake.last_body.lock().unwrap().clone().unwrap();
+    assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+}
+
+#[tokio::test]
+async fn upstream_error_becomes_final_error_with_receipt() {
+    let (url, _fake) = spawn_fake(Reply::Status(StatusCode::INTERNAL_SERVER_ERROR), None).await;
+    let worker = worker(url, None, &["qwen3-8b"]);
+    let (chunks, completion, error, receipt) =
+        run(&worker, chat_manifest("qwen3-8b", vec![])).await;
+    assert!(chunks.is_empty());
+    assert_eq!(completion, Completion::Error);
+    assert!(error.unwrap().contains("500"));
+    assert!(receipt.is_some(), "error path still signs a receipt");
+}
+
+#[tokio::test]
+async fn unlisted_model_is_refused_at_dispatch() {
+    let (url, _fake) = spawn_fake(
+        Reply::Text {
+            deltas: vec!["x"],
+            finish: "stop",
+        },
+        None,
+    )
+    .await;
+    let worker = worker(url, None, &["qwen3-8b"]);
+    let err = worker
+        .execute(chat_manifest("not-served", vec![]))
+        .await
+        .err()
+        .expect("refused");
+    assert!(matches!(err, WorkerError::ArtifactUnavailable(_)), "{err}");
+}
+
+#[tokio::test]
+async fn models_are_discovered_from_upstream() {
+    let (url, _fake) = spawn_fake(
+        Reply::Text {
+            deltas: vec!["ok"],
+            finish: "stop",
+        },
+        Some("sk-test"),
+    )
+    .await;
+    let worker = worker(url, Some("sk-test"), &[]);
+    let models = worker.list_models().await.expect("list models");
+    assert_eq!(models, vec!["qwen3-8b", "llama3.1-70b"]);
+    // Discovered models become servable.
+    let (_, completion, _, _) = run(&worker, chat_manifest("llama3.1-70b", vec![])).await;
+    assert_eq!(completion, Completion::Stop);
+}
+
+#[tokio::test]
+async fn wrong_api_key_fails_model_listing() {
+    let (url, _fake) = spawn_fake(
+        Reply::Text {
+            deltas: vec!["ok"],
+            finish: "stop",
+        },
+        Some("sk-test"),
+    )
+    .await;
+    let worker = worker(url, Some("sk-wrong"), &[]);
+    let err = worker.list_models().await.expect_err("401");
+    assert!(err.to_string().contains("401"), "{err}");
+}
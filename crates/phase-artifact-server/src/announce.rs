@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Announcing held blobs on the DHT, and finding who holds one.
+//!
+//! [`BlobAnnouncer`] publishes a signed [`BlobProviderRecord`] for every
+//! blob in an [`ArtifactStore`] and joins the blob's provider set (see
+//! [`crate::dht`] for the key layout). Installed on an [`ArtifactServer`]
+//! with [`ArtifactServer::with_announcer`], it:
+//!
+//! - announces every stored blob at startup and again every
+//!   [`BLOB_REFRESH_INTERVAL`] seconds, before the records expire;
+//! - announces each blob uploaded through `PUT /blobs` as it lands;
+//! - withdraws blobs deleted through `DELETE /blobs/…` or evicted by the
+//!   garbage collector.
+//!
+//! A withdrawal leaves the provider set and publishes a signed tombstone
+//! over the provider's record. Other peers' copies of the provider entry
+//! are not recalled, so [`find_blob_providers`] reads each provider's own
+//! record and drops tombstoned, expired or mis-signed ones. Blobs removed
+//! while the server is down (`plasmd provider gc`) are not withdrawn; their
+//! records lapse within [`DEFAULT_BLOB_RECORD_TTL`](crate::dht::DEFAULT_BLOB_RECORD_TTL)
+//! seconds.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # async fn run(discovery: Arc<phase_net::Discovery>, id: phase_artifact_server::BlobId) -> anyhow::Result<()> {
+//! use phase_artifact_server::{find_blob_providers, BlobFetcher};
+//!
+//! let mut sources = Vec::new();
+//! let mut peers = Vec::new();
+//! for record in find_blob_providers(&discovery, &id).await? {
+//!     sources.extend(record.http_url.clone());
+//!     if let (Some(peer), Some(addr)) = (record.peer(), record.p2p_addrs.first()) {
+//!         discovery.dial_peer(addr).await?;
+//!         peers.push(peer);
+//!     }
+//! }
+//! let mut fetcher = BlobFetcher::new(sources);
+//! for peer in peers {
+//!     fetcher = fetcher.with_peer(Arc::clone(&discovery), peer);
+//! }
+//! fetcher.fetch(&id, std::path::Path::new("/tmp/blob.bin")).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`ArtifactServer`]: crate::ArtifactServer
+//! [`ArtifactServer::with_announcer`]: crate::ArtifactServer::with_announcer
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures_util::stream::{self, StreamExt};
+use phase_net::{Discovery, PeerId};
+use tracing::{debug, info, warn};
+
+use crate::artifacts::{ArtifactStore, BlobId};
+use crate::dht::{blob_dht_key, BlobProviderRecord, BLOB_REFRESH_INTERVAL};
+
+/// Provider records read at once by [`find_blob_providers`].
+const LOOKUP_CONCURRENCY: usize = 8;
+
+/// Publishes provider records for the blobs in a store.
+pub struct BlobAnnouncer {
+    discovery: Arc<Discovery>,
+    store: Arc<ArtifactStore>,
+    http_url: Option<String>,
+}
+
+impl BlobAnnouncer {
+    /// Announce `store`'s blobs through `discovery`, reachable over
+    /// libp2p only until [`Self::with_http_url`] says otherwise.
+    pub fn new(discovery: Arc<Discovery>, store: Arc<ArtifactStore>) -> Self {
+        Self {
+            discovery,
+            store,
+            http_url: None,
+        }
+    }
+
+    /// Advertise `url` (`http://ip:port`, as peers reach it) as the HTTP
+    /// source for every blob.
+    pub fn with_http_url(mut self, url: impl Into<String>) -> Self {
+        self.http_url = Some(url.into());
+        self
+    }
+
+    /// Publish this node's record for `id` and join its provider set.
+    pub async fn announce(&self, id: &BlobId) -> Result<()> {
+        let meta = self
+            .store
+            .get_blob(id)?
+            .ok_or_else(|| anyhow!("Blob {} is not stored", id))?;
+        let record = BlobProviderRecord::new(
+            id.as_str(),
+            self.discovery.local_peer_id(),
+            meta.size_bytes,
+            self.http_url.clone(),
+            self.p2p_addrs().await?,
+        );
+        self.publish(&record).await?;
+        self.discovery
+            .start_providing(blob_dht_key(id.as_str()).to_vec())
+            .await
+    }
+
+    /// Leave `id`'s provider set and tombstone this node's record for it.
+    pub async fn withdraw(&self, id: &BlobId) -> Result<()> {
+        self.discovery
+            .stop_providing(blob_dht_key(id.as_str()).to_vec())
+            .await?;
+        let tombstone = BlobProviderRecord::withdrawal(id.as_str(), self.discovery.local_peer_id());
+        self.publish(&tombstone).await
+    }
+
+    /// Announce every blob in the store. Returns how many were announced;
+    /// failures are logged and skipped.
+    pub async fn announce_all(&self) -> Result<usize> {
+        let store = Arc::clone(&self.store);
+        let ids = tokio::task::spawn_blocking(move || store.list_blobs()).await??;
+        let mut announced = 0;
+        for id in &ids {
+            match self.announce(id).await {
+                Ok(()) => announced += 1,
+                Err(e) => warn!("Failed to announce blob {}: {:#}", id, e),
+            }
+        }
+        Ok(announced)
+    }
+
+    /// Re-announce everything now and every [`BLOB_REFRESH_INTERVAL`]
+    /// seconds after.
+    pub(crate) async fn run_refresh_loop(self: Arc<Self>) {
+        let mut tick = tokio::time::interval(Duration::from_secs(BLOB_REFRESH_INTERVAL));
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tick.tick().await;
+            match self.announce_all().await {
+                Ok(n) => info!("Announced {} blobs on the DHT", n),
+                Err(e) => warn!("Blob announcement failed: {:#}", e),
+            }
+        }
+    }
+
+    async fn publish(&self, record: &BlobProviderRecord) -> Result<()> {
+        self.discovery
+            .publish_signed_record(
+                record.key().to_vec(),
+                record.to_bytes()?,
+                Duration::from_secs(record.ttl_secs),
+            )
+            .await
+    }
+
+    /// Listen addresses with this node's `/p2p/` suffix.
+    async fn p2p_addrs(&self) -> Result<Vec<String>> {
+        let suffix = format!("/p2p/{}", self.discovery.local_peer_id());
+        Ok(self
+            .discovery
+            .listen_addrs()
+            .await?
+            .into_iter()
+            .map(|addr| {
+                if addr.ends_with(&suffix) {
+                    addr
+                } else {
+                    format!("{}{}", addr, suffix)
+                }
+            })
+            .collect())
+    }
+}
+
+impl std::fmt::Debug for BlobAnnouncer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlobAnnouncer")
+            .field("peer", self.discovery.local_peer_id())
+            .field("http_url", &self.http_url)
+            .finish()
+    }
+}
+
+/// Current providers of `id`: peers in the blob's provider set whose own
+/// signed record is live and not a withdrawal. An empty `Vec` is a normal
+/// miss.
+pub async fn find_blob_providers(discovery: &Discovery, id: &BlobId) -> Result<Vec<BlobProviderRecord>> {
+    let peers = discovery.get_providers(blob_dht_key(id.as_str()).to_vec()).await?;
+    let records: Vec<Option<BlobProviderRecord>> = stream::iter(peers)
+        .map(|peer| provider_record(discovery, id, peer))
+        .buffered(LOOKUP_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(records.into_iter().flatten().collect())
+}
+
+/// `peer`'s newest valid record for `id`, unless it is a withdrawal.
+async fn provider_record(discovery: &Discovery, id: &BlobId, peer: PeerId) -> Option<BlobProviderRecord> {
+    let key = BlobProviderRecord::dht_key(id.as_str(), &peer);
+    let values = match discovery.get_kad_record(key.to_vec()).await {
+        Ok(values) => values,
+        Err(e) => {
+            debug!("No provider record for blob {} from {}: {:#}", id, peer, e);
+            return None;
+        }
+    };
+    values
+        .iter()
+        .filter_map(|bytes| BlobProviderRecord::from_signed_bytes(bytes).ok())
+        .filter(|r| r.blob_id == id.as_str() && r.peer() == Some(peer) && !r.is_expired())
+        .max_by(|a, b| a.created_at.cmp(&b.created_at))
+        .filter(|r| !r.withdrawn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use phase_net::DiscoveryConfig;
+
+    fn node() -> Arc<Discovery> {
+        Arc::new(
+            Discovery::new(DiscoveryConfig {
+                mdns: false,
+                autonat: false,
+                ..DiscoveryConfig::default()
+            })
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn announced_blobs_are_found_until_withdrawn() {
+        tokio::time::timeout(Duration::from_secs(30), async {
+            let temp = tempfile::TempDir::new().unwrap();
+            let store = Arc::new(ArtifactStore::new(temp.path().to_path_buf()).unwrap());
+            let id = store.add_blob(b"announced blob").unwrap();
+            let discovery = node();
+            discovery.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+            let announcer = BlobAnnouncer::new(Arc::clone(&discovery), Arc::clone(&store))
+                .with_http_url("http://127.0.0.1:8080");
+
+            assert_eq!(announcer.announce_all().await.unwrap(), 1);
+            let providers = find_blob_providers(&discovery, &id).await.unwrap();
+            assert_eq!(providers.len(), 1, "{providers:?}");
+            let record = &providers[0];
+            assert_eq!(record.peer(), Some(*discovery.local_peer_id()));
+            assert_eq!(record.size_bytes, 14);
+            assert_eq!(record.http_url.as_deref(), Some("http://127.0.0.1:8080"));
+            assert!(record.p2p_addrs.iter().all(|a| a.ends_with(&record.peer_id)));
+            assert!(!record.p2p_addrs.is_empty());
+
+            announcer.withdraw(&id).await.unwrap();
+            assert!(find_blob_providers(&discovery, &id).await.unwrap().is_empty());
+
+            let missing = BlobId::from_content(b"never stored");
+            assert!(announcer.announce(&missing).await.is_err());
+        })
+        .await
+        .expect("announcement round trip timed out");
+    }
+}
@@ -12,6 +12,16 @@
 //!    `<aa>` is the first two hex characters. Added in M6 so the server can
 //!    distribute any blob keyed by its hash, independent of channel/arch
 //!    semantics.
+//!
+//! Both layouts can be filled from a byte slice or streamed from an
+//! [`AsyncRead`] / file ([`ArtifactStore::ingest_blob`] and friends). A
+//! streamed ingest is hashed while it is written to a temp file under
+//! `blobs/.incoming/`, then renamed into place, so a 40 GB model never sits
+//! in memory and a reader never sees a half-written blob.
+//!
+//! Every blob also gets an [`Outboard`] of per-chunk hashes at
+//! `blobs/<aa>/<full_hex>.outboard`, so downloads can be verified a chunk
+//! at a time (see [`crate::outboard`]).
 
 use anyhow::{Context, Result};
 use sha2::{Digest, Sha256};
@@ -20,13 +30,31 @@ use std::fmt;
 use std::fs;
 use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tracing::warn;
 
+use crate::outboard::{Outboard, OutboardBuilder};
+
+/// Staging directory for streamed ingests, under `blobs/`. On the same
+/// filesystem as the final paths so publishing is an atomic rename; the
+/// leading dot keeps it from ever parsing as a bucket.
+const INCOMING_DIR: &str = ".incoming";
+
+/// Read size for streamed ingests.
+const INGEST_CHUNK: usize = 256 * 1024;
+
+/// Staged files older than this are left over from a crash and are
+/// removed when a store opens.
+const STALE_INGEST_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
 /// Content-address for a blob. The wire form is the lowercase hex
 /// SHA-256 of the blob's contents — no `sha256:` prefix because the
 /// algorithm is implicit in the path layout.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(transparent)]
 pub struct BlobId(String);
 
 impl BlobId {
@@ -62,6 +90,14 @@ impl BlobId {
     pub fn relative_path(&self) -> PathBuf {
         PathBuf::from("blobs").join(self.prefix()).join(format!("{}.bin", self.0))
     }
+
+    /// Relative path of this blob's [`Outboard`]:
+    /// `blobs/<aa>/<full_hex>.outboard`.
+    pub fn outboard_relative_path(&self) -> PathBuf {
+        PathBuf::from("blobs")
+            .join(self.prefix())
+            .join(format!("{}.outboard", self.0))
+    }
 }
 
 impl fmt::Display for BlobId {
@@ -82,6 +118,54 @@ pub struct ArtifactMeta {
     pub hash: String,
 }
 
+/// Result of a streamed blob ingest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestedBlob {
+    pub id: BlobId,
+    pub size_bytes: u64,
+    /// The blob was already stored (or another ingest stored it first);
+    /// nothing new was written.
+    pub deduplicated: bool,
+}
+
+/// A streamed ingest's content didn't hash to the id the caller expected.
+/// Returned inside the `anyhow::Error`; downcast to tell it apart from I/O
+/// failures.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("blob hash mismatch: expected {expected}, got {actual}")]
+pub struct HashMismatch {
+    pub expected: BlobId,
+    pub actual: BlobId,
+}
+
+/// A channel/arch/filename write or delete named an unsafe path component.
+/// Returned inside the `anyhow::Error`, like [`HashMismatch`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid channel/arch/filename: {channel}/{arch}/{filename}")]
+pub struct InvalidChannelPath {
+    pub channel: String,
+    pub arch: String,
+    pub filename: String,
+}
+
+impl InvalidChannelPath {
+    fn new(channel: &str, arch: &str, filename: &str) -> Self {
+        Self {
+            channel: channel.to_string(),
+            arch: arch.to_string(),
+            filename: filename.to_string(),
+        }
+    }
+}
+
+/// Lookups answered from the channel/arch hash cache versus hashed from
+/// disk, since the store was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct HashCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
 /// Manages artifact storage and retrieval across both the channel/arch and
 /// content-addressed layouts.
 #[derive(Debug)]
@@ -90,6 +174,11 @@ pub struct ArtifactStore {
     /// Cache of computed hashes for channel/arch-keyed lookups:
     /// `(channel, arch, name) -> "sha256:<hex>"`.
     hash_cache: RwLock<HashMap<(String, String, String), String>>,
+    hash_cache_hits: AtomicU64,
+    hash_cache_misses: AtomicU64,
+    /// One lock per expected blob id with an ingest in flight, so
+    /// concurrent ingests of known content write it once.
+    ingests: Mutex<HashMap<BlobId, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl ArtifactStore {
@@ -100,9 +189,14 @@ impl ArtifactStore {
                 .with_context(|| format!("Failed to create artifacts dir: {:?}", base_dir))?;
         }
 
+        remove_stale_ingests(&base_dir.join("blobs").join(INCOMING_DIR));
+
         Ok(Self {
             base_dir,
             hash_cache: RwLock::new(HashMap::new()),
+            hash_cache_hits: AtomicU64::new(0),
+            hash_cache_misses: AtomicU64::new(0),
+            ingests: Mutex::new(HashMap::new()),
         })
     }
 
@@ -111,6 +205,14 @@ impl ArtifactStore {
         &self.base_dir
     }
 
+    /// Hit and miss counts of the channel/arch hash cache.
+    pub fn hash_cache_stats(&self) -> HashCacheStats {
+        HashCacheStats {
+            hits: self.hash_cache_hits.load(Ordering::Relaxed),
+            misses: self.hash_cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
     // ------------------------------------------------------------------
     // Channel / arch layout (legacy, byte-identical to pre-M6)
     // ------------------------------------------------------------------
@@ -241,12 +343,7 @@ impl ArtifactStore {
             || !Self::is_valid_name(arch)
             || !Self::is_valid_name(filename)
         {
-            anyhow::bail!(
-                "invalid channel/arch/filename: {}/{}/{}",
-                channel,
-                arch,
-                filename
-            );
+            return Err(InvalidChannelPath::new(channel, arch, filename).into());
         }
         let dir = self.base_dir.join(channel).join(arch);
         fs::create_dir_all(&dir)
@@ -288,6 +385,7 @@ impl ArtifactStore {
                     .with_context(|| format!("create blob bucket dir {:?}", parent))?;
             }
             fs::write(&path, content).with_context(|| format!("write blob {:?}", path))?;
+            self.write_outboard(&id, &Outboard::compute(content))?;
         }
         Ok(id)
     }
@@ -318,6 +416,331 @@ impl ArtifactStore {
         }))
     }
 
+    /// On-disk path of a blob's [`Outboard`], computing it first if the
+    /// blob predates outboards. `None` if the blob isn't stored. Reads the
+    /// whole blob when it has to compute, so call off the async runtime.
+    pub fn ensure_outboard(&self, id: &BlobId) -> Result<Option<PathBuf>> {
+        let path = self.base_dir.join(id.outboard_relative_path());
+        if path.is_file() {
+            return Ok(Some(path));
+        }
+        let Some(blob) = self.get_blob_path(id) else {
+            return Ok(None);
+        };
+        self.write_outboard(id, &Outboard::compute_file(&blob)?)?;
+        Ok(Some(path))
+    }
+
+    /// A blob's [`Outboard`], computing it if needed.
+    pub fn get_outboard(&self, id: &BlobId) -> Result<Option<Outboard>> {
+        let Some(path) = self.ensure_outboard(id)? else {
+            return Ok(None);
+        };
+        let bytes = fs::read(&path).with_context(|| format!("read outboard {:?}", path))?;
+        Outboard::from_bytes(&bytes)
+            .with_context(|| format!("parse outboard {:?}", path))
+            .map(Some)
+    }
+
+    /// Publish `outboard` for `id` via a staged file and rename.
+    fn write_outboard(&self, id: &BlobId, outboard: &Outboard) -> Result<()> {
+        let path = self.base_dir.join(id.outboard_relative_path());
+        let staged = self.staging_path();
+        let written = (|| {
+            if let Some(parent) = staged.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("create ingest dir {:?}", parent))?;
+            }
+            fs::write(&staged, outboard.to_bytes())
+                .with_context(|| format!("write {:?}", staged))?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("create blob bucket dir {:?}", parent))?;
+            }
+            fs::rename(&staged, &path).with_context(|| format!("publish outboard {:?}", path))
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&staged);
+        }
+        written
+    }
+
+    // ------------------------------------------------------------------
+    // Removal
+    // ------------------------------------------------------------------
+
+    /// Delete a content-addressed blob and its outboard. Returns `false`
+    /// if it wasn't stored.
+    pub fn remove_blob(&self, id: &BlobId) -> Result<bool> {
+        let _ = fs::remove_file(self.base_dir.join(id.outboard_relative_path()));
+        let path = self.base_dir.join(id.relative_path());
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("remove blob {:?}", path)),
+        }
+    }
+
+    /// Delete the file at exactly `<base>/<channel>/<arch>/<filename>`.
+    /// Unlike lookups, arch aliases and filename alternatives are not
+    /// followed, so a delete only ever removes the file it names. Returns
+    /// `false` if there was nothing there.
+    pub fn remove_channel_artifact(
+        &self,
+        channel: &str,
+        arch: &str,
+        filename: &str,
+    ) -> Result<bool> {
+        if !Self::is_valid_channel_path(channel, arch, filename) {
+            return Err(InvalidChannelPath::new(channel, arch, filename).into());
+        }
+        let path = self.base_dir.join(channel).join(arch).join(filename);
+        if let Ok(mut cache) = self.hash_cache.write() {
+            cache.remove(&(channel.to_string(), arch.to_string(), filename.to_string()));
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("remove channel artifact {:?}", path)),
+        }
+    }
+
+    // ------------------------------------------------------------------
+    // Streamed ingest
+    // ------------------------------------------------------------------
+
+    /// Stream `reader` into the blob layout, hashing as it goes. With
+    /// `expected`, content that hashes differently is discarded with a
+    /// [`HashMismatch`] error, an already-stored blob returns at once
+    /// without reading, and concurrent ingests of that id wait for the
+    /// first rather than writing it again.
+    pub async fn ingest_blob<R>(&self, reader: R, expected: Option<&BlobId>) -> Result<IngestedBlob>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let Some(expected) = expected else {
+            return self.ingest_blob_unlocked(reader, None).await;
+        };
+        // Declared first so it drops last, after the lock (or the wait for
+        // it) is gone, even when this future is dropped mid-ingest.
+        let _forget = ForgetIngestLock { store: self, id: expected };
+        let guard = self.ingest_lock(expected).lock_owned().await;
+        let result = self.ingest_blob_unlocked(reader, Some(expected)).await;
+        drop(guard);
+        result
+    }
+
+    /// [`ingest_blob`](Self::ingest_blob) from a file on disk.
+    pub async fn ingest_blob_file(
+        &self,
+        path: &Path,
+        expected: Option<&BlobId>,
+    ) -> Result<IngestedBlob> {
+        let file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("open {:?} for ingest", path))?;
+        self.ingest_blob(file, expected).await
+    }
+
+    /// Stream `reader` into the channel/arch layout at
+    /// `<base>/<channel>/<arch>/<filename>`, replacing any existing file
+    /// atomically. With `expected`, content that hashes differently is
+    /// discarded with a [`HashMismatch`] error and the old file is kept.
+    pub async fn ingest_channel_artifact<R>(
+        &self,
+        channel: &str,
+        arch: &str,
+        filename: &str,
+        reader: R,
+        expected: Option<&BlobId>,
+    ) -> Result<ArtifactMeta>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if !Self::is_valid_channel_path(channel, arch, filename) {
+            return Err(InvalidChannelPath::new(channel, arch, filename).into());
+        }
+        let Staged {
+            part,
+            id,
+            size: size_bytes,
+            ..
+        } = self.stage(reader).await?;
+        if let Some(expected) = expected.filter(|e| **e != id) {
+            return Err(HashMismatch {
+                expected: expected.clone(),
+                actual: id,
+            }
+            .into());
+        }
+        let dir = self.base_dir.join(channel).join(arch);
+        let path = dir.join(filename);
+        let published = async {
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .with_context(|| format!("create channel artifact dir {:?}", dir))?;
+            tokio::fs::rename(&part.path, &path)
+                .await
+                .with_context(|| format!("publish channel artifact {:?}", path))
+        }
+        .await;
+        published?;
+        part.published();
+
+        let hash = format!("sha256:{}", id.as_str());
+        if let Ok(mut cache) = self.hash_cache.write() {
+            cache.insert(
+                (channel.to_string(), arch.to_string(), filename.to_string()),
+                hash.clone(),
+            );
+        }
+        Ok(ArtifactMeta {
+            name: filename.to_string(),
+            path,
+            size_bytes,
+            hash,
+        })
+    }
+
+    async fn ingest_blob_unlocked<R>(
+        &self,
+        reader: R,
+        expected: Option<&BlobId>,
+    ) -> Result<IngestedBlob>
+    where
+        R: AsyncRead + Unpin,
+    {
+        if let Some(expected) = expected {
+            if let Some(meta) = self.get_blob(expected)? {
+                return Ok(IngestedBlob {
+                    id: expected.clone(),
+                    size_bytes: meta.size_bytes,
+                    deduplicated: true,
+                });
+            }
+        }
+
+        let Staged {
+            part,
+            id,
+            size: size_bytes,
+            outboard,
+        } = self.stage(reader).await?;
+        if let Some(expected) = expected.filter(|e| **e != id) {
+            return Err(HashMismatch {
+                expected: expected.clone(),
+                actual: id,
+            }
+            .into());
+        }
+
+        // Identical content that landed first (a concurrent ingest without
+        // an expected id, or an `add_blob`) wins; ours is redundant.
+        let path = self.base_dir.join(id.relative_path());
+        if path.exists() {
+            return Ok(IngestedBlob {
+                id,
+                size_bytes,
+                deduplicated: true,
+            });
+        }
+        let published = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .with_context(|| format!("create blob bucket dir {:?}", parent))?;
+            }
+            tokio::fs::rename(&part.path, &path)
+                .await
+                .with_context(|| format!("publish blob {:?}", path))
+        }
+        .await;
+        published?;
+        part.published();
+        // A missing outboard is rebuilt on first request, so this can't
+        // fail the ingest.
+        if let Err(e) = self.write_outboard(&id, &outboard) {
+            warn!("Failed to write outboard for blob {}: {:#}", id, e);
+        }
+
+        Ok(IngestedBlob {
+            id,
+            size_bytes,
+            deduplicated: false,
+        })
+    }
+
+    /// A fresh, unused path under `blobs/.incoming/`.
+    fn staging_path(&self) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        self.base_dir.join("blobs").join(INCOMING_DIR).join(format!(
+            "{}-{}.part",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// Copy `reader` to a fresh file under `blobs/.incoming/`, fsync it,
+    /// and return it with its content id, size and outboard. The file is
+    /// removed on failure, or if this future is dropped.
+    async fn stage<R>(&self, mut reader: R) -> Result<Staged>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let part = PartFile::new(self.staging_path());
+        let staged = &part.path;
+        if let Some(dir) = staged.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("create ingest dir {:?}", dir))?;
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(staged)
+            .await
+            .with_context(|| format!("create {:?}", staged))?;
+        let mut hasher = Sha256::new();
+        let mut outboard = OutboardBuilder::new();
+        let mut size = 0u64;
+        let mut buf = vec![0u8; INGEST_CHUNK];
+        loop {
+            let n = reader.read(&mut buf).await.context("read ingest source")?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            outboard.update(&buf[..n]);
+            file.write_all(&buf[..n])
+                .await
+                .with_context(|| format!("write {:?}", staged))?;
+            size += n as u64;
+        }
+        file.sync_all()
+            .await
+            .with_context(|| format!("sync {:?}", staged))?;
+        Ok(Staged {
+            part,
+            id: BlobId(hex::encode(hasher.finalize())),
+            size,
+            outboard: outboard.finish(),
+        })
+    }
+
+    fn ingest_lock(&self, id: &BlobId) -> Arc<tokio::sync::Mutex<()>> {
+        let mut ingests = self.ingests.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(ingests.entry(id.clone()).or_default())
+    }
+
+    /// Drop `id`'s lock once nobody else holds or awaits it.
+    fn forget_ingest_lock(&self, id: &BlobId) {
+        let mut ingests = self.ingests.lock().unwrap_or_else(PoisonError::into_inner);
+        if ingests.get(id).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            ingests.remove(id);
+        }
+    }
+
     // ------------------------------------------------------------------
     // Hashing helpers
     // ------------------------------------------------------------------
@@ -353,10 +776,12 @@ impl ArtifactStore {
 
         if let Ok(cache) = self.hash_cache.read() {
             if let Some(hash) = cache.get(&key) {
+                self.hash_cache_hits.fetch_add(1, Ordering::Relaxed);
                 return Ok(hash.clone());
             }
         }
 
+        self.hash_cache_misses.fetch_add(1, Ordering::Relaxed);
         let hash = Self::compute_hash(path)?;
         if let Ok(mut cache) = self.hash_cache.write() {
             cache.insert(key, hash.clone());
@@ -377,6 +802,15 @@ impl ArtifactStore {
             && name != "."
     }
 
+    /// Names a writable channel/arch path. The `blobs` tree is off limits
+    /// so nothing can land at a content address without being hashed.
+    fn is_valid_channel_path(channel: &str, arch: &str, filename: &str) -> bool {
+        channel != "blobs"
+            && Self::is_valid_name(channel)
+            && Self::is_valid_name(arch)
+            && Self::is_valid_name(filename)
+    }
+
     fn artifact_alternatives(name: &str) -> Vec<String> {
         match name {
             "kernel" => vec![
@@ -405,6 +839,76 @@ impl ArtifactStore {
     }
 }
 
+/// A fully written, fsynced ingest waiting to be published.
+struct Staged {
+    part: PartFile,
+    id: BlobId,
+    size: u64,
+    outboard: Outboard,
+}
+
+/// A file under `blobs/.incoming/`, removed on drop unless it was
+/// [`published`](Self::published). Cleanup can't live on the error path
+/// alone: an ingest whose future is dropped (a client hanging up mid-PUT)
+/// never reaches it.
+struct PartFile {
+    path: PathBuf,
+    published: bool,
+}
+
+impl PartFile {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            published: false,
+        }
+    }
+
+    /// The file was renamed into place; leave it be.
+    fn published(mut self) {
+        self.published = true;
+    }
+}
+
+impl Drop for PartFile {
+    fn drop(&mut self) {
+        if !self.published {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Releases an expected id's ingest lock entry once the ingest holding or
+/// awaiting it is gone, however it ended.
+struct ForgetIngestLock<'a> {
+    store: &'a ArtifactStore,
+    id: &'a BlobId,
+}
+
+impl Drop for ForgetIngestLock<'_> {
+    fn drop(&mut self) {
+        self.store.forget_ingest_lock(self.id);
+    }
+}
+
+/// Best-effort removal of staged ingests a crashed process left behind.
+fn remove_stale_ingests(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age > STALE_INGEST_AGE);
+        if stale {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -545,6 +1049,167 @@ mod tests {
         assert_eq!(a, b);
     }
 
+    fn incoming_is_empty(temp: &TempDir) -> bool {
+        fs::read_dir(temp.path().join("blobs").join(INCOMING_DIR))
+            .map(|mut d| d.next().is_none())
+            .unwrap_or(true)
+    }
+
+    #[tokio::test]
+    async fn test_ingest_blob_streams_into_blob_layout() {
+        let (temp, store) = setup_test_store();
+        let content = vec![0x5au8; 3 * INGEST_CHUNK + 17];
+        let ingested = store.ingest_blob(&content[..], None).await.unwrap();
+        assert_eq!(ingested.id, BlobId::from_content(&content));
+        assert_eq!(ingested.size_bytes, content.len() as u64);
+        assert!(!ingested.deduplicated);
+        assert_eq!(fs::read(temp.path().join(ingested.id.relative_path())).unwrap(), content);
+        assert!(incoming_is_empty(&temp));
+
+        // Same content again, by file path this time.
+        let source = temp.path().join("source.bin");
+        fs::write(&source, &content).unwrap();
+        let again = store.ingest_blob_file(&source, None).await.unwrap();
+        assert_eq!(again.id, ingested.id);
+        assert!(again.deduplicated);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_blob_rejects_hash_mismatch() {
+        let (temp, store) = setup_test_store();
+        let expected = BlobId::from_content(b"what we asked for");
+        let err = store
+            .ingest_blob(&b"what we got"[..], Some(&expected))
+            .await
+            .unwrap_err();
+        let mismatch = err.downcast_ref::<HashMismatch>().expect("typed mismatch");
+        assert_eq!(mismatch.actual, BlobId::from_content(b"what we got"));
+        assert!(store.get_blob(&mismatch.actual).unwrap().is_none());
+        assert!(incoming_is_empty(&temp));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_ingests_of_expected_blob_write_once() {
+        let (temp, store) = setup_test_store();
+        let content = vec![7u8; 2 * INGEST_CHUNK];
+        let id = BlobId::from_content(&content);
+        let (a, b, c, d) = tokio::join!(
+            store.ingest_blob(&content[..], Some(&id)),
+            store.ingest_blob(&content[..], Some(&id)),
+            store.ingest_blob(&content[..], Some(&id)),
+            store.ingest_blob(&content[..], Some(&id)),
+        );
+        let written = [a, b, c, d]
+            .into_iter()
+            .map(|r| r.unwrap())
+            .filter(|r| !r.deduplicated)
+            .count();
+        assert_eq!(written, 1);
+        assert!(store.ingests.lock().unwrap().is_empty());
+        assert!(incoming_is_empty(&temp));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_ingest_cleans_up() {
+        use tokio::io::AsyncWriteExt;
+
+        let (temp, store) = setup_test_store();
+        let id = BlobId::from_content(b"never finished");
+        // A client that sends part of the body and then stalls; the
+        // request future is dropped, as axum does when the client hangs up.
+        let (mut client, body) = tokio::io::duplex(64);
+        client.write_all(b"never").await.unwrap();
+        let ingest = store.ingest_blob(body, Some(&id));
+        assert!(tokio::time::timeout(Duration::from_millis(50), ingest).await.is_err());
+
+        assert!(store.ingests.lock().unwrap().is_empty());
+        assert!(incoming_is_empty(&temp));
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_channel_artifact_replaces_atomically() {
+        let (temp, store) = setup_test_store();
+        store
+            .add_channel_artifact("stable", "x86_64", "initramfs", b"old")
+            .unwrap();
+        let meta = store
+            .ingest_channel_artifact("stable", "x86_64", "initramfs", &b"new initramfs"[..], None)
+            .await
+            .unwrap();
+        assert_eq!(meta.size_bytes, 13);
+        assert_eq!(
+            meta.hash,
+            format!("sha256:{}", BlobId::from_content(b"new initramfs"))
+        );
+        let path = temp.path().join("stable").join("x86_64").join("initramfs");
+        assert_eq!(fs::read(path).unwrap(), b"new initramfs");
+        let err = store
+            .ingest_channel_artifact("..", "x86_64", "kernel", &b""[..], None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<InvalidChannelPath>(),
+            Some(&InvalidChannelPath::new("..", "x86_64", "kernel"))
+        );
+        assert!(store
+            .ingest_channel_artifact("blobs", "ab", "kernel", &b""[..], None)
+            .await
+            .is_err());
+
+        let wrong = BlobId::from_content(b"something else");
+        let err = store
+            .ingest_channel_artifact("stable", "x86_64", "initramfs", &b"evil"[..], Some(&wrong))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<HashMismatch>().is_some());
+        assert_eq!(
+            fs::read(temp.path().join("stable").join("x86_64").join("initramfs")).unwrap(),
+            b"new initramfs"
+        );
+        assert!(incoming_is_empty(&temp));
+    }
+
+    #[tokio::test]
+    async fn test_outboards_follow_blobs() {
+        let (temp, store) = setup_test_store();
+        let content = vec![7u8; 3 * 1024 * 1024 + 1];
+        let blob = store.ingest_blob(&content[..], None).await.unwrap();
+        let path = temp.path().join(blob.id.outboard_relative_path());
+        assert_eq!(
+            Outboard::from_bytes(&fs::read(&path).unwrap()).unwrap(),
+            Outboard::compute(&content)
+        );
+
+        // Blobs stored before outboards existed get one on demand.
+        fs::remove_file(&path).unwrap();
+        let rebuilt = store.get_outboard(&blob.id).unwrap().unwrap();
+        assert_eq!(rebuilt.chunk_count(), 4);
+        assert!(path.exists());
+
+        assert!(store.remove_blob(&blob.id).unwrap());
+        assert!(!path.exists());
+        assert!(store.get_outboard(&blob.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_remove_blob_and_channel_artifact() {
+        let (temp, store) = setup_test_store();
+        let id = store.add_blob(b"doomed").unwrap();
+        assert!(store.remove_blob(&id).unwrap());
+        assert!(store.get_blob(&id).unwrap().is_none());
+        assert!(!store.remove_blob(&id).unwrap());
+
+        store
+            .add_channel_artifact("stable", "x86_64", "vmlinuz", b"kernel")
+            .unwrap();
+        // Aliases resolve on lookup but never on delete.
+        assert!(!store.remove_channel_artifact("stable", "x86_64", "kernel").unwrap());
+        assert!(store.remove_channel_artifact("stable", "x86_64", "vmlinuz").unwrap());
+        assert!(!temp.path().join("stable/x86_64/vmlinuz").exists());
+        assert!(store.remove_channel_artifact("blobs", "ab", "x").is_err());
+    }
+
     #[test]
     fn test_list_channels_excludes_blobs_bucket() {
         let (temp, store) = setup_test_store();
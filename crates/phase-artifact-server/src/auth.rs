@@ -0,0 +1,354 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Signed write requests.
+//!
+//! Reads are open, but uploads and deletes must be signed by a
+//! [`NodeIdentity`] on the server's operator allowlist
+//! ([`crate::ArtifactServerConfig::operators`]). The signature travels in
+//! headers:
+//!
+//! ```text
+//! x-phase-key:            <hex Ed25519 public key>
+//! x-phase-timestamp:      <unix milliseconds>
+//! x-phase-content-sha256: <hex SHA-256 of the body>   (PUT, required)
+//! x-phase-signature:      <hex Ed25519 signature>
+//! ```
+//!
+//! and covers
+//! `"phase-artifact-request:v1:" || method || "\n" || path || "\n" || timestamp || "\n" || content_sha256`.
+//! Binding the content hash means a captured signature can't be reused to
+//! carry different bytes: the server streams the body through the hashing
+//! ingest and discards it if it doesn't match. The hash is required on
+//! every upload: without it a signature captured off the wire could carry
+//! any bytes to `PUT /blobs` within the replay window, since the signed
+//! path names no content.
+//!
+//! Timestamps more than [`MAX_CLOCK_SKEW`] from the server's clock are
+//! rejected, and accepted signatures are remembered until they age out of
+//! that window, so a request can't be replayed.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use phase_identity::{allowlist_permits, NodeIdentity, Signature, VerifyingKey};
+
+use crate::artifacts::BlobId;
+
+/// Header carrying the signer's hex public key.
+pub const KEY_HEADER: &str = "x-phase-key";
+/// Header carrying the signing time in unix milliseconds.
+pub const TIMESTAMP_HEADER: &str = "x-phase-timestamp";
+/// Header carrying the hex SHA-256 of the request body.
+pub const CONTENT_HASH_HEADER: &str = "x-phase-content-sha256";
+/// Header carrying the hex Ed25519 signature.
+pub const SIGNATURE_HEADER: &str = "x-phase-signature";
+
+/// How far a request's timestamp may be from the server's clock.
+pub const MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+const SIGNING_DOMAIN: &[u8] = b"phase-artifact-request:v1:";
+
+/// Why a write request was refused.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AuthError {
+    #[error("uploads are disabled: no operators configured")]
+    WritesDisabled,
+    #[error("missing {0} header")]
+    MissingHeader(&'static str),
+    #[error("malformed {0} header")]
+    MalformedHeader(&'static str),
+    #[error("request timestamp {timestamp_ms} is too far from server time {now_ms}")]
+    Stale { timestamp_ms: u64, now_ms: u64 },
+    #[error("request signature does not verify")]
+    BadSignature,
+    #[error("request was already accepted once")]
+    Replayed,
+    #[error("key {0} is not an operator")]
+    NotOperator(String),
+    #[error("uploads must sign a {CONTENT_HASH_HEADER}")]
+    ContentHashRequired,
+}
+
+/// An operator's signature over one write request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestSignature {
+    pub public_key: VerifyingKey,
+    pub timestamp_ms: u64,
+    /// Hash the body must have; `None` for deletes.
+    pub content_hash: Option<BlobId>,
+    pub signature: Signature,
+}
+
+impl RequestSignature {
+    /// Sign `method path` now. `path` is the request path exactly as sent
+    /// (e.g. `/blobs` or `/stable/x86_64/kernel`).
+    pub fn sign(
+        identity: &NodeIdentity,
+        method: &str,
+        path: &str,
+        content_hash: Option<&BlobId>,
+    ) -> Self {
+        Self::sign_at(identity, method, path, content_hash, unix_ms_now())
+    }
+
+    /// [`sign`](Self::sign) with an explicit timestamp.
+    pub fn sign_at(
+        identity: &NodeIdentity,
+        method: &str,
+        path: &str,
+        content_hash: Option<&BlobId>,
+        timestamp_ms: u64,
+    ) -> Self {
+        let message = signed_message(method, path, timestamp_ms, content_hash);
+        Self {
+            public_key: identity.verifying_key(),
+            timestamp_ms,
+            content_hash: content_hash.cloned(),
+            signature: identity.sign(&message),
+        }
+    }
+
+    /// The headers to attach to the request.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            (KEY_HEADER, hex::encode(self.public_key.as_bytes())),
+            (TIMESTAMP_HEADER, self.timestamp_ms.to_string()),
+            (SIGNATURE_HEADER, hex::encode(self.signature.to_bytes())),
+        ];
+        if let Some(hash) = &self.content_hash {
+            headers.push((CONTENT_HASH_HEADER, hash.as_str().to_string()));
+        }
+        headers
+    }
+
+    /// Parse the signature headers of an incoming request.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, AuthError> {
+        let get = |name: &'static str| -> Result<Option<&str>, AuthError> {
+            headers
+                .get(name)
+                .map(|v| v.to_str().map_err(|_| AuthError::MalformedHeader(name)))
+                .transpose()
+        };
+        let required =
+            |name: &'static str| get(name)?.ok_or(AuthError::MissingHeader(name));
+
+        let key: [u8; 32] = decode_hex(required(KEY_HEADER)?)
+            .ok_or(AuthError::MalformedHeader(KEY_HEADER))?;
+        let public_key =
+            VerifyingKey::from_bytes(&key).map_err(|_| AuthError::MalformedHeader(KEY_HEADER))?;
+        let timestamp_ms = required(TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| AuthError::MalformedHeader(TIMESTAMP_HEADER))?;
+        let signature: [u8; 64] = decode_hex(required(SIGNATURE_HEADER)?)
+            .ok_or(AuthError::MalformedHeader(SIGNATURE_HEADER))?;
+        let content_hash = get(CONTENT_HASH_HEADER)?
+            .map(|h| BlobId::from_hex(h).ok_or(AuthError::MalformedHeader(CONTENT_HASH_HEADER)))
+            .transpose()?;
+
+        Ok(Self {
+            public_key,
+            timestamp_ms,
+            content_hash,
+            signature: Signature::from_bytes(&signature),
+        })
+    }
+
+    /// Check the timestamp against `now_ms` and the signature against
+    /// `method path`. Says nothing about whether the key may write.
+    pub fn verify(&self, method: &str, path: &str, now_ms: u64) -> Result<(), AuthError> {
+        if self.timestamp_ms.abs_diff(now_ms) > MAX_CLOCK_SKEW.as_millis() as u64 {
+            return Err(AuthError::Stale {
+                timestamp_ms: self.timestamp_ms,
+                now_ms,
+            });
+        }
+        let message = signed_message(method, path, self.timestamp_ms, self.content_hash.as_ref());
+        self.public_key
+            .verify_strict(&message, &self.signature)
+            .map_err(|_| AuthError::BadSignature)
+    }
+}
+
+/// The server side: who may write, and which signatures were already used.
+#[derive(Debug, Default)]
+pub(crate) struct WriteGate {
+    operators: Vec<String>,
+    successions: Vec<String>,
+    /// Accepted signatures and their timestamps, kept until they fall
+    /// outside [`MAX_CLOCK_SKEW`].
+    seen: Mutex<HashMap<[u8; 64], u64>>,
+}
+
+impl WriteGate {
+    pub(crate) fn new(operators: Vec<String>, successions: Vec<String>) -> Self {
+        Self {
+            operators,
+            successions,
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Authenticate a write request from its method, path and headers. A
+    /// `PUT` whose signature doesn't name the body's hash is refused.
+    pub(crate) fn authorize(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<RequestSignature, AuthError> {
+        self.authorize_at(method, path, headers, unix_ms_now())
+    }
+
+    fn authorize_at(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        now_ms: u64,
+    ) -> Result<RequestSignature, AuthError> {
+        if self.operators.is_empty() {
+            return Err(AuthError::WritesDisabled);
+        }
+        let request = RequestSignature::from_headers(headers)?;
+        request.verify(method, path, now_ms)?;
+        let key_hex = hex::encode(request.public_key.as_bytes());
+        if !allowlist_permits(&self.operators, &self.successions, &key_hex) {
+            return Err(AuthError::NotOperator(key_hex));
+        }
+        if method.eq_ignore_ascii_case("PUT") && request.content_hash.is_none() {
+            return Err(AuthError::ContentHashRequired);
+        }
+
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        let window = MAX_CLOCK_SKEW.as_millis() as u64;
+        seen.retain(|_, ts| ts.saturating_add(window) >= now_ms);
+        if seen
+            .insert(request.signature.to_bytes(), request.timestamp_ms)
+            .is_some()
+        {
+            return Err(AuthError::Replayed);
+        }
+        Ok(request)
+    }
+}
+
+fn signed_message(
+    method: &str,
+    path: &str,
+    timestamp_ms: u64,
+    content_hash: Option<&BlobId>,
+) -> Vec<u8> {
+    let mut message = SIGNING_DOMAIN.to_vec();
+    message.extend_from_slice(
+        format!(
+            "{method}\n{path}\n{timestamp_ms}\n{}",
+            content_hash.map(BlobId::as_str).unwrap_or_default()
+        )
+        .as_bytes(),
+    );
+    message
+}
+
+fn decode_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    hex::decode(s).ok()?.try_into().ok()
+}
+
+fn unix_ms_now() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderName, HeaderValue};
+
+    fn header_map(signature: &RequestSignature) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in signature.headers() {
+            map.insert(
+                HeaderName::from_static(name),
+                HeaderValue::from_str(&value).unwrap(),
+            );
+        }
+        map
+    }
+
+    #[test]
+    fn signature_binds_method_path_and_content() {
+        let operator = NodeIdentity::generate();
+        let hash = BlobId::from_content(b"kernel");
+        let signed = RequestSignature::sign_at(
+            &operator,
+            "PUT",
+            "/stable/x86_64/kernel",
+            Some(&hash),
+            1_000_000,
+        );
+        let parsed = RequestSignature::from_headers(&header_map(&signed)).unwrap();
+        assert_eq!(parsed, signed);
+        parsed.verify("PUT", "/stable/x86_64/kernel", 1_000_000).unwrap();
+
+        assert_eq!(
+            parsed.verify("DELETE", "/stable/x86_64/kernel", 1_000_000),
+            Err(AuthError::BadSignature)
+        );
+        assert_eq!(
+            parsed.verify("PUT", "/stable/x86_64/initramfs", 1_000_000),
+            Err(AuthError::BadSignature)
+        );
+        let swapped = RequestSignature {
+            content_hash: Some(BlobId::from_content(b"rootkit")),
+            ..parsed.clone()
+        };
+        assert_eq!(
+            swapped.verify("PUT", "/stable/x86_64/kernel", 1_000_000),
+            Err(AuthError::BadSignature)
+        );
+        let late = 1_000_000 + MAX_CLOCK_SKEW.as_millis() as u64 + 1;
+        assert!(matches!(
+            parsed.verify("PUT", "/stable/x86_64/kernel", late),
+            Err(AuthError::Stale { .. })
+        ));
+    }
+
+    #[test]
+    fn gate_checks_allowlist_and_replays() {
+        let operator = NodeIdentity::generate();
+        let stranger = NodeIdentity::generate();
+        let gate = WriteGate::new(
+            vec![hex::encode(operator.verifying_key().as_bytes())],
+            Vec::new(),
+        );
+        let now = 5_000_000;
+
+        let ok = header_map(&RequestSignature::sign_at(&operator, "DELETE", "/blobs", None, now));
+        gate.authorize_at("DELETE", "/blobs", &ok, now).unwrap();
+        assert_eq!(
+            gate.authorize_at("DELETE", "/blobs", &ok, now + 1),
+            Err(AuthError::Replayed)
+        );
+
+        let other = header_map(&RequestSignature::sign_at(&stranger, "DELETE", "/blobs", None, now));
+        assert!(matches!(
+            gate.authorize_at("DELETE", "/blobs", &other, now),
+            Err(AuthError::NotOperator(_))
+        ));
+        assert_eq!(
+            gate.authorize_at("DELETE", "/blobs", &HeaderMap::new(), now),
+            Err(AuthError::MissingHeader(KEY_HEADER))
+        );
+        for path in ["/stable/x86_64/kernel", "/blobs"] {
+            let unpinned = header_map(&RequestSignature::sign_at(&operator, "PUT", path, None, now));
+            assert_eq!(
+                gate.authorize_at("PUT", path, &unpinned, now),
+                Err(AuthError::ContentHashRequired)
+            );
+        }
+        assert_eq!(
+            WriteGate::default().authorize_at("DELETE", "/blobs", &ok, now),
+            Err(AuthError::WritesDisabled)
+        );
+    }
+}
@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validators, cache lifetimes and content coding for served responses.
+//!
+//! Every artifact, blob, outboard and manifest response carries a strong
+//! `ETag` derived from a hash of its content, so a client that already
+//! holds it can revalidate instead of downloading again:
+//!
+//! | Response | `ETag` | `Cache-Control` |
+//! |----------|--------|-----------------|
+//! | `/blobs/…/<hex>.bin` | `"sha256:<hex>"` | `public, max-age=31536000, immutable` |
+//! | `/blobs/…/<hex>.outboard` | `"blake3:<root>"` | `public, max-age=31536000, immutable` |
+//! | `/:channel/:arch/:artifact` | `"sha256:<hex>"` | `no-cache` |
+//! | manifests | `"sha256:<hex of the JSON body>"` | `no-cache` |
+//!
+//! Content-addressed paths can never change, so caches may keep them
+//! forever. Channel artifacts and manifests can be replaced in place and
+//! are revalidated on every use; channel artifacts also carry
+//! `Last-Modified`. A blob's modification time records its last use for
+//! the garbage collector, so blobs don't.
+//!
+//! A request whose `If-None-Match` (or, without one, `If-Modified-Since`)
+//! matches gets `304 Not Modified`. A Range request whose `If-Range` no
+//! longer matches gets the whole, current body rather than a slice of it.
+//!
+//! Clients that send `Accept-Encoding` get manifests, and full artifact
+//! bodies that don't already start with a compressed format's signature,
+//! in zstd or gzip (see [`compression_layer`]). Range responses are never
+//! encoded. An encoded body is a different representation, so its `ETag`
+//! names the coding too (`"sha256:<hex>+gzip"`, see [`tag_coded_bodies`]).
+//! `If-None-Match` accepts either form; `If-Range` only the identity one,
+//! since a range is always cut from the identity body.
+
+use std::time::SystemTime;
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, Extensions, HeaderMap, HeaderValue, StatusCode, Version};
+use axum::middleware::Next;
+use axum::response::Response;
+use tower_http::compression::predicate::{DefaultPredicate, Predicate};
+use tower_http::compression::CompressionLayer;
+
+/// `Cache-Control` for content-addressed responses.
+pub const CACHE_IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// `Cache-Control` for responses that may change under the same path.
+pub const CACHE_REVALIDATE: &str = "no-cache";
+
+/// A strong entity tag over `hash` (e.g. `"sha256:<hex>"`).
+pub fn etag(hash: &str) -> String {
+    format!("\"{}\"", hash)
+}
+
+/// Content codings [`compression_layer`] applies, which may suffix an
+/// entity tag.
+const CODINGS: [&str; 2] = ["gzip", "zstd"];
+
+/// `etag` for the body encoded with `coding`: `"x"` → `"x+gzip"`.
+pub fn coded_etag(etag: &str, coding: &str) -> String {
+    format!("{}+{}\"", etag.trim_end_matches('"'), coding)
+}
+
+/// `tag` without a [`coded_etag`] suffix, if it has one.
+fn strip_coding(tag: &str) -> String {
+    let base = tag.strip_suffix('"').and_then(|inner| {
+        CODINGS
+            .iter()
+            .find_map(|coding| inner.strip_suffix(coding)?.strip_suffix('+'))
+    });
+    match base {
+        Some(base) => format!("{}\"", base),
+        None => tag.to_string(),
+    }
+}
+
+/// Middleware outside [`compression_layer`] that gives encoded bodies a
+/// [`coded_etag`], and has a `304` echo the coded tag the client
+/// revalidated with.
+pub(crate) async fn tag_coded_bodies(request: Request, next: Next) -> Response {
+    let if_none_match = header_str(request.headers(), header::IF_NONE_MATCH).map(str::to_string);
+    let mut response = next.run(request).await;
+    let Some(etag) = header_str(response.headers(), header::ETAG).map(str::to_string) else {
+        return response;
+    };
+    let coded = if response.status() == StatusCode::NOT_MODIFIED {
+        if_none_match.and_then(|tags| {
+            tags.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .find(|tag| *tag != etag && strip_coding(tag) == etag)
+                .map(str::to_string)
+        })
+    } else {
+        header_str(response.headers(), header::CONTENT_ENCODING)
+            .filter(|coding| CODINGS.contains(coding))
+            .map(|coding| coded_etag(&etag, coding))
+    };
+    if let Some(value) = coded.and_then(|tag| HeaderValue::from_str(&tag).ok()) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    response
+}
+
+/// Response extension marking a body worth compressing.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Compressible;
+
+/// Compresses responses marked [`Compressible`] with zstd or gzip, as the
+/// client's `Accept-Encoding` prefers.
+pub(crate) fn compression_layer() -> CompressionLayer<impl Predicate> {
+    let marked = |_: StatusCode, _: Version, _: &HeaderMap, extensions: &Extensions| {
+        extensions.get::<Compressible>().is_some()
+    };
+    CompressionLayer::new()
+        .gzip(true)
+        .zstd(true)
+        .compress_when(DefaultPredicate::new().and(marked))
+}
+
+/// Whether `prefix`, the start of a file, is already in a compressed or
+/// archive format that another pass wouldn't shrink.
+pub fn looks_compressed(prefix: &[u8]) -> bool {
+    const SIGNATURES: &[&[u8]] = &[
+        b"\x1f\x8b",             // gzip
+        b"\x28\xb5\x2f\xfd",     // zstd
+        b"\xfd7zXZ\x00",         // xz
+        b"BZh",                  // bzip2
+        b"\x04\x22\x4d\x18",     // lz4 frame
+        b"\x02\x21\x4c\x18",     // lz4 legacy (kernels, initramfs)
+        b"\x89LZO",              // lzop
+        b"\x5d\x00\x00",         // lzma
+        b"PK\x03\x04",           // zip
+        b"7z\xbc\xaf\x27\x1c",   // 7z
+        b"\x89PNG",              // png
+        b"\xff\xd8\xff",         // jpeg
+        b"hsqs",                 // squashfs
+    ];
+    SIGNATURES.iter().any(|sig| prefix.starts_with(sig))
+}
+
+/// The validators and caching policy of one response.
+#[derive(Debug, Clone)]
+pub(crate) struct Validators {
+    etag: String,
+    last_modified: Option<SystemTime>,
+    cache_control: &'static str,
+}
+
+impl Validators {
+    pub(crate) fn new(etag: String, cache_control: &'static str) -> Self {
+        Self {
+            etag,
+            last_modified: None,
+            cache_control,
+        }
+    }
+
+    pub(crate) fn with_last_modified(mut self, modified: Option<SystemTime>) -> Self {
+        self.last_modified = modified;
+        self
+    }
+
+    /// Whether the client's copy is current: its `If-None-Match` names
+    /// this entity tag, in any coding, or, without that header, its
+    /// `If-Modified-Since` is no earlier than `Last-Modified`.
+    pub(crate) fn client_is_current(&self, headers: &HeaderMap) -> bool {
+        if let Some(tags) = header_str(headers, header::IF_NONE_MATCH) {
+            return tags.split(',').map(str::trim).any(|tag| {
+                // Weak comparison: a W/ prefix on the client's tag is ignored.
+                tag == "*" || strip_coding(tag.strip_prefix("W/").unwrap_or(tag)) == self.etag
+            });
+        }
+        match (header_str(headers, header::IF_MODIFIED_SINCE), self.last_modified) {
+            (Some(since), Some(modified)) => httpdate::parse_http_date(since)
+                .map(|since| since >= truncate_to_seconds(modified))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Whether a Range request may be answered with a slice: it has no
+    /// `If-Range`, or its `If-Range` still names this entity or date.
+    pub(crate) fn range_allowed(&self, headers: &HeaderMap) -> bool {
+        let Some(condition) = header_str(headers, header::IF_RANGE) else {
+            return true;
+        };
+        if condition.starts_with('"') {
+            // Strong comparison, against the identity tag: a coded tag
+            // names a body the range isn't cut from.
+            return condition == self.etag;
+        }
+        match (httpdate::parse_http_date(condition), self.last_modified) {
+            (Ok(date), Some(modified)) => date == truncate_to_seconds(modified),
+            _ => false,
+        }
+    }
+
+    /// Add `ETag`, `Cache-Control` and, when known, `Last-Modified`.
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(self.cache_control),
+        );
+        if let Some(modified) = self.last_modified {
+            if let Ok(date) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+                headers.insert(header::LAST_MODIFIED, date);
+            }
+        }
+    }
+
+    /// `304 Not Modified` with the validators and no body.
+    pub(crate) fn not_modified(&self) -> Response {
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        self.apply(response.headers_mut());
+        response
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// HTTP dates have whole-second resolution.
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    httpdate::parse_http_date(&httpdate::fmt_http_date(time)).unwrap_or(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn preconditions_follow_the_validators() {
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+        let validators =
+            Validators::new(etag("sha256:ab"), CACHE_REVALIDATE).with_last_modified(Some(modified));
+        let date = httpdate::fmt_http_date(modified);
+
+        assert!(!validators.client_is_current(&HeaderMap::new()));
+        assert!(validators.client_is_current(&headers(&[(header::IF_NONE_MATCH, "\"x\", W/\"sha256:ab\"")])));
+        assert!(validators.client_is_current(&headers(&[(header::IF_NONE_MATCH, "*")])));
+        assert!(validators.client_is_current(&headers(&[(header::IF_MODIFIED_SINCE, &date)])));
+        // If-None-Match wins over a date that would match.
+        assert!(!validators.client_is_current(&headers(&[
+            (header::IF_NONE_MATCH, "\"sha256:cd\""),
+            (header::IF_MODIFIED_SINCE, &date),
+        ])));
+        let earlier = httpdate::fmt_http_date(modified - Duration::from_secs(60));
+        assert!(!validators.client_is_current(&headers(&[(header::IF_MODIFIED_SINCE, &earlier)])));
+
+        assert!(validators.range_allowed(&HeaderMap::new()));
+        assert!(validators.range_allowed(&headers(&[(header::IF_RANGE, "\"sha256:ab\"")])));
+        assert!(validators.range_allowed(&headers(&[(header::IF_RANGE, &date)])));
+        assert!(!validators.range_allowed(&headers(&[(header::IF_RANGE, "\"sha256:cd\"")])));
+        assert!(!validators.range_allowed(&headers(&[(header::IF_RANGE, "W/\"sha256:ab\"")])));
+
+        // Coded tags revalidate but never allow a range.
+        let gzip = coded_etag(&etag("sha256:ab"), "gzip");
+        assert_eq!(gzip, "\"sha256:ab+gzip\"");
+        assert!(validators.client_is_current(&headers(&[(header::IF_NONE_MATCH, &gzip)])));
+        assert!(!validators.client_is_current(&headers(&[(header::IF_NONE_MATCH, "\"sha256:ab+br\"")])));
+        assert!(!validators.range_allowed(&headers(&[(header::IF_RANGE, &gzip)])));
+        assert_eq!(strip_coding("\"sha256:ab+zstd\""), "\"sha256:ab\"");
+        assert_eq!(strip_coding("\"gzip\""), "\"gzip\"");
+
+        let response = validators.not_modified();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], "\"sha256:ab\"");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(response.headers()[header::LAST_MODIFIED], date.as_str());
+    }
+
+    #[test]
+    fn compressed_formats_are_recognised() {
+        assert!(looks_compressed(b"\x1f\x8b\x08\x00"));
+        assert!(looks_compressed(b"\x28\xb5\x2f\xfd\x00"));
+        assert!(!looks_compressed(b"{\"manifest_version\""));
+        assert!(!looks_compressed(b"\x00asm\x01\x00\x00\x00"));
+        assert!(!looks_compressed(b""));
+    }
+}
@@ -9,7 +9,9 @@
 //! they are not intrinsic to "an HTTP server that serves content-addressed
 //! blobs". They now live on the daemon side. This crate's
 //! [`ArtifactServerConfig`] only carries fields the server itself needs:
-//! where to bind and where the artifacts live on disk.
+//! where to bind, where the artifacts live on disk, who may write to
+//! them, how much of the disk the blob cache may use, and the certificate
+//! to terminate TLS with.
 
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -33,6 +35,39 @@ pub struct ArtifactServerConfig {
     /// Root directory holding artifacts. Both the channel/arch layout and
     /// the content-addressed `blobs/` layout live underneath this directory.
     pub artifacts_dir: PathBuf,
+
+    /// Hex Ed25519 public keys allowed to upload and delete artifacts over
+    /// HTTP. Empty (the default) leaves the server read-only.
+    #[serde(default)]
+    pub operators: Vec<String>,
+
+    /// Hex succession chains (from `plasmd identity rotate`) that carry an
+    /// operator's upload rights over to its rotated keys.
+    #[serde(default)]
+    pub operator_successions: Vec<String>,
+
+    /// Byte budget for the `blobs/` bucket. When set, unpinned blobs are
+    /// evicted least recently used first to stay under it (see
+    /// [`crate::gc`]). `None` (the default) never evicts.
+    #[serde(default)]
+    pub blob_quota_bytes: Option<u64>,
+
+    /// Certificate and key to serve HTTPS with. `None` (the default) serves
+    /// plain HTTP.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// PEM files for TLS termination (see [`crate::tls`]). Both are re-read
+/// when either changes on disk, so a renewed certificate takes effect
+/// without a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// Certificate chain, leaf first.
+    pub cert_path: PathBuf,
+
+    /// Private key for the leaf certificate: PKCS#8, PKCS#1 or SEC1.
+    pub key_path: PathBuf,
 }
 
 impl ArtifactServerConfig {
@@ -60,6 +95,10 @@ mod tests {
             bind_addr: "127.0.0.1".to_string(),
             port: 9090,
             artifacts_dir: PathBuf::from("/tmp/x"),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         };
         assert_eq!(config.bind_address(), "127.0.0.1:9090");
     }
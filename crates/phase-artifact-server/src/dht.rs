@@ -11,9 +11,24 @@
 //!    Lets a peer announce "I have the blob with this hash and you can fetch
 //!    it from this HTTP address" without forcing channel/arch semantics on
 //!    the workload.
+//!
+//! A blob has many providers, but a Kademlia key holds one record, so the
+//! blob key is used as a *provider* key: each provider joins the key's
+//! provider set and publishes its [`BlobProviderRecord`] — addresses, size,
+//! expiry — under its own `/phase/blob/<sha256_hex>/<peer_id>` key. This is
+//! the same split phase-net uses for capability advertisements.
+//! [`crate::announce`] does the publishing and the lookup.
+//!
+//! Both live under `/phase/`, where phase-net's default validator registry
+//! only admits [`SignedRecord`] envelopes — publish manifest records with
+//! [`ManifestRecord::to_signed_bytes`], not the bare JSON.
+
+use std::time::Duration;
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use libp2p::kad::RecordKey;
+use phase_identity::NodeIdentity;
+use phase_net::{PeerId, SignedRecord};
 use serde::{Deserialize, Serialize};
 
 /// DHT record advertising a Phase Boot manifest.
@@ -86,6 +101,29 @@ impl ManifestRecord {
         serde_json::from_slice(bytes).context("Failed to deserialize manifest record")
     }
 
+    /// Sign with `identity` for DHT storage under [`Self::key`], valid for
+    /// `ttl_secs`.
+    pub fn to_signed_bytes(&self, identity: &NodeIdentity) -> Result<Vec<u8>> {
+        let value = self.to_bytes()?;
+        let ttl = Duration::from_secs(self.ttl_secs);
+        Ok(SignedRecord::sign(identity, self.key().as_ref(), &value, ttl).to_bytes())
+    }
+
+    /// Verify and decode a record produced by [`Self::to_signed_bytes`].
+    /// Returns the record with the peer that signed it. The envelope must
+    /// be bound to the key the record itself names.
+    pub fn from_signed_bytes(bytes: &[u8]) -> Result<(Self, PeerId)> {
+        let envelope = SignedRecord::from_bytes(bytes).context("Invalid signed manifest record")?;
+        let record = Self::from_bytes(&envelope.value)?;
+        envelope
+            .verify(record.key().as_ref(), unix_ms_now())
+            .context("Manifest record failed verification")?;
+        let publisher = envelope
+            .publisher()
+            .ok_or_else(|| anyhow!("Manifest record has no usable signer key"))?;
+        Ok((record, publisher))
+    }
+
     /// True if the record's age exceeds its TTL (or the timestamp is bogus).
     pub fn is_expired(&self) -> bool {
         if let Ok(created) = chrono::DateTime::parse_from_rfc3339(&self.created_at) {
@@ -98,6 +136,10 @@ impl ManifestRecord {
     }
 }
 
+fn unix_ms_now() -> u64 {
+    chrono::Utc::now().timestamp_millis().max(0) as u64
+}
+
 /// DHT key for a content-addressed blob.
 ///
 /// Format: `/phase/blob/<sha256_hex>`. Independent of channel/arch — used by
@@ -108,6 +150,136 @@ pub fn blob_dht_key(blob_id_hex: &str) -> RecordKey {
     RecordKey::new(&key_str.into_bytes())
 }
 
+/// Default TTL for blob provider records (1 hour).
+pub const DEFAULT_BLOB_RECORD_TTL: u64 = 3600;
+
+/// Blob provider records are re-announced at half their TTL.
+pub const BLOB_REFRESH_INTERVAL: u64 = DEFAULT_BLOB_RECORD_TTL / 2;
+
+/// DHT record describing one provider of one blob.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobProviderRecord {
+    /// SHA-256 hex of the blob.
+    pub blob_id: String,
+    /// The provider; also the record's signer.
+    pub peer_id: String,
+    /// Blob size in bytes.
+    pub size_bytes: u64,
+    /// Base URL of the provider's artifact server (`http://ip:port`), if it
+    /// has a reachable one. Usable as a [`crate::BlobFetcher`] source.
+    pub http_url: Option<String>,
+    /// libp2p addresses of the provider, each ending in `/p2p/<peer_id>`.
+    pub p2p_addrs: Vec<String>,
+    /// Set on the tombstone published when the provider drops the blob.
+    #[serde(default)]
+    pub withdrawn: bool,
+    /// Record creation timestamp (ISO 8601).
+    pub created_at: String,
+    /// Record TTL in seconds.
+    pub ttl_secs: u64,
+}
+
+impl BlobProviderRecord {
+    /// Build a fresh record announcing that `peer` holds `blob_id_hex`.
+    pub fn new(
+        blob_id_hex: &str,
+        peer: &PeerId,
+        size_bytes: u64,
+        http_url: Option<String>,
+        p2p_addrs: Vec<String>,
+    ) -> Self {
+        Self {
+            blob_id: blob_id_hex.to_string(),
+            peer_id: peer.to_string(),
+            size_bytes,
+            http_url,
+            p2p_addrs,
+            withdrawn: false,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            ttl_secs: DEFAULT_BLOB_RECORD_TTL,
+        }
+    }
+
+    /// A tombstone replacing `peer`'s record for `blob_id_hex`. It lives a
+    /// full TTL so it outlasts any copy of the record it replaces.
+    pub fn withdrawal(blob_id_hex: &str, peer: &PeerId) -> Self {
+        Self {
+            withdrawn: true,
+            ..Self::new(blob_id_hex, peer, 0, None, Vec::new())
+        }
+    }
+
+    /// DHT key for `peer`'s record about a blob. Format:
+    /// `/phase/blob/<sha256_hex>/<peer_id>`.
+    pub fn dht_key(blob_id_hex: &str, peer: &PeerId) -> RecordKey {
+        let key_str = format!("/phase/blob/{}/{}", blob_id_hex, peer);
+        RecordKey::new(&key_str.into_bytes())
+    }
+
+    /// DHT key for this record.
+    pub fn key(&self) -> RecordKey {
+        let key_str = format!("/phase/blob/{}/{}", self.blob_id, self.peer_id);
+        RecordKey::new(&key_str.into_bytes())
+    }
+
+    /// The provider's peer id, if `peer_id` parses.
+    pub fn peer(&self) -> Option<PeerId> {
+        self.peer_id.parse().ok()
+    }
+
+    /// Serialise to bytes for DHT storage.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).context("Failed to serialize blob provider record")
+    }
+
+    /// Deserialise from DHT bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).context("Failed to deserialize blob provider record")
+    }
+
+    /// Sign with `identity` for DHT storage under [`Self::key`], valid for
+    /// `ttl_secs`. `identity` must be the node named by `peer_id`.
+    pub fn to_signed_bytes(&self, identity: &NodeIdentity) -> Result<Vec<u8>> {
+        let value = self.to_bytes()?;
+        let ttl = Duration::from_secs(self.ttl_secs);
+        Ok(SignedRecord::sign(identity, self.key().as_ref(), &value, ttl).to_bytes())
+    }
+
+    /// Verify and decode a signed record. Beyond the envelope checks, the
+    /// signer must be the provider the record names, so no peer can
+    /// announce (or withdraw) a blob on another's behalf.
+    pub fn from_signed_bytes(bytes: &[u8]) -> Result<Self> {
+        let envelope =
+            SignedRecord::from_bytes(bytes).context("Invalid signed blob provider record")?;
+        let record = Self::from_bytes(&envelope.value)?;
+        envelope
+            .verify(record.key().as_ref(), unix_ms_now())
+            .context("Blob provider record failed verification")?;
+        let publisher = envelope
+            .publisher()
+            .ok_or_else(|| anyhow!("Blob provider record has no usable signer key"))?;
+        if record.peer() != Some(publisher) {
+            return Err(anyhow!(
+                "Blob provider record for {} signed by {}",
+                record.peer_id,
+                publisher
+            ));
+        }
+        Ok(record)
+    }
+
+    /// True if the record's age exceeds its TTL (or the timestamp is bogus).
+    pub fn is_expired(&self) -> bool {
+        match chrono::DateTime::parse_from_rfc3339(&self.created_at) {
+            Ok(created) => {
+                let age = chrono::Utc::now().signed_duration_since(created);
+                age.num_seconds() as u64 > self.ttl_secs
+            }
+            Err(_) => true,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,6 +326,29 @@ mod tests {
         assert_eq!(restored.http_addr, record.http_addr);
     }
 
+    #[test]
+    fn test_signed_roundtrip_names_publisher() {
+        let identity = NodeIdentity::generate();
+        let record = ManifestRecord::new(
+            "stable".to_string(),
+            "arm64".to_string(),
+            "10.0.0.1:9000".to_string(),
+            "0.3.0".to_string(),
+        );
+
+        let bytes = record.to_signed_bytes(&identity).unwrap();
+        let (restored, publisher) = ManifestRecord::from_signed_bytes(&bytes).unwrap();
+        assert_eq!(restored.manifest_version, "0.3.0");
+        let key = phase_net::libp2p_identity::ed25519::PublicKey::try_from_bytes(
+            &identity.verifying_key().to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(publisher, PeerId::from(phase_net::libp2p_identity::PublicKey::from(key)));
+
+        // Bare JSON is not a signed record.
+        assert!(ManifestRecord::from_signed_bytes(&record.to_bytes().unwrap()).is_err());
+    }
+
     #[test]
     fn test_not_expired_initially() {
         let record = ManifestRecord::new(
@@ -175,4 +370,39 @@ mod tests {
         assert!(key_str.starts_with("/phase/blob/"));
         assert!(key_str.contains("b94d27b9"));
     }
+
+    #[test]
+    fn test_blob_provider_record_must_be_signed_by_its_provider() {
+        let identity = NodeIdentity::generate();
+        let key = phase_net::libp2p_identity::ed25519::PublicKey::try_from_bytes(
+            &identity.verifying_key().to_bytes(),
+        )
+        .unwrap();
+        let peer = PeerId::from(phase_net::libp2p_identity::PublicKey::from(key));
+        let hex = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+        let record = BlobProviderRecord::new(
+            hex,
+            &peer,
+            11,
+            Some("http://10.0.0.1:8080".to_string()),
+            vec![format!("/ip4/10.0.0.1/tcp/4001/p2p/{}", peer)],
+        );
+        assert_eq!(record.key(), BlobProviderRecord::dht_key(hex, &peer));
+        let restored =
+            BlobProviderRecord::from_signed_bytes(&record.to_signed_bytes(&identity).unwrap())
+                .unwrap();
+        assert_eq!(restored, record);
+        assert!(!restored.is_expired());
+
+        let tombstone = BlobProviderRecord::withdrawal(hex, &peer);
+        let restored =
+            BlobProviderRecord::from_signed_bytes(&tombstone.to_signed_bytes(&identity).unwrap())
+                .unwrap();
+        assert!(restored.withdrawn);
+
+        // Someone else's key can't speak for `peer`.
+        let forged = record.to_signed_bytes(&NodeIdentity::generate()).unwrap();
+        assert!(BlobProviderRecord::from_signed_bytes(&forged).is_err());
+    }
 }
@@ -0,0 +1,857 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parallel, resumable, chunk-verified blob downloads.
+//!
+//! [`BlobFetcher`] pulls one blob from several artifact servers at once:
+//!
+//! 1. Fetch the blob's [`Outboard`] from the first source that has one
+//!    (checked against an [`OutboardRoot`] when the caller knows it).
+//!    An outboard that claims an unreasonable size or chunk size is
+//!    refused before anything is allocated for it.
+//! 2. Re-verify any chunks already present in `<dest>.part` from an earlier,
+//!    interrupted attempt, and keep them.
+//! 3. Fetch the remaining chunks with `Range:` requests spread across the
+//!    sources, verifying each against the outboard as it arrives. A bad
+//!    chunk is retried from another source; a source that keeps serving
+//!    bad or no data is dropped.
+//! 4. Check the finished file against its [`BlobId`] and rename it to
+//!    `dest`.
+//!
+//! Without a pinned root, the first source's outboard is only a guess: if
+//! no source can serve chunks matching it, or the finished file fails the
+//! [`BlobId`] check, the fetch starts over from the next source's
+//! outboard.
+//!
+//! An interrupted fetch leaves `<dest>.part` behind; calling
+//! [`BlobFetcher::fetch`] again picks up where it stopped.
+//!
+//! Sources are artifact server URLs or, for providers without a reachable
+//! HTTP port, libp2p peers added with [`BlobFetcher::with_peer`] and read
+//! over phase-net's blob protocol (see [`crate::p2p`]).
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use phase_net::{BlobPart, BlobRequest, BlobResponse, Discovery, PeerId, BLOB_MAX_RANGE_BYTES};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{debug, warn};
+
+use crate::artifacts::{ArtifactStore, BlobId, HashMismatch};
+use crate::outboard::{Outboard, OutboardRoot};
+
+/// Chunks in flight at once unless [`BlobFetcher::with_parallelism`] says
+/// otherwise.
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+/// Failed chunk fetches after which a source is dropped.
+pub const DEFAULT_MAX_SOURCE_FAILURES: u32 = 3;
+
+/// Largest outboard accepted from a source: 64 MiB of chunk hashes
+/// describes a 2 TiB blob.
+const MAX_OUTBOARD_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Largest blob an outboard may describe; `<dest>.part` is sized to it
+/// before a single chunk is verified.
+const MAX_BLOB_BYTES: u64 = 2 * 1024 * 1024 * 1024 * 1024;
+
+/// Largest chunk size an outboard may use; each chunk is read into memory
+/// whole.
+const MAX_CHUNK_BYTES: u32 = 16 * 1024 * 1024;
+
+/// Rate-limit answers a peer read waits out before giving up on the peer.
+const MAX_PEER_RATE_LIMITS: u32 = 5;
+
+/// Outcome of a successful [`BlobFetcher::fetch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchReport {
+    pub id: BlobId,
+    pub size_bytes: u64,
+    pub chunks_total: usize,
+    /// Chunks found intact in `<dest>.part` and not downloaded again.
+    pub chunks_resumed: usize,
+    pub chunks_fetched: usize,
+    /// Chunk responses that failed verification and were retried.
+    pub chunks_rejected: usize,
+    /// Sources dropped for repeated failures.
+    pub sources_dropped: Vec<String>,
+}
+
+/// Downloads blobs from a set of artifact servers and peers.
+#[derive(Debug, Clone)]
+pub struct BlobFetcher {
+    client: reqwest::Client,
+    sources: Vec<Source>,
+    parallelism: usize,
+    max_source_failures: u32,
+}
+
+impl BlobFetcher {
+    /// Fetch from `sources`, each an artifact server base URL such as
+    /// `http://10.0.0.5:8080`.
+    pub fn new(sources: Vec<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            sources: sources
+                .into_iter()
+                .map(|s| Source::Http(s.trim_end_matches('/').to_string()))
+                .collect(),
+            parallelism: DEFAULT_PARALLELISM,
+            max_source_failures: DEFAULT_MAX_SOURCE_FAILURES,
+        }
+    }
+
+    /// Also fetch from `peer` over libp2p, through `discovery`'s existing
+    /// (or dialled) connection to it. Reported as `/p2p/<peer>` in
+    /// [`FetchReport::sources_dropped`].
+    pub fn with_peer(mut self, discovery: Arc<Discovery>, peer: PeerId) -> Self {
+        self.sources.push(Source::Peer { discovery, peer });
+        self
+    }
+
+    /// Chunks to keep in flight at once (at least one).
+    pub fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Failed chunk fetches after which a source is dropped.
+    pub fn with_max_source_failures(mut self, failures: u32) -> Self {
+        self.max_source_failures = failures.max(1);
+        self
+    }
+
+    /// Use a custom HTTP client (timeouts, proxies, TLS roots).
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Download blob `id` to `dest`, starting from whichever outboard a
+    /// source serves first and moving on to the next source's when that
+    /// one leads nowhere. Use [`fetch_pinned`](Self::fetch_pinned) to
+    /// refuse a lying source's outboard up front.
+    pub async fn fetch(&self, id: &BlobId, dest: &Path) -> Result<FetchReport> {
+        self.fetch_inner(id, None, dest).await
+    }
+
+    /// [`fetch`](Self::fetch), accepting only an outboard with `root`.
+    pub async fn fetch_pinned(
+        &self,
+        id: &BlobId,
+        root: &OutboardRoot,
+        dest: &Path,
+    ) -> Result<FetchReport> {
+        self.fetch_inner(id, Some(root), dest).await
+    }
+
+    async fn fetch_inner(
+        &self,
+        id: &BlobId,
+        root: Option<&OutboardRoot>,
+        dest: &Path,
+    ) -> Result<FetchReport> {
+        if self.sources.is_empty() {
+            bail!("no sources to fetch blob {} from", id);
+        }
+        let mut rejected_roots = Vec::new();
+        let mut first_source = 0;
+        loop {
+            let (source, outboard) = self
+                .fetch_outboard(id, root, first_source, &rejected_roots)
+                .await?;
+            match self.fetch_with(id, &outboard, dest).await {
+                Err(e) if root.is_none() && outboard_at_fault(&e) => {
+                    warn!(
+                        "Outboard {} for blob {} from {:?} led nowhere: {:#}",
+                        outboard.root(),
+                        id,
+                        self.sources[source],
+                        e
+                    );
+                    if source + 1 >= self.sources.len() {
+                        return Err(e);
+                    }
+                    rejected_roots.push(outboard.root());
+                    first_source = source + 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Download blob `id` to `dest` against `outboard`.
+    async fn fetch_with(&self, id: &BlobId, outboard: &Outboard, dest: &Path) -> Result<FetchReport> {
+        let part = part_path(dest);
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&part)
+            .await
+            .with_context(|| format!("open {:?}", part))?;
+        let present = file.metadata().await?.len();
+        file.set_len(outboard.size())
+            .await
+            .with_context(|| format!("resize {:?}", part))?;
+
+        // Only chunks an earlier attempt could have written are worth
+        // reading back; the rest of the file is fresh zeroes.
+        let mut missing = Vec::new();
+        for index in 0..outboard.chunk_count() {
+            let (start, len) = outboard.chunk_range(index);
+            if start + len > present {
+                missing.push(index);
+                continue;
+            }
+            let mut data = vec![0u8; len as usize];
+            file.seek(SeekFrom::Start(start)).await?;
+            file.read_exact(&mut data)
+                .await
+                .with_context(|| format!("read {:?}", part))?;
+            if !outboard.verify_chunk(index, &data) {
+                missing.push(index);
+            }
+        }
+        let chunks_resumed = outboard.chunk_count() - missing.len();
+        if chunks_resumed > 0 {
+            debug!(
+                "Resuming blob {}: {}/{} chunks already present",
+                id,
+                chunks_resumed,
+                outboard.chunk_count()
+            );
+        }
+
+        let file = tokio::sync::Mutex::new(file);
+        let labels: Vec<String> = self.sources.iter().map(Source::label).collect();
+        let sources = Mutex::new(SourceHealth::new(&labels));
+        let chunks_fetched = missing.len();
+        let rejected: usize = stream::iter(missing)
+            .map(|index| self.fetch_chunk(id, outboard, index, &sources, &file))
+            .buffer_unordered(self.parallelism)
+            .try_fold(0, |total, rejected| async move { Ok(total + rejected) })
+            .await?;
+
+        {
+            let file = file.lock().await;
+            file.sync_all()
+                .await
+                .with_context(|| format!("sync {:?}", part))?;
+        }
+        let hash_path = part.clone();
+        let hash = tokio::task::spawn_blocking(move || ArtifactStore::compute_hash(&hash_path))
+            .await??;
+        let actual = BlobId::from_hex(hash.trim_start_matches("sha256:"))
+            .ok_or_else(|| anyhow!("unexpected hash format {hash}"))?;
+        if actual != *id {
+            // Every chunk matched the outboard, so the outboard itself
+            // was wrong: nothing in the partial file can be trusted.
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(HashMismatch {
+                expected: id.clone(),
+                actual,
+            }
+            .into());
+        }
+        tokio::fs::rename(&part, dest)
+            .await
+            .with_context(|| format!("move {:?} to {:?}", part, dest))?;
+
+        let sources_dropped = sources
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .dropped();
+        Ok(FetchReport {
+            id: id.clone(),
+            size_bytes: outboard.size(),
+            chunks_total: outboard.chunk_count(),
+            chunks_resumed,
+            chunks_fetched,
+            chunks_rejected: rejected,
+            sources_dropped,
+        })
+    }
+
+    /// The first outboard a source from index `first` on serves that
+    /// parses, stays within [`MAX_BLOB_BYTES`] and [`MAX_CHUNK_BYTES`],
+    /// isn't one of `rejected`, and matches `root`, if given. Returns the
+    /// index of the source that served it too.
+    async fn fetch_outboard(
+        &self,
+        id: &BlobId,
+        root: Option<&OutboardRoot>,
+        first: usize,
+        rejected: &[OutboardRoot],
+    ) -> Result<(usize, Outboard)> {
+        let mut last_error = None;
+        for (index, source) in self.sources.iter().enumerate().skip(first) {
+            let attempt = async {
+                let bytes = match source {
+                    Source::Http(base) => {
+                        let url = format!("{}/blobs/{}/{}.outboard", base, id.prefix(), id.as_str());
+                        let mut response = self.client.get(&url).send().await?.error_for_status()?;
+                        let mut bytes = Vec::new();
+                        while let Some(chunk) = response.chunk().await? {
+                            if (bytes.len() + chunk.len()) as u64 > MAX_OUTBOARD_BYTES {
+                                bail!("outboard is larger than {} bytes", MAX_OUTBOARD_BYTES);
+                            }
+                            bytes.extend_from_slice(&chunk);
+                        }
+                        bytes
+                    }
+                    Source::Peer { discovery, peer } => {
+                        read_peer(discovery, *peer, id, BlobPart::Outboard, 0, None).await?
+                    }
+                };
+                let outboard = Outboard::from_bytes(&bytes)?;
+                if outboard.size() > MAX_BLOB_BYTES {
+                    bail!("outboard describes a {}-byte blob", outboard.size());
+                }
+                if outboard.chunk_size() > MAX_CHUNK_BYTES {
+                    bail!("outboard uses {}-byte chunks", outboard.chunk_size());
+                }
+                if rejected.contains(&outboard.root()) {
+                    bail!("outboard root {} already led nowhere", outboard.root());
+                }
+                if let Some(root) = root.filter(|r| **r != outboard.root()) {
+                    bail!("outboard root {} does not match {}", outboard.root(), root);
+                }
+                Ok(outboard)
+            }
+            .await;
+            match attempt {
+                Ok(outboard) => return Ok((index, outboard)),
+                Err(e) => {
+                    warn!("No usable outboard for blob {} from {:?}: {:#}", id, source, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| anyhow!("no sources"))
+            .context(format!("fetch outboard for blob {}", id)))
+    }
+
+    /// Fetch and store one chunk, trying sources until one serves it
+    /// intact. Returns how many bad responses it got along the way.
+    async fn fetch_chunk(
+        &self,
+        id: &BlobId,
+        outboard: &Outboard,
+        index: usize,
+        sources: &Mutex<SourceHealth>,
+        file: &tokio::sync::Mutex<tokio::fs::File>,
+    ) -> Result<usize> {
+        let (start, len) = outboard.chunk_range(index);
+        let mut rejected = 0;
+        let mut attempt = 0;
+        let data = loop {
+            let source = sources
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pick(index + attempt)
+                .ok_or(ChunkUnavailable { index })?;
+            attempt += 1;
+
+            let response = self.read_chunk(&source, id, start, len).await;
+            match response {
+                Ok(data) if outboard.verify_chunk(index, &data) => break data,
+                Ok(_) => {
+                    warn!("Chunk {} of blob {} from {} failed verification", index, id, source);
+                    rejected += 1;
+                }
+                Err(e) => warn!("Chunk {} of blob {} from {}: {:#}", index, id, source, e),
+            }
+            sources
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .strike(&source, self.max_source_failures);
+        };
+
+        let mut file = file.lock().await;
+        file.seek(SeekFrom::Start(start)).await?;
+        file.write_all(&data).await.context("write fetched chunk")?;
+        Ok(rejected)
+    }
+
+    /// `len` bytes of blob `id` from `start`, unverified, from the source
+    /// labelled `label`.
+    async fn read_chunk(&self, label: &str, id: &BlobId, start: u64, len: u64) -> Result<Vec<u8>> {
+        let source = self
+            .sources
+            .iter()
+            .find(|s| s.label() == label)
+            .ok_or_else(|| anyhow!("unknown source {}", label))?;
+        match source {
+            Source::Http(base) => {
+                let url = format!("{}/blobs/{}/{}.bin", base, id.prefix(), id.as_str());
+                let response = self
+                    .client
+                    .get(&url)
+                    .header(
+                        reqwest::header::RANGE,
+                        format!("bytes={}-{}", start, start + len - 1),
+                    )
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                // A source that ignores Range would send the whole blob to
+                // every chunk task at once.
+                if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                    bail!("answered a range request with {}", response.status());
+                }
+                let range = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_RANGE)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_content_range);
+                if range != Some((start, start + len - 1)) {
+                    bail!("sent a different range than bytes {}-{}", start, start + len - 1);
+                }
+                let mut response = response;
+                let mut data = Vec::with_capacity(len as usize);
+                while let Some(chunk) = response.chunk().await? {
+                    if (data.len() + chunk.len()) as u64 > len {
+                        bail!("sent more than the {} bytes asked for", len);
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                Ok(data)
+            }
+            Source::Peer { discovery, peer } => {
+                read_peer(discovery, *peer, id, BlobPart::Content, start, Some(len)).await
+            }
+        }
+    }
+}
+
+/// Every source failed to serve a chunk matching the outboard.
+#[derive(Debug)]
+struct ChunkUnavailable {
+    index: usize,
+}
+
+impl fmt::Display for ChunkUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "every source failed; chunk {} is unavailable", self.index)
+    }
+}
+
+impl std::error::Error for ChunkUnavailable {}
+
+/// Whether a fetch failed in a way that points at its outboard rather than
+/// at the sources or the local disk: no source had chunks matching it, or
+/// chunks matching it didn't add up to the blob.
+fn outboard_at_fault(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<ChunkUnavailable>().is_some()
+        || error.downcast_ref::<HashMismatch>().is_some()
+}
+
+/// The `(first, last)` byte positions of a `Content-Range: bytes
+/// first-last/total` header.
+fn parse_content_range(value: &str) -> Option<(u64, u64)> {
+    let (range, _total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    Some((first.trim().parse().ok()?, last.trim().parse().ok()?))
+}
+
+/// Somewhere a blob can be fetched from.
+#[derive(Clone)]
+enum Source {
+    /// Artifact server base URL.
+    Http(String),
+    /// A peer serving the blob protocol.
+    Peer {
+        discovery: Arc<Discovery>,
+        peer: PeerId,
+    },
+}
+
+impl Source {
+    /// How the source appears in logs and [`FetchReport::sources_dropped`].
+    fn label(&self) -> String {
+        match self {
+            Source::Http(base) => base.clone(),
+            Source::Peer { peer, .. } => format!("/p2p/{}", peer),
+        }
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label())
+    }
+}
+
+/// Read `part` of blob `id` from `peer`, paging through
+/// [`BLOB_MAX_RANGE_BYTES`] ranges: `len` bytes from `offset`, or
+/// everything from `offset` to the end when `len` is `None`.
+async fn read_peer(
+    discovery: &Discovery,
+    peer: PeerId,
+    id: &BlobId,
+    part: BlobPart,
+    offset: u64,
+    len: Option<u64>,
+) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut rate_limits = 0;
+    loop {
+        let got = data.len() as u64;
+        if len == Some(got) {
+            return Ok(data);
+        }
+        let want = len.map_or(u64::from(BLOB_MAX_RANGE_BYTES), |l| l - got);
+        let request = BlobRequest {
+            blob_id: id.as_str().to_string(),
+            part,
+            offset: offset + got,
+            length: want.min(u64::from(BLOB_MAX_RANGE_BYTES)) as u32,
+        };
+        match discovery.request_blob(peer, request).await? {
+            BlobResponse::Data { total_size, bytes } => {
+                let end = len.map_or(total_size, |l| offset + l);
+                if end > total_size {
+                    bail!("range ends past the {}-byte {:?}", total_size, part);
+                }
+                if len.is_none() && total_size > MAX_OUTBOARD_BYTES {
+                    bail!("{}-byte {:?} is too large", total_size, part);
+                }
+                if bytes.is_empty() && offset + got < end {
+                    bail!("peer sent an empty range");
+                }
+                data.extend_from_slice(&bytes);
+                if offset + data.len() as u64 >= end {
+                    data.truncate((end - offset) as usize);
+                    return Ok(data);
+                }
+            }
+            BlobResponse::NotFound => bail!("peer does not have blob {}", id),
+            BlobResponse::Err { reason } => bail!("peer refused: {}", reason),
+            BlobResponse::Busy => bail!("peer is busy"),
+            BlobResponse::RateLimited { retry_after_ms } => {
+                rate_limits += 1;
+                if rate_limits > MAX_PEER_RATE_LIMITS {
+                    bail!("peer kept rate limiting us");
+                }
+                tokio::time::sleep(Duration::from_millis(retry_after_ms.min(10_000))).await;
+            }
+        }
+    }
+}
+
+/// Failure counts per source.
+#[derive(Debug)]
+struct SourceHealth {
+    sources: Vec<(String, u32)>,
+    dropped: HashSet<String>,
+}
+
+impl SourceHealth {
+    fn new(sources: &[String]) -> Self {
+        Self {
+            sources: sources.iter().map(|s| (s.clone(), 0)).collect(),
+            dropped: HashSet::new(),
+        }
+    }
+
+    /// A live source, rotating with `turn` so chunks spread across them.
+    fn pick(&self, turn: usize) -> Option<String> {
+        let live: Vec<&String> = self
+            .sources
+            .iter()
+            .filter(|(s, _)| !self.dropped.contains(s))
+            .map(|(s, _)| s)
+            .collect();
+        (!live.is_empty()).then(|| live[turn % live.len()].clone())
+    }
+
+    fn strike(&mut self, source: &str, limit: u32) {
+        if let Some((_, failures)) = self.sources.iter_mut().find(|(s, _)| s == source) {
+            *failures += 1;
+            if *failures >= limit && self.dropped.insert(source.to_string()) {
+                warn!("Dropping source {} after {} failures", source, failures);
+            }
+        }
+    }
+
+    fn dropped(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .filter(|(s, _)| self.dropped.contains(s))
+            .map(|(s, _)| s.clone())
+            .collect()
+    }
+}
+
+/// Where an in-progress download of `dest` lives: `model.gguf` →
+/// `model.gguf.part`.
+fn part_path(dest: &Path) -> PathBuf {
+    let mut name = OsString::from(dest.as_os_str());
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ArtifactServer, ArtifactServerConfig, OUTBOARD_CHUNK_SIZE};
+    use std::net::SocketAddr;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Serve `content` as a blob; returns the server handle, its base URL
+    /// and the blob's on-disk path.
+    async fn serve(
+        temp: &tempfile::TempDir,
+        content: &[u8],
+    ) -> (crate::ServerHandle, String, BlobId, PathBuf) {
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
+        };
+        let server = ArtifactServer::new(config).unwrap();
+        let id = server.add_blob(content).await.unwrap();
+        let path = temp.path().join(id.relative_path());
+        let handle = server
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let url = format!("http://{}", handle.local_addr());
+        (handle, url, id, path)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn corrupt_source_is_routed_around() {
+        let chunk = OUTBOARD_CHUNK_SIZE as usize;
+        let content = payload(chunk * 4 + 1000);
+        let good_dir = tempfile::TempDir::new().unwrap();
+        let bad_dir = tempfile::TempDir::new().unwrap();
+        let (_good, good_url, id, _) = serve(&good_dir, &content).await;
+        let (_bad, bad_url, _, bad_path) = serve(&bad_dir, &content).await;
+
+        // Flip one byte in every chunk of the bad copy; its outboard was
+        // computed before, so it still describes the real blob.
+        let mut corrupt = content.clone();
+        for at in (10..corrupt.len()).step_by(chunk) {
+            corrupt[at] ^= 0xFF;
+        }
+        std::fs::write(&bad_path, &corrupt).unwrap();
+
+        let out = tempfile::TempDir::new().unwrap();
+        let dest = out.path().join("blob.bin");
+        let report = BlobFetcher::new(vec![bad_url.clone(), good_url])
+            .with_parallelism(2)
+            .with_max_source_failures(2)
+            .fetch(&id, &dest)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+        assert_eq!(report.chunks_total, 5);
+        assert_eq!(report.chunks_fetched, 5);
+        assert!(report.chunks_rejected >= 1);
+        assert_eq!(report.sources_dropped, vec![bad_url]);
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn lying_outboard_falls_back_to_the_next_source() {
+        let chunk = OUTBOARD_CHUNK_SIZE as usize;
+        let content = payload(chunk * 2 + 77);
+        let good_dir = tempfile::TempDir::new().unwrap();
+        let bad_dir = tempfile::TempDir::new().unwrap();
+        let (_good, good_url, id, _) = serve(&good_dir, &content).await;
+        let (_bad, bad_url, _, bad_path) = serve(&bad_dir, &content).await;
+
+        // The bad copy is consistent with its own outboard, so every chunk
+        // it serves verifies; only the finished file gives it away.
+        let mut forged = content.clone();
+        forged[5] ^= 0xFF;
+        std::fs::write(&bad_path, &forged).unwrap();
+        std::fs::write(
+            bad_dir.path().join(id.outboard_relative_path()),
+            Outboard::compute(&forged).to_bytes(),
+        )
+        .unwrap();
+
+        let out = tempfile::TempDir::new().unwrap();
+        let dest = out.path().join("blob.bin");
+        let fetcher = BlobFetcher::new(vec![bad_url.clone(), good_url]).with_max_source_failures(1);
+        fetcher.fetch(&id, &dest).await.unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+
+        // With only the liar to go on there is nothing to fall back to.
+        let again = out.path().join("again.bin");
+        let err = BlobFetcher::new(vec![bad_url])
+            .fetch(&id, &again)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<HashMismatch>().is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn oversized_outboards_are_refused() {
+        let content = payload(1000);
+        let dir = tempfile::TempDir::new().unwrap();
+        let (_server, url, id, _) = serve(&dir, &content).await;
+
+        // One hash, but a chunk size (and so a blob size) far past the
+        // limits.
+        let mut forged = Outboard::compute(&content).to_bytes();
+        forged[9..17].copy_from_slice(&u64::from(u32::MAX).to_be_bytes());
+        forged[17..21].copy_from_slice(&u32::MAX.to_be_bytes());
+        std::fs::write(dir.path().join(id.outboard_relative_path()), forged).unwrap();
+
+        let out = tempfile::TempDir::new().unwrap();
+        let dest = out.path().join("blob.bin");
+        let err = BlobFetcher::new(vec![url]).fetch(&id, &dest).await.unwrap_err();
+        assert!(format!("{err:#}").contains("byte chunks"), "{err:#}");
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn interrupted_fetch_resumes_from_part_file() {
+        let chunk = OUTBOARD_CHUNK_SIZE as usize;
+        let content = payload(chunk * 3 + 5);
+        let dir = tempfile::TempDir::new().unwrap();
+        let (_server, url, id, _) = serve(&dir, &content).await;
+
+        // A previous attempt got the first two chunks and part of a third.
+        let out = tempfile::TempDir::new().unwrap();
+        let dest = out.path().join("blob.bin");
+        std::fs::write(part_path(&dest), &content[..chunk * 2 + 100]).unwrap();
+
+        let fetcher = BlobFetcher::new(vec![url.clone()]);
+        let (_, outboard) = fetcher.fetch_outboard(&id, None, 0, &[]).await.unwrap();
+        let report = fetcher
+            .fetch_pinned(&id, &outboard.root(), &dest)
+            .await
+            .unwrap();
+        assert_eq!(report.chunks_resumed, 2);
+        assert_eq!(report.chunks_fetched, 2);
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+
+        // A pinned root that doesn't match refuses to start.
+        let wrong = Outboard::compute(b"other").root();
+        let again = out.path().join("again.bin");
+        assert!(fetcher.fetch_pinned(&id, &wrong, &again).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn fetches_from_a_peer_over_libp2p() {
+        let node = || {
+            Discovery::new(phase_net::DiscoveryConfig {
+                mdns: false,
+                autonat: false,
+                ..phase_net::DiscoveryConfig::default()
+            })
+            .unwrap()
+        };
+        let content = payload(OUTBOARD_CHUNK_SIZE as usize * 2 + 33);
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = Arc::new(ArtifactStore::new(dir.path().to_path_buf()).unwrap());
+        let id = store.add_blob(&content).unwrap();
+
+        let provider = node();
+        provider.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        provider
+            .set_blob_handler(Some(crate::p2p::blob_handler(store)))
+            .await
+            .unwrap();
+        let addr = loop {
+            if let Some(a) = provider.listen_addrs().await.unwrap().into_iter().next() {
+                break a;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        let client = node();
+        client
+            .dial_peer(&format!("{addr}/p2p/{}", provider.local_peer_id()))
+            .await
+            .unwrap();
+
+        let out = tempfile::TempDir::new().unwrap();
+        let dest = out.path().join("blob.bin");
+        let report = tokio::time::timeout(
+            Duration::from_secs(30),
+            BlobFetcher::new(Vec::new())
+                .with_peer(Arc::new(client), *provider.local_peer_id())
+                .fetch(&id, &dest),
+        )
+        .await
+        .expect("peer fetch timed out")
+        .unwrap();
+        assert_eq!(report.chunks_fetched, 3);
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn source_ignoring_range_is_dropped() {
+        use axum::routing::get;
+
+        let chunk = OUTBOARD_CHUNK_SIZE as usize;
+        let content = payload(chunk * 3 + 10);
+        let dir = tempfile::TempDir::new().unwrap();
+        let (_good, good_url, id, _) = serve(&dir, &content).await;
+
+        // Serves the right outboard, but the whole blob with 200 for any
+        // chunk.
+        let outboard = Outboard::compute(&content).to_bytes();
+        let whole = content.clone();
+        let router = axum::Router::new().route(
+            "/blobs/:prefix/:file",
+            get(move |axum::extract::Path((_, file)): axum::extract::Path<(String, String)>| {
+                let body = if file.ends_with(".outboard") { outboard.clone() } else { whole.clone() };
+                async move { body }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ignorer_url = format!("http://{}", listener.local_addr().unwrap());
+        let ignorer = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let out = tempfile::TempDir::new().unwrap();
+        let dest = out.path().join("blob.bin");
+        let report = BlobFetcher::new(vec![ignorer_url.clone(), good_url])
+            .with_max_source_failures(1)
+            .fetch(&id, &dest)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+        assert_eq!(report.sources_dropped, vec![ignorer_url]);
+        ignorer.abort();
+
+        assert_eq!(parse_content_range("bytes 0-9/100"), Some((0, 9)));
+        assert_eq!(parse_content_range("bytes */100"), None);
+    }
+
+    #[test]
+    fn sources_rotate_and_drop() {
+        let mut health = SourceHealth::new(&["a".to_string(), "b".to_string()]);
+        assert_eq!(health.pick(0).as_deref(), Some("a"));
+        assert_eq!(health.pick(1).as_deref(), Some("b"));
+        health.strike("a", 2);
+        assert_eq!(health.pick(0).as_deref(), Some("a"));
+        health.strike("a", 2);
+        assert_eq!(health.pick(0).as_deref(), Some("b"));
+        assert_eq!(health.dropped(), vec!["a".to_string()]);
+        health.strike("b", 1);
+        assert_eq!(health.pick(0), None);
+    }
+}
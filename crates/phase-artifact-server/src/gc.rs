@@ -0,0 +1,578 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pinning, usage accounting and quota-driven eviction for the blob bucket.
+//!
+//! A node that caches modules and model weights for its peers would
+//! otherwise grow `blobs/` until the disk fills. Two kinds of blob are
+//! *pinned* and never evicted:
+//!
+//! - blobs a channel manifest refers to: the SHA-256 of every channel/arch
+//!   artifact, plus every `"hash"` listed under `"artifacts"` in a JSON
+//!   manifest stored in a channel directory;
+//! - blobs an operator pinned by hand ([`ArtifactStore::pin_blob`]),
+//!   recorded one hex id per line in `blobs/.pins`.
+//!
+//! Everything else is a cache entry. [`ArtifactStore::collect_garbage`]
+//! evicts unpinned blobs least recently used first until the bucket fits
+//! its quota. "Used" is the blob file's mtime, which ingests set and reads
+//! refresh through [`ArtifactStore::touch_blob`].
+//!
+//! The server runs a collection every [`GC_INTERVAL`], and after each
+//! upload, when `blob_quota_bytes` is configured. Blobs it evicts are
+//! withdrawn from the DHT if the server announces them (see
+//! [`crate::announce`]). The disk use `GET /metrics` reports comes from a
+//! [`UsageCache`] that each collection refreshes and that otherwise
+//! measures again only once it is a [`GC_INTERVAL`] old.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use crate::announce::BlobAnnouncer;
+use crate::artifacts::{ArtifactStore, BlobId};
+
+/// How often the server collects garbage when it has a quota.
+pub const GC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Operator pins, under `blobs/`. The leading dot keeps it from ever
+/// parsing as a bucket.
+const PINS_FILE: &str = ".pins";
+
+/// Reads refresh a blob's mtime at most this often, so a hot blob costs a
+/// `stat`, not a write, per request.
+const TOUCH_GRANULARITY: Duration = Duration::from_secs(10 * 60);
+
+/// Disk use of one top-level directory of the store: `blobs` or a channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BucketUsage {
+    pub name: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Disk use of a whole store.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageUsage {
+    /// `blobs` first, then channels by name.
+    pub buckets: Vec<BucketUsage>,
+    pub blobs: u64,
+    /// Blob and outboard bytes; what the quota is measured against.
+    pub blob_bytes: u64,
+    pub pinned_blobs: u64,
+    pub pinned_bytes: u64,
+}
+
+/// Outcome of [`ArtifactStore::collect_garbage`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    /// Evicted blobs, least recently used first.
+    pub evicted: Vec<BlobId>,
+    pub bytes_freed: u64,
+    /// Outboards whose blob was already gone.
+    pub orphans_removed: u64,
+    pub blob_bytes_before: u64,
+    pub blob_bytes_after: u64,
+    /// Pinned blobs alone exceed the quota; nothing more can be evicted.
+    pub over_quota: bool,
+}
+
+/// One stored blob as the collector sees it.
+struct StoredBlob {
+    id: BlobId,
+    /// Blob plus outboard.
+    bytes: u64,
+    last_used: SystemTime,
+}
+
+impl ArtifactStore {
+    /// Pin `id` so garbage collection never evicts it. The blob need not
+    /// be stored yet. Returns `false` if it was already pinned.
+    pub fn pin_blob(&self, id: &BlobId) -> Result<bool> {
+        let mut pins = self.operator_pins()?;
+        if !pins.insert(id.clone()) {
+            return Ok(false);
+        }
+        self.write_pins(&pins)?;
+        Ok(true)
+    }
+
+    /// Drop an operator pin. A blob a channel manifest refers to stays
+    /// pinned regardless. Returns `false` if it wasn't pinned.
+    pub fn unpin_blob(&self, id: &BlobId) -> Result<bool> {
+        let mut pins = self.operator_pins()?;
+        if !pins.remove(id) {
+            return Ok(false);
+        }
+        self.write_pins(&pins)?;
+        Ok(true)
+    }
+
+    /// Blobs an operator pinned.
+    pub fn operator_pins(&self) -> Result<HashSet<BlobId>> {
+        let path = self.base_dir().join("blobs").join(PINS_FILE);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+            Err(e) => return Err(e).with_context(|| format!("read pins {:?}", path)),
+        };
+        Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .filter_map(|l| {
+                let id = BlobId::from_hex(l);
+                if id.is_none() {
+                    warn!("Ignoring malformed pin {:?} in {:?}", l, path);
+                }
+                id
+            })
+            .collect())
+    }
+
+    /// Every pinned blob: operator pins plus blobs channel manifests
+    /// refer to.
+    pub fn pinned_blobs(&self) -> Result<HashSet<BlobId>> {
+        let mut pins = self.operator_pins()?;
+        pins.extend(self.manifest_references()?);
+        Ok(pins)
+    }
+
+    /// Mark `id` as just used, for LRU eviction. Best effort.
+    pub fn touch_blob(&self, id: &BlobId) {
+        let path = self.base_dir().join(id.relative_path());
+        let now = SystemTime::now();
+        let stale = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map(|t| now.duration_since(t).unwrap_or_default() >= TOUCH_GRANULARITY)
+            .unwrap_or(false);
+        if stale {
+            if let Err(e) = fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|f| f.set_modified(now))
+            {
+                debug!("Failed to touch blob {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Every blob in the store. Walks the blob bucket, so call off the
+    /// async runtime.
+    pub fn list_blobs(&self) -> Result<Vec<BlobId>> {
+        Ok(self.stored_blobs()?.into_iter().map(|b| b.id).collect())
+    }
+
+    /// Disk use per bucket, and how much of the blob bucket is pinned.
+    /// Walks the whole store, so call off the async runtime.
+    pub fn usage(&self) -> Result<StorageUsage> {
+        let pins = self.pinned_blobs()?;
+        let blobs = self.stored_blobs()?;
+        let pinned: Vec<&StoredBlob> = blobs.iter().filter(|b| pins.contains(&b.id)).collect();
+
+        let mut buckets = vec![BucketUsage {
+            name: "blobs".to_string(),
+            files: 0,
+            bytes: 0,
+        }];
+        let blobs_dir = self.base_dir().join("blobs");
+        if blobs_dir.is_dir() {
+            tally(&blobs_dir, &mut buckets[0])?;
+        }
+        let mut channels = self.list_channels()?;
+        channels.sort();
+        for channel in channels {
+            let mut bucket = BucketUsage {
+                name: channel.clone(),
+                files: 0,
+                bytes: 0,
+            };
+            tally(&self.base_dir().join(&channel), &mut bucket)?;
+            buckets.push(bucket);
+        }
+
+        Ok(StorageUsage {
+            buckets,
+            blobs: blobs.len() as u64,
+            blob_bytes: blobs.iter().map(|b| b.bytes).sum(),
+            pinned_blobs: pinned.len() as u64,
+            pinned_bytes: pinned.iter().map(|b| b.bytes).sum(),
+        })
+    }
+
+    /// Remove outboards left without a blob and, with `quota_bytes`, evict
+    /// unpinned blobs least recently used first until the blob bucket fits.
+    pub fn collect_garbage(&self, quota_bytes: Option<u64>) -> Result<GcReport> {
+        let orphans_removed = self.remove_orphan_outboards()?;
+        let pins = self.pinned_blobs()?;
+        let mut blobs = self.stored_blobs()?;
+        let before: u64 = blobs.iter().map(|b| b.bytes).sum();
+
+        let mut report = GcReport {
+            evicted: Vec::new(),
+            bytes_freed: 0,
+            orphans_removed,
+            blob_bytes_before: before,
+            blob_bytes_after: before,
+            over_quota: false,
+        };
+        let Some(quota) = quota_bytes else {
+            return Ok(report);
+        };
+
+        blobs.retain(|b| !pins.contains(&b.id));
+        blobs.sort_by_key(|b| b.last_used);
+        let mut remaining = before;
+        for blob in blobs {
+            if remaining <= quota {
+                break;
+            }
+            if self.remove_blob(&blob.id)? {
+                debug!("Evicted blob {} ({} bytes)", blob.id, blob.bytes);
+                remaining -= blob.bytes;
+                report.bytes_freed += blob.bytes;
+                report.evicted.push(blob.id);
+            }
+        }
+        report.blob_bytes_after = remaining;
+        report.over_quota = remaining > quota;
+        Ok(report)
+    }
+
+    /// Every `<hex>.bin` in the blob bucket with its size (outboard
+    /// included) and mtime.
+    fn stored_blobs(&self) -> Result<Vec<StoredBlob>> {
+        let mut blobs = Vec::new();
+        for_each_bucket_file(&self.base_dir().join("blobs"), |path, name| {
+            let Some(id) = name.strip_suffix(".bin").and_then(BlobId::from_hex) else {
+                return Ok(());
+            };
+            let meta = fs::metadata(path).with_context(|| format!("stat {:?}", path))?;
+            let outboard = fs::metadata(self.base_dir().join(id.outboard_relative_path()))
+                .map(|m| m.len())
+                .unwrap_or(0);
+            blobs.push(StoredBlob {
+                id,
+                bytes: meta.len() + outboard,
+                last_used: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+            Ok(())
+        })?;
+        Ok(blobs)
+    }
+
+    fn remove_orphan_outboards(&self) -> Result<u64> {
+        let mut removed = 0;
+        for_each_bucket_file(&self.base_dir().join("blobs"), |path, name| {
+            let Some(id) = name.strip_suffix(".outboard").and_then(BlobId::from_hex) else {
+                return Ok(());
+            };
+            if self.get_blob_path(&id).is_none() && fs::remove_file(path).is_ok() {
+                removed += 1;
+            }
+            Ok(())
+        })?;
+        Ok(removed)
+    }
+
+    /// Blob ids the channel layout refers to: each artifact's own hash and
+    /// the `artifacts.*.hash` entries of any JSON manifest.
+    fn manifest_references(&self) -> Result<HashSet<BlobId>> {
+        let mut refs = HashSet::new();
+        for channel in self.list_channels()? {
+            let channel_dir = self.base_dir().join(&channel);
+            for entry in fs::read_dir(&channel_dir)? {
+                let entry = entry?;
+                if !entry.path().is_dir() {
+                    continue;
+                }
+                let Some(arch) = entry.file_name().to_str().map(str::to_string) else {
+                    continue;
+                };
+                for artifact in self.list_artifacts(&channel, &arch)? {
+                    refs.extend(blob_id_of_hash(&artifact.hash));
+                    if artifact.name.ends_with(".json") {
+                        refs.extend(manifest_hashes(&artifact.path));
+                    }
+                }
+            }
+        }
+        Ok(refs)
+    }
+
+    fn write_pins(&self, pins: &HashSet<BlobId>) -> Result<()> {
+        let dir = self.base_dir().join("blobs");
+        fs::create_dir_all(&dir).with_context(|| format!("create {:?}", dir))?;
+        let mut sorted: Vec<&str> = pins.iter().map(BlobId::as_str).collect();
+        sorted.sort_unstable();
+        let mut text = String::new();
+        for id in sorted {
+            text.push_str(id);
+            text.push('\n');
+        }
+        let staged = dir.join(format!("{}.tmp", PINS_FILE));
+        let path = dir.join(PINS_FILE);
+        fs::write(&staged, text).with_context(|| format!("write {:?}", staged))?;
+        fs::rename(&staged, &path).with_context(|| format!("publish {:?}", path))
+    }
+}
+
+/// [`ArtifactStore::usage`] as last measured, so a scrape doesn't walk
+/// the whole store.
+#[derive(Debug, Default)]
+pub(crate) struct UsageCache {
+    measured: Mutex<Option<(Instant, StorageUsage)>>,
+}
+
+impl UsageCache {
+    /// The cached usage, measuring it first if it is missing or a
+    /// [`GC_INTERVAL`] old. `None` (logged) if the walk failed.
+    pub(crate) async fn get(&self, store: &Arc<ArtifactStore>) -> Option<StorageUsage> {
+        let cached = self
+            .measured
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .filter(|(at, _)| at.elapsed() < GC_INTERVAL)
+            .map(|(_, usage)| usage.clone());
+        match cached {
+            Some(usage) => Some(usage),
+            None => self.refresh(store).await,
+        }
+    }
+
+    /// Measure the store's usage off the async runtime and cache it.
+    pub(crate) async fn refresh(&self, store: &Arc<ArtifactStore>) -> Option<StorageUsage> {
+        let usage = measure_usage(store).await?;
+        *self.measured.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some((Instant::now(), usage.clone()));
+        Some(usage)
+    }
+}
+
+/// Disk use of the store, measured off the async runtime. `None` (logged)
+/// if the walk failed.
+pub(crate) async fn measure_usage(store: &Arc<ArtifactStore>) -> Option<StorageUsage> {
+    let store = Arc::clone(store);
+    match tokio::task::spawn_blocking(move || store.usage()).await {
+        Ok(Ok(usage)) => Some(usage),
+        Ok(Err(e)) => {
+            warn!("Failed to measure storage: {:#}", e);
+            None
+        }
+        Err(e) => {
+            warn!("Storage measurement task failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Collect garbage every [`GC_INTERVAL`] and whenever `trigger` fires,
+/// refreshing `usage` after each run. Never returns; the server selects it
+/// against its listener.
+pub(crate) async fn run_gc_loop(
+    store: Arc<ArtifactStore>,
+    quota_bytes: u64,
+    trigger: Arc<Notify>,
+    announcer: Option<Arc<BlobAnnouncer>>,
+    usage: Arc<UsageCache>,
+) {
+    let mut tick = tokio::time::interval(GC_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = tick.tick() => {}
+            _ = trigger.notified() => {}
+        }
+        let gc_store = Arc::clone(&store);
+        match tokio::task::spawn_blocking(move || gc_store.collect_garbage(Some(quota_bytes))).await {
+            Ok(Ok(report)) => {
+                if !report.evicted.is_empty() {
+                    info!(
+                        "Evicted {} blobs ({} bytes) to fit the {}-byte blob quota",
+                        report.evicted.len(),
+                        report.bytes_freed,
+                        quota_bytes
+                    );
+                }
+                if let Some(announcer) = &announcer {
+                    for id in &report.evicted {
+                        if let Err(e) = announcer.withdraw(id).await {
+                            warn!("Failed to withdraw evicted blob {}: {:#}", id, e);
+                        }
+                    }
+                }
+                if report.over_quota {
+                    warn!(
+                        "Pinned blobs use {} bytes, over the {}-byte blob quota",
+                        report.blob_bytes_after, quota_bytes
+                    );
+                }
+            }
+            Ok(Err(e)) => warn!("Blob garbage collection failed: {:#}", e),
+            Err(e) => warn!("Blob garbage collection task failed: {}", e),
+        }
+        usage.refresh(&store).await;
+    }
+}
+
+/// Call `f(path, file_name)` for every file in a `blobs/<aa>/` bucket.
+fn for_each_bucket_file(
+    blobs_dir: &Path,
+    mut f: impl FnMut(&Path, &str) -> Result<()>,
+) -> Result<()> {
+    if !blobs_dir.is_dir() {
+        return Ok(());
+    }
+    for bucket in fs::read_dir(blobs_dir)? {
+        let bucket = bucket?;
+        let is_bucket = bucket
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.len() == 2 && n.bytes().all(|b| b.is_ascii_hexdigit()));
+        if !is_bucket || !bucket.path().is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(bucket.path())? {
+            let path = entry?.path();
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                f(&path, name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add every file under `dir` to `usage`.
+fn tally(dir: &Path, usage: &mut BucketUsage) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("read {:?}", dir))? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            tally(&entry.path(), usage)?;
+        } else if meta.is_file() {
+            usage.files += 1;
+            usage.bytes += meta.len();
+        }
+    }
+    Ok(())
+}
+
+fn blob_id_of_hash(hash: &str) -> Option<BlobId> {
+    BlobId::from_hex(hash.strip_prefix("sha256:")?)
+}
+
+/// `sha256:` hashes under `"artifacts"` in a JSON manifest, whether it is
+/// a name → artifact map (`BootManifest`) or a list. Anything unparseable
+/// refers to nothing.
+fn manifest_hashes(path: &Path) -> Vec<BlobId> {
+    let Some(manifest) = fs::read(path)
+        .ok()
+        .and_then(|b| serde_json::from_slice::<serde_json::Value>(&b).ok())
+    else {
+        return Vec::new();
+    };
+    let artifacts: Vec<&serde_json::Value> = match &manifest["artifacts"] {
+        serde_json::Value::Object(map) => map.values().collect(),
+        serde_json::Value::Array(list) => list.iter().collect(),
+        _ => Vec::new(),
+    };
+    artifacts
+        .into_iter()
+        .filter_map(|a| a["hash"].as_str().and_then(blob_id_of_hash))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn age(store: &ArtifactStore, id: &BlobId, secs_ago: u64) {
+        let path = store.base_dir().join(id.relative_path());
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(secs_ago))
+            .unwrap();
+    }
+
+    #[test]
+    fn pins_survive_and_lru_goes_first() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = ArtifactStore::new(temp.path().to_path_buf()).unwrap();
+        let old = store.add_blob(&[1u8; 1000]).unwrap();
+        let pinned = store.add_blob(&[2u8; 1000]).unwrap();
+        let recent = store.add_blob(&[3u8; 1000]).unwrap();
+        let kernel = store.add_blob(b"kernel bytes").unwrap();
+        age(&store, &old, 3000);
+        age(&store, &pinned, 4000);
+        age(&store, &recent, 2000);
+        age(&store, &kernel, 5000);
+
+        assert!(store.pin_blob(&pinned).unwrap());
+        assert!(!store.pin_blob(&pinned).unwrap());
+        // The channel layout refers to `kernel` by content.
+        store
+            .add_channel_artifact("stable", "x86_64", "kernel", b"kernel bytes")
+            .unwrap();
+
+        // A read makes `recent` the most recently used blob.
+        store.touch_blob(&recent);
+        let usage = store.usage().unwrap();
+        assert_eq!(usage.blobs, 4);
+        assert_eq!(usage.pinned_blobs, 2);
+        assert_eq!(usage.buckets[0].name, "blobs");
+        assert_eq!(usage.buckets[1].name, "stable");
+        assert_eq!(usage.buckets[1].files, 1);
+
+        let report = store.collect_garbage(Some(usage.blob_bytes - 1)).unwrap();
+        assert_eq!(report.evicted, vec![old.clone()]);
+        assert!(!report.over_quota);
+        assert!(store.get_blob_path(&old).is_none());
+        assert!(store.get_blob_path(&recent).is_some());
+
+        // A quota below the pinned set evicts everything else and says so.
+        let report = store.collect_garbage(Some(0)).unwrap();
+        assert_eq!(report.evicted, vec![recent]);
+        assert!(report.over_quota);
+        assert!(store.get_blob_path(&pinned).is_some());
+        assert!(store.get_blob_path(&kernel).is_some());
+
+        assert!(store.unpin_blob(&pinned).unwrap());
+        assert_eq!(store.collect_garbage(Some(0)).unwrap().evicted, vec![pinned]);
+    }
+
+    #[test]
+    fn manifests_pin_what_they_list_and_orphans_are_swept() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = ArtifactStore::new(temp.path().to_path_buf()).unwrap();
+        let model = store.add_blob(b"model weights").unwrap();
+        let manifest = serde_json::json!({
+            "artifacts": { "model": { "hash": format!("sha256:{}", model) } }
+        });
+        store
+            .add_channel_artifact(
+                "stable",
+                "arm64",
+                "manifest.json",
+                manifest.to_string().as_bytes(),
+            )
+            .unwrap();
+        assert!(store.pinned_blobs().unwrap().contains(&model));
+
+        let gone = store.add_blob(b"evicted elsewhere").unwrap();
+        fs::remove_file(temp.path().join(gone.relative_path())).unwrap();
+        let report = store.collect_garbage(Some(0)).unwrap();
+        assert_eq!(report.orphans_removed, 1);
+        assert!(report.evicted.is_empty());
+        assert!(!temp.path().join(gone.outboard_relative_path()).exists());
+        assert!(store.get_blob_path(&model).is_some());
+    }
+}
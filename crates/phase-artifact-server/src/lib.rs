@@ -22,13 +22,36 @@
 //! │       ├── kernel               <- legacy channel/arch path
 //! │       └── initramfs.img        <- legacy channel/arch path
 //! └── blobs/
+//!     ├── .pins                     <- operator-pinned blob ids
 //!     └── ab/
-//!         └── ab8723...c4.bin     <- content-addressed blob (SHA-256 hex)
+//!         ├── ab8723...c4.bin       <- content-addressed blob (SHA-256 hex)
+//!         └── ab8723...c4.outboard  <- per-chunk BLAKE3 hashes of that blob
 //! ```
 //!
 //! Both layouts share the same `ArtifactStore`, the same Range-request
 //! aware HTTP handler, and the same metrics counters.
 //!
+//! The outboard lets [`BlobFetcher`] download a blob from several servers in
+//! parallel, verify every chunk as it arrives, and resume after an
+//! interruption. Its sources can also be libp2p peers that serve the store
+//! with [`p2p::blob_handler`], for providers behind NAT.
+//!
+//! A [`BlobAnnouncer`] publishes a signed DHT record for every blob the
+//! server holds, and [`find_blob_providers`] reads them back (see
+//! [`announce`]).
+//!
+//! With `blob_quota_bytes` set, the server evicts cached blobs least
+//! recently used first to stay under it, keeping any blob a channel
+//! manifest or an operator pin refers to (see [`gc`]).
+//!
+//! Responses carry `ETag`s derived from content hashes, so clients can
+//! revalidate instead of downloading again (see [`conditional`]).
+//!
+//! With `tls` set, the server speaks HTTPS over HTTP/2 or HTTP/1.1 and
+//! picks up a renewed certificate without a restart. A [`SelfSignedCert`]
+//! over a key derived from the node's identity gives it a stable
+//! public-key pin that clients can check in place of a CA (see [`tls`]).
+//!
 //! # Quick start
 //!
 //! ```no_run
@@ -40,6 +63,10 @@
 //!     bind_addr: "127.0.0.1".to_string(),
 //!     port: 8080,
 //!     artifacts_dir: PathBuf::from("/var/lib/phase/artifacts"),
+//!     operators: Vec::new(),
+//!     operator_successions: Vec::new(),
+//!     blob_quota_bytes: None,
+//!     tls: None,
 //! };
 //! let server = ArtifactServer::new(config)?;
 //!
@@ -47,6 +74,12 @@
 //! let blob_id = server.add_blob(b"hello world").await?;
 //! println!("Blob available at /blobs/{}/{}.bin", &blob_id.as_str()[..2], blob_id.as_str());
 //!
+//! // Large blobs stream from disk without being read into memory.
+//! let model = server
+//!     .ingest_blob_file(std::path::Path::new("/models/qwen3.gguf"), None)
+//!     .await?;
+//! println!("Model blob {} ({} bytes)", model.id, model.size_bytes);
+//!
 //! // Or add a legacy channel/arch artifact.
 //! server.add_channel_artifact("stable", "x86_64", "kernel", b"...kernel bytes...").await?;
 //!
@@ -59,18 +92,42 @@
 #![deny(missing_debug_implementations)]
 #![deny(unsafe_code)]
 
+pub mod announce;
 pub mod artifacts;
+pub mod auth;
+pub mod conditional;
 pub mod config;
 pub mod dht;
+pub mod fetch;
+pub mod gc;
 pub mod mdns;
 pub mod metrics;
+pub mod outboard;
+pub mod p2p;
 pub mod server;
+pub mod tls;
 
-pub use artifacts::{ArtifactMeta, ArtifactStore, BlobId};
-pub use config::ArtifactServerConfig;
-pub use dht::{ManifestRecord, DEFAULT_MANIFEST_TTL, MANIFEST_REFRESH_INTERVAL};
-pub use mdns::{MdnsAdvertiser, MdnsConfig, MDNS_SERVICE_TYPE};
+pub use announce::{find_blob_providers, BlobAnnouncer};
+pub use artifacts::{
+    ArtifactMeta, ArtifactStore, BlobId, HashCacheStats, HashMismatch, IngestedBlob,
+    InvalidChannelPath,
+};
+pub use auth::{AuthError, RequestSignature, MAX_CLOCK_SKEW};
+pub use config::{ArtifactServerConfig, TlsConfig};
+pub use dht::{
+    BlobProviderRecord, ManifestRecord, BLOB_REFRESH_INTERVAL, DEFAULT_BLOB_RECORD_TTL,
+    DEFAULT_MANIFEST_TTL, MANIFEST_REFRESH_INTERVAL,
+};
+pub use fetch::{BlobFetcher, FetchReport};
+pub use gc::{BucketUsage, GcReport, StorageUsage, GC_INTERVAL};
+pub use mdns::{
+    MdnsAdvertiser, MdnsBrowser, MdnsConfig, MdnsEvent, MdnsProvider, MDNS_SERVICE_TYPE,
+    TXT_PEER_ID,
+};
+pub use outboard::{Outboard, OutboardBuilder, OutboardRoot, OUTBOARD_CHUNK_SIZE};
 pub use metrics::{
-    perform_health_check, HealthCheck, HealthChecks, MetricsSnapshot, ProviderMetrics,
+    perform_health_check, render_store_metrics, HealthCheck, HealthChecks, MetricsSnapshot,
+    ProviderMetrics, ServedBucket, LATENCY_BUCKETS,
 };
 pub use server::{ArtifactServer, ManifestProvider, ServerHandle};
+pub use tls::{pinned_client_config, pinned_http_client, ReloadingCert, SelfSignedCert};
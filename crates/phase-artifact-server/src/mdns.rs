@@ -1,14 +1,26 @@
 // SPDX-License-Identifier: Apache-2.0
 
-//! mDNS service advertisement for LAN discovery.
+//! mDNS service advertisement and browsing for LAN discovery.
 //!
 //! Advertises Phase Boot Provider as a DNS-SD service on the local network.
 //! This is separate from libp2p's mDNS peer discovery - we use DNS-SD for
 //! HTTP service advertisement so clients can discover boot images via:
 //!   `avahi-browse _phase-image._tcp` or `dns-sd -B _phase-image._tcp`
+//!
+//! [`MdnsBrowser`] is the Rust-side client: it reports providers as they
+//! appear and leave, with their TXT records parsed into [`MdnsProvider`].
+//!
+//! A provider serving HTTPS says so in its TXT record, along with the
+//! SPKI pin of a self-signed certificate (see [`crate::tls`]), so browsers
+//! build `https://` URLs and know which key to expect.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use futures_util::Stream;
+use mdns_sd::{Receiver, ResolvedService, ServiceDaemon, ServiceEvent, ServiceInfo};
 use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
 use tracing::{info, warn};
 
 /// mDNS service type for Phase boot providers
@@ -19,6 +31,12 @@ pub const TXT_CHANNEL: &str = "channel";
 pub const TXT_ARCH: &str = "arch";
 pub const TXT_VERSION: &str = "version";
 pub const TXT_HTTP_PORT: &str = "http_port";
+/// `http` or `https`
+pub const TXT_SCHEME: &str = "scheme";
+/// Hex SHA-256 of the TLS certificate's public key, when self-signed
+pub const TXT_TLS_SPKI: &str = "tls_spki_sha256";
+/// libp2p peer ID of the key that signs the served manifests
+pub const TXT_PEER_ID: &str = "peer_id";
 
 /// mDNS advertisement configuration
 #[derive(Debug, Clone)]
@@ -28,6 +46,12 @@ pub struct MdnsConfig {
     pub channel: String,
     pub arch: String,
     pub version: String,
+    /// Whether the server speaks HTTPS
+    pub tls: bool,
+    /// SPKI pin of the server's self-signed certificate
+    pub tls_spki_sha256: Option<String>,
+    /// Peer ID of the key that signs the served manifests
+    pub peer_id: Option<String>,
 }
 
 impl MdnsConfig {
@@ -44,9 +68,27 @@ impl MdnsConfig {
             channel: channel.to_string(),
             arch: arch.to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
+            tls: false,
+            tls_spki_sha256: None,
+            peer_id: None,
         }
     }
 
+    /// Advertise an HTTPS server, with the SPKI pin of its certificate
+    /// when clients should pin it rather than trust a CA
+    pub fn with_tls(mut self, spki_sha256: Option<String>) -> Self {
+        self.tls = true;
+        self.tls_spki_sha256 = spki_sha256;
+        self
+    }
+
+    /// Advertise the peer ID whose key signs the served manifests, so
+    /// clients can check a manifest came from this provider
+    pub fn with_peer_id(mut self, peer_id: String) -> Self {
+        self.peer_id = Some(peer_id);
+        self
+    }
+
     /// Generate TXT record entries for DNS-SD
     ///
     /// These records allow clients to filter providers by channel, architecture, etc.
@@ -56,6 +98,16 @@ impl MdnsConfig {
         records.insert(TXT_ARCH.to_string(), self.arch.clone());
         records.insert(TXT_VERSION.to_string(), self.version.clone());
         records.insert(TXT_HTTP_PORT.to_string(), self.http_port.to_string());
+        records.insert(
+            TXT_SCHEME.to_string(),
+            if self.tls { "https" } else { "http" }.to_string(),
+        );
+        if let Some(pin) = &self.tls_spki_sha256 {
+            records.insert(TXT_TLS_SPKI.to_string(), pin.clone());
+        }
+        if let Some(peer_id) = &self.peer_id {
+            records.insert(TXT_PEER_ID.to_string(), peer_id.clone());
+        }
         records
     }
 }
@@ -86,92 +138,302 @@ fn hostname() -> String {
     "unknown".to_string()
 }
 
+/// Host name to announce the service under, e.g. `builder.local.`
+fn local_host_name() -> String {
+    let name = hostname();
+    let label = name.split('.').next().filter(|l| !l.is_empty()).unwrap_or("unknown");
+    format!("{}.local.", label)
+}
+
 /// mDNS service advertiser
 ///
-/// NOTE: This is a placeholder for the actual mDNS implementation.
-/// Full DNS-SD service advertisement requires the `mdns-sd` crate,
-/// which should be added to Cargo.toml:
-///
-/// ```toml
-/// mdns-sd = "0.11"
-/// ```
-///
-/// Then implement using mdns_sd::ServiceDaemon:
-/// ```rust,ignore
-/// use mdns_sd::{ServiceDaemon, ServiceInfo};
-///
-/// pub struct MdnsAdvertiser {
-///     daemon: ServiceDaemon,
-///     service_info: ServiceInfo,
-/// }
-///
-/// impl MdnsAdvertiser {
-///     pub fn new(config: MdnsConfig) -> Result<Self> {
-///         let daemon = ServiceDaemon::new()?;
-///
-///         let service_info = ServiceInfo::new(
-///             MDNS_SERVICE_TYPE,
-///             &config.service_name,
-///             &format!("{}.local.", hostname()),
-///             "",
-///             config.http_port,
-///             config.txt_records(),
-///         )?;
-///
-///         daemon.register(service_info.clone())?;
-///
-///         Ok(Self { daemon, service_info })
-///     }
-///
-///     pub fn shutdown(self) -> Result<()> {
-///         self.daemon.unregister(&self.service_info.get_fullname())?;
-///         self.daemon.shutdown()?;
-///         Ok(())
-///     }
-/// }
-/// ```
-#[derive(Debug)]
+/// Announces the provider as a `_phase-image._tcp` DNS-SD service, with
+/// [`MdnsConfig::txt_records`] as its TXT record, on every interface. The
+/// announced addresses follow the host's as they change. The announcement
+/// is withdrawn on [`shutdown`](Self::shutdown) or drop.
 pub struct MdnsAdvertiser {
     config: MdnsConfig,
+    daemon: ServiceDaemon,
+    fullname: String,
+    stopped: bool,
 }
 
 impl MdnsAdvertiser {
     /// Create and start mDNS advertisement
-    ///
-    /// This currently logs the configuration but does not perform actual
-    /// DNS-SD advertisement. To enable full functionality, add the `mdns-sd`
-    /// crate to Cargo.toml and implement using ServiceDaemon (see above).
     pub fn new(config: MdnsConfig) -> Result<Self> {
-        info!(
-            "mDNS configuration: service={} port={} channel={} arch={}",
-            config.service_name, config.http_port, config.channel, config.arch
-        );
+        let daemon = ServiceDaemon::new().context("Failed to start mDNS daemon")?;
+        let service = ServiceInfo::new(
+            MDNS_SERVICE_TYPE,
+            &config.service_name,
+            &local_host_name(),
+            (),
+            config.http_port,
+            config.txt_records(),
+        )
+        .context("Invalid mDNS service description")?
+        .enable_addr_auto();
+        let fullname = service.get_fullname().to_string();
 
-        warn!(
-            "mDNS service advertisement not yet implemented - requires 'mdns-sd' crate"
-        );
-        warn!(
-            "To enable: add 'mdns-sd = \"0.11\"' to Cargo.toml and implement ServiceDaemon"
-        );
+        if let Err(e) = daemon.register(service) {
+            let _ = daemon.shutdown();
+            return Err(e).context("Failed to register mDNS service");
+        }
 
+        info!(
+            "Advertising {} on port {} (channel={} arch={})",
+            fullname, config.http_port, config.channel, config.arch
+        );
         info!(
             "Clients can discover via: avahi-browse {} or dns-sd -B {}",
             MDNS_SERVICE_TYPE, MDNS_SERVICE_TYPE
         );
 
-        Ok(Self { config })
+        Ok(Self {
+            config,
+            daemon,
+            fullname,
+            stopped: false,
+        })
     }
 
-    /// Shutdown advertisement
-    pub fn shutdown(self) -> Result<()> {
-        info!("mDNS advertiser shutdown (placeholder)");
-        Ok(())
+    /// Withdraw the announcement and stop the daemon
+    pub fn shutdown(mut self) -> Result<()> {
+        self.stop()
     }
 
     /// Get configuration
     pub fn config(&self) -> &MdnsConfig {
         &self.config
     }
+
+    /// Full DNS-SD instance name, e.g. `plasmd-host._phase-image._tcp.local.`
+    pub fn fullname(&self) -> &str {
+        &self.fullname
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if std::mem::replace(&mut self.stopped, true) {
+            return Ok(());
+        }
+        let unregistered = self.daemon.unregister(&self.fullname).map(|goodbye| {
+            // Give the daemon a moment to send the goodbye packets.
+            let _ = goodbye.recv_timeout(Duration::from_secs(1));
+        });
+        let stopped = self.daemon.shutdown();
+        unregistered.context("Failed to unregister mDNS service")?;
+        stopped.context("Failed to stop mDNS daemon")?;
+        Ok(())
+    }
+}
+
+impl Drop for MdnsAdvertiser {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            warn!("mDNS advertiser shutdown failed: {:#}", e);
+        }
+    }
+}
+
+impl fmt::Debug for MdnsAdvertiser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MdnsAdvertiser")
+            .field("config", &self.config)
+            .field("fullname", &self.fullname)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A provider found on the local network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MdnsProvider {
+    /// DNS-SD instance name, e.g. `plasmd-host._phase-image._tcp.local.`
+    pub fullname: String,
+    /// Host the service runs on, e.g. `host.local.`
+    pub host: String,
+    /// The host's addresses, routable IPv4 first
+    pub addresses: Vec<IpAddr>,
+    /// The `http_port` TXT entry, or the service's port without one
+    pub http_port: u16,
+    /// Whether the `scheme` TXT entry says `https`
+    pub tls: bool,
+    /// The `tls_spki_sha256` TXT entry: the key to pin over HTTPS
+    pub tls_spki_sha256: Option<String>,
+    pub channel: Option<String>,
+    pub arch: Option<String>,
+    pub version: Option<String>,
+    /// Every TXT entry, including ones not parsed above
+    pub txt: HashMap<String, String>,
+}
+
+impl MdnsProvider {
+    /// Build a provider from a resolved service, parsing the TXT entries
+    /// [`MdnsConfig::txt_records`] writes.
+    pub fn from_txt(
+        fullname: &str,
+        host: &str,
+        mut addresses: Vec<IpAddr>,
+        port: u16,
+        txt: HashMap<String, String>,
+    ) -> Self {
+        addresses.sort_by_key(|ip| (ip.is_loopback(), ip.is_ipv6(), *ip));
+        addresses.dedup();
+        let http_port = txt
+            .get(TXT_HTTP_PORT)
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(port);
+        let entry = |key: &str| txt.get(key).filter(|v| !v.is_empty()).cloned();
+
+        Self {
+            fullname: fullname.to_string(),
+            host: host.to_string(),
+            addresses,
+            http_port,
+            tls: txt.get(TXT_SCHEME).is_some_and(|s| s == "https"),
+            tls_spki_sha256: entry(TXT_TLS_SPKI),
+            channel: entry(TXT_CHANNEL),
+            arch: entry(TXT_ARCH),
+            version: entry(TXT_VERSION),
+            txt,
+        }
+    }
+
+    fn from_resolved(service: &ResolvedService) -> Self {
+        let txt = service
+            .get_properties()
+            .iter()
+            .map(|p| (p.key().to_string(), p.val_str().to_string()))
+            .collect();
+        let addresses = service
+            .get_addresses()
+            .iter()
+            .map(|ip| ip.to_ip_addr())
+            .collect();
+        Self::from_txt(
+            service.get_fullname(),
+            service.get_hostname(),
+            addresses,
+            service.get_port(),
+            txt,
+        )
+    }
+
+    /// Whether the provider advertises `channel` for `arch`
+    pub fn serves(&self, channel: &str, arch: &str) -> bool {
+        self.channel.as_deref() == Some(channel) && self.arch.as_deref() == Some(arch)
+    }
+
+    /// Base URLs of the provider's HTTP server, one per address, in
+    /// order of preference, `https://` when it advertises TLS
+    pub fn http_urls(&self) -> Vec<String> {
+        let scheme = if self.tls { "https" } else { "http" };
+        self.addresses
+            .iter()
+            .map(|ip| format!("{}://{}", scheme, SocketAddr::new(*ip, self.http_port)))
+            .collect()
+    }
+
+    /// Manifest URL for `channel`/`arch` on the preferred address
+    pub fn manifest_url(&self, channel: &str, arch: &str) -> Option<String> {
+        self.http_urls()
+            .into_iter()
+            .next()
+            .map(|base| format!("{}/{}/{}/manifest.json", base, channel, arch))
+    }
+}
+
+/// A change in the set of providers on the local network
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MdnsEvent {
+    /// A provider appeared, or its addresses or TXT record changed
+    Resolved(MdnsProvider),
+    /// The provider with this instance name withdrew or expired
+    Removed(String),
+}
+
+/// Browses the local network for `_phase-image._tcp` providers
+///
+/// Events arrive as providers come and go for as long as the browser
+/// lives, so callers decide how long to listen.
+pub struct MdnsBrowser {
+    daemon: ServiceDaemon,
+    events: Receiver<ServiceEvent>,
+    stopped: bool,
+}
+
+impl MdnsBrowser {
+    /// Start browsing
+    pub fn new() -> Result<Self> {
+        let daemon = ServiceDaemon::new().context("Failed to start mDNS daemon")?;
+        let events = match daemon.browse(MDNS_SERVICE_TYPE) {
+            Ok(events) => events,
+            Err(e) => {
+                let _ = daemon.shutdown();
+                return Err(e).context("Failed to browse for mDNS services");
+            }
+        };
+        Ok(Self {
+            daemon,
+            events,
+            stopped: false,
+        })
+    }
+
+    /// The next change, or `None` once browsing has stopped
+    ///
+    /// Cancel-safe: dropping the future loses no event.
+    pub async fn next(&mut self) -> Option<MdnsEvent> {
+        loop {
+            match self.events.recv_async().await.ok()? {
+                ServiceEvent::ServiceResolved(service) => {
+                    return Some(MdnsEvent::Resolved(MdnsProvider::from_resolved(&service)));
+                }
+                ServiceEvent::ServiceRemoved(_, fullname) => {
+                    return Some(MdnsEvent::Removed(fullname));
+                }
+                ServiceEvent::SearchStopped(_) => return None,
+                _ => {}
+            }
+        }
+    }
+
+    /// The events as a stream; dropping it stops browsing
+    pub fn into_stream(self) -> impl Stream<Item = MdnsEvent> + Send {
+        futures_util::stream::unfold(self, |mut browser| async move {
+            browser.next().await.map(|event| (event, browser))
+        })
+    }
+
+    /// Stop browsing and the daemon
+    pub fn shutdown(mut self) -> Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if std::mem::replace(&mut self.stopped, true) {
+            return Ok(());
+        }
+        let unbrowsed = self.daemon.stop_browse(MDNS_SERVICE_TYPE);
+        let stopped = self.daemon.shutdown();
+        unbrowsed.context("Failed to stop mDNS browse")?;
+        stopped.context("Failed to stop mDNS daemon")?;
+        Ok(())
+    }
+}
+
+impl Drop for MdnsBrowser {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            warn!("mDNS browser shutdown failed: {:#}", e);
+        }
+    }
+}
+
+impl fmt::Debug for MdnsBrowser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MdnsBrowser")
+            .field("stopped", &self.stopped)
+            .finish_non_exhaustive()
+    }
 }
 
 #[cfg(test)]
@@ -198,6 +460,17 @@ mod tests {
         assert_eq!(records.get(TXT_ARCH).unwrap(), "arm64");
         assert_eq!(records.get(TXT_HTTP_PORT).unwrap(), "8080");
         assert_eq!(records.get(TXT_VERSION).unwrap(), env!("CARGO_PKG_VERSION"));
+        assert_eq!(records.get(TXT_SCHEME).unwrap(), "http");
+        assert!(!records.contains_key(TXT_TLS_SPKI));
+        assert!(!records.contains_key(TXT_PEER_ID));
+
+        let records = config
+            .with_tls(Some("ab".repeat(32)))
+            .with_peer_id("12D3KooWtest".to_string())
+            .txt_records();
+        assert_eq!(records.get(TXT_SCHEME).unwrap(), "https");
+        assert_eq!(records.get(TXT_TLS_SPKI).unwrap(), &"ab".repeat(32));
+        assert_eq!(records.get(TXT_PEER_ID).unwrap(), "12D3KooWtest");
     }
 
     #[test]
@@ -213,7 +486,6 @@ mod tests {
         let config = MdnsConfig::new(8080, "stable", "x86_64");
         let advertiser = MdnsAdvertiser::new(config.clone());
 
-        // Should succeed even though it's a placeholder
         assert!(advertiser.is_ok());
 
         let adv = advertiser.unwrap();
@@ -222,4 +494,88 @@ mod tests {
         // Shutdown should succeed
         assert!(adv.shutdown().is_ok());
     }
+
+    #[test]
+    fn test_provider_from_txt() {
+        let txt = MdnsConfig::new(8080, "stable", "arm64").txt_records();
+        let provider = MdnsProvider::from_txt(
+            "plasmd-a._phase-image._tcp.local.",
+            "a.local.",
+            vec![
+                "127.0.0.1".parse().unwrap(),
+                "fe80::1".parse().unwrap(),
+                "192.168.1.20".parse().unwrap(),
+            ],
+            9000,
+            txt,
+        );
+
+        // The TXT port wins over the service's.
+        assert_eq!(provider.http_port, 8080);
+        assert!(!provider.tls);
+        assert_eq!(provider.tls_spki_sha256, None);
+        assert!(!provider.txt.contains_key(TXT_PEER_ID));
+        assert!(provider.serves("stable", "arm64"));
+        assert!(!provider.serves("testing", "arm64"));
+        assert_eq!(provider.version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+        assert_eq!(
+            provider.http_urls(),
+            vec!["http://192.168.1.20:8080", "http://[fe80::1]:8080", "http://127.0.0.1:8080"]
+        );
+        assert_eq!(
+            provider.manifest_url("stable", "arm64").unwrap(),
+            "http://192.168.1.20:8080/stable/arm64/manifest.json"
+        );
+
+        let pin = "cd".repeat(32);
+        let txt = MdnsConfig::new(8443, "stable", "arm64")
+            .with_tls(Some(pin.clone()))
+            .with_peer_id("12D3KooWtest".to_string())
+            .txt_records();
+        let secure = MdnsProvider::from_txt("s", "s.local.", vec!["10.0.0.5".parse().unwrap()], 8443, txt);
+        assert!(secure.tls);
+        assert_eq!(secure.tls_spki_sha256, Some(pin));
+        assert_eq!(secure.txt.get(TXT_PEER_ID).unwrap(), "12D3KooWtest");
+        assert_eq!(
+            secure.manifest_url("stable", "arm64").unwrap(),
+            "https://10.0.0.5:8443/stable/arm64/manifest.json"
+        );
+
+        let bare = MdnsProvider::from_txt("x", "x.local.", vec![], 9000, HashMap::new());
+        assert_eq!(bare.http_port, 9000);
+        assert!(!bare.serves("stable", "arm64"));
+        assert_eq!(bare.manifest_url("stable", "arm64"), None);
+    }
+
+    #[tokio::test]
+    async fn test_browser_sees_advertised_provider() {
+        let mut config = MdnsConfig::new(18080, "testing", "arm64");
+        config.service_name = format!("phase-test-{}", std::process::id());
+        let advertiser = MdnsAdvertiser::new(config).unwrap();
+        let fullname = advertiser.fullname().to_string();
+        let mut browser = MdnsBrowser::new().unwrap();
+
+        let provider = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match browser.next().await {
+                    Some(MdnsEvent::Resolved(p)) if p.fullname == fullname => break p,
+                    Some(_) => {}
+                    None => panic!("browse stopped"),
+                }
+            }
+        })
+        .await
+        .expect("advertised provider not seen");
+        assert!(provider.serves("testing", "arm64"));
+        assert_eq!(provider.http_port, 18080);
+        assert!(!provider.addresses.is_empty());
+
+        advertiser.shutdown().unwrap();
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while browser.next().await != Some(MdnsEvent::Removed(fullname.clone())) {}
+        })
+        .await
+        .expect("withdrawn provider not removed");
+        browser.shutdown().unwrap();
+    }
 }
@@ -1,18 +1,84 @@
 // SPDX-License-Identifier: Apache-2.0
 
-//! Lightweight metrics for the artifact server: request counter, total bytes
+//! Lightweight metrics for the artifact server: request counters, bytes
 //! served, and a one-shot health check over the artifacts directory.
+//!
+//! [`ProviderMetrics::render_prometheus`] writes the counters in the
+//! Prometheus text exposition format for `GET /metrics`:
+//!
+//! | metric | type | labels |
+//! |--------|------|--------|
+//! | `phase_artifact_http_requests_total` | counter | `route`, `method`, `status` |
+//! | `phase_artifact_http_request_duration_seconds` | histogram | `route` |
+//! | `phase_artifact_range_requests_total` | counter | `bucket` |
+//! | `phase_artifact_bytes_served_total` | counter | `bucket` |
+//! | `phase_artifact_uptime_seconds` | gauge | |
+//!
+//! `route` is the matched route pattern (`/blobs/:prefix/:filename`), never
+//! the raw path, and `method` is `GET`, `HEAD`, `PUT`, `DELETE` or `other`,
+//! so label cardinality stays fixed. Request duration is the time to the
+//! response head; streaming the body is not included. The server appends
+//! store gauges (disk use, hash cache) with [`render_store_metrics`]; disk
+//! use is measured at most once per [`crate::GC_INTERVAL`], not on
+//! every scrape.
 
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::artifacts::HashCacheStats;
+use crate::gc::StorageUsage;
+
+/// Upper bounds, in seconds, of the request duration histogram buckets.
+pub const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Which layout a response was served from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServedBucket {
+    /// `/<channel>/<arch>/<artifact>` files.
+    Channel,
+    /// `/blobs/…` blobs and their outboards.
+    Blobs,
+}
+
+impl ServedBucket {
+    const ALL: [ServedBucket; 2] = [ServedBucket::Channel, ServedBucket::Blobs];
+
+    fn label(self) -> &'static str {
+        match self {
+            ServedBucket::Channel => "channel",
+            ServedBucket::Blobs => "blobs",
+        }
+    }
+}
+
+/// Per-route request counts and durations.
+#[derive(Debug, Default)]
+struct RouteStats {
+    /// Requests by `(method, status)`, the method as [`method_label`]
+    /// names it.
+    responses: BTreeMap<(&'static str, u16), u64>,
+    /// Requests per [`LATENCY_BUCKETS`] entry, not cumulative; the last
+    /// slot counts the ones slower than every bound.
+    latency_counts: [u64; LATENCY_BUCKETS.len() + 1],
+    latency_sum_secs: f64,
+}
 
 /// Metrics for the provider server
 #[derive(Debug)]
 pub struct ProviderMetrics {
     requests_total: AtomicU64,
     bytes_served_total: AtomicU64,
+    /// Indexed like [`ServedBucket::ALL`].
+    bucket_bytes_served: [AtomicU64; 2],
+    range_requests: [AtomicU64; 2],
+    routes: Mutex<BTreeMap<String, RouteStats>>,
     start_time: Instant,
 }
 
@@ -21,6 +87,9 @@ impl ProviderMetrics {
         Self {
             requests_total: AtomicU64::new(0),
             bytes_served_total: AtomicU64::new(0),
+            bucket_bytes_served: Default::default(),
+            range_requests: Default::default(),
+            routes: Mutex::new(BTreeMap::new()),
             start_time: Instant::now(),
         }
     }
@@ -29,14 +98,114 @@ impl ProviderMetrics {
         self.requests_total.fetch_add(1, Ordering::Relaxed);
     }
 
-    pub fn add_bytes_served(&self, bytes: u64) {
+    pub fn add_bytes_served(&self, bucket: ServedBucket, bytes: u64) {
         self.bytes_served_total.fetch_add(bytes, Ordering::Relaxed);
+        self.bucket_bytes_served[bucket as usize].fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count a request carrying a `Range:` header, satisfiable or not.
+    pub fn increment_range_requests(&self, bucket: ServedBucket) {
+        self.range_requests[bucket as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record one finished request against its matched `route` pattern.
+    pub fn record_request(&self, route: &str, method: &str, status: u16, duration: Duration) {
+        let mut routes = self.routes.lock().unwrap_or_else(PoisonError::into_inner);
+        let stats = routes.entry(route.to_string()).or_default();
+        *stats.responses.entry((method_label(method), status)).or_insert(0) += 1;
+        let secs = duration.as_secs_f64();
+        let slot = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        stats.latency_counts[slot] += 1;
+        stats.latency_sum_secs += secs;
     }
 
     pub fn uptime_secs(&self) -> u64 {
         self.start_time.elapsed().as_secs()
     }
 
+    /// The server's own counters in the Prometheus text format.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let routes = self.routes.lock().unwrap_or_else(PoisonError::into_inner);
+
+        family(&mut out, "phase_artifact_http_requests_total", "counter", "HTTP requests by route, method and status.");
+        for (route, stats) in routes.iter() {
+            for ((method, status), count) in &stats.responses {
+                let _ = writeln!(
+                    out,
+                    "phase_artifact_http_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                    escape(route),
+                    escape(method),
+                    status,
+                    count
+                );
+            }
+        }
+
+        family(
+            &mut out,
+            "phase_artifact_http_request_duration_seconds",
+            "histogram",
+            "Time to the response head, by route.",
+        );
+        for (route, stats) in routes.iter() {
+            let route = escape(route);
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&stats.latency_counts) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "phase_artifact_http_request_duration_seconds_bucket{{route=\"{}\",le=\"{}\"}} {}",
+                    route, bound, cumulative
+                );
+            }
+            let total: u64 = stats.latency_counts.iter().sum();
+            let _ = writeln!(
+                out,
+                "phase_artifact_http_request_duration_seconds_bucket{{route=\"{}\",le=\"+Inf\"}} {}",
+                route, total
+            );
+            let _ = writeln!(
+                out,
+                "phase_artifact_http_request_duration_seconds_sum{{route=\"{}\"}} {}",
+                route, stats.latency_sum_secs
+            );
+            let _ = writeln!(
+                out,
+                "phase_artifact_http_request_duration_seconds_count{{route=\"{}\"}} {}",
+                route, total
+            );
+        }
+        drop(routes);
+
+        family(&mut out, "phase_artifact_range_requests_total", "counter", "Requests with a Range header, by bucket.");
+        for bucket in ServedBucket::ALL {
+            let _ = writeln!(
+                out,
+                "phase_artifact_range_requests_total{{bucket=\"{}\"}} {}",
+                bucket.label(),
+                self.range_requests[bucket as usize].load(Ordering::Relaxed)
+            );
+        }
+
+        family(&mut out, "phase_artifact_bytes_served_total", "counter", "Response body bytes served, by bucket.");
+        for bucket in ServedBucket::ALL {
+            let _ = writeln!(
+                out,
+                "phase_artifact_bytes_served_total{{bucket=\"{}\"}} {}",
+                bucket.label(),
+                self.bucket_bytes_served[bucket as usize].load(Ordering::Relaxed)
+            );
+        }
+
+        family(&mut out, "phase_artifact_uptime_seconds", "gauge", "Seconds since the server started.");
+        let _ = writeln!(out, "phase_artifact_uptime_seconds {}", self.uptime_secs());
+        out
+    }
+
     pub fn snapshot(&self) -> MetricsSnapshot {
         MetricsSnapshot {
             requests_total: self.requests_total.load(Ordering::Relaxed),
@@ -57,6 +226,62 @@ pub struct MetricsSnapshot {
     pub bytes_served_total: u64,
 }
 
+/// Append the artifact store's gauges to a [`ProviderMetrics::render_prometheus`]
+/// page: disk use per bucket, blob pinning, the blob quota, and the
+/// channel/arch hash cache.
+pub fn render_store_metrics(
+    out: &mut String,
+    usage: &StorageUsage,
+    quota_bytes: Option<u64>,
+    hash_cache: HashCacheStats,
+) {
+    family(out, "phase_artifact_disk_bytes", "gauge", "Bytes on disk, by bucket.");
+    for bucket in &usage.buckets {
+        let _ = writeln!(out, "phase_artifact_disk_bytes{{bucket=\"{}\"}} {}", escape(&bucket.name), bucket.bytes);
+    }
+    family(out, "phase_artifact_disk_files", "gauge", "Files on disk, by bucket.");
+    for bucket in &usage.buckets {
+        let _ = writeln!(out, "phase_artifact_disk_files{{bucket=\"{}\"}} {}", escape(&bucket.name), bucket.files);
+    }
+    family(out, "phase_artifact_blobs", "gauge", "Blobs stored.");
+    let _ = writeln!(out, "phase_artifact_blobs {}", usage.blobs);
+    family(out, "phase_artifact_pinned_blobs", "gauge", "Blobs pinned against eviction.");
+    let _ = writeln!(out, "phase_artifact_pinned_blobs {}", usage.pinned_blobs);
+    family(out, "phase_artifact_pinned_bytes", "gauge", "Bytes of pinned blobs and their outboards.");
+    let _ = writeln!(out, "phase_artifact_pinned_bytes {}", usage.pinned_bytes);
+    if let Some(quota) = quota_bytes {
+        family(out, "phase_artifact_blob_quota_bytes", "gauge", "Configured blob bucket quota.");
+        let _ = writeln!(out, "phase_artifact_blob_quota_bytes {}", quota);
+    }
+    family(out, "phase_artifact_hash_cache_hits_total", "counter", "Channel artifact hashes answered from cache.");
+    let _ = writeln!(out, "phase_artifact_hash_cache_hits_total {}", hash_cache.hits);
+    family(out, "phase_artifact_hash_cache_misses_total", "counter", "Channel artifact hashes computed from disk.");
+    let _ = writeln!(out, "phase_artifact_hash_cache_misses_total {}", hash_cache.misses);
+}
+
+/// The `method` label for a request method: the ones the server routes,
+/// or `other`, so clients can't mint label values.
+fn method_label(method: &str) -> &'static str {
+    match method {
+        "GET" => "GET",
+        "HEAD" => "HEAD",
+        "PUT" => "PUT",
+        "DELETE" => "DELETE",
+        _ => "other",
+    }
+}
+
+/// The `# HELP` and `# TYPE` lines that open a metric family.
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct HealthCheck {
     pub status: String,
@@ -101,12 +326,39 @@ mod tests {
     fn test_metrics_increment() {
         let metrics = ProviderMetrics::new();
         metrics.increment_requests();
-        metrics.add_bytes_served(1024);
+        metrics.add_bytes_served(ServedBucket::Blobs, 1024);
         let snapshot = metrics.snapshot();
         assert_eq!(snapshot.requests_total, 1);
         assert_eq!(snapshot.bytes_served_total, 1024);
     }
 
+    #[test]
+    fn test_prometheus_rendering() {
+        let metrics = ProviderMetrics::new();
+        metrics.record_request("/blobs/:prefix/:filename", "GET", 206, Duration::from_millis(20));
+        metrics.record_request("/blobs/:prefix/:filename", "GET", 206, Duration::from_secs(60));
+        metrics.record_request("/health", "GET", 200, Duration::from_millis(1));
+        metrics.record_request("/health", "PROPFIND", 405, Duration::from_millis(1));
+        metrics.record_request("/health", "X-MADE-UP", 405, Duration::from_millis(1));
+        metrics.increment_range_requests(ServedBucket::Blobs);
+        metrics.add_bytes_served(ServedBucket::Channel, 10);
+        metrics.add_bytes_served(ServedBucket::Blobs, 5);
+
+        let text = metrics.render_prometheus();
+        let has = |line: &str| text.lines().any(|l| l == line);
+        assert!(has(r#"phase_artifact_http_requests_total{route="/blobs/:prefix/:filename",method="GET",status="206"} 2"#), "{text}");
+        assert!(has(r#"phase_artifact_http_request_duration_seconds_bucket{route="/blobs/:prefix/:filename",le="0.01"} 0"#), "{text}");
+        assert!(has(r#"phase_artifact_http_request_duration_seconds_bucket{route="/blobs/:prefix/:filename",le="0.025"} 1"#), "{text}");
+        assert!(has(r#"phase_artifact_http_request_duration_seconds_bucket{route="/blobs/:prefix/:filename",le="30"} 1"#), "{text}");
+        assert!(has(r#"phase_artifact_http_request_duration_seconds_bucket{route="/blobs/:prefix/:filename",le="+Inf"} 2"#), "{text}");
+        assert!(has(r#"phase_artifact_http_requests_total{route="/health",method="other",status="405"} 2"#), "{text}");
+        assert!(has(r#"phase_artifact_http_request_duration_seconds_count{route="/health"} 3"#), "{text}");
+        assert!(has(r#"phase_artifact_range_requests_total{bucket="blobs"} 1"#), "{text}");
+        assert!(has(r#"phase_artifact_bytes_served_total{bucket="channel"} 10"#), "{text}");
+        assert!(has("# TYPE phase_artifact_http_request_duration_seconds histogram"), "{text}");
+        assert_eq!(escape("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+
     #[test]
     fn test_health_check() {
         let temp = TempDir::new().unwrap();
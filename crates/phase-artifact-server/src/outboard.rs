@@ -0,0 +1,320 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Chunk-level Merkle outboards for content-addressed blobs.
+//!
+//! A [`BlobId`](crate::BlobId) can only be checked once the whole blob is
+//! in hand, so a flipped byte in a 20 GB model surfaces at the very end.
+//! Next to every blob the store keeps an *outboard*: the BLAKE3 hash of
+//! each [`OUTBOARD_CHUNK_SIZE`] chunk, plus a Merkle root over those
+//! hashes. A client fetches the outboard first (32 bytes per MiB), then
+//! verifies each `Range:` response on arrival and retries only the chunk
+//! that failed.
+//!
+//! On disk and on the wire (`GET /blobs/<aa>/<hex>.outboard`):
+//!
+//! ```text
+//! magic "PHASEOBD" (8) || version (1) || size (u64 BE) || chunk_size (u32 BE)
+//!   || leaf hash (32) per chunk
+//! ```
+//!
+//! Leaves are `BLAKE3(0x00 || chunk)`, parents `BLAKE3(0x01 || left ||
+//! right)` with an odd node carried up unchanged, and the
+//! [`OutboardRoot`] is `BLAKE3(0x02 || size || chunk_size || tree root)`.
+//! An empty blob has a single empty chunk.
+
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// Bytes per verified chunk.
+pub const OUTBOARD_CHUNK_SIZE: u32 = 1 << 20;
+
+const MAGIC: &[u8; 8] = b"PHASEOBD";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 8 + 4;
+const LEAF: u8 = 0x00;
+const PARENT: u8 = 0x01;
+const ROOT: u8 = 0x02;
+
+/// Merkle root committing to a blob's size and every chunk hash. Small
+/// enough to carry in a manifest, so clients can pin the outboard itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutboardRoot([u8; 32]);
+
+impl OutboardRoot {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    /// Parse a 64-character hex root.
+    pub fn from_hex(hex_str: &str) -> Option<Self> {
+        hex::decode(hex_str).ok()?.try_into().ok().map(Self)
+    }
+}
+
+impl fmt::Display for OutboardRoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// Per-chunk hashes of one blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outboard {
+    size: u64,
+    chunk_size: u32,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl Outboard {
+    /// Outboard of an in-memory blob.
+    pub fn compute(content: &[u8]) -> Self {
+        let mut builder = OutboardBuilder::new();
+        builder.update(content);
+        builder.finish()
+    }
+
+    /// Outboard of the file at `path`, read in one pass.
+    pub fn compute_file(path: &Path) -> Result<Self> {
+        let mut file =
+            fs::File::open(path).with_context(|| format!("open {:?} for outboard", path))?;
+        let mut builder = OutboardBuilder::new();
+        let mut buf = vec![0u8; 256 * 1024];
+        loop {
+            let n = file
+                .read(&mut buf)
+                .with_context(|| format!("read {:?} for outboard", path))?;
+            if n == 0 {
+                break;
+            }
+            builder.update(&buf[..n]);
+        }
+        Ok(builder.finish())
+    }
+
+    /// Size of the blob this outboard describes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Byte offset and length of chunk `index`.
+    pub fn chunk_range(&self, index: usize) -> (u64, u64) {
+        let start = index as u64 * u64::from(self.chunk_size);
+        let len = u64::from(self.chunk_size).min(self.size.saturating_sub(start));
+        (start, len)
+    }
+
+    /// Whether `data` is exactly chunk `index`.
+    pub fn verify_chunk(&self, index: usize, data: &[u8]) -> bool {
+        self.leaves.get(index).is_some_and(|leaf| {
+            data.len() as u64 == self.chunk_range(index).1 && leaf_hash(data) == *leaf
+        })
+    }
+
+    pub fn root(&self) -> OutboardRoot {
+        let mut level = self.leaves.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => {
+                        let mut hasher = blake3::Hasher::new();
+                        hasher.update(&[PARENT]);
+                        hasher.update(left);
+                        hasher.update(right);
+                        *hasher.finalize().as_bytes()
+                    }
+                    [odd] => *odd,
+                    _ => unreachable!("chunks(2) yields one or two items"),
+                })
+                .collect();
+        }
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&[ROOT]);
+        hasher.update(&self.size.to_be_bytes());
+        hasher.update(&self.chunk_size.to_be_bytes());
+        hasher.update(&level[0]);
+        OutboardRoot(*hasher.finalize().as_bytes())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.leaves.len() * 32);
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&self.size.to_be_bytes());
+        out.extend_from_slice(&self.chunk_size.to_be_bytes());
+        for leaf in &self.leaves {
+            out.extend_from_slice(leaf);
+        }
+        out
+    }
+
+    /// Parse an outboard, checking that its leaf count matches its size.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || !bytes.starts_with(MAGIC) {
+            bail!("not a blob outboard");
+        }
+        if bytes[MAGIC.len()] != FORMAT_VERSION {
+            bail!("unsupported outboard version {}", bytes[MAGIC.len()]);
+        }
+        let size = u64::from_be_bytes(bytes[9..17].try_into().expect("8-byte slice"));
+        let chunk_size = u32::from_be_bytes(bytes[17..21].try_into().expect("4-byte slice"));
+        if chunk_size == 0 {
+            bail!("outboard chunk size is zero");
+        }
+        let body = &bytes[HEADER_LEN..];
+        let expected = size.div_ceil(u64::from(chunk_size)).max(1);
+        if !body.len().is_multiple_of(32) || (body.len() / 32) as u64 != expected {
+            bail!(
+                "outboard has {} bytes of hashes, expected {} chunks",
+                body.len(),
+                expected
+            );
+        }
+        Ok(Self {
+            size,
+            chunk_size,
+            leaves: body
+                .chunks_exact(32)
+                .map(|c| c.try_into().expect("32-byte chunk"))
+                .collect(),
+        })
+    }
+}
+
+/// Builds an [`Outboard`] from a stream of arbitrarily sized writes.
+#[derive(Debug)]
+pub struct OutboardBuilder {
+    size: u64,
+    current: blake3::Hasher,
+    current_len: u32,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl OutboardBuilder {
+    pub fn new() -> Self {
+        Self {
+            size: 0,
+            current: leaf_hasher(),
+            current_len: 0,
+            leaves: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.size += data.len() as u64;
+        while !data.is_empty() {
+            let room = (OUTBOARD_CHUNK_SIZE - self.current_len) as usize;
+            let (head, rest) = data.split_at(room.min(data.len()));
+            self.current.update(head);
+            self.current_len += head.len() as u32;
+            if self.current_len == OUTBOARD_CHUNK_SIZE {
+                self.leaves.push(*self.current.finalize().as_bytes());
+                self.current = leaf_hasher();
+                self.current_len = 0;
+            }
+            data = rest;
+        }
+    }
+
+    pub fn finish(mut self) -> Outboard {
+        if self.current_len > 0 || self.leaves.is_empty() {
+            self.leaves.push(*self.current.finalize().as_bytes());
+        }
+        Outboard {
+            size: self.size,
+            chunk_size: OUTBOARD_CHUNK_SIZE,
+            leaves: self.leaves,
+        }
+    }
+}
+
+impl Default for OutboardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn leaf_hasher() -> blake3::Hasher {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF]);
+    hasher
+}
+
+fn leaf_hash(data: &[u8]) -> [u8; 32] {
+    let mut hasher = leaf_hasher();
+    hasher.update(data);
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn streaming_matches_one_shot_and_round_trips() {
+        let content = payload(OUTBOARD_CHUNK_SIZE as usize * 2 + 17);
+        let whole = Outboard::compute(&content);
+        let mut builder = OutboardBuilder::new();
+        for piece in content.chunks(7919) {
+            builder.update(piece);
+        }
+        assert_eq!(builder.finish(), whole);
+        assert_eq!(whole.chunk_count(), 3);
+        assert_eq!(
+            whole.chunk_range(2),
+            (2 * u64::from(OUTBOARD_CHUNK_SIZE), 17)
+        );
+
+        let parsed = Outboard::from_bytes(&whole.to_bytes()).unwrap();
+        assert_eq!(parsed, whole);
+        assert_eq!(parsed.root(), whole.root());
+        assert!(Outboard::from_bytes(&whole.to_bytes()[..40]).is_err());
+    }
+
+    #[test]
+    fn chunks_verify_individually() {
+        let content = payload(OUTBOARD_CHUNK_SIZE as usize + 100);
+        let outboard = Outboard::compute(&content);
+        let (start, len) = outboard.chunk_range(1);
+        let tail = &content[start as usize..(start + len) as usize];
+        assert!(outboard.verify_chunk(1, tail));
+
+        let mut corrupt = tail.to_vec();
+        corrupt[50] ^= 1;
+        assert!(!outboard.verify_chunk(1, &corrupt));
+        assert!(!outboard.verify_chunk(0, tail));
+        assert!(!outboard.verify_chunk(2, tail));
+    }
+
+    #[test]
+    fn root_commits_to_content_and_size() {
+        let a = Outboard::compute(b"hello");
+        assert_eq!(a.chunk_count(), 1);
+        assert_ne!(a.root(), Outboard::compute(b"hellp").root());
+        assert_ne!(Outboard::compute(b"").root(), a.root());
+        assert_eq!(
+            OutboardRoot::from_hex(&a.root().to_hex()),
+            Some(a.root())
+        );
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serving blobs over libp2p.
+//!
+//! A provider behind NAT can announce a blob under
+//! [`blob_dht_key`](crate::dht::blob_dht_key) but nobody can reach its HTTP
+//! port. [`blob_handler`] answers phase-net's `/phase/blob/1.0.0` range
+//! requests from the same [`ArtifactStore`] the HTTP server reads, so
+//! install it on the node's `Discovery`:
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # async fn run(discovery: phase_net::Discovery, server: phase_artifact_server::ArtifactServer) -> anyhow::Result<()> {
+//! discovery
+//!     .set_blob_handler(Some(phase_artifact_server::p2p::blob_handler(server.store().clone())))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! On the requesting side, [`BlobFetcher::with_peer`](crate::BlobFetcher::with_peer)
+//! adds the peer as a source next to any HTTP ones. Chunks are verified
+//! against the outboard and the finished file against its [`BlobId`], the
+//! same as over HTTP — which is how a worker resolves a job's `module_cid`
+//! or a node pulls model weights from a peer it can only reach over libp2p.
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use phase_net::{BlobHandler, BlobPart, BlobRequest, BlobResponse, BLOB_MAX_RANGE_BYTES};
+use tracing::warn;
+
+use crate::artifacts::{ArtifactStore, BlobId};
+
+/// A phase-net [`BlobHandler`] serving ranges of blobs, and their
+/// outboards, from `store`.
+pub fn blob_handler(store: Arc<ArtifactStore>) -> BlobHandler {
+    Arc::new(move |peer, request: BlobRequest| {
+        let store = Arc::clone(&store);
+        Box::pin(async move {
+            let blob_id = request.blob_id.clone();
+            match tokio::task::spawn_blocking(move || serve(&store, &request)).await {
+                Ok(Ok(response)) => response,
+                Ok(Err(e)) => {
+                    warn!("Blob {} for peer {}: {:#}", blob_id, peer, e);
+                    BlobResponse::Err {
+                        reason: format!("{:#}", e),
+                    }
+                }
+                Err(e) => BlobResponse::Err {
+                    reason: format!("blob read task failed: {}", e),
+                },
+            }
+        })
+    })
+}
+
+/// Resolve and read one request. Blocking: the outboard may have to be
+/// computed first.
+fn serve(store: &ArtifactStore, request: &BlobRequest) -> Result<BlobResponse> {
+    let Some(id) = BlobId::from_hex(&request.blob_id) else {
+        return Ok(BlobResponse::NotFound);
+    };
+    let path = match request.part {
+        BlobPart::Content => {
+            store.touch_blob(&id);
+            store.get_blob_path(&id)
+        }
+        BlobPart::Outboard => store.ensure_outboard(&id)?,
+    };
+    match path {
+        Some(path) => read_range(&path, request.offset, request.length),
+        None => Ok(BlobResponse::NotFound),
+    }
+}
+
+/// Up to `length` bytes of `path` from `offset`, clamped to
+/// [`BLOB_MAX_RANGE_BYTES`] and the end of the file.
+fn read_range(path: &Path, offset: u64, length: u32) -> Result<BlobResponse> {
+    let mut file = fs::File::open(path).with_context(|| format!("open {:?}", path))?;
+    let total_size = file.metadata()?.len();
+    if offset > total_size {
+        return Ok(BlobResponse::Err {
+            reason: format!("offset {} is past the end of a {}-byte file", offset, total_size),
+        });
+    }
+    let len = u64::from(length.min(BLOB_MAX_RANGE_BYTES)).min(total_size - offset);
+    let mut bytes = vec![0u8; len as usize];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut bytes)
+        .with_context(|| format!("read {:?}", path))?;
+    Ok(BlobResponse::Data { total_size, bytes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outboard::Outboard;
+
+    fn request(blob_id: &str, part: BlobPart, offset: u64, length: u32) -> BlobRequest {
+        BlobRequest {
+            blob_id: blob_id.to_string(),
+            part,
+            offset,
+            length,
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_content_and_outboard_ranges() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let store = Arc::new(ArtifactStore::new(temp.path().to_path_buf()).unwrap());
+        let id = store.add_blob(b"hello blob").unwrap();
+        let handler = blob_handler(Arc::clone(&store));
+        let peer = phase_net::PeerId::random();
+
+        let response = handler(peer, request(id.as_str(), BlobPart::Content, 6, 100)).await;
+        assert_eq!(
+            response,
+            BlobResponse::Data {
+                total_size: 10,
+                bytes: b"blob".to_vec()
+            }
+        );
+
+        match handler(peer, request(id.as_str(), BlobPart::Outboard, 0, u32::MAX)).await {
+            BlobResponse::Data { bytes, .. } => {
+                assert_eq!(Outboard::from_bytes(&bytes).unwrap(), Outboard::compute(b"hello blob"));
+            }
+            other => panic!("expected outboard bytes, got {other:?}"),
+        }
+
+        let past_end = handler(peer, request(id.as_str(), BlobPart::Content, 11, 1)).await;
+        assert!(matches!(past_end, BlobResponse::Err { .. }), "{past_end:?}");
+
+        let missing = BlobId::from_content(b"not stored");
+        let response = handler(peer, request(missing.as_str(), BlobPart::Content, 0, 1)).await;
+        assert_eq!(response, BlobResponse::NotFound);
+        let response = handler(peer, request("../../etc/passwd", BlobPart::Content, 0, 1)).await;
+        assert_eq!(response, BlobResponse::NotFound);
+    }
+}
@@ -15,32 +15,77 @@
 //!   - `GET /blobs/:prefix/:filename` — fetch a blob by its SHA-256. The
 //!     filename is `<full_hex>.bin`; the prefix is the first two hex chars.
 //!     Same Range support as the channel/arch path.
+//!   - `GET /blobs/:prefix/<hex>.outboard` — the blob's per-chunk
+//!     [`crate::Outboard`], with its root in `X-Outboard-Root`. Lets
+//!     clients verify each Range response as it arrives.
+//!
+//! Writes (signed by an operator key, see [`crate::auth`]):
+//! * `PUT /blobs` — stream the body into the blob layout; responds with the
+//!   resulting [`BlobId`], which must equal the signed
+//!   `x-phase-content-sha256`.
+//! * `PUT /:channel/:arch/:artifact` — replace a channel/arch artifact. The
+//!   body's hash must be signed.
+//! * `DELETE /blobs/:prefix/:filename` and `DELETE /:channel/:arch/:artifact`.
+//!
+//! With a [`BlobAnnouncer`] installed, uploaded blobs are announced on the
+//! DHT and deleted ones withdrawn.
+//!
+//! Uploads go through the same hashing ingest as
+//! [`ArtifactServer::ingest_blob`], so nothing reaches a served path
+//! without its hash being checked.
 //!
 //! Health / status / info endpoints are also exposed:
 //! * `GET /` — server info JSON.
 //! * `GET /health` — health probe (200 / 503).
-//! * `GET /status` — metrics + health.
+//! * `GET /status` — metrics, health, and disk use per bucket (see
+//!   [`crate::gc`]).
+//! * `GET /metrics` — the same and more in the Prometheus text format:
+//!   requests and latency per route, Range requests, bytes served per
+//!   bucket, hash cache hits (see [`crate::metrics`]).
+//!
+//! Artifact, blob and manifest responses carry strong `ETag`s from their
+//! content hashes and answer `If-None-Match` with `304`; blobs are marked
+//! immutable, and manifests and compressible artifacts are sent in zstd or
+//! gzip when the client accepts it (see [`crate::conditional`]).
+//!
+//! With [`ArtifactServerConfig::tls`] set, every route is served over
+//! HTTPS instead, as HTTP/2 or HTTP/1.1 (see [`crate::tls`]).
 
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{header, HeaderMap, StatusCode},
+    extract::{MatchedPath, Path, Request, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::get,
+    routing::{get, put},
     Json, Router,
 };
+use futures_util::TryStreamExt;
 use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::future::{Future, IntoFuture};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::fs::File;
-use tokio_util::io::ReaderStream;
+use tokio::sync::Notify;
+use tokio_util::io::{ReaderStream, StreamReader};
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn};
 
-use crate::artifacts::{ArtifactMeta, ArtifactStore, BlobId};
+use crate::announce::BlobAnnouncer;
+use crate::artifacts::{
+    ArtifactMeta, ArtifactStore, BlobId, HashMismatch, IngestedBlob, InvalidChannelPath,
+};
+use crate::auth::{AuthError, WriteGate};
+use crate::conditional::{
+    compression_layer, etag, looks_compressed, tag_coded_bodies, Compressible, Validators,
+    CACHE_IMMUTABLE, CACHE_REVALIDATE,
+};
 use crate::config::ArtifactServerConfig;
-use crate::metrics::{perform_health_check, ProviderMetrics};
+use crate::gc::{measure_usage, run_gc_loop, UsageCache};
+use crate::metrics::{perform_health_check, render_store_metrics, ProviderMetrics, ServedBucket};
+use crate::tls::{serve_tls, ReloadingCert};
 
 /// Pluggable manifest provider. The daemon side of the artifact-server crate
 /// implements this with its `BootManifest`-specific `ManifestGenerator`;
@@ -101,6 +146,15 @@ pub struct ArtifactServer {
     start_time: Instant,
     artifact_store: Arc<ArtifactStore>,
     metrics: Arc<ProviderMetrics>,
+    write_gate: Arc<WriteGate>,
+    /// Wakes the garbage collector early, after an upload.
+    gc_trigger: Arc<Notify>,
+    /// Disk use for `GET /metrics`, refreshed by the garbage collector.
+    usage: Arc<UsageCache>,
+    /// Announces held blobs on the DHT, when installed.
+    announcer: Option<Arc<BlobAnnouncer>>,
+    /// Certificate to terminate TLS with, when configured.
+    tls: Option<Arc<ReloadingCert>>,
     manifest_provider: Option<Arc<dyn ManifestProvider>>,
     info_name: String,
     info_version: String,
@@ -108,14 +162,28 @@ pub struct ArtifactServer {
 
 impl ArtifactServer {
     /// Construct a server with the given configuration. Creates the
-    /// artifacts directory if it does not already exist.
+    /// artifacts directory if it does not already exist, and loads the TLS
+    /// certificate if one is configured.
     pub fn new(config: ArtifactServerConfig) -> anyhow::Result<Self> {
         let artifact_store = Arc::new(ArtifactStore::new(config.artifacts_dir.clone())?);
+        let tls = match &config.tls {
+            Some(tls) => Some(Arc::new(ReloadingCert::load(tls.clone())?)),
+            None => None,
+        };
+        let write_gate = Arc::new(WriteGate::new(
+            config.operators.clone(),
+            config.operator_successions.clone(),
+        ));
         Ok(Self {
             config,
             start_time: Instant::now(),
             artifact_store,
             metrics: Arc::new(ProviderMetrics::new()),
+            write_gate,
+            gc_trigger: Arc::new(Notify::new()),
+            usage: Arc::new(UsageCache::default()),
+            announcer: None,
+            tls,
             manifest_provider: None,
             info_name: "phase-artifact-server".to_string(),
             info_version: env!("CARGO_PKG_VERSION").to_string(),
@@ -141,6 +209,14 @@ impl ArtifactServer {
         self
     }
 
+    /// Install a [`BlobAnnouncer`] built over [`Self::store`]. The server
+    /// then announces its blobs on the DHT and keeps the records fresh and
+    /// current; see [`crate::announce`].
+    pub fn with_announcer(mut self, announcer: BlobAnnouncer) -> Self {
+        self.announcer = Some(Arc::new(announcer));
+        self
+    }
+
     /// Bound port from the config.
     pub fn config(&self) -> &ArtifactServerConfig {
         &self.config
@@ -158,6 +234,12 @@ impl ArtifactServer {
         &self.metrics
     }
 
+    /// The TLS certificate, when the server speaks HTTPS. Its
+    /// [`ReloadingCert::spki_sha256`] is the pin to publish.
+    pub fn tls(&self) -> Option<&Arc<ReloadingCert>> {
+        self.tls.as_ref()
+    }
+
     /// Write `content` as a content-addressed blob. Returns its [`BlobId`]
     /// (the SHA-256 hex of the content).
     pub async fn add_blob(&self, content: &[u8]) -> anyhow::Result<BlobId> {
@@ -185,8 +267,31 @@ impl ArtifactServer {
         .await?
     }
 
+    /// Stream a content-addressed blob from `reader` without buffering it.
+    /// See [`ArtifactStore::ingest_blob`].
+    pub async fn ingest_blob<R>(
+        &self,
+        reader: R,
+        expected: Option<&BlobId>,
+    ) -> anyhow::Result<IngestedBlob>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        self.artifact_store.ingest_blob(reader, expected).await
+    }
+
+    /// Stream a content-addressed blob from the file at `path`.
+    pub async fn ingest_blob_file(
+        &self,
+        path: &std::path::Path,
+        expected: Option<&BlobId>,
+    ) -> anyhow::Result<IngestedBlob> {
+        self.artifact_store.ingest_blob_file(path, expected).await
+    }
+
     /// Build the axum router. Public so callers that already own a
-    /// `tokio::net::TcpListener` can drive `axum::serve` themselves.
+    /// `tokio::net::TcpListener` can drive `axum::serve` themselves; that
+    /// path serves plain HTTP whatever the config says.
     pub fn build_router(&self) -> Router {
         let state = AppState {
             info_name: self.info_name.clone(),
@@ -194,6 +299,11 @@ impl ArtifactServer {
             start_time: self.start_time,
             artifact_store: Arc::clone(&self.artifact_store),
             metrics: Arc::clone(&self.metrics),
+            write_gate: Arc::clone(&self.write_gate),
+            gc_trigger: Arc::clone(&self.gc_trigger),
+            usage: Arc::clone(&self.usage),
+            announcer: self.announcer.clone(),
+            blob_quota_bytes: self.config.blob_quota_bytes,
             manifest_provider: self.manifest_provider.clone(),
             artifacts_dir: self.config.artifacts_dir.clone(),
             default_channel: self
@@ -212,10 +322,26 @@ impl ArtifactServer {
             .route("/", get(info_handler))
             .route("/health", get(health_handler))
             .route("/status", get(status_handler))
+            .route("/metrics", get(metrics_handler))
             .route("/manifest.json", get(default_manifest_handler))
-            .route("/blobs/:prefix/:filename", get(blob_handler))
+            .route("/blobs", put(put_blob_handler))
+            .route(
+                "/blobs/:prefix/:filename",
+                get(blob_handler).delete(delete_blob_handler),
+            )
             .route("/:channel/:arch/manifest.json", get(manifest_handler))
-            .route("/:channel/:arch/:artifact", get(artifact_handler))
+            .route(
+                "/:channel/:arch/:artifact",
+                get(artifact_handler)
+                    .put(put_artifact_handler)
+                    .delete(delete_artifact_handler),
+            )
+            .layer(compression_layer())
+            .layer(middleware::from_fn(tag_coded_bodies))
+            .layer(middleware::from_fn_with_state(
+                Arc::clone(&self.metrics),
+                track_request,
+            ))
             .layer(TraceLayer::new_for_http())
             .with_state(Arc::new(state))
     }
@@ -225,11 +351,20 @@ impl ArtifactServer {
     /// `local_addr()` reflects the bound port (useful when `addr.port() == 0`).
     pub async fn serve_on(self, addr: SocketAddr) -> anyhow::Result<ServerHandle> {
         let router = self.build_router();
+        let maintenance = self.maintenance();
         let listener = tokio::net::TcpListener::bind(addr).await?;
         let actual = listener.local_addr()?;
         info!("phase-artifact-server listening on {}", actual);
+        let tls = self.tls.clone();
         let task = tokio::spawn(async move {
-            let _ = axum::serve(listener, router).await;
+            tokio::select! {
+                served = serve(listener, router, tls) => {
+                    if let Err(e) = served {
+                        warn!("Artifact server stopped: {:#}", e);
+                    }
+                }
+                _ = maintenance => {}
+            }
         });
         Ok(ServerHandle { addr: actual, task })
     }
@@ -243,13 +378,69 @@ impl ArtifactServer {
         info!("Serving artifacts from: {:?}", self.config.artifacts_dir);
 
         let router = self.build_router();
+        let maintenance = self.maintenance();
 
         let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-        info!("Provider server listening on {}", bind_addr);
+        info!(
+            "Provider server listening on {} ({})",
+            bind_addr,
+            if self.tls.is_some() { "https" } else { "http" }
+        );
 
-        axum::serve(listener, router).await?;
+        tokio::select! {
+            served = serve(listener, router, self.tls.clone()) => served?,
+            _ = maintenance => {}
+        }
         Ok(())
     }
+
+    /// Background upkeep that runs next to the listener: garbage
+    /// collection under the blob quota, when one is configured, DHT
+    /// re-announcement, when an announcer is installed, and certificate
+    /// reloads, when serving TLS.
+    fn maintenance(&self) -> impl Future<Output = ()> + Send + 'static {
+        let store = Arc::clone(&self.artifact_store);
+        let trigger = Arc::clone(&self.gc_trigger);
+        let usage = Arc::clone(&self.usage);
+        let quota = self.config.blob_quota_bytes;
+        let announcer = self.announcer.clone();
+        let gc = {
+            let announcer = announcer.clone();
+            async move {
+                match quota {
+                    Some(quota) => {
+                        info!("Blob quota: {} bytes", quota);
+                        run_gc_loop(store, quota, trigger, announcer, usage).await
+                    }
+                    None => std::future::pending().await,
+                }
+            }
+        };
+        let announce = async move {
+            match announcer {
+                Some(announcer) => announcer.run_refresh_loop().await,
+                None => std::future::pending().await,
+            }
+        };
+        let tls = self.tls.clone();
+        let reload = async move {
+            match tls {
+                Some(tls) => tls.run_reload_loop().await,
+                None => std::future::pending().await,
+            }
+        };
+        async move {
+            tokio::join!(gc, announce, reload);
+        }
+    }
+}
+
+/// Serve `router` on `listener`, over TLS when a certificate is given.
+async fn serve(listener: tokio::net::TcpListener, router: Router, tls: Option<Arc<ReloadingCert>>) -> anyhow::Result<()> {
+    match tls {
+        Some(cert) => serve_tls(listener, router, cert).await,
+        None => Ok(axum::serve(listener, router).into_future().await?),
+    }
 }
 
 /// Shared application state for axum handlers.
@@ -260,6 +451,11 @@ struct AppState {
     start_time: Instant,
     artifact_store: Arc<ArtifactStore>,
     metrics: Arc<ProviderMetrics>,
+    write_gate: Arc<WriteGate>,
+    gc_trigger: Arc<Notify>,
+    usage: Arc<UsageCache>,
+    announcer: Option<Arc<BlobAnnouncer>>,
+    blob_quota_bytes: Option<u64>,
     manifest_provider: Option<Arc<dyn ManifestProvider>>,
     artifacts_dir: std::path::PathBuf,
     default_channel: Option<String>,
@@ -304,6 +500,17 @@ async fn status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
     let uptime = state.start_time.elapsed().as_secs();
     let metrics = state.metrics.snapshot();
     let health = perform_health_check(&state.artifacts_dir);
+    let storage = match measure_usage(&state.artifact_store).await {
+        Some(usage) => json!({
+            "buckets": usage.buckets,
+            "blobs": usage.blobs,
+            "blob_bytes": usage.blob_bytes,
+            "pinned_blobs": usage.pinned_blobs,
+            "pinned_bytes": usage.pinned_bytes,
+            "quota_bytes": state.blob_quota_bytes,
+        }),
+        None => serde_json::Value::Null,
+    };
 
     let status = json!({
         "name": state.info_name,
@@ -320,11 +527,44 @@ async fn status_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse
             "requests_total": metrics.requests_total,
             "bytes_served_total": metrics.bytes_served_total,
         },
+        "storage": storage,
     });
 
     (StatusCode::OK, Json(status))
 }
 
+/// `GET /metrics` — the Prometheus text format; see [`crate::metrics`].
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut body = state.metrics.render_prometheus();
+    if let Some(usage) = state.usage.get(&state.artifact_store).await {
+        render_store_metrics(
+            &mut body,
+            &usage,
+            state.blob_quota_bytes,
+            state.artifact_store.hash_cache_stats(),
+        );
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        body,
+    )
+}
+
+/// Middleware recording every request against its matched route.
+async fn track_request(
+    State(metrics): State<Arc<ProviderMetrics>>,
+    matched: Option<MatchedPath>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = matched.map_or_else(|| "unmatched".to_string(), |m| m.as_str().to_string());
+    let method = request.method().clone();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    metrics.record_request(&route, method.as_str(), response.status().as_u16(), started.elapsed());
+    response
+}
+
 /// Parse HTTP Range header, returns (start, end) inclusive. Supports
 /// `"bytes=0-1023"` and `"bytes=1024-"`.
 fn parse_range(header: &str, file_size: u64) -> Option<(u64, u64)> {
@@ -353,18 +593,31 @@ fn parse_range(header: &str, file_size: u64) -> Option<(u64, u64)> {
     }
 }
 
-/// Stream an [`ArtifactMeta`] back as an HTTP response. Honours `Range:` and
-/// preserves the byte-identical headers from the pre-extraction server.
+/// Stream an [`ArtifactMeta`] back as an HTTP response. Honours `Range:`
+/// and preserves the byte-identical headers from the pre-extraction server,
+/// adding the validators and caching policy of [`crate::conditional`].
 async fn serve_artifact_with_range(
     meta: ArtifactMeta,
     headers: HeaderMap,
     metrics: &ProviderMetrics,
+    bucket: ServedBucket,
     content_type: &'static str,
 ) -> Result<Response, (StatusCode, &'static str)> {
     let file_size = meta.size_bytes;
-    let range_header = headers.get(header::RANGE);
+    let validators = match bucket {
+        ServedBucket::Blobs => Validators::new(etag(&meta.hash), CACHE_IMMUTABLE),
+        ServedBucket::Channel => Validators::new(etag(&meta.hash), CACHE_REVALIDATE)
+            .with_last_modified(tokio::fs::metadata(&meta.path).await.and_then(|m| m.modified()).ok()),
+    };
+    if validators.client_is_current(&headers) {
+        return Ok(validators.not_modified());
+    }
+    let range_header = headers
+        .get(header::RANGE)
+        .filter(|_| validators.range_allowed(&headers));
 
     if let Some(range_value) = range_header {
+        metrics.increment_range_requests(bucket);
         let range_str = match range_value.to_str() {
             Ok(s) => s,
             Err(_) => return Err((StatusCode::BAD_REQUEST, "Invalid Range header")),
@@ -405,7 +658,7 @@ async fn serve_artifact_with_range(
                 let stream = ReaderStream::new(limited);
                 let body = Body::from_stream(stream);
 
-                metrics.add_bytes_served(content_length);
+                metrics.add_bytes_served(bucket, content_length);
 
                 use axum::http::header::{HeaderName, HeaderValue};
                 let mut response_headers = HeaderMap::new();
@@ -426,6 +679,7 @@ async fn serve_artifact_with_range(
                     HeaderName::from_static("x-artifact-hash"),
                     HeaderValue::from_str(&meta.hash).unwrap(),
                 );
+                validators.apply(&mut response_headers);
 
                 let mut response = Response::new(body);
                 *response.status_mut() = StatusCode::PARTIAL_CONTENT;
@@ -450,7 +704,7 @@ async fn serve_artifact_with_range(
             }
         }
     } else {
-        let file = match File::open(&meta.path).await {
+        let mut file = match File::open(&meta.path).await {
             Ok(f) => f,
             Err(e) => {
                 warn!("Error opening artifact file {:?}: {}", meta.path, e);
@@ -461,10 +715,28 @@ async fn serve_artifact_with_range(
             }
         };
 
+        // Peek at the signature to skip compressing what already is.
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+        let mut magic = [0u8; 8];
+        let peeked = match file.read(&mut magic).await {
+            Ok(n) => file.rewind().await.map(|_| n),
+            Err(e) => Err(e),
+        };
+        let peeked = match peeked {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Error reading artifact file {:?}: {}", meta.path, e);
+                return Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to read artifact",
+                ));
+            }
+        };
+
         let stream = ReaderStream::new(file);
         let body = Body::from_stream(stream);
 
-        metrics.add_bytes_served(meta.size_bytes);
+        metrics.add_bytes_served(bucket, meta.size_bytes);
 
         use axum::http::header::{HeaderName, HeaderValue};
         let mut response_headers = HeaderMap::new();
@@ -479,9 +751,13 @@ async fn serve_artifact_with_range(
             HeaderName::from_static("x-artifact-hash"),
             HeaderValue::from_str(&meta.hash).unwrap(),
         );
+        validators.apply(&mut response_headers);
 
         let mut response = Response::new(body);
         *response.headers_mut() = response_headers;
+        if !looks_compressed(&magic[..peeked]) {
+            response.extensions_mut().insert(Compressible);
+        }
         Ok(response)
     }
 }
@@ -507,7 +783,14 @@ async fn artifact_handler(
         }
     };
 
-    serve_artifact_with_range(meta, headers, &state.metrics, "application/octet-stream").await
+    serve_artifact_with_range(
+        meta,
+        headers,
+        &state.metrics,
+        ServedBucket::Channel,
+        "application/octet-stream",
+    )
+    .await
 }
 
 /// `GET /blobs/:prefix/:filename`. The filename must be `<hex>.bin` where
@@ -522,6 +805,10 @@ async fn blob_handler(
     info!("Blob request: /blobs/{}/{}", prefix, filename);
     state.metrics.increment_requests();
 
+    if let Some(hex) = filename.strip_suffix(".outboard") {
+        return outboard_response(&state, &prefix, hex, &headers).await;
+    }
+
     let hex = match filename.strip_suffix(".bin") {
         Some(h) => h,
         None => return Err((StatusCode::NOT_FOUND, "Blob not found")),
@@ -544,14 +831,302 @@ async fn blob_handler(
             return Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"));
         }
     };
+    state.artifact_store.touch_blob(&blob_id);
+
+    serve_artifact_with_range(
+        meta,
+        headers,
+        &state.metrics,
+        ServedBucket::Blobs,
+        "application/octet-stream",
+    )
+    .await
+}
+
+/// Authenticate a write, mapping refusals onto HTTP statuses: 403 when the
+/// server takes no writes or the key isn't an operator, 400 when an upload
+/// didn't sign its content hash, 401 otherwise.
+fn authorize_write(
+    state: &AppState,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+) -> Result<crate::auth::RequestSignature, (StatusCode, String)> {
+    state
+        .write_gate
+        .authorize(method.as_str(), uri.path(), headers)
+        .map_err(|e| {
+            warn!("Refused {} {}: {}", method, uri.path(), e);
+            let status = match e {
+                AuthError::WritesDisabled | AuthError::NotOperator(_) => StatusCode::FORBIDDEN,
+                AuthError::ContentHashRequired => StatusCode::BAD_REQUEST,
+                _ => StatusCode::UNAUTHORIZED,
+            };
+            (status, e.to_string())
+        })
+}
 
-    serve_artifact_with_range(meta, headers, &state.metrics, "application/octet-stream").await
+/// Map a store error from an upload or delete: hash mismatches and bad
+/// path components are the client's fault, anything else is ours.
+fn write_error(what: &str, e: anyhow::Error) -> (StatusCode, String) {
+    if let Some(mismatch) = e.downcast_ref::<HashMismatch>() {
+        warn!("Rejected {}: {}", what, mismatch);
+        return (StatusCode::BAD_REQUEST, mismatch.to_string());
+    }
+    if let Some(invalid) = e.downcast_ref::<InvalidChannelPath>() {
+        return (StatusCode::BAD_REQUEST, invalid.to_string());
+    }
+    warn!("Error writing {}: {:#}", what, e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal server error".to_string(),
+    )
+}
+
+/// Read a request body as an `AsyncRead` without buffering it.
+fn body_reader(body: Body) -> impl tokio::io::AsyncRead + Unpin {
+    StreamReader::new(body.into_data_stream().map_err(std::io::Error::other))
+}
+
+/// `PUT /blobs`. Responds 201 with the blob's id and path, or 200 if the
+/// blob was already stored.
+async fn put_blob_handler(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, (StatusCode, String)> {
+    state.metrics.increment_requests();
+    let signed = authorize_write(&state, &method, &uri, &headers)?;
+
+    let blob = state
+        .artifact_store
+        .ingest_blob(body_reader(body), signed.content_hash.as_ref())
+        .await
+        .map_err(|e| write_error("blob upload", e))?;
+    info!(
+        "Blob {} uploaded by {} ({} bytes{})",
+        blob.id,
+        hex::encode(signed.public_key.as_bytes()),
+        blob.size_bytes,
+        if blob.deduplicated { ", already stored" } else { "" }
+    );
+
+    let status = if blob.deduplicated {
+        state.artifact_store.touch_blob(&blob.id);
+        StatusCode::OK
+    } else {
+        state.gc_trigger.notify_one();
+        if let Some(announcer) = state.announcer.clone() {
+            let id = blob.id.clone();
+            tokio::spawn(async move {
+                if let Err(e) = announcer.announce(&id).await {
+                    warn!("Failed to announce blob {}: {:#}", id, e);
+                }
+            });
+        }
+        StatusCode::CREATED
+    };
+    let body = json!({
+        "blob_id": blob.id.as_str(),
+        "path": format!("/blobs/{}/{}.bin", blob.id.prefix(), blob.id.as_str()),
+        "size_bytes": blob.size_bytes,
+        "deduplicated": blob.deduplicated,
+    });
+    Ok((status, Json(body)).into_response())
+}
+
+/// `PUT /:channel/:arch/:artifact`. Replaces the artifact atomically once
+/// the body has hashed to the signed `x-phase-content-sha256`.
+async fn put_artifact_handler(
+    State(state): State<Arc<AppState>>,
+    Path((channel, arch, artifact)): Path<(String, String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, (StatusCode, String)> {
+    state.metrics.increment_requests();
+    let signed = authorize_write(&state, &method, &uri, &headers)?;
+
+    let what = format!("{}/{}/{}", channel, arch, artifact);
+    let meta = state
+        .artifact_store
+        .ingest_channel_artifact(
+            &channel,
+            &arch,
+            &artifact,
+            body_reader(body),
+            signed.content_hash.as_ref(),
+        )
+        .await
+        .map_err(|e| write_error(&what, e))?;
+    info!(
+        "Artifact {} uploaded by {} ({} bytes)",
+        what,
+        hex::encode(signed.public_key.as_bytes()),
+        meta.size_bytes
+    );
+
+    let body = json!({
+        "name": meta.name,
+        "size_bytes": meta.size_bytes,
+        "hash": meta.hash,
+    });
+    Ok((StatusCode::OK, Json(body)).into_response())
+}
+
+/// `DELETE /blobs/:prefix/:filename`. 204 once removed, 404 if absent.
+async fn delete_blob_handler(
+    State(state): State<Arc<AppState>>,
+    Path((prefix, filename)): Path<(String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state.metrics.increment_requests();
+    let signed = authorize_write(&state, &method, &uri, &headers)?;
+
+    let not_found = || (StatusCode::NOT_FOUND, "Blob not found".to_string());
+    let blob_id = filename
+        .strip_suffix(".bin")
+        .and_then(BlobId::from_hex)
+        .filter(|id| id.prefix() == prefix)
+        .ok_or_else(not_found)?;
+    let removed = state
+        .artifact_store
+        .remove_blob(&blob_id)
+        .map_err(|e| write_error("blob delete", e))?;
+    if !removed {
+        return Err(not_found());
+    }
+    if let Some(announcer) = state.announcer.clone() {
+        let id = blob_id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = announcer.withdraw(&id).await {
+                warn!("Failed to withdraw blob {}: {:#}", id, e);
+            }
+        });
+    }
+    info!(
+        "Blob {} deleted by {}",
+        blob_id,
+        hex::encode(signed.public_key.as_bytes())
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `DELETE /:channel/:arch/:artifact`. Removes exactly the named file;
+/// 204 once removed, 404 if absent.
+async fn delete_artifact_handler(
+    State(state): State<Arc<AppState>>,
+    Path((channel, arch, artifact)): Path<(String, String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state.metrics.increment_requests();
+    let signed = authorize_write(&state, &method, &uri, &headers)?;
+
+    let what = format!("{}/{}/{}", channel, arch, artifact);
+    let removed = state
+        .artifact_store
+        .remove_channel_artifact(&channel, &arch, &artifact)
+        .map_err(|e| write_error(&what, e))?;
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, "Artifact not found".to_string()));
+    }
+    info!(
+        "Artifact {} deleted by {}",
+        what,
+        hex::encode(signed.public_key.as_bytes())
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Body of `GET /blobs/:prefix/<hex>.outboard`. Blobs stored before
+/// outboards existed get one computed on first request.
+async fn outboard_response(
+    state: &AppState,
+    prefix: &str,
+    hex: &str,
+    request_headers: &HeaderMap,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let blob_id = match BlobId::from_hex(hex) {
+        Some(id) if id.prefix() == prefix => id,
+        _ => return Err((StatusCode::NOT_FOUND, "Blob not found")),
+    };
+
+    let store = Arc::clone(&state.artifact_store);
+    let lookup = blob_id.clone();
+    let outboard = tokio::task::spawn_blocking(move || store.get_outboard(&lookup))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|r| r);
+    let outboard = match outboard {
+        Ok(Some(o)) => o,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, "Blob not found")),
+        Err(e) => {
+            warn!("Error building outboard for blob {}: {:#}", blob_id, e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"));
+        }
+    };
+
+    let root = outboard.root().to_hex();
+    let validators = Validators::new(etag(&format!("blake3:{}", root)), CACHE_IMMUTABLE);
+    if validators.client_is_current(request_headers) {
+        return Ok(validators.not_modified());
+    }
+
+    let bytes = outboard.to_bytes();
+    state.metrics.add_bytes_served(ServedBucket::Blobs, bytes.len() as u64);
+
+    use axum::http::header::{HeaderName, HeaderValue};
+    let mut response = Response::new(Body::from(bytes));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    headers.insert(
+        HeaderName::from_static("x-outboard-root"),
+        HeaderValue::from_str(&root).unwrap(),
+    );
+    validators.apply(headers);
+    Ok(response)
+}
+
+/// A manifest as JSON, tagged with the SHA-256 of its body. `304` when the
+/// client already holds those exact bytes.
+fn manifest_response(manifest: &serde_json::Value, headers: &HeaderMap) -> Response {
+    let body = match serde_json::to_vec(manifest) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Error serializing manifest: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to generate manifest").into_response();
+        }
+    };
+    let hash = format!("sha256:{}", hex::encode(Sha256::digest(&body)));
+    let validators = Validators::new(etag(&hash), CACHE_REVALIDATE);
+    if validators.client_is_current(headers) {
+        return validators.not_modified();
+    }
+    let mut response = Response::new(Body::from(body));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/json"),
+    );
+    validators.apply(response.headers_mut());
+    response.extensions_mut().insert(Compressible);
+    response
 }
 
 /// `GET /manifest.json`.
 async fn default_manifest_handler(
     State(state): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, &'static str)> {
     let provider = state
         .manifest_provider
         .as_ref()
@@ -569,14 +1144,15 @@ async fn default_manifest_handler(
             "Failed to generate manifest",
         )
     })?;
-    Ok((StatusCode::OK, Json(manifest)))
+    Ok(manifest_response(&manifest, &headers))
 }
 
 /// `GET /:channel/:arch/manifest.json`.
 async fn manifest_handler(
     State(state): State<Arc<AppState>>,
     Path((channel, arch)): Path<(String, String)>,
-) -> Result<impl IntoResponse, (StatusCode, &'static str)> {
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, &'static str)> {
     info!("Manifest request for {}/{}", channel, arch);
 
     let provider = state
@@ -598,7 +1174,7 @@ async fn manifest_handler(
             )
         }
     })?;
-    Ok((StatusCode::OK, Json(manifest)))
+    Ok(manifest_response(&manifest, &headers))
 }
 
 #[cfg(test)]
@@ -612,6 +1188,10 @@ mod tests {
             bind_addr: "127.0.0.1".to_string(),
             port: 8080,
             artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         };
         let server = ArtifactServer::new(config).unwrap();
         assert_eq!(server.config.port, 8080);
@@ -697,6 +1277,10 @@ mod tests {
             bind_addr: "127.0.0.1".to_string(),
             port: 0,
             artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         };
         let server = ArtifactServer::new(config).unwrap();
 
@@ -762,6 +1346,10 @@ mod tests {
             bind_addr: "127.0.0.1".to_string(),
             port: 0,
             artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         };
         let server = ArtifactServer::new(config).unwrap();
 
@@ -826,4 +1414,507 @@ mod tests {
 
         handle.abort();
     }
+
+    /// Send `method path` to `port`, signed by `operator` when given.
+    async fn write_request(
+        port: u16,
+        method: reqwest::Method,
+        path: &str,
+        operator: Option<&phase_identity::NodeIdentity>,
+        content_hash: Option<&BlobId>,
+        body: &'static [u8],
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .request(method.clone(), format!("http://127.0.0.1:{port}{path}"))
+            .body(body);
+        if let Some(operator) = operator {
+            let signed =
+                crate::auth::RequestSignature::sign(operator, method.as_str(), path, content_hash);
+            for (name, value) in signed.headers() {
+                request = request.header(name, value);
+            }
+        }
+        request.send().await.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn signed_uploads_and_deletes() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let operator = phase_identity::NodeIdentity::generate();
+        let stranger = phase_identity::NodeIdentity::generate();
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().to_path_buf(),
+            operators: vec![hex::encode(operator.verifying_key().as_bytes())],
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
+        };
+        let handle = ArtifactServer::new(config)
+            .unwrap()
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let port = handle.local_addr().port();
+        let put = reqwest::Method::PUT;
+        let delete = reqwest::Method::DELETE;
+
+        // Unsigned and non-operator writes are refused.
+        let resp = write_request(port, put.clone(), "/blobs", None, None, b"blob").await;
+        assert_eq!(resp.status().as_u16(), 401);
+        let resp =
+            write_request(port, put.clone(), "/blobs", Some(&stranger), None, b"blob").await;
+        assert_eq!(resp.status().as_u16(), 403);
+
+        // Blob uploads pin their content too, or a captured signature
+        // could carry any bytes within the replay window.
+        let resp =
+            write_request(port, put.clone(), "/blobs", Some(&operator), None, b"blob").await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        // Content-addressed upload returns the id; the blob is then served.
+        let id = BlobId::from_content(b"blob");
+        let resp =
+            write_request(port, put.clone(), "/blobs", Some(&operator), Some(&id), b"blob").await;
+        assert_eq!(resp.status().as_u16(), 201);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["blob_id"], id.as_str());
+        let blob_path = json["path"].as_str().unwrap().to_string();
+        let got = reqwest::get(format!("http://127.0.0.1:{port}{blob_path}"))
+            .await
+            .unwrap();
+        assert_eq!(got.bytes().await.unwrap().as_ref(), b"blob");
+
+        // A body that doesn't match the signed hash never lands.
+        let claimed = BlobId::from_content(b"kernel v2");
+        let resp = write_request(
+            port,
+            put.clone(),
+            "/stable/x86_64/kernel",
+            Some(&operator),
+            Some(&claimed),
+            b"rootkit",
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 400);
+        assert!(!temp.path().join("stable/x86_64/kernel").exists());
+
+        // Channel uploads must pin their content.
+        let resp = write_request(
+            port,
+            put.clone(),
+            "/stable/x86_64/kernel",
+            Some(&operator),
+            None,
+            b"kernel v2",
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 400);
+
+        let resp = write_request(
+            port,
+            put.clone(),
+            "/stable/x86_64/kernel",
+            Some(&operator),
+            Some(&claimed),
+            b"kernel v2",
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 200);
+        let got = reqwest::get(format!("http://127.0.0.1:{port}/stable/x86_64/kernel"))
+            .await
+            .unwrap();
+        assert_eq!(got.bytes().await.unwrap().as_ref(), b"kernel v2");
+
+        // Deletes.
+        let resp =
+            write_request(port, delete.clone(), &blob_path, Some(&operator), None, b"").await;
+        assert_eq!(resp.status().as_u16(), 204);
+        let resp =
+            write_request(port, delete.clone(), &blob_path, Some(&operator), None, b"").await;
+        assert_eq!(resp.status().as_u16(), 404);
+        let resp = write_request(
+            port,
+            delete.clone(),
+            "/stable/x86_64/kernel",
+            Some(&operator),
+            None,
+            b"",
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 204);
+        let got = reqwest::get(format!("http://127.0.0.1:{port}/stable/x86_64/kernel"))
+            .await
+            .unwrap();
+        assert_eq!(got.status().as_u16(), 404);
+
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn uploads_past_the_quota_evict_the_oldest_blob() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let operator = phase_identity::NodeIdentity::generate();
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().to_path_buf(),
+            operators: vec![hex::encode(operator.verifying_key().as_bytes())],
+            operator_successions: Vec::new(),
+            // Room for one small blob and its outboard, not two.
+            blob_quota_bytes: Some(100),
+            tls: None,
+        };
+        let server = ArtifactServer::new(config).unwrap();
+        let old = server.add_blob(b"old cached blob").await.unwrap();
+        let old_path = temp.path().join(old.relative_path());
+        std::fs::File::options()
+            .write(true)
+            .open(&old_path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(3600))
+            .unwrap();
+        let handle = server
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let port = handle.local_addr().port();
+
+        let resp = write_request(
+            port,
+            reqwest::Method::PUT,
+            "/blobs",
+            Some(&operator),
+            Some(&BlobId::from_content(b"new blob")),
+            b"new blob",
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 201);
+        for _ in 0..100 {
+            if !old_path.exists() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(!old_path.exists(), "least recently used blob was not evicted");
+
+        let status: serde_json::Value = reqwest::get(format!("http://127.0.0.1:{port}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(status["storage"]["blobs"], 1);
+        assert_eq!(status["storage"]["quota_bytes"], 100);
+        assert_eq!(status["storage"]["buckets"][0]["name"], "blobs");
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn writes_are_disabled_without_operators() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
+        };
+        let handle = ArtifactServer::new(config)
+            .unwrap()
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let operator = phase_identity::NodeIdentity::generate();
+        let resp = write_request(
+            handle.local_addr().port(),
+            reqwest::Method::PUT,
+            "/blobs",
+            Some(&operator),
+            None,
+            b"blob",
+        )
+        .await;
+        assert_eq!(resp.status().as_u16(), 403);
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn metrics_are_exposed_for_prometheus() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
+        };
+        let server = ArtifactServer::new(config).unwrap();
+        let id = server.add_blob(b"metered blob").await.unwrap();
+        // Written behind the store's back, so its hash isn't cached yet.
+        std::fs::create_dir_all(temp.path().join("stable/x86_64")).unwrap();
+        std::fs::write(temp.path().join("stable/x86_64/kernel"), b"kernel").unwrap();
+        let store = Arc::clone(server.store());
+        let handle = server
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let base = format!("http://127.0.0.1:{}", handle.local_addr().port());
+        let client = reqwest::Client::new();
+
+        let blob_url = format!("{base}/blobs/{}/{}.bin", id.prefix(), id.as_str());
+        let resp = client.get(&blob_url).header("Range", "bytes=0-4").send().await.unwrap();
+        assert_eq!(resp.status().as_u16(), 206);
+        resp.bytes().await.unwrap();
+        for _ in 0..2 {
+            let resp = client.get(format!("{base}/stable/x86_64/kernel")).send().await.unwrap();
+            assert_eq!(resp.bytes().await.unwrap().as_ref(), b"kernel");
+        }
+        let resp = client.get(format!("{base}/no/such/file")).send().await.unwrap();
+        assert_eq!(resp.status().as_u16(), 404);
+        assert_eq!(
+            store.hash_cache_stats(),
+            crate::artifacts::HashCacheStats { hits: 1, misses: 1 }
+        );
+
+        let resp = client.get(format!("{base}/metrics")).send().await.unwrap();
+        assert!(resp.headers()[header::CONTENT_TYPE.as_str()]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain; version=0.0.4"));
+        let text = resp.text().await.unwrap();
+        let has = |line: &str| text.lines().any(|l| l == line);
+        assert!(has(r#"phase_artifact_http_requests_total{route="/blobs/:prefix/:filename",method="GET",status="206"} 1"#), "{text}");
+        assert!(has(r#"phase_artifact_http_requests_total{route="/:channel/:arch/:artifact",method="GET",status="200"} 2"#), "{text}");
+        assert!(has(r#"phase_artifact_http_requests_total{route="/:channel/:arch/:artifact",method="GET",status="404"} 1"#), "{text}");
+        assert!(has(r#"phase_artifact_http_request_duration_seconds_count{route="/:channel/:arch/:artifact"} 3"#), "{text}");
+        assert!(has(r#"phase_artifact_range_requests_total{bucket="blobs"} 1"#), "{text}");
+        assert!(has(r#"phase_artifact_range_requests_total{bucket="channel"} 0"#), "{text}");
+        assert!(has(r#"phase_artifact_bytes_served_total{bucket="blobs"} 5"#), "{text}");
+        assert!(has(r#"phase_artifact_bytes_served_total{bucket="channel"} 12"#), "{text}");
+        assert!(has("phase_artifact_blobs 1"), "{text}");
+        assert!(has(r#"phase_artifact_disk_files{bucket="stable"} 1"#), "{text}");
+        assert!(has("phase_artifact_hash_cache_misses_total 1"), "{text}");
+
+        // Disk use is cached between collections, not measured per scrape.
+        store.add_blob(b"another blob").unwrap();
+        let text = client.get(format!("{base}/metrics")).send().await.unwrap().text().await.unwrap();
+        assert!(text.lines().any(|l| l == "phase_artifact_blobs 1"), "{text}");
+        handle.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn tls_serves_http2_to_pinned_clients() {
+        use crate::config::TlsConfig;
+        use crate::tls::{pinned_http_client, SelfSignedCert};
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let tls = TlsConfig {
+            cert_path: temp.path().join("cert.pem"),
+            key_path: temp.path().join("key.pem"),
+        };
+        let identity = phase_identity::NodeIdentity::generate();
+        SelfSignedCert::generate(&identity, vec!["127.0.0.1".to_string()])
+            .unwrap()
+            .write(&tls)
+            .unwrap();
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().join("artifacts"),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: Some(tls.clone()),
+        };
+        let server = ArtifactServer::new(config).unwrap();
+        let id = server.add_blob(b"over tls").await.unwrap();
+        let cert = Arc::clone(server.tls().unwrap());
+        let pin = cert.spki_sha256();
+        let handle = server
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let url = format!(
+            "https://127.0.0.1:{}/blobs/{}/{}.bin",
+            handle.local_addr().port(),
+            &id.as_str()[..2],
+            id.as_str()
+        );
+
+        let resp = pinned_http_client(&pin).unwrap().get(&url).send().await.unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(resp.version(), reqwest::Version::HTTP_2);
+        assert_eq!(resp.bytes().await.unwrap().as_ref(), b"over tls");
+
+        // Without the pin there is no CA to fall back on.
+        let stranger = SelfSignedCert::generate(&phase_identity::NodeIdentity::generate(), vec![]).unwrap();
+        assert!(pinned_http_client(&stranger_pin(&stranger)).unwrap().get(&url).send().await.is_err());
+        assert!(reqwest::get(&url).await.is_err());
+
+        // A replaced certificate is served once reloaded.
+        stranger.write(&tls).unwrap();
+        assert!(cert.reload_if_changed().unwrap());
+        assert_eq!(cert.spki_sha256(), stranger_pin(&stranger));
+        assert!(pinned_http_client(&pin).unwrap().get(&url).send().await.is_err());
+        let resp = pinned_http_client(&cert.spki_sha256())
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        handle.abort();
+
+        fn stranger_pin(cert: &SelfSignedCert) -> String {
+            use rustls::pki_types::{pem::PemObject, CertificateDer};
+            let der = CertificateDer::from_pem_slice(cert.cert_pem.as_bytes()).unwrap();
+            crate::tls::spki_sha256(&der).unwrap()
+        }
+    }
+
+    #[derive(Debug)]
+    struct FixedManifest;
+
+    impl ManifestProvider for FixedManifest {
+        fn manifest(&self, channel: &str, arch: &str) -> Result<serde_json::Value, anyhow::Error> {
+            Ok(json!({ "channel": channel, "arch": arch, "artifacts": { "kernel": "k".repeat(200) } }))
+        }
+
+        fn defaults(&self) -> Option<(String, String)> {
+            Some(("stable".to_string(), "x86_64".to_string()))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn conditional_requests_and_content_coding() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = ArtifactServerConfig {
+            bind_addr: "127.0.0.1".to_string(),
+            port: 0,
+            artifacts_dir: temp.path().to_path_buf(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
+        };
+        let server = ArtifactServer::new(config)
+            .unwrap()
+            .with_manifest_provider(Arc::new(FixedManifest));
+        let text = "phase ".repeat(1000);
+        let id = server.add_blob(text.as_bytes()).await.unwrap();
+        server
+            .add_channel_artifact("stable", "x86_64", "initramfs", text.as_bytes())
+            .await
+            .unwrap();
+        let mut gzipped = b"\x1f\x8b\x08\x00".to_vec();
+        gzipped.extend_from_slice(text.as_bytes());
+        server
+            .add_channel_artifact("stable", "x86_64", "kernel", &gzipped)
+            .await
+            .unwrap();
+        let handle = server
+            .serve_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let base = format!("http://127.0.0.1:{}", handle.local_addr().port());
+        let client = reqwest::Client::new();
+        let get = |path: &str, headers: &[(&str, &str)]| {
+            let mut request = client.get(format!("{base}{path}"));
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            request.send()
+        };
+
+        // Blobs are immutable and revalidate by content hash.
+        let blob = format!("/blobs/{}/{}.bin", id.prefix(), id.as_str());
+        let resp = get(&blob, &[]).await.unwrap();
+        let blob_etag = format!("\"sha256:{}\"", id.as_str());
+        assert_eq!(resp.headers()["etag"], blob_etag.as_str());
+        assert_eq!(resp.headers()["cache-control"], "public, max-age=31536000, immutable");
+        assert!(resp.headers().get("last-modified").is_none());
+        let resp = get(&blob, &[("if-none-match", &blob_etag)]).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 304);
+        assert!(resp.bytes().await.unwrap().is_empty());
+        let resp = get(&format!("/blobs/{}/{}.outboard", id.prefix(), id.as_str()), &[])
+            .await
+            .unwrap();
+        let outboard_etag = resp.headers()["etag"].to_str().unwrap().to_string();
+        assert!(outboard_etag.starts_with("\"blake3:"), "{outboard_etag}");
+        let resp = get(
+            &format!("/blobs/{}/{}.outboard", id.prefix(), id.as_str()),
+            &[("if-none-match", &outboard_etag)],
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.status().as_u16(), 304);
+
+        // Channel artifacts revalidate by hash or date, and a stale If-Range
+        // gets the whole body.
+        let resp = get("/stable/x86_64/initramfs", &[]).await.unwrap();
+        assert_eq!(resp.headers()["cache-control"], "no-cache");
+        let etag = resp.headers()["etag"].to_str().unwrap().to_string();
+        let modified = resp.headers()["last-modified"].to_str().unwrap().to_string();
+        assert_eq!(get("/stable/x86_64/initramfs", &[("if-none-match", &etag)]).await.unwrap().status().as_u16(), 304);
+        assert_eq!(get("/stable/x86_64/initramfs", &[("if-modified-since", &modified)]).await.unwrap().status().as_u16(), 304);
+        let resp = get("/stable/x86_64/initramfs", &[("range", "bytes=0-5"), ("if-range", &etag)])
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 206);
+        let resp = get("/stable/x86_64/initramfs", &[("range", "bytes=0-5"), ("if-range", "\"sha256:00\"")])
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(resp.bytes().await.unwrap().len(), text.len());
+
+        // Compressible bodies are encoded on request; compressed ones and
+        // ranges are not.
+        for encoding in ["zstd", "gzip"] {
+            let resp = get("/stable/x86_64/initramfs", &[("accept-encoding", encoding)]).await.unwrap();
+            assert_eq!(resp.headers()["content-encoding"], encoding);
+            let coded = resp.headers()["etag"].to_str().unwrap().to_string();
+            assert_eq!(coded, format!("{}+{}\"", etag.trim_end_matches('"'), encoding));
+            assert!(resp.bytes().await.unwrap().len() < text.len() / 10);
+
+            // The coded tag revalidates, and the 304 echoes it, but it
+            // never satisfies If-Range.
+            let resp = get("/stable/x86_64/initramfs", &[("accept-encoding", encoding), ("if-none-match", &coded)])
+                .await
+                .unwrap();
+            assert_eq!(resp.status().as_u16(), 304);
+            assert_eq!(resp.headers()["etag"], coded.as_str());
+            let resp = get("/stable/x86_64/initramfs", &[("range", "bytes=0-5"), ("if-range", &coded)])
+                .await
+                .unwrap();
+            assert_eq!(resp.status().as_u16(), 200);
+        }
+        let resp = get("/stable/x86_64/kernel", &[("accept-encoding", "zstd")]).await.unwrap();
+        assert!(resp.headers().get("content-encoding").is_none());
+        let resp = get("/stable/x86_64/initramfs", &[("accept-encoding", "zstd"), ("range", "bytes=0-99")])
+            .await
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 206);
+        assert!(resp.headers().get("content-encoding").is_none());
+
+        // Manifests are tagged by their bytes and compressed too.
+        let resp = get("/manifest.json", &[]).await.unwrap();
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let etag = resp.headers()["etag"].to_str().unwrap().to_string();
+        let body = resp.bytes().await.unwrap();
+        assert_eq!(
+            etag,
+            format!("\"sha256:{}\"", hex::encode(Sha256::digest(&body)))
+        );
+        let resp = get("/stable/x86_64/manifest.json", &[("if-none-match", &etag)]).await.unwrap();
+        assert_eq!(resp.status().as_u16(), 304);
+        let resp = get("/manifest.json", &[("accept-encoding", "gzip")]).await.unwrap();
+        assert_eq!(resp.headers()["content-encoding"], "gzip");
+        handle.abort();
+    }
 }
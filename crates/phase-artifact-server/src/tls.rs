@@ -0,0 +1,466 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! HTTPS for the artifact server.
+//!
+//! With [`ArtifactServerConfig::tls`](crate::ArtifactServerConfig::tls) set,
+//! the server terminates TLS itself with rustls and speaks HTTP/2 or
+//! HTTP/1.1, whichever the client offers over ALPN. The certificate and
+//! key are loaded into a [`ReloadingCert`], which re-reads both files every
+//! [`RELOAD_INTERVAL`] seconds and swaps them in when they change; open
+//! connections keep the certificate they were accepted with.
+//!
+//! A node without a CA-issued certificate can use [`SelfSignedCert`], issued
+//! over an Ed25519 key derived from its identity. The identity's own secret
+//! never leaves the identity file; the derived key never changes, so
+//! neither does its [`ReloadingCert::spki_sha256`] pin. Publishing that pin
+//! in a manifest the identity signs lets clients check the server with
+//! [`pinned_client_config`] instead of a CA:
+//!
+//! ```no_run
+//! # async fn run(pin: &str) -> anyhow::Result<()> {
+//! let client = phase_artifact_server::tls::pinned_http_client(pin)?;
+//! let health = client.get("https://203.0.113.7:8443/health").send().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The same client drives [`BlobFetcher::with_client`](crate::BlobFetcher::with_client).
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use axum::Router;
+use hkdf::Hkdf;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, info, warn};
+
+use phase_identity::NodeIdentity;
+
+use crate::config::TlsConfig;
+
+/// Seconds between checks of the certificate and key files.
+pub const RELOAD_INTERVAL: u64 = 10;
+
+/// How long a client gets to finish the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// ALPN protocols offered by the server, preferred first.
+const ALPN_PROTOCOLS: [&[u8]; 2] = [b"h2", b"http/1.1"];
+
+/// HKDF-SHA256 `info` that derives a [`SelfSignedCert`] key from an
+/// identity seed. Changing it changes every self-signed pin.
+const TLS_KEY_INFO: &[u8] = b"phase-artifact-server self-signed tls key v1";
+
+/// DER prefix that wraps a 32-byte Ed25519 seed as a PKCS#8 v1
+/// `PrivateKeyInfo` (RFC 8410).
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Hex SHA-256 of the DER `SubjectPublicKeyInfo` in `cert`: the value
+/// [`pinned_client_config`] checks a server against.
+pub fn spki_sha256(cert: &CertificateDer<'_>) -> Result<String> {
+    let (_, parsed) = x509_parser::parse_x509_certificate(cert.as_ref())
+        .map_err(|e| anyhow!("Invalid certificate: {}", e))?;
+    Ok(hex::encode(Sha256::digest(parsed.public_key().raw)))
+}
+
+/// The certificate in use and a fingerprint of the files it came from.
+#[derive(Debug)]
+struct Loaded {
+    key: Arc<CertifiedKey>,
+    spki_sha256: String,
+    source_sha256: [u8; 32],
+}
+
+/// A certificate and key read from PEM files, reloaded when the files
+/// change. Installed as the rustls certificate resolver, so every new
+/// handshake sees the latest pair.
+#[derive(Debug)]
+pub struct ReloadingCert {
+    config: TlsConfig,
+    loaded: RwLock<Loaded>,
+}
+
+impl ReloadingCert {
+    /// Read and check the pair named by `config`.
+    pub fn load(config: TlsConfig) -> Result<Self> {
+        let loaded = load_pair(&config)?;
+        info!(
+            "Loaded TLS certificate {:?} (SPKI SHA-256 {})",
+            config.cert_path, loaded.spki_sha256
+        );
+        Ok(Self {
+            config,
+            loaded: RwLock::new(loaded),
+        })
+    }
+
+    /// Hex SHA-256 of the current leaf certificate's public key.
+    pub fn spki_sha256(&self) -> String {
+        self.loaded.read().expect("tls lock poisoned").spki_sha256.clone()
+    }
+
+    /// Re-read the files and swap them in if they changed. Returns whether
+    /// they did. A pair that fails to load leaves the current one in place.
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let source = source_sha256(&self.config)?;
+        if source == self.loaded.read().expect("tls lock poisoned").source_sha256 {
+            return Ok(false);
+        }
+        let loaded = load_pair(&self.config)?;
+        info!(
+            "Reloaded TLS certificate {:?} (SPKI SHA-256 {})",
+            self.config.cert_path, loaded.spki_sha256
+        );
+        *self.loaded.write().expect("tls lock poisoned") = loaded;
+        Ok(true)
+    }
+
+    /// Check for new files every [`RELOAD_INTERVAL`] seconds.
+    pub(crate) async fn run_reload_loop(self: Arc<Self>) {
+        let mut tick = tokio::time::interval(Duration::from_secs(RELOAD_INTERVAL));
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        tick.tick().await;
+        loop {
+            tick.tick().await;
+            if let Err(e) = self.reload_if_changed() {
+                warn!("Keeping the current TLS certificate: {:#}", e);
+            }
+        }
+    }
+
+    /// A rustls server config resolving certificates through `self`, with
+    /// HTTP/2 and HTTP/1.1 offered over ALPN.
+    pub fn server_config(self: &Arc<Self>) -> Result<Arc<rustls::ServerConfig>> {
+        let mut config = rustls::ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::clone(self) as Arc<dyn ResolvesServerCert>);
+        config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+        Ok(Arc::new(config))
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(Arc::clone(&self.loaded.read().ok()?.key))
+    }
+}
+
+fn source_sha256(config: &TlsConfig) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    for path in [&config.cert_path, &config.key_path] {
+        hasher.update(fs::read(path).with_context(|| format!("read {:?}", path))?);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn load_pair(config: &TlsConfig) -> Result<Loaded> {
+    let cert_pem = fs::read(&config.cert_path).with_context(|| format!("read {:?}", config.cert_path))?;
+    let key_pem = fs::read(&config.key_path).with_context(|| format!("read {:?}", config.key_path))?;
+    let chain = CertificateDer::pem_slice_iter(&cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parse {:?}", config.cert_path))?;
+    let leaf = chain
+        .first()
+        .ok_or_else(|| anyhow!("No certificate in {:?}", config.cert_path))?;
+    let spki_sha256 = spki_sha256(leaf)?;
+    let key = PrivateKeyDer::from_pem_slice(&key_pem).with_context(|| format!("parse {:?}", config.key_path))?;
+    let key = CertifiedKey::from_der(chain, key, &provider())
+        .with_context(|| format!("{:?} does not match {:?}", config.key_path, config.cert_path))?;
+    let mut hasher = Sha256::new();
+    hasher.update(&cert_pem);
+    hasher.update(&key_pem);
+    Ok(Loaded {
+        key: Arc::new(key),
+        spki_sha256,
+        source_sha256: hasher.finalize().into(),
+    })
+}
+
+/// Accept TLS connections on `listener` and serve `router` over each,
+/// as HTTP/2 or HTTP/1.1 per the negotiated ALPN protocol.
+pub(crate) async fn serve_tls(listener: TcpListener, router: Router, cert: Arc<ReloadingCert>) -> Result<()> {
+    let acceptor = TlsAcceptor::from(cert.server_config()?);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Usually out of file descriptors; back off rather than spin.
+                warn!("Accept failed: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        tokio::spawn(serve_connection(acceptor.clone(), stream, peer, router.clone()));
+    }
+}
+
+async fn serve_connection(acceptor: TlsAcceptor, stream: tokio::net::TcpStream, peer: SocketAddr, router: Router) {
+    let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            debug!("TLS handshake with {} failed: {}", peer, e);
+            return;
+        }
+        Err(_) => {
+            debug!("TLS handshake with {} timed out", peer);
+            return;
+        }
+    };
+    let service = TowerToHyperService::new(router);
+    if let Err(e) = auto::Builder::new(TokioExecutor::new())
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+    {
+        debug!("Connection from {} closed: {}", peer, e);
+    }
+}
+
+/// A self-signed certificate for a node, in PEM, over a key derived from
+/// its identity.
+///
+/// The key is HKDF-SHA256 over the identity's Ed25519 seed, so a leaked
+/// TLS key says nothing about the identity key that signs manifests.
+/// Issuing is deterministic: the same identity and names always give the
+/// same certificate, valid from 1975 to 4096 and with a serial derived from
+/// the public key. Trust comes from the pinned key, not the dates.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SelfSignedCert {
+    /// The certificate.
+    pub cert_pem: String,
+    /// Its PKCS#8 private key, derived from (but not) the identity's
+    /// signing key.
+    pub key_pem: String,
+}
+
+impl SelfSignedCert {
+    /// Issue a certificate for the TLS key derived from `identity`,
+    /// naming `names` (DNS names or IP addresses) as subject alternative
+    /// names.
+    pub fn generate(identity: &NodeIdentity, names: Vec<String>) -> Result<Self> {
+        let mut seed = [0u8; 32];
+        Hkdf::<Sha256>::new(None, &identity.signing_key().to_bytes())
+            .expand(TLS_KEY_INFO, &mut seed)
+            .map_err(|e| anyhow!("derive TLS key: {}", e))?;
+        let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+        pkcs8.extend_from_slice(&seed);
+        let key_der = PrivatePkcs8KeyDer::from(pkcs8);
+        let key_pair = rcgen::KeyPair::from_pkcs8_der_and_sign_algo(&key_der, &rcgen::PKCS_ED25519)?;
+        let mut params = rcgen::CertificateParams::new(names)?;
+        params.distinguished_name.push(
+            rcgen::DnType::CommonName,
+            format!("phase provider {}", hex::encode(identity.peer_id_bytes())),
+        );
+        let cert = params.self_signed(&key_pair)?;
+        Ok(Self {
+            cert_pem: cert.pem(),
+            key_pem: key_pair.serialize_pem(),
+        })
+    }
+
+    /// Write the certificate and key (owner-only on Unix) to `config`'s
+    /// paths, creating parent directories.
+    pub fn write(&self, config: &TlsConfig) -> Result<()> {
+        for path in [&config.cert_path, &config.key_path] {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("create {:?}", parent))?;
+            }
+        }
+        fs::write(&config.cert_path, &self.cert_pem).with_context(|| format!("write {:?}", config.cert_path))?;
+        write_private(&config.key_path, self.key_pem.as_bytes())
+    }
+}
+
+impl std::fmt::Debug for SelfSignedCert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SelfSignedCert")
+            .field("cert_pem", &self.cert_pem)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("write {:?}", path))?;
+    // `mode` only applies on creation; tighten a file that already existed.
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(bytes).with_context(|| format!("write {:?}", path))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, bytes: &[u8]) -> Result<()> {
+    fs::write(path, bytes).with_context(|| format!("write {:?}", path))
+}
+
+/// Accepts exactly the servers whose leaf certificate carries the pinned
+/// public key, whatever name or issuer it has. Handshake signatures are
+/// still checked, so the server must hold the matching private key.
+#[derive(Debug)]
+struct SpkiPinVerifier {
+    pin: String,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for SpkiPinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let actual = spki_sha256(end_entity).map_err(|e| rustls::Error::General(format!("{:#}", e)))?;
+        if actual == self.pin {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General(format!(
+                "server key {} does not match the pinned {}",
+                actual, self.pin
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+
+    fn root_hint_subjects(&self) -> Option<&[DistinguishedName]> {
+        None
+    }
+}
+
+/// A rustls client config that trusts only servers presenting the public
+/// key whose hex SHA-256 is `spki_sha256`, as published in a provider's
+/// signed manifest. Offers HTTP/2 and HTTP/1.1 over ALPN.
+pub fn pinned_client_config(spki_sha256: &str) -> Result<rustls::ClientConfig> {
+    let pin = spki_sha256.to_ascii_lowercase();
+    if pin.len() != 64 || !pin.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow!("TLS pin must be 64 hex characters, got {:?}", spki_sha256));
+    }
+    let provider = provider();
+    let verifier = Arc::new(SpkiPinVerifier {
+        pin,
+        provider: Arc::clone(&provider),
+    });
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth();
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    Ok(config)
+}
+
+/// An HTTP client over [`pinned_client_config`].
+pub fn pinned_http_client(spki_sha256: &str) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .use_preconfigured_tls(pinned_client_config(spki_sha256)?)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_signed_pin_follows_the_identity() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let identity = NodeIdentity::generate();
+        let names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+        let cert = SelfSignedCert::generate(&identity, names.clone()).unwrap();
+        assert_eq!(cert, SelfSignedCert::generate(&identity, names).unwrap());
+
+        let config = TlsConfig {
+            cert_path: temp.path().join("tls/cert.pem"),
+            key_path: temp.path().join("tls/key.pem"),
+        };
+        cert.write(&config).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&config.key_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = ReloadingCert::load(config.clone()).unwrap();
+        let pin = loaded.spki_sha256();
+        assert!(!loaded.reload_if_changed().unwrap());
+
+        // The key on disk is derived, not the identity's own.
+        let key = PrivateKeyDer::from_pem_slice(cert.key_pem.as_bytes()).unwrap();
+        let secret = identity.signing_key().to_bytes();
+        assert!(!key.secret_der().windows(32).any(|w| w == secret));
+        let mut identity_spki = vec![0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+        identity_spki.extend_from_slice(&identity.verifying_key().to_bytes());
+        assert_ne!(pin, hex::encode(Sha256::digest(&identity_spki)));
+
+        // Same key, other names: the pin holds across reissues.
+        SelfSignedCert::generate(&identity, vec!["provider.example".to_string()])
+            .unwrap()
+            .write(&config)
+            .unwrap();
+        assert!(loaded.reload_if_changed().unwrap());
+        assert_eq!(loaded.spki_sha256(), pin);
+
+        // A key that doesn't match the certificate is refused and the
+        // loaded pair stays.
+        let other = SelfSignedCert::generate(&NodeIdentity::generate(), vec!["localhost".to_string()]).unwrap();
+        fs::write(&config.key_path, &other.key_pem).unwrap();
+        assert!(loaded.reload_if_changed().is_err());
+        assert_eq!(loaded.spki_sha256(), pin);
+
+        assert!(pinned_client_config(&pin).is_ok());
+        assert!(pinned_client_config("not-a-pin").is_err());
+    }
+}
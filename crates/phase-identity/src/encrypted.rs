@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Passphrase-encrypted identity files.
+//!
+//! Mode `0o600` keeps other local users away from the key, but not backups,
+//! disk images or a copied Phase Boot USB stick. An encrypted identity file
+//! seals the same 32-byte secret under a key derived from a passphrase:
+//!
+//! ```text
+//! magic "PHASEKEY" (8) || version (1) || argon2 m_cost, t_cost, p_cost (u32 BE each)
+//!   || salt (16) || nonce (24) || XChaCha20-Poly1305(secret) (32 + 16 tag)
+//! ```
+//!
+//! The key is argon2id over the passphrase and salt; everything before the
+//! ciphertext is authenticated as associated data, so the KDF parameters
+//! can't be downgraded in place. That check only happens after the key is
+//! derived, so costs past [`MAX_M_COST`], [`MAX_T_COST`] or [`MAX_P_COST`]
+//! are refused unread rather than letting a corrupt header stall the load. Raw files are exactly 32 bytes and encrypted
+//! ones start with the magic, so `NodeIdentity::load` tells them apart
+//! without a flag.
+//!
+//! Unattended daemons supply the passphrase through
+//! [`PASSPHRASE_ENV`] or, to keep it out of the environment, an inherited
+//! file descriptor named by [`PASSPHRASE_FD_ENV`].
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use rand_core::{OsRng, RngCore};
+use zeroize::Zeroizing;
+
+use crate::error::IdentityError;
+use crate::storage::SECRET_LEN;
+
+/// Environment variable holding the passphrase itself.
+pub const PASSPHRASE_ENV: &str = "PHASE_IDENTITY_PASSPHRASE";
+
+/// Environment variable naming an open file descriptor to read the
+/// passphrase from (e.g. `PHASE_IDENTITY_PASSPHRASE_FD=3 plasmd start 3<pass`).
+pub const PASSPHRASE_FD_ENV: &str = "PHASE_IDENTITY_PASSPHRASE_FD";
+
+const MAGIC: &[u8; 8] = b"PHASEKEY";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 1 + 12 + SALT_LEN + NONCE_LEN;
+const ENCRYPTED_LEN: usize = HEADER_LEN + SECRET_LEN + TAG_LEN;
+
+/// Most argon2 memory a file may ask for, in KiB (1 GiB).
+const MAX_M_COST: u32 = 1024 * 1024;
+/// Most argon2 passes a file may ask for.
+const MAX_T_COST: u32 = 16;
+/// Most argon2 lanes a file may ask for.
+const MAX_P_COST: u32 = 16;
+
+/// A passphrase for an encrypted identity file. Wiped from memory on drop;
+/// `Debug` never prints it.
+#[derive(Clone)]
+pub struct Passphrase(Zeroizing<String>);
+
+impl Passphrase {
+    pub fn new(passphrase: impl Into<String>) -> Self {
+        Self(Zeroizing::new(passphrase.into()))
+    }
+
+    /// Read a passphrase from a file (or `/dev/fd/N`). One trailing newline
+    /// is dropped, so `echo secret > file` works.
+    pub fn read_from(path: &Path) -> Result<Self, IdentityError> {
+        let text = Zeroizing::new(fs::read_to_string(path).map_err(|e| IdentityError::Io {
+            path: path.to_path_buf(),
+            source: e,
+        })?);
+        let line = text
+            .strip_suffix('\n')
+            .map(|s| s.strip_suffix('\r').unwrap_or(s))
+            .unwrap_or(&text);
+        Ok(Self::new(line))
+    }
+
+    /// The passphrase configured for this process: [`PASSPHRASE_ENV`] if
+    /// set, else the descriptor named by [`PASSPHRASE_FD_ENV`], else
+    /// `None`. A descriptor can only be read once, so its contents are kept
+    /// for later calls.
+    pub fn from_env() -> Result<Option<Self>, IdentityError> {
+        static FROM_FD: OnceLock<Passphrase> = OnceLock::new();
+
+        if let Ok(value) = std::env::var(PASSPHRASE_ENV) {
+            return Ok(Some(Self::new(value)));
+        }
+        if let Some(cached) = FROM_FD.get() {
+            return Ok(Some(cached.clone()));
+        }
+        let Ok(fd) = std::env::var(PASSPHRASE_FD_ENV) else {
+            return Ok(None);
+        };
+        let fd: u32 = fd.trim().parse().map_err(|_| {
+            IdentityError::InvalidPassphraseSource(format!(
+                "{PASSPHRASE_FD_ENV}={fd:?} is not a file descriptor number"
+            ))
+        })?;
+        let passphrase = Self::read_from(&PathBuf::from(format!("/dev/fd/{fd}")))?;
+        Ok(Some(FROM_FD.get_or_init(|| passphrase).clone()))
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+/// argon2id cost parameters, stored in each file's header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl KdfParams {
+    /// What new files are sealed with: argon2's recommended defaults
+    /// (19 MiB, 2 passes). Unit tests use the minimum so they stay fast.
+    fn for_new_files() -> Self {
+        if cfg!(test) {
+            Self {
+                m_cost: Params::MIN_M_COST,
+                t_cost: 1,
+                p_cost: 1,
+            }
+        } else {
+            Self {
+                m_cost: Params::DEFAULT_M_COST,
+                t_cost: Params::DEFAULT_T_COST,
+                p_cost: Params::DEFAULT_P_COST,
+            }
+        }
+    }
+
+    /// Whether deriving with these costs is something this build will do.
+    fn within_limits(&self) -> bool {
+        self.m_cost <= MAX_M_COST && self.t_cost <= MAX_T_COST && self.p_cost <= MAX_P_COST
+    }
+
+    fn derive(
+        &self,
+        passphrase: &Passphrase,
+        salt: &[u8],
+    ) -> Option<Zeroizing<[u8; 32]>> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32)).ok()?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+            .ok()?;
+        Some(key)
+    }
+}
+
+/// Whether `bytes` are an encrypted identity file (of any version).
+pub(crate) fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Seal `secret` under `passphrase`.
+pub(crate) fn encrypt(
+    secret: &[u8; SECRET_LEN],
+    passphrase: &Passphrase,
+) -> Vec<u8> {
+    let params = KdfParams::for_new_files();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut out = Vec::with_capacity(ENCRYPTED_LEN);
+    out.extend_from_slice(MAGIC);
+    out.push(FORMAT_VERSION);
+    for cost in [params.m_cost, params.t_cost, params.p_cost] {
+        out.extend_from_slice(&cost.to_be_bytes());
+    }
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let key = params
+        .derive(passphrase, &salt)
+        .expect("argon2 accepts the default parameters");
+    let sealed = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: secret,
+                aad: &out,
+            },
+        )
+        .expect("sealing a 32-byte message cannot fail");
+    out.extend_from_slice(&sealed);
+    out
+}
+
+/// Open an encrypted identity file read from `path`.
+pub(crate) fn decrypt(
+    path: &Path,
+    bytes: &[u8],
+    passphrase: &Passphrase,
+) -> Result<Zeroizing<[u8; SECRET_LEN]>, IdentityError> {
+    let version = bytes.get(MAGIC.len()).copied().unwrap_or(0);
+    if version != FORMAT_VERSION {
+        return Err(IdentityError::UnsupportedFormat {
+            path: path.to_path_buf(),
+            version,
+        });
+    }
+    let failed = || IdentityError::Decryption(path.to_path_buf());
+    if bytes.len() != ENCRYPTED_LEN {
+        return Err(failed());
+    }
+    let (header, sealed) = bytes.split_at(HEADER_LEN);
+    let word = |at: usize| u32::from_be_bytes(header[at..at + 4].try_into().expect("4-byte slice"));
+    let costs_at = MAGIC.len() + 1;
+    let params = KdfParams {
+        m_cost: word(costs_at),
+        t_cost: word(costs_at + 4),
+        p_cost: word(costs_at + 8),
+    };
+    if !params.within_limits() {
+        return Err(IdentityError::UnsupportedFormat {
+            path: path.to_path_buf(),
+            version,
+        });
+    }
+    let salt = &header[costs_at + 12..costs_at + 12 + SALT_LEN];
+    let nonce = &header[HEADER_LEN - NONCE_LEN..];
+
+    let key = params.derive(passphrase, salt).ok_or_else(failed)?;
+    let plain = Zeroizing::new(
+        XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: sealed,
+                    aad: header,
+                },
+            )
+            .map_err(|_| failed())?,
+    );
+    let mut secret = Zeroizing::new([0u8; SECRET_LEN]);
+    secret.copy_from_slice(&plain);
+    Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_wrong_passphrase() {
+        let path = Path::new("node.key");
+        let secret = [0x42; SECRET_LEN];
+        let sealed = encrypt(&secret, &Passphrase::new("correct horse"));
+        assert!(is_encrypted(&sealed));
+        assert_eq!(sealed.len(), ENCRYPTED_LEN);
+        assert!(!sealed.windows(SECRET_LEN).any(|w| w == secret));
+
+        let opened = decrypt(path, &sealed, &Passphrase::new("correct horse")).unwrap();
+        assert_eq!(*opened, secret);
+        assert!(matches!(
+            decrypt(path, &sealed, &Passphrase::new("battery staple")),
+            Err(IdentityError::Decryption(_))
+        ));
+    }
+
+    #[test]
+    fn header_is_authenticated() {
+        let path = Path::new("node.key");
+        let pass = Passphrase::new("pw");
+        let mut sealed = encrypt(&[7; SECRET_LEN], &pass);
+        // Bump t_cost: the derived key changes *and* the AAD no longer
+        // matches, either of which must fail.
+        sealed[MAGIC.len() + 1 + 7] ^= 1;
+        assert!(decrypt(path, &sealed, &pass).is_err());
+
+        let mut future = encrypt(&[7; SECRET_LEN], &pass);
+        future[MAGIC.len()] = 9;
+        assert!(matches!(
+            decrypt(path, &future, &pass),
+            Err(IdentityError::UnsupportedFormat { version: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn oversized_costs_are_refused_before_deriving() {
+        let path = Path::new("node.key");
+        let pass = Passphrase::new("pw");
+        let costs_at = MAGIC.len() + 1;
+        for (offset, cost) in [(0, u32::MAX), (4, u32::MAX), (8, MAX_P_COST + 1)] {
+            let mut sealed = encrypt(&[7; SECRET_LEN], &pass);
+            sealed[costs_at + offset..costs_at + offset + 4].copy_from_slice(&cost.to_be_bytes());
+            assert!(matches!(
+                decrypt(path, &sealed, &pass),
+                Err(IdentityError::UnsupportedFormat { version: FORMAT_VERSION, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn passphrase_debug_is_redacted() {
+        assert!(!format!("{:?}", Passphrase::new("hunter2")).contains("hunter2"));
+    }
+}
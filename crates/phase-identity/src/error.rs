@@ -43,4 +43,31 @@ pub enum IdentityError {
     /// `default_identity_path()`.
     #[error("could not resolve a platform-appropriate config directory for the identity file")]
     NoConfigDir,
+
+    /// The identity file is encrypted and no passphrase was supplied.
+    #[error("identity file at {0:?} is encrypted; set PHASE_IDENTITY_PASSPHRASE or PHASE_IDENTITY_PASSPHRASE_FD")]
+    PassphraseRequired(PathBuf),
+
+    /// The passphrase is wrong or the encrypted file was modified. AEAD
+    /// can't tell the two apart.
+    #[error("could not decrypt identity file at {0:?}: wrong passphrase or corrupted file")]
+    Decryption(PathBuf),
+
+    /// The encrypted file's header names a format version this build
+    /// doesn't know, or KDF costs beyond what it will spend on a key.
+    #[error("identity file at {path:?} uses an unsupported encrypted format (version {version})")]
+    UnsupportedFormat { path: PathBuf, version: u8 },
+
+    /// A passphrase source in the environment is malformed.
+    #[error("invalid identity passphrase source: {0}")]
+    InvalidPassphraseSource(String),
+
+    /// A succession record or chain failed to decode, verify or link.
+    #[error("invalid succession chain: {0}")]
+    InvalidSuccession(String),
+
+    /// A rotation was attempted with a key that is not the newest one in
+    /// its succession chain.
+    #[error("identity is not the newest key in its succession chain")]
+    NotChainHead,
 }
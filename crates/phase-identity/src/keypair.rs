@@ -9,7 +9,8 @@
 //!     material from which a libp2p `PeerId` is derived
 //!
 //! Persistence format is delegated to `storage` (raw 32-byte secret,
-//! `0o600` on Unix).
+//! `0o600` on Unix) and `encrypted` (the same secret sealed under a
+//! passphrase).
 
 use std::fmt;
 use std::path::Path;
@@ -17,8 +18,11 @@ use std::path::Path;
 use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
 use rand_core::OsRng;
 
+use crate::encrypted::{self, Passphrase};
 use crate::error::IdentityError;
-use crate::storage::{create_new_secret, read_secret, write_secret, SECRET_LEN};
+use crate::storage::{
+    create_new_secret, read_secret, write_file, write_secret, StoredSecret, SECRET_LEN,
+};
 
 /// A persistent Ed25519 node identity. Cheap to clone (`SigningKey` is a
 /// 32-byte secret + cached scalar internally), so callers may freely pass
@@ -60,14 +64,39 @@ impl NodeIdentity {
     /// Load an existing identity from `path`. Returns
     /// `IdentityError::NotFound` if the file does not exist; use
     /// `load_or_create` if you want creation on absence.
+    ///
+    /// Encrypted files are detected automatically and opened with the
+    /// passphrase from [`Passphrase::from_env`];
+    /// `IdentityError::PassphraseRequired` if none is configured.
     pub fn load(path: &Path) -> Result<Self, IdentityError> {
-        let bytes = read_secret(path)?;
-        Ok(Self::from_secret_bytes(bytes))
+        match read_secret(path)? {
+            StoredSecret::Plain(bytes) => Ok(Self::from_secret_bytes(bytes)),
+            StoredSecret::Encrypted(sealed) => {
+                let passphrase = Passphrase::from_env()?
+                    .ok_or_else(|| IdentityError::PassphraseRequired(path.to_path_buf()))?;
+                Self::open(path, &sealed, &passphrase)
+            }
+        }
+    }
+
+    /// Load the identity at `path` with an explicit passphrase. A raw
+    /// (unencrypted) file loads as-is.
+    pub fn load_with_passphrase(path: &Path, passphrase: &Passphrase) -> Result<Self, IdentityError> {
+        match read_secret(path)? {
+            StoredSecret::Plain(bytes) => Ok(Self::from_secret_bytes(bytes)),
+            StoredSecret::Encrypted(sealed) => Self::open(path, &sealed, passphrase),
+        }
+    }
+
+    /// Whether the identity file at `path` is passphrase-encrypted.
+    pub fn is_encrypted(path: &Path) -> Result<bool, IdentityError> {
+        Ok(matches!(read_secret(path)?, StoredSecret::Encrypted(_)))
     }
 
     /// Load the identity at `path` if it exists, otherwise generate a new
     /// one and persist it to that path. This is the entry point the daemon
-    /// uses on startup.
+    /// uses on startup. A new key is encrypted if a passphrase is
+    /// configured in the environment.
     ///
     /// Race-free: when several callers (threads, processes) hit a fresh path
     /// at once, the persistence layer uses `O_CREAT|O_EXCL` so exactly one
@@ -81,8 +110,8 @@ impl NodeIdentity {
             Ok(id) => Ok(id),
             Err(IdentityError::NotFound(_)) => {
                 let candidate = Self::generate();
-                let secret = candidate.signing_key.to_bytes();
-                match create_new_secret(path, &secret) {
+                let stored = candidate.to_stored_bytes(Passphrase::from_env()?.as_ref());
+                match create_new_secret(path, &stored) {
                     // We won the create race: our generated key is the one on
                     // disk.
                     Ok(()) => Ok(candidate),
@@ -102,6 +131,22 @@ impl NodeIdentity {
         write_secret(path, &secret)
     }
 
+    /// Like [`save`](Self::save), but sealed under `passphrase` (argon2id +
+    /// XChaCha20-Poly1305).
+    pub fn save_encrypted(&self, path: &Path, passphrase: &Passphrase) -> Result<(), IdentityError> {
+        write_file(path, &self.to_stored_bytes(Some(passphrase)))
+    }
+
+    /// The identity file contents: the raw secret, or sealed under
+    /// `passphrase` when one is given.
+    pub(crate) fn to_stored_bytes(&self, passphrase: Option<&Passphrase>) -> Vec<u8> {
+        let secret = zeroize::Zeroizing::new(self.signing_key.to_bytes());
+        match passphrase {
+            Some(p) => encrypted::encrypt(&secret, p),
+            None => secret.to_vec(),
+        }
+    }
+
     /// The underlying Ed25519 signing key. Exposed by reference so callers
     /// can pass it to libraries that already work with `SigningKey`
     /// (e.g. `phase-manifest` once M5 lands).
@@ -131,6 +176,11 @@ impl NodeIdentity {
         self.signing_key.sign(msg)
     }
 
+    fn open(path: &Path, sealed: &[u8], passphrase: &Passphrase) -> Result<Self, IdentityError> {
+        let secret = encrypted::decrypt(path, sealed, passphrase)?;
+        Ok(Self::from_secret_bytes(*secret))
+    }
+
     /// Construct from a raw 32-byte secret. Private: external callers
     /// should go through `load`/`load_or_create`/`generate`.
     fn from_secret_bytes(bytes: [u8; SECRET_LEN]) -> Self {
@@ -299,6 +349,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encrypted_file_is_detected_and_needs_the_passphrase() {
+        let (_tmp, path) = fresh_path();
+        let id = NodeIdentity::generate();
+        let pass = Passphrase::new("correct horse");
+        id.save_encrypted(&path, &pass).expect("save");
+        assert!(NodeIdentity::is_encrypted(&path).unwrap());
+
+        let on_disk = std::fs::read(&path).unwrap();
+        assert!(!on_disk
+            .windows(32)
+            .any(|w| w == id.signing_key().to_bytes()));
+
+        let loaded = NodeIdentity::load_with_passphrase(&path, &pass).expect("load");
+        assert_eq!(loaded.peer_id_bytes(), id.peer_id_bytes());
+        assert!(matches!(
+            NodeIdentity::load_with_passphrase(&path, &Passphrase::new("wrong")),
+            Err(IdentityError::Decryption(_))
+        ));
+
+        // Decrypting back to the raw format.
+        loaded.save(&path).expect("save raw");
+        assert!(!NodeIdentity::is_encrypted(&path).unwrap());
+        assert_eq!(
+            NodeIdentity::load(&path).unwrap().peer_id_bytes(),
+            id.peer_id_bytes()
+        );
+    }
+
     #[test]
     fn signature_survives_save_load_cycle() {
         // The operational guarantee the daemon depends on: a signature
@@ -32,15 +32,35 @@
 //! if they do not already exist. The format intentionally matches the
 //! boundary test contract documented in
 //! `daemon/tests/boundary_persistent_identity.rs`.
+//!
+//! Optionally the file is encrypted under a passphrase instead
+//! ([`NodeIdentity::save_encrypted`]): a versioned header, argon2id key
+//! derivation and XChaCha20-Poly1305. [`NodeIdentity::load`] recognises
+//! either format and takes the passphrase from [`PASSPHRASE_ENV`] or the
+//! file descriptor named by [`PASSPHRASE_FD_ENV`].
+//!
+//! # Rotation
+//!
+//! [`rotate_identity`] replaces the key and appends a record, signed by
+//! the old key, to a [`SuccessionChain`] stored beside it. Verifiers that
+//! trust an earlier key follow the chain to the current one; see the
+//! `succession` module docs.
 
 mod default_path;
+mod encrypted;
 mod error;
 mod keypair;
 mod storage;
+mod succession;
 
 pub use default_path::default_identity_path;
+pub use encrypted::{Passphrase, PASSPHRASE_ENV, PASSPHRASE_FD_ENV};
 pub use error::IdentityError;
 pub use keypair::NodeIdentity;
+pub use succession::{
+    allowlist_permits, rotate_identity, succession_path, SuccessionChain, SuccessionRecord, RECORD_LEN,
+    SIGNING_DOMAIN as SUCCESSION_SIGNING_DOMAIN,
+};
 
 // Re-export the underlying ed25519-dalek types so downstream crates do not
 // need to add ed25519-dalek directly just to talk about identities.
@@ -6,9 +6,9 @@
 //!
 //! Keeping the format intentionally minimal:
 //!   - Zero parsing surface (no JSON / PEM / TOML to get wrong)
-//!   - No version envelope yet -- if we need one later, the file length
-//!     check (32 bytes exactly) will catch every legacy file and force a
-//!     deliberate migration.
+//!   - No version envelope on raw keys -- a raw file is exactly 32 bytes.
+//!     The passphrase-encrypted format (`encrypted`) is the one that
+//!     carries a versioned header, and is recognised by its magic.
 //!   - Mode `0o600` on Unix so other local users cannot read the secret.
 
 use std::fs;
@@ -16,18 +16,29 @@ use std::io;
 use std::io::Write;
 use std::path::Path;
 
+use crate::encrypted;
 use crate::error::IdentityError;
 
 /// Length of an Ed25519 secret key in bytes. Re-exported as a constant so
 /// callers and tests can reference it symbolically.
 pub(crate) const SECRET_LEN: usize = 32;
 
-/// Read a 32-byte Ed25519 secret from `path`.
+/// What an identity file holds.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum StoredSecret {
+    /// The raw 32-byte Ed25519 secret.
+    Plain([u8; SECRET_LEN]),
+    /// A passphrase-encrypted secret, still sealed.
+    Encrypted(Vec<u8>),
+}
+
+/// Read an identity file from `path`: a raw 32-byte Ed25519 secret, or an
+/// encrypted one (left sealed for the caller).
 ///
 /// Returns `IdentityError::NotFound` if the path does not exist, distinct
 /// from a generic I/O error so callers (notably `load_or_create`) can
 /// decide whether to fall back to generation.
-pub(crate) fn read_secret(path: &Path) -> Result<[u8; SECRET_LEN], IdentityError> {
+pub(crate) fn read_secret(path: &Path) -> Result<StoredSecret, IdentityError> {
     let bytes = match fs::read(path) {
         Ok(b) => b,
         Err(e) if e.kind() == io::ErrorKind::NotFound => {
@@ -41,6 +52,9 @@ pub(crate) fn read_secret(path: &Path) -> Result<[u8; SECRET_LEN], IdentityError
         }
     };
 
+    if encrypted::is_encrypted(&bytes) {
+        return Ok(StoredSecret::Encrypted(bytes));
+    }
     if bytes.len() != SECRET_LEN {
         return Err(IdentityError::InvalidLength {
             path: path.to_path_buf(),
@@ -50,7 +64,7 @@ pub(crate) fn read_secret(path: &Path) -> Result<[u8; SECRET_LEN], IdentityError
 
     let mut out = [0u8; SECRET_LEN];
     out.copy_from_slice(&bytes);
-    Ok(out)
+    Ok(StoredSecret::Plain(out))
 }
 
 /// Persist `secret` to `path`.
@@ -86,10 +100,7 @@ pub(crate) fn write_secret(path: &Path, secret: &[u8; SECRET_LEN]) -> Result<(),
 /// single key (the winner's) instead of clobbering each other.
 ///
 /// Atomicity and mode-0600-at-creation are identical to [`write_secret`].
-pub(crate) fn create_new_secret(
-    path: &Path,
-    secret: &[u8; SECRET_LEN],
-) -> Result<(), IdentityError> {
+pub(crate) fn create_new_secret(path: &Path, secret: &[u8]) -> Result<(), IdentityError> {
     let parent = match path.parent() {
         Some(p) if !p.as_os_str().is_empty() => p,
         _ => Path::new("."),
@@ -98,6 +109,19 @@ pub(crate) fn create_new_secret(
     write_temp_then_publish(parent, path, secret, Publish::ExclusiveCreate)
 }
 
+/// Atomically replace `path` with `bytes`, with the same temp+rename and
+/// 0600 guarantees as [`write_secret`]. Used for encrypted keys and for
+/// the succession chain, which sits next to the key and is just as worth
+/// not truncating.
+pub(crate) fn write_file(path: &Path, bytes: &[u8]) -> Result<(), IdentityError> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    create_dir_secure(parent)?;
+    write_temp_then_publish(parent, path, bytes, Publish::Clobber)
+}
+
 /// How the staged temp file is moved onto its final path.
 enum Publish {
     /// Overwrite any existing file (atomic `rename`). Used by `save`.
@@ -115,7 +139,7 @@ enum Publish {
 fn write_temp_then_publish(
     parent: &Path,
     final_path: &Path,
-    secret: &[u8; SECRET_LEN],
+    secret: &[u8],
     mode: Publish,
 ) -> Result<(), IdentityError> {
     // Unique temp name in the same dir so the publish stays on one
@@ -256,7 +280,7 @@ mod tests {
         let secret = [7u8; SECRET_LEN];
         write_secret(&path, &secret).expect("write");
         let read_back = read_secret(&path).expect("read");
-        assert_eq!(read_back, secret);
+        assert_eq!(read_back, StoredSecret::Plain(secret));
     }
 
     #[test]
@@ -362,13 +386,17 @@ mod tests {
         let (_tmp, path) = fresh_path();
         let a = [9u8; SECRET_LEN];
         create_new_secret(&path, &a).expect("first create wins");
-        assert_eq!(read_secret(&path).unwrap(), a);
+        assert_eq!(read_secret(&path).unwrap(), StoredSecret::Plain(a));
 
         // Second create on the same path must not clobber.
         let b = [3u8; SECRET_LEN];
         let err = create_new_secret(&path, &b).expect_err("second create must fail");
         assert!(matches!(err, IdentityError::AlreadyExists(_)));
-        assert_eq!(read_secret(&path).unwrap(), a, "existing key untouched");
+        assert_eq!(
+            read_secret(&path).unwrap(),
+            StoredSecret::Plain(a),
+            "existing key untouched"
+        );
     }
 
     #[cfg(unix)]
@@ -0,0 +1,507 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Key rotation via signed succession records.
+//!
+//! A node that rotates its key signs a [`SuccessionRecord`] with the *old*
+//! key naming the *new* one. Records chain: record `n` is signed by the key
+//! record `n - 1` introduced. A verifier that trusts any key in a
+//! [`SuccessionChain`] can follow it forward to the node's current key, so
+//! allowlists and reputation keep working across a rotation without being
+//! re-edited. Trust only flows forward: a chain never makes an older key
+//! stand in for a newer one.
+//!
+//! Receipts need no chain: a relayed receipt is bound to the `PeerId` it
+//! was dispatched to, and that `PeerId` is derived from the current key.
+//! Reputation and other long-lived records should key on
+//! [`SuccessionChain::root`], the one key that never changes.
+//!
+//! # On-disk format
+//!
+//! The chain lives next to the key file (`node.key` → `node.succession`)
+//! as a concatenation of fixed-size records, oldest first:
+//!
+//! ```text
+//! previous (32) || next (32) || sequence (u64 BE) || rotated_at (u64 BE, unix secs) || signature (64)
+//! ```
+//!
+//! The signature covers [`SIGNING_DOMAIN`] followed by the first 80 bytes.
+//! The same bytes, hex-encoded, are what operators paste into peers'
+//! configs.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+use crate::encrypted::Passphrase;
+use crate::error::IdentityError;
+use crate::keypair::NodeIdentity;
+use crate::storage::{create_new_secret, write_file};
+
+/// Domain-separation prefix for succession signatures, so no other Phase
+/// signature can be replayed as a rotation.
+pub const SIGNING_DOMAIN: &[u8] = b"phase-succession:v1:";
+
+/// Encoded length of one [`SuccessionRecord`].
+pub const RECORD_LEN: usize = 32 + 32 + 8 + 8 + 64;
+
+const SIGNED_LEN: usize = RECORD_LEN - 64;
+
+/// The old key's statement that `next` replaces `previous`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuccessionRecord {
+    pub previous: VerifyingKey,
+    pub next: VerifyingKey,
+    /// Position in the chain, from 0.
+    pub sequence: u64,
+    /// When the rotation happened, unix seconds.
+    pub rotated_at: u64,
+    /// `previous`'s signature over `SIGNING_DOMAIN || fields`.
+    pub signature: Signature,
+}
+
+impl SuccessionRecord {
+    /// Sign a record handing `previous`'s identity on to `next`.
+    pub fn sign(
+        previous: &NodeIdentity,
+        next: VerifyingKey,
+        sequence: u64,
+        rotated_at: u64,
+    ) -> Self {
+        let fields = signed_fields(&previous.verifying_key(), &next, sequence, rotated_at);
+        let mut message = SIGNING_DOMAIN.to_vec();
+        message.extend_from_slice(&fields);
+        Self {
+            previous: previous.verifying_key(),
+            next,
+            sequence,
+            rotated_at,
+            signature: previous.sign(&message),
+        }
+    }
+
+    /// Check the signature against `previous`.
+    pub fn verify(&self) -> Result<(), IdentityError> {
+        let fields = signed_fields(&self.previous, &self.next, self.sequence, self.rotated_at);
+        let mut message = SIGNING_DOMAIN.to_vec();
+        message.extend_from_slice(&fields);
+        self.previous
+            .verify(&message, &self.signature)
+            .map_err(|_| {
+                IdentityError::InvalidSuccession(format!(
+                    "record {} has a bad signature",
+                    self.sequence
+                ))
+            })
+    }
+
+    pub fn to_bytes(&self) -> [u8; RECORD_LEN] {
+        let mut out = [0u8; RECORD_LEN];
+        out[..SIGNED_LEN].copy_from_slice(&signed_fields(
+            &self.previous,
+            &self.next,
+            self.sequence,
+            self.rotated_at,
+        ));
+        out[SIGNED_LEN..].copy_from_slice(&self.signature.to_bytes());
+        out
+    }
+
+    /// Decode one record. Does not verify the signature.
+    pub fn from_bytes(bytes: &[u8; RECORD_LEN]) -> Result<Self, IdentityError> {
+        let key = |range: std::ops::Range<usize>| {
+            let raw: [u8; 32] = bytes[range].try_into().expect("32-byte slice");
+            VerifyingKey::from_bytes(&raw).map_err(|_| {
+                IdentityError::InvalidSuccession("record holds an invalid public key".into())
+            })
+        };
+        let word = |at: usize| u64::from_be_bytes(bytes[at..at + 8].try_into().expect("8-byte slice"));
+        let signature: [u8; 64] = bytes[SIGNED_LEN..].try_into().expect("64-byte slice");
+        Ok(Self {
+            previous: key(0..32)?,
+            next: key(32..64)?,
+            sequence: word(64),
+            rotated_at: word(72),
+            signature: Signature::from_bytes(&signature),
+        })
+    }
+}
+
+fn signed_fields(
+    previous: &VerifyingKey,
+    next: &VerifyingKey,
+    sequence: u64,
+    rotated_at: u64,
+) -> [u8; SIGNED_LEN] {
+    let mut out = [0u8; SIGNED_LEN];
+    out[..32].copy_from_slice(previous.as_bytes());
+    out[32..64].copy_from_slice(next.as_bytes());
+    out[64..72].copy_from_slice(&sequence.to_be_bytes());
+    out[72..].copy_from_slice(&rotated_at.to_be_bytes());
+    out
+}
+
+/// An ordered, verified list of [`SuccessionRecord`]s. Every constructor
+/// checks signatures and links, so holding one means the chain is sound.
+/// The empty chain is a node that has never rotated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SuccessionChain {
+    records: Vec<SuccessionRecord>,
+}
+
+impl SuccessionChain {
+    /// Verify `records` as a chain, oldest first.
+    pub fn from_records(records: Vec<SuccessionRecord>) -> Result<Self, IdentityError> {
+        let mut chain = Self::default();
+        for record in records {
+            chain.push(record)?;
+        }
+        Ok(chain)
+    }
+
+    /// Append a record. It must be signed by the current key, carry the
+    /// next sequence number, and introduce a key not already in the chain.
+    pub fn push(&mut self, record: SuccessionRecord) -> Result<(), IdentityError> {
+        let invalid = |msg: String| Err(IdentityError::InvalidSuccession(msg));
+        if record.sequence != self.records.len() as u64 {
+            return invalid(format!(
+                "record {} found where {} was expected",
+                record.sequence,
+                self.records.len()
+            ));
+        }
+        if let Some(current) = self.current() {
+            if record.previous != current {
+                return invalid(format!(
+                    "record {} is not signed by the key before it",
+                    record.sequence
+                ));
+            }
+        }
+        if self.contains(&record.next) || record.next == record.previous {
+            return invalid(format!("record {} reintroduces an earlier key", record.sequence));
+        }
+        record.verify()?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Rotate away from `current`: generate a new identity, sign a record
+    /// for it and append that record. `current` must be the chain's newest
+    /// key (any key, for an empty chain).
+    pub fn rotate(&mut self, current: &NodeIdentity) -> Result<NodeIdentity, IdentityError> {
+        if self.current().is_some_and(|k| k != current.verifying_key()) {
+            return Err(IdentityError::NotChainHead);
+        }
+        let next = NodeIdentity::generate();
+        let rotated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.push(SuccessionRecord::sign(
+            current,
+            next.verifying_key(),
+            self.records.len() as u64,
+            rotated_at,
+        ))?;
+        Ok(next)
+    }
+
+    pub fn records(&self) -> &[SuccessionRecord] {
+        &self.records
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The original key. `None` for an empty chain.
+    pub fn root(&self) -> Option<VerifyingKey> {
+        self.records.first().map(|r| r.previous)
+    }
+
+    /// The newest key. `None` for an empty chain.
+    pub fn current(&self) -> Option<VerifyingKey> {
+        self.records.last().map(|r| r.next)
+    }
+
+    /// Every key in the chain, oldest first.
+    pub fn keys(&self) -> Vec<VerifyingKey> {
+        self.root()
+            .into_iter()
+            .chain(self.records.iter().map(|r| r.next))
+            .collect()
+    }
+
+    pub fn contains(&self, key: &VerifyingKey) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Whether `later` is `earlier` or one of its successors, i.e. whether
+    /// trust placed in `earlier` carries over to `later`.
+    pub fn succeeds(&self, earlier: &VerifyingKey, later: &VerifyingKey) -> bool {
+        match (self.position(earlier), self.position(later)) {
+            (Some(e), Some(l)) => l >= e,
+            _ => false,
+        }
+    }
+
+    fn position(&self, key: &VerifyingKey) -> Option<usize> {
+        self.keys().iter().position(|k| k == key)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.records.iter().flat_map(|r| r.to_bytes()).collect()
+    }
+
+    /// Decode and verify a chain.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, IdentityError> {
+        if !bytes.len().is_multiple_of(RECORD_LEN) {
+            return Err(IdentityError::InvalidSuccession(format!(
+                "{} bytes is not a whole number of records",
+                bytes.len()
+            )));
+        }
+        let records = bytes
+            .chunks_exact(RECORD_LEN)
+            .map(|chunk| SuccessionRecord::from_bytes(chunk.try_into().expect("exact chunk")))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_records(records)
+    }
+
+    /// Lower-case hex of [`to_bytes`](Self::to_bytes), for config files.
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.to_bytes())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self, IdentityError> {
+        let hex = hex.trim();
+        let bad = || IdentityError::InvalidSuccession("chain is not valid hex".into());
+        if !hex.len().is_multiple_of(2) {
+            return Err(bad());
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(bad)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Load a chain file. A missing file is the empty chain.
+    pub fn load(path: &Path) -> Result<Self, IdentityError> {
+        match fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(IdentityError::Io {
+                path: path.to_path_buf(),
+                source: e,
+            }),
+        }
+    }
+
+    /// Atomically write the chain to `path`.
+    pub fn save(&self, path: &Path) -> Result<(), IdentityError> {
+        write_file(path, &self.to_bytes())
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write as _;
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+/// Check a hex pubkey against a hex allowlist, following rotations: the
+/// key passes if it is listed, or if one of `successions` (hex-encoded
+/// chains, as printed by `plasmd identity rotate`) shows it succeeding a
+/// listed key. Comparison is case-insensitive; chains that don't decode
+/// or verify are ignored.
+pub fn allowlist_permits(allowlist: &[String], successions: &[String], pubkey_hex: &str) -> bool {
+    let listed = |key: &str| allowlist.iter().any(|k| k.eq_ignore_ascii_case(key));
+    if listed(pubkey_hex) {
+        return true;
+    }
+    successions
+        .iter()
+        .filter_map(|hex| SuccessionChain::from_hex(hex).ok())
+        .any(|chain| {
+            let keys: Vec<String> = chain.keys().iter().map(|k| to_hex(k.as_bytes())).collect();
+            keys.iter()
+                .position(|k| k.eq_ignore_ascii_case(pubkey_hex))
+                .is_some_and(|at| keys[..=at].iter().any(|k| listed(k)))
+        })
+}
+
+/// Where the succession chain for the key at `identity_path` lives:
+/// `node.key` → `node.succession`.
+pub fn succession_path(identity_path: &Path) -> PathBuf {
+    identity_path.with_extension("succession")
+}
+
+/// The new key is staged here until the chain naming it is on disk.
+fn pending_path(identity_path: &Path) -> PathBuf {
+    let mut name = identity_path.as_os_str().to_owned();
+    name.push(".next");
+    PathBuf::from(name)
+}
+
+/// Rotate the identity stored at `identity_path` and return the new
+/// identity with its updated chain.
+///
+/// Crash-safe in three steps: the new key is staged next to the old one,
+/// the chain naming it is written, and only then does the new key replace
+/// the old. A rotation interrupted after the chain was written is finished
+/// by the next call instead of starting another. An encrypted key is
+/// replaced by one encrypted under the same passphrase.
+pub fn rotate_identity(
+    identity_path: &Path,
+) -> Result<(NodeIdentity, SuccessionChain), IdentityError> {
+    let chain_path = succession_path(identity_path);
+    let pending = pending_path(identity_path);
+    let mut chain = SuccessionChain::load(&chain_path)?;
+
+    match NodeIdentity::load(&pending) {
+        Ok(staged) if chain.current() == Some(staged.verifying_key()) => {
+            publish(&pending, identity_path)?;
+            return Ok((staged, chain));
+        }
+        // Staged but never recorded: nothing refers to it yet.
+        Ok(_) => remove(&pending)?,
+        Err(IdentityError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+
+    let current = NodeIdentity::load(identity_path)?;
+    // `load` already needed the passphrase if the key is encrypted.
+    let passphrase = match NodeIdentity::is_encrypted(identity_path)? {
+        true => Passphrase::from_env()?,
+        false => None,
+    };
+    let next = chain.rotate(&current)?;
+    create_new_secret(&pending, &next.to_stored_bytes(passphrase.as_ref()))?;
+    chain.save(&chain_path)?;
+    publish(&pending, identity_path)?;
+    Ok((next, chain))
+}
+
+fn publish(pending: &Path, identity_path: &Path) -> Result<(), IdentityError> {
+    fs::rename(pending, identity_path).map_err(|e| IdentityError::Io {
+        path: identity_path.to_path_buf(),
+        source: e,
+    })
+}
+
+fn remove(path: &Path) -> Result<(), IdentityError> {
+    fs::remove_file(path).map_err(|e| IdentityError::Io {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn chain_follows_rotations_forward_only() {
+        let a = NodeIdentity::generate();
+        let mut chain = SuccessionChain::default();
+        let b = chain.rotate(&a).unwrap();
+        let c = chain.rotate(&b).unwrap();
+        let (a, b, c) = (a.verifying_key(), b.verifying_key(), c.verifying_key());
+
+        assert_eq!(chain.root(), Some(a));
+        assert_eq!(chain.current(), Some(c));
+        assert_eq!(chain.keys(), vec![a, b, c]);
+        assert!(chain.succeeds(&a, &c));
+        assert!(chain.succeeds(&b, &b));
+        assert!(!chain.succeeds(&c, &a), "trust never flows backwards");
+        assert!(!chain.succeeds(&a, &NodeIdentity::generate().verifying_key()));
+
+        let decoded = SuccessionChain::from_hex(&chain.to_hex()).unwrap();
+        assert_eq!(decoded, chain);
+    }
+
+    #[test]
+    fn only_the_current_key_can_extend_the_chain() {
+        let a = NodeIdentity::generate();
+        let mut chain = SuccessionChain::default();
+        chain.rotate(&a).unwrap();
+        assert!(matches!(chain.rotate(&a), Err(IdentityError::NotChainHead)));
+
+        // A forged record from a stranger doesn't link.
+        let stranger = NodeIdentity::generate();
+        let forged = SuccessionRecord::sign(&stranger, NodeIdentity::generate().verifying_key(), 1, 0);
+        assert!(chain.push(forged).is_err());
+    }
+
+    #[test]
+    fn allowlist_follows_successions() {
+        let a = NodeIdentity::generate();
+        let mut chain = SuccessionChain::default();
+        let b = chain.rotate(&a).unwrap();
+        let (a_hex, b_hex) = (to_hex(a.verifying_key().as_bytes()), to_hex(b.verifying_key().as_bytes()));
+        let chains = vec![chain.to_hex()];
+
+        assert!(allowlist_permits(&[a_hex.to_uppercase()], &[], &a_hex));
+        assert!(!allowlist_permits(std::slice::from_ref(&a_hex), &[], &b_hex));
+        assert!(allowlist_permits(std::slice::from_ref(&a_hex), &chains, &b_hex));
+        assert!(!allowlist_permits(std::slice::from_ref(&b_hex), &chains, &a_hex));
+        assert!(!allowlist_permits(&[a_hex], &["zz".into()], &b_hex));
+    }
+
+    #[test]
+    fn tampered_bytes_fail_verification() {
+        let mut chain = SuccessionChain::default();
+        chain.rotate(&NodeIdentity::generate()).unwrap();
+        let mut bytes = chain.to_bytes();
+        bytes[72] ^= 1; // rotated_at
+        assert!(SuccessionChain::from_bytes(&bytes).is_err());
+        assert!(SuccessionChain::from_bytes(&bytes[..RECORD_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn rotate_identity_replaces_key_and_records_succession() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("node.key");
+        let original = NodeIdentity::load_or_create(&path).unwrap();
+
+        let (rotated, chain) = rotate_identity(&path).unwrap();
+        assert_eq!(NodeIdentity::load(&path).unwrap().peer_id_bytes(), rotated.peer_id_bytes());
+        assert_eq!(SuccessionChain::load(&succession_path(&path)).unwrap(), chain);
+        assert!(chain.succeeds(&original.verifying_key(), &rotated.verifying_key()));
+        assert!(!pending_path(&path).exists());
+
+        let (again, chain) = rotate_identity(&path).unwrap();
+        assert_eq!(chain.records().len(), 2);
+        assert_eq!(chain.current(), Some(again.verifying_key()));
+    }
+
+    #[test]
+    fn rotate_identity_finishes_an_interrupted_rotation() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("node.key");
+        let original = NodeIdentity::load_or_create(&path).unwrap();
+
+        // Crash after the chain was written but before the key moved.
+        let mut chain = SuccessionChain::default();
+        let staged = chain.rotate(&original).unwrap();
+        staged.save(&pending_path(&path)).unwrap();
+        chain.save(&succession_path(&path)).unwrap();
+
+        let (rotated, chain) = rotate_identity(&path).unwrap();
+        assert_eq!(rotated.peer_id_bytes(), staged.peer_id_bytes());
+        assert_eq!(chain.records().len(), 1);
+        assert_eq!(NodeIdentity::load(&path).unwrap().peer_id_bytes(), staged.peer_id_bytes());
+    }
+}
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Private-network mode: who may connect, and with which key.
+//!
+//! Two independent controls, both set on [`crate::DiscoveryConfig`]:
+//!
+//! - A libp2p private-network pre-shared key ([`NetworkKey`]). Every TCP
+//!   connection is wrapped in the pnet handshake before noise, so a peer
+//!   without the key can't complete a connection at all — it never sees
+//!   the DHT or the job protocols. pnet only covers TCP; QUIC is disabled
+//!   while a key is set.
+//! - A [`PeerFilter`] allowlist or denylist of `PeerId`s, enforced when a
+//!   connection is established and again on every inbound job offer and
+//!   job relay, before the handler runs.
+//!
+//! The key file uses the go-ipfs `swarm.key` format:
+//!
+//! ```text
+//! /key/swarm/psk/1.0.0/
+//! /base16/
+//! <64 hex chars>
+//! ```
+
+use std::{collections::HashSet, fmt, path::Path};
+
+use anyhow::{anyhow, bail, Context, Result};
+use libp2p::{pnet::PreSharedKey, PeerId};
+
+/// A private-network pre-shared key. `Debug` shows only the fingerprint,
+/// so the key never ends up in a log line via a config dump.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct NetworkKey(PreSharedKey);
+
+impl NetworkKey {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(PreSharedKey::new(bytes))
+    }
+
+    /// Read a key from a `swarm.key` file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read network key {}", path.display()))?;
+        text.trim()
+            .parse()
+            .map(Self)
+            .map_err(|e| anyhow!("Invalid network key {}: {e}", path.display()))
+    }
+
+    /// Hex fingerprint, safe to log and compare between nodes.
+    pub fn fingerprint(&self) -> String {
+        self.0.fingerprint().to_string()
+    }
+
+    pub(crate) fn psk(&self) -> PreSharedKey {
+        self.0
+    }
+}
+
+impl fmt::Debug for NetworkKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NetworkKey").field(&self.fingerprint()).finish()
+    }
+}
+
+/// Which remote peers this node talks to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PeerFilter {
+    /// Any peer (the default).
+    #[default]
+    Open,
+    /// Only these peers. Relays and bootstrap peers must be listed too.
+    Allow(HashSet<PeerId>),
+    /// Any peer except these.
+    Deny(HashSet<PeerId>),
+}
+
+impl PeerFilter {
+    /// Build a filter from configured peer-id strings. At most one of the
+    /// lists may be non-empty; both empty means [`PeerFilter::Open`].
+    pub fn from_lists(allow: &[String], deny: &[String]) -> Result<Self> {
+        if !allow.is_empty() && !deny.is_empty() {
+            bail!("Configure either a peer allowlist or a denylist, not both");
+        }
+        let parse = |ids: &[String]| -> Result<HashSet<PeerId>> {
+            ids.iter()
+                .map(|s| s.parse().with_context(|| format!("Invalid peer id {s:?}")))
+                .collect()
+        };
+        Ok(if !allow.is_empty() {
+            PeerFilter::Allow(parse(allow)?)
+        } else if !deny.is_empty() {
+            PeerFilter::Deny(parse(deny)?)
+        } else {
+            PeerFilter::Open
+        })
+    }
+
+    /// Whether `peer` may connect and send requests.
+    pub fn permits(&self, peer: &PeerId) -> bool {
+        match self {
+            PeerFilter::Open => true,
+            PeerFilter::Allow(peers) => peers.contains(peer),
+            PeerFilter::Deny(peers) => !peers.contains(peer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_lists_are_exclusive() {
+        let a = PeerId::random();
+        let b = PeerId::random();
+
+        let allow = PeerFilter::from_lists(&[a.to_string()], &[]).unwrap();
+        assert!(allow.permits(&a) && !allow.permits(&b));
+        let deny = PeerFilter::from_lists(&[], &[a.to_string()]).unwrap();
+        assert!(!deny.permits(&a) && deny.permits(&b));
+        assert_eq!(PeerFilter::from_lists(&[], &[]).unwrap(), PeerFilter::Open);
+
+        assert!(PeerFilter::from_lists(&[a.to_string()], &[b.to_string()]).is_err());
+        assert!(PeerFilter::from_lists(&["not-a-peer".into()], &[]).is_err());
+    }
+
+    #[test]
+    fn network_key_reads_swarm_key_format_and_hides_itself() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("swarm.key");
+        let key = NetworkKey::new([0x5a; 32]);
+        std::fs::write(&path, format!("{}\n", key.psk())).unwrap();
+        assert_eq!(NetworkKey::load(&path).unwrap(), key);
+        assert!(!format!("{key:?}").contains("5a5a5a"));
+
+        std::fs::write(&path, "/key/swarm/psk/1.0.0/\n/base16/\nzz\n").unwrap();
+        assert!(NetworkKey::load(&path).is_err());
+    }
+}
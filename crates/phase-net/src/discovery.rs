@@ -14,31 +14,80 @@
 //! by the *same* swarm. A driver task lets the public API send a command and
 //! receive the response without the caller having to interleave it with
 //! swarm polling.
+//!
+//! ## NAT traversal
+//!
+//! Two home machines behind NAT can't dial each other directly, so the swarm
+//! also carries the circuit relay v2 client, DCUtR and AutoNAT, plus
+//! `identify` which the other three lean on for observed addresses:
+//!
+//! - A node with [`DiscoveryConfig::relays`] reserves a slot on each relay
+//!   and becomes reachable at `<relay>/p2p-circuit/p2p/<self>`.
+//! - A node with [`DiscoveryConfig::relay_server`] hands out those
+//!   reservations. It needs a confirmed external address to put in them —
+//!   either [`DiscoveryConfig::external_addrs`] or one AutoNAT confirms.
+//! - Once two peers are talking over a circuit, DCUtR tries to hole-punch a
+//!   direct connection. If that fails the job still flows over the relay,
+//!   within the circuit limits below.
+//! - AutoNAT probes whether our own listen addresses are publicly reachable;
+//!   the verdict is exposed as [`Discovery::reachability`].
+//!
+//! ## Blob transfer
+//!
+//! A provider behind NAT can advertise a blob but not serve it over HTTP.
+//! The `/phase/blob/1.0.0` protocol carries byte ranges of a blob and its
+//! outboard over whatever connection the swarm already has, direct or
+//! hole-punched. [`Discovery::request_blob`] asks; the handler installed
+//! with [`Discovery::set_blob_handler`] answers, behind the same peer
+//! filter and per-peer limits as job relays.
+//!
+//! ## Private networks
+//!
+//! A fleet that shouldn't mix with the public network sets
+//! [`DiscoveryConfig::network_key`] (pnet over TCP, no QUIC) and/or a
+//! [`DiscoveryConfig::peer_filter`]; see [`crate::access`].
 
 use anyhow::{anyhow, Context, Result};
 use ed25519_dalek::SigningKey;
-use futures::StreamExt;
+use futures::{FutureExt, StreamExt};
 use libp2p::{
+    allow_block_list::{self, AllowedPeers, BlockedPeers},
+    autonat, connection_limits,
+    core::upgrade,
+    dcutr, identify,
     identity::Keypair,
     kad::{
-        store::MemoryStore, Behaviour as KademliaBehaviour, Event as KademliaEvent,
-        GetRecordOk, Mode as KademliaMode, QueryId, QueryResult,
+        store::RecordStore, Behaviour as KademliaBehaviour, Config as KademliaConfig, Event as KademliaEvent,
+        GetProvidersOk, GetRecordOk, InboundRequest, Mode as KademliaMode, QueryId, QueryResult, Record,
+        StoreInserts,
     },
     mdns,
+    multiaddr::Protocol,
+    ping,
+    pnet::PnetConfig,
+    relay,
     request_response::{self, cbor, json, OutboundRequestId, ProtocolSupport, ResponseChannel},
-    swarm::{NetworkBehaviour, SwarmEvent},
-    Multiaddr, PeerId, StreamProtocol, Swarm, SwarmBuilder,
+    swarm::{behaviour::toggle::Toggle, dial_opts::DialOpts, NetworkBehaviour, SwarmEvent},
+    Multiaddr, PeerId, StreamProtocol, Swarm, SwarmBuilder, Transport,
 };
 use phase_identity::NodeIdentity;
 use std::{
     collections::HashMap,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tracing::{debug, info, warn};
 
-use crate::peer::PeerCapabilities;
-use crate::protocol::{JobOffer, JobRelayRequest, JobRelayResponse, JobResponse, RejectionReason};
+use crate::access::{NetworkKey, PeerFilter};
+use crate::limits::{LimitsConfig, PeerRateLimiter};
+use crate::peer::{BandwidthBucket, LatencyBucket, PeerCapabilities, PeerStats};
+use crate::record::{unix_ms_now, SignedRecord, ValidatorRegistry};
+use crate::store::{instant_from_unix_ms, PeerSnapshot, PersistentStore, StoreConfig};
+use crate::protocol::{
+    BlobRequest, BlobResponse, JobOffer, JobRelayRequest, JobRelayResponse, JobResponse,
+    RejectionReason, BLOB_MAX_RANGE_BYTES,
+};
 
 /// Wire protocol identifier for the JobOffer request/response exchange.
 const JOB_OFFER_PROTOCOL: &str = "/phase/job-offer/1.0.0";
@@ -71,6 +120,37 @@ pub type JobRelayHandler = std::sync::Arc<
         + 'static,
 >;
 
+/// Blob range protocol identifier. Lets a peer read byte ranges of a
+/// content-addressed blob (and its outboard) from a provider whose HTTP
+/// port it cannot reach. The bytes are checked by the requester exactly as
+/// over HTTP.
+///
+/// Over a `/p2p-circuit` connection every range counts against the relay
+/// server's [`CIRCUIT_MAX_BYTES`], so multi-gigabyte transfers only work
+/// once DCUtR has upgraded the connection to a direct one.
+const BLOB_PROTOCOL: &str = "/phase/blob/1.0.0";
+
+/// Callback that serves inbound blob range requests, installed with
+/// [`Discovery::set_blob_handler`]. phase-net has no blob store of its
+/// own; `phase-artifact-server` supplies a handler backed by its
+/// `ArtifactStore`. Receives the requesting peer's `PeerId` so a handler
+/// can apply its own policy.
+pub type BlobHandler = std::sync::Arc<
+    dyn Fn(
+            PeerId,
+            BlobRequest,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = BlobResponse> + Send>>
+        + Send
+        + Sync
+        + 'static,
+>;
+
+/// A blob request is a hash, a part, an offset and a length.
+const BLOB_MAX_REQUEST_BYTES: usize = 4 * 1024;
+
+/// One range plus CBOR framing and an error string's worth of headroom.
+const BLOB_MAX_RESPONSE_BYTES: usize = BLOB_MAX_RANGE_BYTES as usize + 64 * 1024;
+
 /// SEC-06: inbound relay request-size cap. A `SignedManifest<JobSpec>` for an
 /// inference job is a few KB (a chat history plus a signature); 256 KiB is a
 /// generous ceiling that rejects buffer-exhaustion floods at the libp2p codec
@@ -86,17 +166,79 @@ const RELAY_MAX_RESPONSE_BYTES: usize = 8 * 1024 * 1024;
 /// SEC-06: JobOffer is a tiny fixed-shape struct; 64 KiB each way is ample.
 const OFFER_MAX_BYTES: usize = 64 * 1024;
 
+/// `identify` protocol version string. Peers on a different major version
+/// still identify each other; this is informational.
+const IDENTIFY_PROTOCOL: &str = "/phase/1.0.0";
+
+/// Per-circuit byte budget on a relay server. libp2p's default (128 KiB) is
+/// sized for DCUtR coordination only; when hole punching fails the job
+/// itself rides the circuit, so allow a maximal relay request and response
+/// plus the Kademlia/identify chatter sharing the connection.
+const CIRCUIT_MAX_BYTES: u64 = 2 * (RELAY_MAX_REQUEST_BYTES + RELAY_MAX_RESPONSE_BYTES) as u64;
+
+/// Per-circuit lifetime on a relay server. Matches the job-relay request
+/// timeout with headroom, so a relayed inference job isn't cut off by the
+/// circuit before the requester gives up on it.
+const CIRCUIT_MAX_DURATION: Duration = Duration::from_secs(6 * 60);
+
+/// How often the driver writes dirty DHT state to
+/// [`DiscoveryConfig::state_dir`].
+const PERSIST_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Upper bound on the per-peer measurement table. Past it the peer measured
+/// longest ago is dropped, so a churny mesh can't grow the table unbounded.
+const MAX_TRACKED_PEERS: usize = 1024;
+
+/// Measurements older than this don't count towards the buckets this node
+/// publishes about itself.
+const MEASUREMENT_FRESH_SECS: u64 = 10 * 60;
+
+/// Lifetime of the signed capability record. Long enough to outlast a
+/// missed re-advertisement, short enough that the measured buckets in it
+/// don't outlive their freshness by much.
+const CAPABILITY_RECORD_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How often an advertising node re-signs its capability record, so the
+/// measured buckets and live load in it track [`MEASUREMENT_FRESH_SECS`].
+const CAPABILITY_REPUBLISH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Kademlia key of the capability record `peer` publishes about itself
+/// (JSON `PeerCapabilities`, measured buckets included). Fetch with
+/// [`Discovery::get_kad_record`].
+pub fn capability_record_key(peer: &PeerId) -> Vec<u8> {
+    format!("/phase/peer-capabilities/{peer}").into_bytes()
+}
+
 /// Combined network behaviour: Kademlia DHT + mDNS local discovery +
-/// JSON-coded request/response for JobOffer.
+/// JSON-coded request/response for JobOffer, plus the NAT-traversal set
+/// (identify, circuit relay v2 server/client, DCUtR, AutoNAT).
 #[derive(NetworkBehaviour)]
 struct CombinedBehaviour {
-    kademlia: KademliaBehaviour<MemoryStore>,
-    mdns: mdns::tokio::Behaviour,
+    /// Connection-level [`PeerFilter`]: exactly one of these two is on
+    /// when a list is configured.
+    allowed_peers: Toggle<allow_block_list::Behaviour<AllowedPeers>>,
+    blocked_peers: Toggle<allow_block_list::Behaviour<BlockedPeers>>,
+    connection_limits: connection_limits::Behaviour,
+    kademlia: KademliaBehaviour<PersistentStore>,
+    mdns: Toggle<mdns::tokio::Behaviour>,
+    identify: identify::Behaviour,
+    /// Keep-alive pings; every round trip feeds the peer's measured RTT.
+    ping: ping::Behaviour,
+    /// Circuit relay v2 server. Only enabled with
+    /// [`DiscoveryConfig::relay_server`].
+    relay: Toggle<relay::Behaviour>,
+    /// Circuit relay v2 client. Always on: it costs nothing until a
+    /// `/p2p-circuit` address is dialled or listened on.
+    relay_client: relay::client::Behaviour,
+    dcutr: Toggle<dcutr::Behaviour>,
+    autonat: Toggle<autonat::Behaviour>,
     job_offer: json::Behaviour<JobOffer, JobResponse>,
     /// LUCID M5 peer-relay request/response. CBOR-encoded so the binary
     /// payload (bincode `SignedManifest<JobSpec>` / `Vec<JobEvent>`) doesn't
     /// suffer the 4-5× blow-up JSON's u8 arrays cause.
     job_relay: cbor::Behaviour<JobRelayRequest, JobRelayResponse>,
+    /// Blob byte ranges. CBOR for the same reason as `job_relay`.
+    blob: cbor::Behaviour<BlobRequest, BlobResponse>,
 }
 
 /// Discovery configuration.
@@ -117,6 +259,55 @@ pub struct DiscoveryConfig {
     /// generated (matches the legacy ephemeral behaviour and is appropriate
     /// for tests that don't care about identity continuity).
     pub identity: Option<NodeIdentity>,
+
+    /// mDNS local-network discovery. On by default; in-process tests turn
+    /// it off so peers only find each other through the addresses they are
+    /// given.
+    pub mdns: bool,
+
+    /// Serve circuit relay v2 reservations so NATed peers can be reached
+    /// through this node. Only useful on a publicly reachable node.
+    pub relay_server: bool,
+
+    /// Relays to reserve a slot on, as `/…/p2p/<relay-peer-id>` multiaddrs.
+    /// Once a reservation is accepted the node also listens on
+    /// `<relay>/p2p-circuit`.
+    pub relays: Vec<String>,
+
+    /// Addresses this node is known to be publicly reachable at (a VPS, a
+    /// forwarded port). Confirmed up front rather than waiting for AutoNAT;
+    /// a relay server advertises these in its reservations.
+    pub external_addrs: Vec<String>,
+
+    /// Try to upgrade relayed connections to direct ones via DCUtR.
+    pub hole_punching: bool,
+
+    /// Probe our own reachability with AutoNAT.
+    pub autonat: bool,
+
+    /// Directory for DHT state that should outlive a restart: the Kademlia
+    /// record store and a routing-table snapshot whose peers are redialled
+    /// on start as implicit bootstrap peers (see [`crate::store`]). `None`
+    /// keeps everything in memory.
+    pub state_dir: Option<PathBuf>,
+
+    /// Size and TTL limits for the Kademlia record store.
+    pub store: StoreConfig,
+
+    /// Admission rules per key namespace, applied when publishing, when a
+    /// peer PUTs a record here, and to GET results. The default requires
+    /// signed records under Phase's own prefixes (see [`crate::record`]).
+    pub validators: ValidatorRegistry,
+
+    /// Private-network key. When set, every TCP connection runs the pnet
+    /// handshake first and QUIC is disabled (see [`crate::access`]).
+    pub network_key: Option<NetworkKey>,
+
+    /// Which peers may connect and send job offers or relays.
+    pub peer_filter: PeerFilter,
+
+    /// Connection caps and per-peer request rates (see [`crate::limits`]).
+    pub limits: LimitsConfig,
 }
 
 impl Default for DiscoveryConfig {
@@ -126,10 +317,82 @@ impl Default for DiscoveryConfig {
             bootstrap_peers: Vec::new(),
             capabilities: PeerCapabilities::default(),
             identity: None,
+            mdns: true,
+            relay_server: false,
+            relays: Vec::new(),
+            external_addrs: Vec::new(),
+            hole_punching: true,
+            autonat: true,
+            state_dir: None,
+            store: StoreConfig::default(),
+            validators: ValidatorRegistry::default(),
+            network_key: None,
+            peer_filter: PeerFilter::Open,
+            limits: LimitsConfig::default(),
+        }
+    }
+}
+
+/// Whether this node is reachable from the public internet, as last
+/// determined by AutoNAT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reachability {
+    /// No verdict yet (or AutoNAT disabled).
+    Unknown,
+    /// A peer dialled us back on `address`.
+    Public { address: String },
+    /// Dial-backs fail; we're behind NAT or a firewall and need a relay.
+    Private,
+}
+
+impl From<&autonat::NatStatus> for Reachability {
+    fn from(status: &autonat::NatStatus) -> Self {
+        match status {
+            autonat::NatStatus::Public(addr) => Reachability::Public {
+                address: addr.to_string(),
+            },
+            autonat::NatStatus::Private => Reachability::Private,
+            autonat::NatStatus::Unknown => Reachability::Unknown,
         }
     }
 }
 
+/// Buffered events per [`Discovery::subscribe`] receiver before a slow
+/// subscriber starts missing them (it sees `RecvError::Lagged`).
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Something that happened on the network, as seen by [`Discovery::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkEvent {
+    /// mDNS found `peer` on the local network.
+    PeerDiscovered { peer: PeerId, address: String },
+    /// An mDNS record for `peer` timed out.
+    PeerExpired { peer: PeerId, address: String },
+    /// First connection to `peer` is up.
+    PeerConnected { peer: PeerId, address: String },
+    /// Last connection to `peer` closed. `error` is set when it failed
+    /// (I/O error, remote vanished) rather than closing cleanly, e.g. on
+    /// the idle timeout.
+    PeerDisconnected { peer: PeerId, error: bool },
+    /// `peer` was added to (or updated in) the Kademlia routing table.
+    RoutingUpdated { peer: PeerId, is_new_peer: bool },
+    /// AutoNAT changed its verdict.
+    ReachabilityChanged(Reachability),
+    /// A peer sent a job offer; `accepted` is our answer.
+    JobOfferReceived {
+        peer: PeerId,
+        job_id: String,
+        accepted: bool,
+    },
+    /// An inbound job relay was handed to the handler.
+    JobRelayStarted { peer: PeerId },
+    /// The handler answered an inbound job relay.
+    JobRelayFinished { peer: PeerId, ok: bool },
+    /// An inbound job relay was refused before reaching the handler
+    /// (peer filter, rate limit, concurrency cap, no handler).
+    JobRelayRefused { peer: PeerId, reason: String },
+}
+
 /// Commands the public `Discovery` handle sends to the background driver task.
 enum Command {
     Listen {
@@ -166,6 +429,18 @@ enum Command {
         key: Vec<u8>,
         reply: oneshot::Sender<Result<Vec<Vec<u8>>>>,
     },
+    /// Add this node to the Kademlia provider set for `key`.
+    StartProviding {
+        key: Vec<u8>,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    /// Stop re-publishing this node as a provider for `key`.
+    StopProviding { key: Vec<u8> },
+    /// Collect the provider set for `key`, folded like `GetKadRecord`.
+    GetProviders {
+        key: Vec<u8>,
+        reply: oneshot::Sender<Result<Vec<PeerId>>>,
+    },
     SendJobOffer {
         peer: PeerId,
         offer: JobOffer,
@@ -191,15 +466,48 @@ enum Command {
     /// inbound relay request with a structured "no handler" reason so a
     /// daemon that never wired one in fails closed.
     SetJobRelayHandler { handler: Option<JobRelayHandler> },
+    /// Read a blob range from `peer` over the blob protocol.
+    SendBlobRequest {
+        peer: PeerId,
+        request: BlobRequest,
+        reply: oneshot::Sender<Result<BlobResponse>>,
+    },
+    /// Install (or replace) the callback that serves inbound
+    /// `BlobRequest`s. With none installed every request is refused.
+    SetBlobHandler { handler: Option<BlobHandler> },
+    /// Every address the swarm is listening on, including `/p2p-circuit`
+    /// addresses from accepted relay reservations.
+    ListenAddrs {
+        reply: oneshot::Sender<Vec<String>>,
+    },
+    /// Confirm an externally reachable address (see
+    /// [`DiscoveryConfig::external_addrs`]).
+    AddExternalAddr {
+        addr: String,
+        reply: oneshot::Sender<Result<()>>,
+    },
+    Reachability {
+        reply: oneshot::Sender<Reachability>,
+    },
+    /// Snapshot of the per-peer measurement table.
+    PeerStats {
+        reply: oneshot::Sender<Vec<PeerStats>>,
+    },
+    /// Write DHT state to the state dir now.
+    Persist {
+        reply: oneshot::Sender<Result<()>>,
+    },
 }
 
 /// Peer discovery service. Owns no swarm directly — instead holds a handle
 /// to the background driver task that does.
 pub struct Discovery {
     local_peer_id: PeerId,
+    identity: NodeIdentity,
     signing_key: SigningKey,
     capabilities: PeerCapabilities,
     cmd_tx: mpsc::Sender<Command>,
+    events: broadcast::Sender<NetworkEvent>,
     /// Background driver task. `run()` takes this to await shutdown; if the
     /// daemon never calls `run()`, the task is torn down when Discovery
     /// drops (the cmd_rx side sees its last Sender go and exits).
@@ -238,74 +546,181 @@ impl Discovery {
         // with "protocol not supported". For our small-network use (mDNS
         // discovery on a LAN, no global DHT bootstrap), we always want to
         // be a server so other peers can resolve our advertised records.
-        let store = MemoryStore::new(local_peer_id);
+        let store = PersistentStore::new(
+            local_peer_id,
+            &config.store,
+            config.state_dir.as_deref(),
+            &config.validators,
+        );
+        //
+        // Inbound PUTs are filtered: Kademlia hands them to the driver,
+        // which stores them only if the namespace validator accepts.
         let kad_behaviour = {
-            let mut k = KademliaBehaviour::new(local_peer_id, store);
+            let mut kad_config = KademliaConfig::new(libp2p::kad::PROTOCOL_NAME);
+            kad_config.set_record_filtering(StoreInserts::FilterBoth);
+            let mut k = KademliaBehaviour::with_config(local_peer_id, store, kad_config);
             k.set_mode(Some(KademliaMode::Server));
             k
         };
 
-        // Build swarm with tokio executor.
-        let mut swarm = SwarmBuilder::with_existing_identity(keypair)
-            .with_tokio()
-            .with_tcp(
-                libp2p::tcp::Config::default(),
-                libp2p::noise::Config::new,
-                libp2p::yamux::Config::default,
-            )?
-            .with_quic()
-            .with_behaviour(|key| {
-                // Create mDNS behaviour for local network discovery.
-                let mdns_behaviour = mdns::tokio::Behaviour::new(
-                    mdns::Config::default(),
-                    key.public().to_peer_id(),
-                )?;
-
-                // JSON-coded request/response for JobOffer. SEC-06: cap both
-                // directions — a JobOffer is a small fixed-shape struct, so an
-                // oversized frame is always abuse. The size maxima live on the
-                // codec; `with_codec` installs the configured one.
-                let offer_codec = json::codec::Codec::<JobOffer, JobResponse>::default()
-                    .set_request_size_maximum(OFFER_MAX_BYTES as u64)
-                    .set_response_size_maximum(OFFER_MAX_BYTES as u64);
-                let job_offer = json::Behaviour::<JobOffer, JobResponse>::with_codec(
-                    offer_codec,
-                    [(
-                        StreamProtocol::new(JOB_OFFER_PROTOCOL),
-                        ProtocolSupport::Full,
-                    )],
-                    request_response::Config::default(),
-                );
+        // The behaviour is the same either way; only the transport stack
+        // differs between open and private-network mode below.
+        let build_behaviour = |key: &Keypair,
+                               relay_client: relay::client::Behaviour|
+         -> Result<CombinedBehaviour, Box<dyn std::error::Error + Send + Sync>> {
+            let peer_id = key.public().to_peer_id();
+
+            let (allowed_peers, blocked_peers) = match &config.peer_filter {
+                PeerFilter::Open => (None, None),
+                PeerFilter::Allow(peers) => {
+                    let mut b = allow_block_list::Behaviour::<AllowedPeers>::default();
+                    for p in peers {
+                        b.allow_peer(*p);
+                    }
+                    (Some(b), None)
+                }
+                PeerFilter::Deny(peers) => {
+                    let mut b = allow_block_list::Behaviour::<BlockedPeers>::default();
+                    for p in peers {
+                        b.block_peer(*p);
+                    }
+                    (None, Some(b))
+                }
+            };
 
-                // CBOR-coded request/response for the LUCID M5 job relay.
-                // The default per-request timeout is generous enough for a
-                // batch-shaped inference response (we set a wall-clock cap
-                // on the requesting side anyway). SEC-06: cap request and
-                // response sizes so an oversized frame is rejected at the
-                // codec (`io.take(max)`) before it is ever buffered/parsed.
-                let relay_codec =
-                    cbor::codec::Codec::<JobRelayRequest, JobRelayResponse>::default()
-                        .set_request_size_maximum(RELAY_MAX_REQUEST_BYTES as u64)
-                        .set_response_size_maximum(RELAY_MAX_RESPONSE_BYTES as u64);
-                let job_relay = cbor::Behaviour::<JobRelayRequest, JobRelayResponse>::with_codec(
-                    relay_codec,
-                    [(
-                        StreamProtocol::new(JOB_RELAY_PROTOCOL),
-                        ProtocolSupport::Full,
-                    )],
-                    request_response::Config::default()
-                        .with_request_timeout(Duration::from_secs(5 * 60)),
-                );
+            // Create mDNS behaviour for local network discovery.
+            let mdns_behaviour = if config.mdns {
+                Some(mdns::tokio::Behaviour::new(mdns::Config::default(), peer_id)?)
+            } else {
+                None
+            };
 
-                Ok(CombinedBehaviour {
-                    kademlia: kad_behaviour,
-                    mdns: mdns_behaviour,
-                    job_offer,
-                    job_relay,
-                })
-            })?
-            .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
-            .build();
+            let identify = identify::Behaviour::new(
+                identify::Config::new(IDENTIFY_PROTOCOL.to_string(), key.public())
+                    .with_agent_version(format!("phase-net/{}", env!("CARGO_PKG_VERSION"))),
+            );
+            let ping = ping::Behaviour::new(ping::Config::new());
+
+            // Circuit relay server. Reservation and circuit-count limits
+            // keep libp2p's defaults; only the per-circuit budget is
+            // raised so a relayed job fits (see CIRCUIT_MAX_BYTES).
+            let relay_server = config.relay_server.then(|| {
+                relay::Behaviour::new(
+                    peer_id,
+                    relay::Config {
+                        max_circuit_bytes: CIRCUIT_MAX_BYTES,
+                        max_circuit_duration: CIRCUIT_MAX_DURATION,
+                        ..relay::Config::default()
+                    },
+                )
+            });
+            let dcutr = config.hole_punching.then(|| dcutr::Behaviour::new(peer_id));
+            let autonat = config
+                .autonat
+                .then(|| autonat::Behaviour::new(peer_id, autonat::Config::default()));
+
+            // JSON-coded request/response for JobOffer. SEC-06: cap both
+            // directions — a JobOffer is a small fixed-shape struct, so an
+            // oversized frame is always abuse. The size maxima live on the
+            // codec; `with_codec` installs the configured one.
+            let offer_codec = json::codec::Codec::<JobOffer, JobResponse>::default()
+                .set_request_size_maximum(OFFER_MAX_BYTES as u64)
+                .set_response_size_maximum(OFFER_MAX_BYTES as u64);
+            let job_offer = json::Behaviour::<JobOffer, JobResponse>::with_codec(
+                offer_codec,
+                [(
+                    StreamProtocol::new(JOB_OFFER_PROTOCOL),
+                    ProtocolSupport::Full,
+                )],
+                request_response::Config::default(),
+            );
+
+            // CBOR-coded request/response for the LUCID M5 job relay.
+            // The default per-request timeout is generous enough for a
+            // batch-shaped inference response (we set a wall-clock cap
+            // on the requesting side anyway). SEC-06: cap request and
+            // response sizes so an oversized frame is rejected at the
+            // codec (`io.take(max)`) before it is ever buffered/parsed.
+            let relay_codec =
+                cbor::codec::Codec::<JobRelayRequest, JobRelayResponse>::default()
+                    .set_request_size_maximum(RELAY_MAX_REQUEST_BYTES as u64)
+                    .set_response_size_maximum(RELAY_MAX_RESPONSE_BYTES as u64);
+            let job_relay = cbor::Behaviour::<JobRelayRequest, JobRelayResponse>::with_codec(
+                relay_codec,
+                [(
+                    StreamProtocol::new(JOB_RELAY_PROTOCOL),
+                    ProtocolSupport::Full,
+                )],
+                request_response::Config::default()
+                    .with_request_timeout(Duration::from_secs(5 * 60)),
+            );
+
+            // CBOR-coded blob ranges. A range is at most
+            // BLOB_MAX_RANGE_BYTES, so the response cap is that plus
+            // framing; anything larger is refused at the codec.
+            let blob_codec = cbor::codec::Codec::<BlobRequest, BlobResponse>::default()
+                .set_request_size_maximum(BLOB_MAX_REQUEST_BYTES as u64)
+                .set_response_size_maximum(BLOB_MAX_RESPONSE_BYTES as u64);
+            let blob = cbor::Behaviour::<BlobRequest, BlobResponse>::with_codec(
+                blob_codec,
+                [(StreamProtocol::new(BLOB_PROTOCOL), ProtocolSupport::Full)],
+                request_response::Config::default().with_request_timeout(Duration::from_secs(60)),
+            );
+
+            Ok(CombinedBehaviour {
+                allowed_peers: allowed_peers.into(),
+                blocked_peers: blocked_peers.into(),
+                connection_limits: connection_limits::Behaviour::new(
+                    config.limits.connection_limits(),
+                ),
+                kademlia: kad_behaviour,
+                mdns: mdns_behaviour.into(),
+                identify,
+                ping,
+                relay: relay_server.into(),
+                relay_client,
+                dcutr: dcutr.into(),
+                autonat: autonat.into(),
+                job_offer,
+                job_relay,
+                blob,
+            })
+        };
+
+        // Build swarm with tokio executor.
+        let idle = |c: libp2p::swarm::Config| c.with_idle_connection_timeout(Duration::from_secs(60));
+        let builder = SwarmBuilder::with_existing_identity(keypair).with_tokio();
+        let mut swarm = match config.network_key {
+            None => builder
+                .with_tcp(
+                    libp2p::tcp::Config::default(),
+                    libp2p::noise::Config::new,
+                    libp2p::yamux::Config::default,
+                )?
+                .with_quic()
+                .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)?
+                .with_behaviour(build_behaviour)?
+                .with_swarm_config(idle)
+                .build(),
+            // pnet wraps the raw TCP socket, under noise. QUIC has its own
+            // handshake that pnet can't wrap, so it is left out entirely.
+            Some(network_key) => {
+                info!("Private network mode, key fingerprint {}", network_key.fingerprint());
+                let psk = network_key.psk();
+                builder
+                    .with_other_transport(|key| -> Result<_, Box<dyn std::error::Error + Send + Sync>> {
+                        Ok(libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::default())
+                            .and_then(move |socket, _| PnetConfig::new(psk).handshake(socket))
+                            .upgrade(upgrade::Version::V1Lazy)
+                            .authenticate(libp2p::noise::Config::new(key)?)
+                            .multiplex(libp2p::yamux::Config::default()))
+                    })?
+                    .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)?
+                    .with_behaviour(build_behaviour)?
+                    .with_swarm_config(idle)
+                    .build()
+            }
+        };
 
         // Wire up bootstrap peers. Format expected:
         //   /ip4/x.x.x.x/tcp/<port>/p2p/<peer-id>
@@ -339,22 +754,76 @@ impl Discovery {
             }
         }
 
+        // Peers from the last run's routing table are implicit bootstrap
+        // peers: a small private network can come back up with no fixed
+        // bootstrap node as long as any one of them is still reachable.
+        if let Some(dir) = &config.state_dir {
+            let known = PeerSnapshot::load(dir).entries();
+            if !known.is_empty() {
+                info!("Redialling {} peers from the last routing table", known.len());
+            }
+            for (peer_id, addrs) in known {
+                if peer_id == local_peer_id {
+                    continue;
+                }
+                for addr in &addrs {
+                    swarm.behaviour_mut().kademlia.add_address(&peer_id, addr.clone());
+                    swarm.add_peer_address(peer_id, addr.clone());
+                }
+                let opts = DialOpts::peer_id(peer_id).addresses(addrs).build();
+                if let Err(e) = swarm.dial(opts) {
+                    debug!("Snapshot peer {} not dialled: {}", peer_id, e);
+                }
+            }
+        }
+
+        for addr_str in &config.external_addrs {
+            let addr: Multiaddr = addr_str
+                .parse()
+                .with_context(|| format!("Invalid external address {addr_str:?}"))?;
+            info!("Confirmed external address: {}", addr);
+            swarm.add_external_address(addr);
+        }
+
+        // Reserve on each relay. Listening on `<relay>/p2p-circuit` makes the
+        // relay client transport dial the relay and request a reservation;
+        // the circuit address shows up as a NewListenAddr once accepted.
+        for relay_str in &config.relays {
+            let addr: Multiaddr = relay_str
+                .parse()
+                .with_context(|| format!("Invalid relay multiaddr {relay_str:?}"))?;
+            let relay_peer = peer_id_of(&addr)
+                .ok_or_else(|| anyhow!("Relay address missing /p2p/ component: {addr}"))?;
+            swarm
+                .behaviour_mut()
+                .kademlia
+                .add_address(&relay_peer, addr.clone());
+            swarm
+                .listen_on(addr.clone().with(Protocol::P2pCircuit))
+                .with_context(|| format!("Failed to request reservation on relay {addr}"))?;
+            info!("Requesting relay reservation on: {}", addr);
+        }
+
         // Channel sized large enough for typical bursts (one command per
         // public method call). Backpressure here would mean the daemon is
         // calling Discovery faster than libp2p can keep up — fine to block.
         let (cmd_tx, cmd_rx) = mpsc::channel::<Command>(32);
 
-        let driver_caps = config.capabilities.clone();
-        let driver_peer_id = local_peer_id;
+        let capabilities = config.capabilities.clone();
+        let driver_identity = node_identity.clone();
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let driver_events = events.clone();
         let driver = tokio::spawn(async move {
-            Driver::run(swarm, cmd_rx, driver_caps, driver_peer_id).await;
+            Driver::run(swarm, cmd_rx, driver_events, driver_identity, config).await;
         });
 
         Ok(Self {
             local_peer_id,
+            identity: node_identity,
             signing_key,
-            capabilities: config.capabilities,
+            capabilities,
             cmd_tx,
+            events,
             driver: Some(driver),
         })
     }
@@ -479,6 +948,20 @@ impl Discovery {
             .map_err(|_| anyhow!("Discovery driver dropped reply"))?
     }
 
+    /// Sign `value` with the node identity and publish it under `key`,
+    /// valid for `ttl`. This is how records under the default registry's
+    /// signed `/phase/…` namespaces get published; read them back with
+    /// [`SignedRecord::open`].
+    pub async fn publish_signed_record(
+        &self,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let record = SignedRecord::sign(&self.identity, &key, &value, ttl);
+        self.publish_kad_record(key, record.to_bytes()).await
+    }
+
     /// Look up records published under `key` on the Kademlia DHT.
     ///
     /// Issues a Kademlia `get_record` query, accumulates every peer-supplied
@@ -488,7 +971,8 @@ impl Discovery {
     /// the caller's responsibility.
     ///
     /// Returns an empty `Vec` if no peer holds a record under `key` — that
-    /// is *not* an error, just a normal "miss".
+    /// is *not* an error, just a normal "miss". Records the namespace
+    /// validator refuses are dropped before they get here.
     pub async fn get_kad_record(&self, key: Vec<u8>) -> Result<Vec<Vec<u8>>> {
         let (tx, rx) = oneshot::channel();
         self.cmd_tx
@@ -499,6 +983,42 @@ impl Discovery {
             .map_err(|_| anyhow!("Discovery driver dropped reply"))?
     }
 
+    /// Announce this node as a provider for `key`: Kademlia keeps a set of
+    /// provider peers per key, where a record under the same key holds one
+    /// value. Pair it with a signed per-peer record carrying the details,
+    /// the way capabilities are advertised. libp2p re-publishes the
+    /// provider record until [`Self::stop_providing`].
+    pub async fn start_providing(&self, key: Vec<u8>) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::StartProviding { key, reply: tx })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))?
+    }
+
+    /// Stop announcing this node as a provider for `key`. Copies already
+    /// held by other peers are not recalled; they lapse with their TTL.
+    pub async fn stop_providing(&self, key: Vec<u8>) -> Result<()> {
+        self.cmd_tx
+            .send(Command::StopProviding { key })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))
+    }
+
+    /// Look up the peers providing `key`, this node included if it is one.
+    /// An empty `Vec` is a normal miss.
+    pub async fn get_providers(&self, key: Vec<u8>) -> Result<Vec<PeerId>> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::GetProviders { key, reply: tx })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))?
+    }
+
     /// Send a `JobOffer` to a peer over the libp2p request/response wire
     /// and await its `JobResponse`.
     ///
@@ -566,6 +1086,110 @@ impl Discovery {
         Ok(())
     }
 
+    /// Read a range of a blob from `peer` over the `/phase/blob/1.0.0`
+    /// protocol. `request.length` is clamped to [`BLOB_MAX_RANGE_BYTES`].
+    ///
+    /// The returned bytes are whatever the peer sent; callers verify them
+    /// against the blob's outboard or hash.
+    pub async fn request_blob(&self, peer: PeerId, mut request: BlobRequest) -> Result<BlobResponse> {
+        request.length = request.length.min(BLOB_MAX_RANGE_BYTES);
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::SendBlobRequest {
+                peer,
+                request,
+                reply: tx,
+            })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))?
+    }
+
+    /// Install (or replace) the inbound handler for the blob protocol.
+    /// Pass `None` to refuse every inbound request with
+    /// `BlobResponse::Err`.
+    pub async fn set_blob_handler(&self, handler: Option<BlobHandler>) -> Result<()> {
+        self.cmd_tx
+            .send(Command::SetBlobHandler { handler })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        Ok(())
+    }
+
+    /// Every address the swarm is currently listening on, including the
+    /// `/p2p-circuit` addresses of accepted relay reservations.
+    pub async fn listen_addrs(&self) -> Result<Vec<String>> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::ListenAddrs { reply: tx })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))
+    }
+
+    /// Confirm an address this node is publicly reachable at. Same as
+    /// listing it in [`DiscoveryConfig::external_addrs`], for addresses only
+    /// known after startup (e.g. an ephemeral listen port).
+    pub async fn add_external_address(&self, addr: &str) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::AddExternalAddr {
+                addr: addr.to_string(),
+                reply: tx,
+            })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))?
+    }
+
+    /// AutoNAT's current verdict on this node's reachability.
+    pub async fn reachability(&self) -> Result<Reachability> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::Reachability { reply: tx })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))
+    }
+
+    /// What this node has measured about each peer it has talked to:
+    /// smoothed ping RTT, best job-relay throughput and identify's agent
+    /// string. Precise figures for local routing decisions; only their
+    /// buckets are ever published (see [`capability_record_key`]).
+    pub async fn peer_stats(&self) -> Result<Vec<PeerStats>> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::PeerStats { reply: tx })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))
+    }
+
+    /// Write the DHT record store and routing-table snapshot to
+    /// [`DiscoveryConfig::state_dir`] now, rather than waiting for the
+    /// periodic flush. A no-op without a state dir.
+    pub async fn persist(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(Command::Persist { reply: tx })
+            .await
+            .map_err(|_| anyhow!("Discovery driver shut down"))?;
+        rx.await
+            .map_err(|_| anyhow!("Discovery driver dropped reply"))?
+    }
+
+    /// Subscribe to [`NetworkEvent`]s from now on. Each receiver gets every
+    /// event; one that falls more than a few hundred behind skips ahead
+    /// (`RecvError::Lagged`) rather than slowing the swarm down.
+    pub fn subscribe(&self) -> broadcast::Receiver<NetworkEvent> {
+        self.events.subscribe()
+    }
+
     /// Run until the background driver task exits. The November 2025 MVP's
     /// `plasmd start` calls this to keep the daemon alive after dispatching
     /// configuration. After M2 the actual swarm polling lives inside the
@@ -629,8 +1253,23 @@ struct Driver {
     /// running accumulation of unique record payloads (the same peer can
     /// report a record more than once; we de-dupe before replying).
     pending_get_records: HashMap<QueryId, PendingGetRecord>,
+    /// Outstanding GetProviders queries and the providers found so far.
+    pending_get_providers: HashMap<QueryId, PendingGetProviders>,
     /// Outstanding outbound JobRelay requests.
     pending_relays: HashMap<OutboundRequestId, oneshot::Sender<Result<JobRelayResponse>>>,
+    /// Per-peer measurements (ping RTT, blob throughput, identify info).
+    peer_stats: HashMap<PeerId, PeerStats>,
+    /// Set by the first `AdvertiseCapabilities`; from then on the driver
+    /// republishes the capability record on its own.
+    advertising: bool,
+    /// Whether a record carrying measurements has reached the DHT. The
+    /// first one is usually published at startup, before any peer has
+    /// been measured (or is even routable), so until then each new sample
+    /// or routing-table peer triggers a republish.
+    advertised_measured: bool,
+    /// The in-flight put of this node's capability record, and whether it
+    /// carries measurements.
+    capability_put: Option<(QueryId, bool)>,
     /// Inbound JobRelay handler. `None` → refuse every inbound request.
     job_relay_handler: Option<JobRelayHandler>,
     /// SEC-06: completed inbound-relay responses flow back from the spawned
@@ -638,7 +1277,47 @@ struct Driver {
     /// awaiting the (slow, GPU-heavy) handler itself. Decouples relay
     /// execution from the swarm event loop — one slow job no longer stalls
     /// peer connectivity.
-    relay_reply_tx: mpsc::Sender<(ResponseChannel<JobRelayResponse>, JobRelayResponse)>,
+    relay_reply_tx: mpsc::Sender<RelayReply>,
+    /// Outstanding outbound blob requests.
+    pending_blobs: HashMap<OutboundRequestId, PendingBlob>,
+    /// Inbound blob handler. `None` → refuse every inbound request.
+    blob_handler: Option<BlobHandler>,
+    /// Finished blob reads, routed back like `relay_reply_tx`.
+    blob_reply_tx: mpsc::Sender<BlobReply>,
+    /// Last AutoNAT verdict.
+    reachability: Reachability,
+    /// Where DHT state is persisted; `None` for memory-only.
+    state_dir: Option<PathBuf>,
+    /// Signs this node's own capability record.
+    identity: NodeIdentity,
+    /// Namespace admission rules for every record in or out.
+    validators: ValidatorRegistry,
+    /// Checked again per inbound job request, behind the connection-level
+    /// allow/block behaviours.
+    peer_filter: PeerFilter,
+    offer_limiter: PeerRateLimiter,
+    relay_limiter: PeerRateLimiter,
+    /// Inbound relays whose handler is still running, per peer.
+    inflight_relays: HashMap<PeerId, usize>,
+    max_inflight_relays_per_peer: usize,
+    blob_limiter: PeerRateLimiter,
+    /// Inbound blob reads still running, per peer.
+    inflight_blobs: HashMap<PeerId, usize>,
+    max_inflight_blobs_per_peer: usize,
+    /// Fan-out to [`Discovery::subscribe`] receivers.
+    events: broadcast::Sender<NetworkEvent>,
+}
+
+/// A spawned relay handler's finished response, with the peer it serves.
+type RelayReply = (PeerId, ResponseChannel<JobRelayResponse>, JobRelayResponse);
+
+/// A spawned blob handler's finished response, with the peer it serves.
+type BlobReply = (PeerId, ResponseChannel<BlobResponse>, BlobResponse);
+
+/// An outbound blob request awaiting its response.
+struct PendingBlob {
+    reply: oneshot::Sender<Result<BlobResponse>>,
+    sent_at: Instant,
 }
 
 /// Accumulator for an outstanding `GetKadRecord` query.
@@ -652,31 +1331,72 @@ struct PendingGetRecord {
     values: Vec<Vec<u8>>,
 }
 
+/// Accumulator for an outstanding `GetProviders` query, folded the same
+/// way as [`PendingGetRecord`].
+struct PendingGetProviders {
+    reply: oneshot::Sender<Result<Vec<PeerId>>>,
+    providers: Vec<PeerId>,
+}
+
 impl Driver {
     async fn run(
         swarm: Swarm<CombinedBehaviour>,
         mut cmd_rx: mpsc::Receiver<Command>,
-        capabilities: PeerCapabilities,
-        local_peer_id: PeerId,
+        events: broadcast::Sender<NetworkEvent>,
+        identity: NodeIdentity,
+        config: DiscoveryConfig,
     ) {
         // SEC-06: channel for spawned relay-handler tasks to hand finished
         // responses back to the driver. Bounded; if it fills, the spawned
         // task awaits — which throttles inbound relay completion, never the
         // swarm loop. Sized to a small multiple of typical concurrency.
-        let (relay_reply_tx, mut relay_reply_rx) =
-            mpsc::channel::<(ResponseChannel<JobRelayResponse>, JobRelayResponse)>(64);
+        let (relay_reply_tx, mut relay_reply_rx) = mpsc::channel::<RelayReply>(64);
+        let (blob_reply_tx, mut blob_reply_rx) = mpsc::channel::<BlobReply>(64);
 
+        let local_peer_id = *swarm.local_peer_id();
         let mut driver = Driver {
             swarm,
-            capabilities,
+            capabilities: config.capabilities,
             local_peer_id,
             pending_offers: HashMap::new(),
             pending_get_records: HashMap::new(),
+            pending_get_providers: HashMap::new(),
             pending_relays: HashMap::new(),
+            peer_stats: HashMap::new(),
+            advertising: false,
+            advertised_measured: false,
+            capability_put: None,
             job_relay_handler: None,
             relay_reply_tx,
+            pending_blobs: HashMap::new(),
+            blob_handler: None,
+            blob_reply_tx,
+            reachability: Reachability::Unknown,
+            state_dir: config.state_dir,
+            identity,
+            validators: config.validators,
+            peer_filter: config.peer_filter,
+            offer_limiter: PeerRateLimiter::new(config.limits.job_offer_rate),
+            relay_limiter: PeerRateLimiter::new(config.limits.job_relay_rate),
+            inflight_relays: HashMap::new(),
+            max_inflight_relays_per_peer: config.limits.max_inflight_relays_per_peer,
+            blob_limiter: PeerRateLimiter::new(config.limits.blob_rate),
+            inflight_blobs: HashMap::new(),
+            max_inflight_blobs_per_peer: config.limits.max_inflight_blob_requests_per_peer,
+            events,
         };
 
+        let mut persist_tick = tokio::time::interval_at(
+            tokio::time::Instant::now() + PERSIST_INTERVAL,
+            PERSIST_INTERVAL,
+        );
+        persist_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut capability_tick = tokio::time::interval_at(
+            tokio::time::Instant::now() + CAPABILITY_REPUBLISH_INTERVAL,
+            CAPABILITY_REPUBLISH_INTERVAL,
+        );
+        capability_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
         loop {
             tokio::select! {
                 cmd = cmd_rx.recv() => {
@@ -686,17 +1406,61 @@ impl Driver {
                     }
                 }
                 // SEC-06: a spawned relay handler finished — ship its response.
-                Some((channel, response)) = relay_reply_rx.recv() => {
+                Some((peer, channel, response)) = relay_reply_rx.recv() => {
+                    driver.finish_relay(peer);
+                    driver.emit(NetworkEvent::JobRelayFinished {
+                        peer,
+                        ok: matches!(response, JobRelayResponse::Ok { .. }),
+                    });
                     driver.send_relay_response(channel, response);
                 }
+                Some((peer, channel, response)) = blob_reply_rx.recv() => {
+                    release_slot(&mut driver.inflight_blobs, peer);
+                    driver.send_blob_response(channel, response);
+                }
                 event = driver.swarm.next() => {
                     match event {
                         Some(ev) => driver.handle_swarm_event(ev).await,
                         None => break,
                     }
                 }
+                _ = persist_tick.tick() => {
+                    if let Err(e) = driver.persist() {
+                        warn!("Failed to persist DHT state: {:#}", e);
+                    }
+                }
+                _ = capability_tick.tick(), if driver.advertising => {
+                    driver.republish_capabilities();
+                }
             }
         }
+
+        if let Err(e) = driver.persist() {
+            warn!("Failed to persist DHT state on shutdown: {:#}", e);
+        }
+    }
+
+    /// Flush the record store and snapshot the routing table.
+    fn persist(&mut self) -> Result<()> {
+        let Some(dir) = self.state_dir.clone() else {
+            return Ok(());
+        };
+        let kademlia = &mut self.swarm.behaviour_mut().kademlia;
+        kademlia.store_mut().flush()?;
+        let mut peers = Vec::new();
+        for bucket in kademlia.kbuckets() {
+            for entry in bucket.iter() {
+                let addrs = entry.node.value.iter().cloned().collect::<Vec<_>>();
+                peers.push((*entry.node.key.preimage(), addrs));
+            }
+        }
+        // Kademlia drops entries whose addresses all fail to dial, so a
+        // node restarted while its peers are down has an empty table. Keep
+        // the previous snapshot then; those peers may come back.
+        if peers.is_empty() {
+            return Ok(());
+        }
+        PeerSnapshot::new(peers).save(&dir)
     }
 
     fn handle_command(&mut self, cmd: Command) {
@@ -733,6 +1497,12 @@ impl Driver {
                 let res = (|| -> Result<()> {
                     let multiaddr: Multiaddr =
                         addr.parse().context("Failed to parse peer address")?;
+                    // Remember the address so a later request-response send
+                    // to this peer can redial it (relayed addresses in
+                    // particular are never learned any other way).
+                    if let Some(peer) = peer_id_of(&multiaddr) {
+                        self.swarm.add_peer_address(peer, multiaddr.clone());
+                    }
                     self.swarm.dial(multiaddr.clone())?;
                     info!("Dialing peer at: {}", multiaddr);
                     Ok(())
@@ -740,41 +1510,8 @@ impl Driver {
                 let _ = reply.send(res);
             }
             Command::AdvertiseCapabilities { reply } => {
-                use libp2p::kad::RecordKey;
-                let res = (|| -> Result<()> {
-                    // Advertise one record per supported kind. Each is a
-                    // (arch, kind_label) tuple so a scheduler can ask the
-                    // DHT for "x86_64 + inference" peers in one query.
-                    for kind in &self.capabilities.supported_kinds {
-                        let kind_label = serde_json::to_string(kind)
-                            .ok()
-                            .and_then(|s| {
-                                // serde_json renders the enum as `"wasm"`;
-                                // strip the quotes for the kad key.
-                                let trimmed = s.trim_matches('"').to_string();
-                                if trimmed.is_empty() {
-                                    None
-                                } else {
-                                    Some(trimmed)
-                                }
-                            })
-                            .unwrap_or_else(|| "unknown".into());
-
-                        let capability_key = format!(
-                            "/phase/capability/{}/{}",
-                            self.capabilities.arch, kind_label
-                        );
-                        let key = RecordKey::new(&capability_key.as_bytes());
-                        self.swarm
-                            .behaviour_mut()
-                            .kademlia
-                            .start_providing(key)
-                            .context("Failed to advertise capabilities")?;
-                        info!("Advertising capabilities: {}", capability_key);
-                    }
-                    Ok(())
-                })();
-                let _ = reply.send(res);
+                self.advertising = true;
+                let _ = reply.send(self.advertise_capabilities());
             }
             Command::DiscoverPeers { arch, kind_label, reply } => {
                 use libp2p::kad::RecordKey;
@@ -785,17 +1522,7 @@ impl Driver {
                 let _ = reply.send(Ok(()));
             }
             Command::PublishKadRecord { key, value, reply } => {
-                use libp2p::kad::{Quorum, Record, RecordKey};
-                let res = (|| -> Result<()> {
-                    let record_key = RecordKey::new(&key);
-                    self.swarm
-                        .behaviour_mut()
-                        .kademlia
-                        .put_record(Record::new(record_key, value), Quorum::One)
-                        .map_err(|e| anyhow!("Failed to publish record: {:?}", e))?;
-                    Ok(())
-                })();
-                let _ = reply.send(res);
+                let _ = reply.send(self.put_validated(key, value).map(|_| ()));
             }
             Command::GetKadRecord { key, reply } => {
                 use libp2p::kad::RecordKey;
@@ -810,6 +1537,39 @@ impl Driver {
                     },
                 );
             }
+            Command::StartProviding { key, reply } => {
+                use libp2p::kad::RecordKey;
+                let res = self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .start_providing(RecordKey::new(&key))
+                    .map(|_| ())
+                    .map_err(|e| anyhow!("Failed to start providing: {:?}", e));
+                let _ = reply.send(res);
+            }
+            Command::StopProviding { key } => {
+                use libp2p::kad::RecordKey;
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .stop_providing(&RecordKey::new(&key));
+            }
+            Command::GetProviders { key, reply } => {
+                use libp2p::kad::RecordKey;
+                let query_id = self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .get_providers(RecordKey::new(&key));
+                self.pending_get_providers.insert(
+                    query_id,
+                    PendingGetProviders {
+                        reply,
+                        providers: Vec::new(),
+                    },
+                );
+            }
             Command::SendJobOffer { peer, offer, reply } => {
                 let req_id = self
                     .swarm
@@ -833,6 +1593,41 @@ impl Driver {
             Command::SetJobRelayHandler { handler } => {
                 self.job_relay_handler = handler;
             }
+            Command::SendBlobRequest { peer, request, reply } => {
+                let req_id = self.swarm.behaviour_mut().blob.send_request(&peer, request);
+                self.pending_blobs.insert(
+                    req_id,
+                    PendingBlob {
+                        reply,
+                        sent_at: Instant::now(),
+                    },
+                );
+            }
+            Command::SetBlobHandler { handler } => {
+                self.blob_handler = handler;
+            }
+            Command::ListenAddrs { reply } => {
+                let _ = reply.send(self.swarm.listeners().map(|a| a.to_string()).collect());
+            }
+            Command::AddExternalAddr { addr, reply } => {
+                let res = (|| -> Result<()> {
+                    let multiaddr: Multiaddr =
+                        addr.parse().context("Failed to parse external address")?;
+                    info!("Confirmed external address: {}", multiaddr);
+                    self.swarm.add_external_address(multiaddr);
+                    Ok(())
+                })();
+                let _ = reply.send(res);
+            }
+            Command::Reachability { reply } => {
+                let _ = reply.send(self.reachability.clone());
+            }
+            Command::PeerStats { reply } => {
+                let _ = reply.send(self.peer_stats.values().cloned().collect());
+            }
+            Command::Persist { reply } => {
+                let _ = reply.send(self.persist());
+            }
         }
     }
 
@@ -853,6 +1648,41 @@ impl Driver {
             SwarmEvent::Behaviour(CombinedBehaviourEvent::JobRelay(rr)) => {
                 self.handle_job_relay_event(rr);
             }
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::Blob(rr)) => {
+                self.handle_blob_event(rr);
+            }
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::Identify(ev)) => {
+                self.handle_identify_event(ev);
+            }
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::Ping(ev)) => match ev.result {
+                Ok(rtt) => {
+                    debug!("Ping {}: {:?}", ev.peer, rtt);
+                    self.stats_mut(ev.peer).record_rtt(rtt, unix_secs_now());
+                    self.publish_measured_if_pending();
+                }
+                Err(e) => debug!("Ping {} failed: {}", ev.peer, e),
+            },
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::Relay(ev)) => {
+                self.handle_relay_server_event(ev);
+            }
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::RelayClient(ev)) => {
+                self.handle_relay_client_event(ev);
+            }
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::Dcutr(ev)) => {
+                match ev.result {
+                    Ok(_) => info!("Hole punch to {} succeeded; connection is direct", ev.remote_peer_id),
+                    Err(e) => info!("Hole punch to {} failed, staying on relay: {}", ev.remote_peer_id, e),
+                }
+            }
+            SwarmEvent::Behaviour(CombinedBehaviourEvent::Autonat(ev)) => {
+                if let autonat::Event::StatusChanged { old, new } = ev {
+                    info!("AutoNAT status changed: {:?} -> {:?}", old, new);
+                    self.reachability = Reachability::from(&new);
+                    self.emit(NetworkEvent::ReachabilityChanged(self.reachability.clone()));
+                } else {
+                    debug!("AutoNAT event: {:?}", ev);
+                }
+            }
             SwarmEvent::NewListenAddr { address, .. } => {
                 info!("Listening on new address: {}", address);
                 let s = address.to_string();
@@ -867,11 +1697,33 @@ impl Driver {
                     info!("External address detected: {}", address);
                 }
             }
-            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+            SwarmEvent::ConnectionEstablished {
+                peer_id,
+                endpoint,
+                num_established,
+                ..
+            } => {
                 info!("Connected to peer: {}", peer_id);
+                if num_established.get() == 1 {
+                    self.emit(NetworkEvent::PeerConnected {
+                        peer: peer_id,
+                        address: endpoint.get_remote_address().to_string(),
+                    });
+                }
             }
-            SwarmEvent::ConnectionClosed { peer_id, cause, .. } => {
+            SwarmEvent::ConnectionClosed {
+                peer_id,
+                cause,
+                num_established,
+                ..
+            } => {
                 debug!("Connection closed to {}: {:?}", peer_id, cause);
+                if num_established == 0 {
+                    self.emit(NetworkEvent::PeerDisconnected {
+                        peer: peer_id,
+                        error: cause.is_some(),
+                    });
+                }
             }
             other => {
                 debug!("Other swarm event: {:?}", other);
@@ -894,17 +1746,72 @@ impl Driver {
                         .behaviour_mut()
                         .kademlia
                         .add_address(&peer_id, multiaddr.clone());
-                    self.swarm.add_peer_address(peer_id, multiaddr);
+                    self.swarm.add_peer_address(peer_id, multiaddr.clone());
+                    self.emit(NetworkEvent::PeerDiscovered {
+                        peer: peer_id,
+                        address: multiaddr.to_string(),
+                    });
                 }
             }
             mdns::Event::Expired(list) => {
                 for (peer_id, multiaddr) in list {
                     debug!("mDNS peer expired: {} at {}", peer_id, multiaddr);
+                    self.emit(NetworkEvent::PeerExpired {
+                        peer: peer_id,
+                        address: multiaddr.to_string(),
+                    });
                 }
             }
         }
     }
 
+    fn handle_identify_event(&mut self, event: identify::Event) {
+        if let identify::Event::Received { peer_id, info, .. } = event {
+            debug!(
+                "Identified {} ({}), observed us at {}",
+                peer_id, info.agent_version, info.observed_addr
+            );
+            self.stats_mut(peer_id).agent_version = Some(info.agent_version);
+            // A peer's self-reported listen addresses are how Kademlia
+            // learns routable addresses for inbound-only peers, including
+            // the circuit addresses of peers reachable only via a relay.
+            for addr in info.listen_addrs {
+                self.swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .add_address(&peer_id, addr);
+            }
+        }
+    }
+
+    fn handle_relay_server_event(&mut self, event: relay::Event) {
+        match event {
+            relay::Event::ReservationReqAccepted { src_peer_id, renewed } => {
+                info!("Relay reservation accepted for {} (renewal: {})", src_peer_id, renewed);
+            }
+            relay::Event::CircuitReqAccepted { src_peer_id, dst_peer_id } => {
+                info!("Relaying circuit {} -> {}", src_peer_id, dst_peer_id);
+            }
+            other => debug!("Relay server event: {:?}", other),
+        }
+    }
+
+    fn handle_relay_client_event(&mut self, event: relay::client::Event) {
+        match event {
+            relay::client::Event::ReservationReqAccepted { relay_peer_id, renewal, .. } => {
+                if !renewal {
+                    info!("Reserved a relay slot on {}", relay_peer_id);
+                }
+            }
+            relay::client::Event::OutboundCircuitEstablished { relay_peer_id, .. } => {
+                debug!("Outbound circuit established via {}", relay_peer_id);
+            }
+            relay::client::Event::InboundCircuitEstablished { src_peer_id, .. } => {
+                debug!("Inbound relayed connection from {}", src_peer_id);
+            }
+        }
+    }
+
     fn handle_kad_event(&mut self, event: KademliaEvent) {
         match event {
             KademliaEvent::OutboundQueryProgressed {
@@ -918,9 +1825,20 @@ impl Driver {
                     if let Some(pending) = self.pending_get_records.get_mut(&id) {
                         match res {
                             Ok(GetRecordOk::FoundRecord(rec)) => {
+                                let record = &rec.record;
+                                if let Err(e) = self.validators.validate(
+                                    record.key.as_ref(),
+                                    &record.value,
+                                    unix_ms_now(),
+                                ) {
+                                    debug!(
+                                        "Dropping invalid record from {:?}: {}",
+                                        rec.peer, e
+                                    );
+                                }
                                 // De-dupe by raw payload — the same record
                                 // can come back from several peers.
-                                if !pending.values.contains(&rec.record.value) {
+                                else if !pending.values.contains(&rec.record.value) {
                                     pending.values.push(rec.record.value);
                                 }
                             }
@@ -945,12 +1863,50 @@ impl Driver {
                     } else {
                         debug!("get_record event for unknown query id {:?}", id);
                     }
+                } else if let QueryResult::GetProviders(res) = result {
+                    let Some(pending) = self.pending_get_providers.get_mut(&id) else {
+                        debug!("get_providers event for unknown query id {:?}", id);
+                        return;
+                    };
+                    match res {
+                        Ok(GetProvidersOk::FoundProviders { providers, .. }) => {
+                            for provider in providers {
+                                if !pending.providers.contains(&provider) {
+                                    pending.providers.push(provider);
+                                }
+                            }
+                        }
+                        Ok(GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                        Err(e) => debug!("get_providers query {:?} returned error: {:?}", id, e),
+                    }
+                    if step.last {
+                        if let Some(p) = self.pending_get_providers.remove(&id) {
+                            let _ = p.reply.send(Ok(p.providers));
+                        }
+                    }
+                } else if let QueryResult::PutRecord(res) = result {
+                    if let Some((query, measured)) = self.capability_put {
+                        if query == id && step.last {
+                            self.capability_put = None;
+                            match res {
+                                Ok(_) => self.advertised_measured |= measured,
+                                Err(e) => debug!("Capability record not stored: {:?}", e),
+                            }
+                        }
+                    }
                 } else {
                     debug!("Outbound query result: {:?}", result);
                 }
             }
-            KademliaEvent::RoutingUpdated { peer, .. } => {
+            KademliaEvent::InboundRequest { request } => self.handle_kad_inbound(request),
+            KademliaEvent::RoutingUpdated {
+                peer, is_new_peer, ..
+            } => {
                 debug!("Routing table updated with peer: {}", peer);
+                self.emit(NetworkEvent::RoutingUpdated { peer, is_new_peer });
+                if is_new_peer {
+                    self.publish_measured_if_pending();
+                }
             }
             KademliaEvent::UnroutablePeer { peer } => {
                 warn!("Unroutable peer: {}", peer);
@@ -971,11 +1927,33 @@ impl Driver {
     ) {
         use request_response::{Event, Message};
         match event {
-            Event::Message { message, .. } => match message {
+            Event::Message { peer, message, .. } => match message {
                 Message::Request {
                     request, channel, ..
                 } => {
-                    let response = self.evaluate_offer(request);
+                    let job_id = request.job_id.clone();
+                    let response = if !self.peer_filter.permits(&peer) {
+                        warn!("Refusing job offer from non-permitted peer {}", peer);
+                        JobResponse::Rejected {
+                            job_id: request.job_id,
+                            reason: RejectionReason::PeerNotPermitted,
+                        }
+                    } else if let Err(wait) = self.offer_limiter.check(peer, Instant::now()) {
+                        debug!("Rate limiting job offers from {}", peer);
+                        JobResponse::Rejected {
+                            job_id: request.job_id,
+                            reason: RejectionReason::RateLimited {
+                                retry_after_ms: retry_after_ms(wait),
+                            },
+                        }
+                    } else {
+                        self.evaluate_offer(request)
+                    };
+                    self.emit(NetworkEvent::JobOfferReceived {
+                        peer,
+                        job_id,
+                        accepted: matches!(response, JobResponse::Accepted { .. }),
+                    });
                     self.send_offer_response(channel, response);
                 }
                 Message::Response {
@@ -1038,6 +2016,23 @@ impl Driver {
         }
     }
 
+    /// Answer an inbound relay that never reaches the handler.
+    fn refuse_relay(
+        &mut self,
+        peer: PeerId,
+        channel: ResponseChannel<JobRelayResponse>,
+        response: JobRelayResponse,
+    ) {
+        let reason = match &response {
+            JobRelayResponse::Err { reason } => reason.clone(),
+            JobRelayResponse::RateLimited { .. } => "rate limited".to_string(),
+            JobRelayResponse::Busy => "busy".to_string(),
+            JobRelayResponse::Ok { .. } => String::new(),
+        };
+        self.emit(NetworkEvent::JobRelayRefused { peer, reason });
+        self.send_relay_response(channel, response);
+    }
+
     /// SEC-06: handle a relay event WITHOUT blocking the swarm loop.
     ///
     /// The inbound `Request` branch no longer `await`s the handler inline.
@@ -1058,18 +2053,42 @@ impl Driver {
                 } => {
                     let reply_tx = self.relay_reply_tx.clone();
                     match self.job_relay_handler.clone() {
+                        _ if !self.peer_filter.permits(&peer) => {
+                            warn!("Refusing job relay from non-permitted peer {}", peer);
+                            self.refuse_relay(
+                                peer,
+                                channel,
+                                JobRelayResponse::Err {
+                                    reason: "peer not permitted".to_string(),
+                                },
+                            );
+                        }
                         Some(handler) => {
+                            if let Err(response) = self.admit_relay(peer) {
+                                self.refuse_relay(peer, channel, response);
+                                return;
+                            }
+                            self.emit(NetworkEvent::JobRelayStarted { peer });
                             // Off-driver: spawn the handler so a slow job
-                            // can't stall the swarm event loop.
+                            // can't stall the swarm event loop. A panicking
+                            // handler still replies, or its in-flight slot
+                            // would never be released.
                             tokio::spawn(async move {
-                                let response = handler(peer, request.payload).await;
-                                let _ = reply_tx.send((channel, response)).await;
+                                let response = catch_handler_panic(async move {
+                                    handler(peer, request.payload).await
+                                })
+                                .await
+                                .unwrap_or_else(|| JobRelayResponse::Err {
+                                    reason: "relay handler failed".to_string(),
+                                });
+                                let _ = reply_tx.send((peer, channel, response)).await;
                             });
                         }
                         None => {
                             // No handler installed → refuse closed. Send
                             // inline (cheap, no await on user code).
-                            self.send_relay_response(
+                            self.refuse_relay(
+                                peer,
                                 channel,
                                 JobRelayResponse::Err {
                                     reason: "no job-relay handler installed".to_string(),
@@ -1082,16 +2101,19 @@ impl Driver {
                     request_id,
                     response,
                 } => {
-                    if let Some(tx) = self.pending_relays.remove(&request_id) {
-                        let _ = tx.send(Ok(response));
+                    if let Some(reply) = self.pending_relays.remove(&request_id) {
+                        // No throughput sample: a relay's wall time is
+                        // mostly the serving peer's compute.
+                        self.stats_mut(peer).relay_transfers += 1;
+                        let _ = reply.send(Ok(response));
                     }
                 }
             },
             Event::OutboundFailure {
                 request_id, error, ..
             } => {
-                if let Some(tx) = self.pending_relays.remove(&request_id) {
-                    let _ = tx.send(Err(anyhow!("JobRelay outbound failure: {:?}", error)));
+                if let Some(reply) = self.pending_relays.remove(&request_id) {
+                    let _ = reply.send(Err(anyhow!("JobRelay outbound failure: {:?}", error)));
                 }
             }
             Event::InboundFailure { error, .. } => {
@@ -1101,6 +2123,302 @@ impl Driver {
         }
     }
 
+    /// Publish to subscribers. Having none is normal, not an error.
+    fn emit(&self, event: NetworkEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Rate and concurrency check for an inbound relay from `peer`. On
+    /// success the relay counts as in flight until [`Self::finish_relay`].
+    fn admit_relay(&mut self, peer: PeerId) -> Result<(), JobRelayResponse> {
+        let inflight = self.inflight_relays.get(&peer).copied().unwrap_or(0);
+        if inflight >= self.max_inflight_relays_per_peer {
+            debug!("Refusing relay from {}: {} already in flight", peer, inflight);
+            return Err(JobRelayResponse::Busy);
+        }
+        if let Err(wait) = self.relay_limiter.check(peer, Instant::now()) {
+            debug!("Rate limiting job relays from {}", peer);
+            return Err(JobRelayResponse::RateLimited {
+                retry_after_ms: retry_after_ms(wait),
+            });
+        }
+        *self.inflight_relays.entry(peer).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn finish_relay(&mut self, peer: PeerId) {
+        release_slot(&mut self.inflight_relays, peer);
+    }
+
+    fn send_blob_response(&mut self, channel: ResponseChannel<BlobResponse>, response: BlobResponse) {
+        if self
+            .swarm
+            .behaviour_mut()
+            .blob
+            .send_response(channel, response)
+            .is_err()
+        {
+            debug!("Failed to send BlobResponse — peer connection lost?");
+        }
+    }
+
+    /// Serve inbound blob reads off the driver, like job relays: the
+    /// handler touches the disk, so it runs in a spawned task and its
+    /// response comes back through `blob_reply_tx`.
+    fn handle_blob_event(&mut self, event: request_response::Event<BlobRequest, BlobResponse>) {
+        use request_response::{Event, Message};
+        match event {
+            Event::Message { peer, message, .. } => match message {
+                Message::Request {
+                    request, channel, ..
+                } => {
+                    if let Err(response) = self.admit_blob(peer) {
+                        self.send_blob_response(channel, response);
+                        return;
+                    }
+                    let reply_tx = self.blob_reply_tx.clone();
+                    let handler = self.blob_handler.clone().expect("admit_blob checks the handler");
+                    tokio::spawn(async move {
+                        let response = catch_handler_panic(async move { handler(peer, request).await })
+                            .await
+                            .unwrap_or_else(|| BlobResponse::Err {
+                                reason: "blob handler failed".to_string(),
+                            });
+                        let _ = reply_tx.send((peer, channel, response)).await;
+                    });
+                }
+                Message::Response {
+                    request_id,
+                    response,
+                } => {
+                    if let Some(pending) = self.pending_blobs.remove(&request_id) {
+                        if let BlobResponse::Data { bytes, .. } = &response {
+                            let sample = bytes.len() as u64;
+                            self.stats_mut(peer).record_transfer(
+                                sample,
+                                pending.sent_at.elapsed(),
+                                unix_secs_now(),
+                            );
+                            self.publish_measured_if_pending();
+                        }
+                        let _ = pending.reply.send(Ok(response));
+                    }
+                }
+            },
+            Event::OutboundFailure {
+                request_id, error, ..
+            } => {
+                if let Some(pending) = self.pending_blobs.remove(&request_id) {
+                    let _ = pending
+                        .reply
+                        .send(Err(anyhow!("Blob request outbound failure: {:?}", error)));
+                }
+            }
+            Event::InboundFailure { error, .. } => {
+                debug!("Blob inbound failure: {:?}", error);
+            }
+            Event::ResponseSent { .. } => {}
+        }
+    }
+
+    /// Peer filter, handler, concurrency and rate checks for an inbound
+    /// blob read. On success the read counts as in flight until its reply
+    /// comes back.
+    fn admit_blob(&mut self, peer: PeerId) -> Result<(), BlobResponse> {
+        let refuse = |reason: &str| {
+            Err(BlobResponse::Err {
+                reason: reason.to_string(),
+            })
+        };
+        if !self.peer_filter.permits(&peer) {
+            warn!("Refusing blob request from non-permitted peer {}", peer);
+            return refuse("peer not permitted");
+        }
+        if self.blob_handler.is_none() {
+            return refuse("no blob handler installed");
+        }
+        let inflight = self.inflight_blobs.get(&peer).copied().unwrap_or(0);
+        if inflight >= self.max_inflight_blobs_per_peer {
+            debug!("Refusing blob request from {}: {} already in flight", peer, inflight);
+            return Err(BlobResponse::Busy);
+        }
+        if let Err(wait) = self.blob_limiter.check(peer, Instant::now()) {
+            debug!("Rate limiting blob requests from {}", peer);
+            return Err(BlobResponse::RateLimited {
+                retry_after_ms: retry_after_ms(wait),
+            });
+        }
+        *self.inflight_blobs.entry(peer).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Validate `value` under `key` and publish it. The record's local
+    /// expiry is capped at the one the validator reports.
+    fn put_validated(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<QueryId> {
+        use libp2p::kad::{Quorum, RecordKey};
+        let expires = self
+            .validators
+            .validate(&key, &value, unix_ms_now())
+            .with_context(|| format!("Refusing to publish record under {}", String::from_utf8_lossy(&key)))?;
+        let mut record = Record::new(RecordKey::new(&key), value);
+        let now = Instant::now();
+        record.expires = expires.map(|ms| instant_from_unix_ms(ms, now, unix_ms_now()).unwrap_or(now));
+        self.swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(record, Quorum::One)
+            .map_err(|e| anyhow!("Failed to publish record: {:?}", e))
+    }
+
+    /// Admit a peer's PUT or ADD_PROVIDER into the local store. Kademlia
+    /// only surfaces the record here because inserts are filtered.
+    fn handle_kad_inbound(&mut self, request: InboundRequest) {
+        match request {
+            InboundRequest::PutRecord {
+                source,
+                record: Some(mut record),
+                ..
+            } => {
+                match self
+                    .validators
+                    .validate(record.key.as_ref(), &record.value, unix_ms_now())
+                {
+                    Ok(expires) => {
+                        if let Some(expires) = expires {
+                            let now = Instant::now();
+                            let own = instant_from_unix_ms(expires, now, unix_ms_now()).unwrap_or(now);
+                            record.expires = Some(record.expires.map_or(own, |e| e.min(own)));
+                        }
+                        if let Err(e) = self.swarm.behaviour_mut().kademlia.store_mut().put(record) {
+                            debug!("Record from {} not stored: {:?}", source, e);
+                        }
+                    }
+                    Err(e) => debug!("Refused record from {}: {}", source, e),
+                }
+            }
+            InboundRequest::AddProvider {
+                record: Some(record),
+            } => {
+                if let Err(e) = self.swarm.behaviour_mut().kademlia.store_mut().add_provider(record) {
+                    debug!("Provider record not stored: {:?}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The measurement entry for `peer`, created on first use. Evicts the
+    /// least recently measured peer when the table is full.
+    fn stats_mut(&mut self, peer: PeerId) -> &mut PeerStats {
+        if !self.peer_stats.contains_key(&peer) && self.peer_stats.len() >= MAX_TRACKED_PEERS {
+            let stalest = self
+                .peer_stats
+                .values()
+                .min_by_key(|s| s.last_measured_at)
+                .map(|s| s.peer_id);
+            if let Some(stalest) = stalest {
+                self.peer_stats.remove(&stalest);
+            }
+        }
+        self.peer_stats
+            .entry(peer)
+            .or_insert_with(|| PeerStats::new(peer))
+    }
+
+    /// Announce the (arch, kind) provider keys and publish the signed
+    /// capability record with the current measurements.
+    fn advertise_capabilities(&mut self) -> Result<()> {
+        use libp2p::kad::RecordKey;
+        // Advertise one record per supported kind. Each is a
+        // (arch, kind_label) tuple so a scheduler can ask the
+        // DHT for "x86_64 + inference" peers in one query.
+        for kind in &self.capabilities.supported_kinds {
+            let kind_label = serde_json::to_string(kind)
+                .ok()
+                .and_then(|s| {
+                    // serde_json renders the enum as `"wasm"`;
+                    // strip the quotes for the kad key.
+                    let trimmed = s.trim_matches('"').to_string();
+                    if trimmed.is_empty() {
+                        None
+                    } else {
+                        Some(trimmed)
+                    }
+                })
+                .unwrap_or_else(|| "unknown".into());
+
+            let capability_key = format!(
+                "/phase/capability/{}/{}",
+                self.capabilities.arch, kind_label
+            );
+            let key = RecordKey::new(&capability_key.as_bytes());
+            self.swarm
+                .behaviour_mut()
+                .kademlia
+                .start_providing(key)
+                .context("Failed to advertise capabilities")?;
+            info!("Advertising capabilities: {}", capability_key);
+        }
+
+        // The full record, measured buckets included, signed
+        // under a per-peer key so a scheduler can read it
+        // directly and trust it came from that peer.
+        let caps = self.measured_capabilities(unix_secs_now());
+        let value = serde_json::to_vec(&caps)
+            .context("Failed to encode capability record")?;
+        let key = capability_record_key(&self.local_peer_id);
+        let signed = SignedRecord::sign(&self.identity, &key, &value, CAPABILITY_RECORD_TTL);
+        let query = self
+            .put_validated(key, signed.to_bytes())
+            .context("Failed to publish capability record")?;
+        self.capability_put = Some((query, caps.last_measured_at.is_some()));
+        Ok(())
+    }
+
+    /// Timer- and measurement-driven re-advertisement; failures are only
+    /// logged, the next tick retries.
+    fn republish_capabilities(&mut self) {
+        if let Err(e) = self.advertise_capabilities() {
+            warn!("Failed to republish capabilities: {:#}", e);
+        }
+    }
+
+    /// Called after every new sample and routing-table peer: if no record
+    /// with measurements has been stored yet, publish one now rather than
+    /// at the next tick.
+    fn publish_measured_if_pending(&mut self) {
+        if self.advertising && !self.advertised_measured && self.capability_put.is_none() {
+            if self.peer_stats.values().all(|s| s.last_measured_at.is_none()) {
+                return;
+            }
+            self.republish_capabilities();
+        }
+    }
+
+    /// This node's capabilities with the measured fields filled in from
+    /// fresh entries of the peer table: the median peer RTT and the best
+    /// blob throughput, bucketed, plus the inbound relays now in flight.
+    fn measured_capabilities(&self, now_unix_secs: u64) -> PeerCapabilities {
+        let fresh: Vec<&PeerStats> = self
+            .peer_stats
+            .values()
+            .filter(|s| {
+                s.last_measured_at
+                    .is_some_and(|t| now_unix_secs.saturating_sub(t) <= MEASUREMENT_FRESH_SECS)
+            })
+            .collect();
+        let mut rtts: Vec<Duration> = fresh.iter().filter_map(|s| s.rtt).collect();
+        rtts.sort();
+        let best_throughput = fresh.iter().filter_map(|s| s.throughput_bytes_per_sec).max();
+
+        let mut caps = self.capabilities.clone();
+        caps.measured_latency_bucket = rtts.get(rtts.len() / 2).map(|rtt| LatencyBucket::from_rtt(*rtt));
+        caps.measured_bandwidth_bucket = best_throughput.map(BandwidthBucket::from_bytes_per_sec);
+        caps.last_measured_at = fresh.iter().filter_map(|s| s.last_measured_at).max();
+        caps.current_concurrency = Some(self.inflight_relays.values().sum::<usize>() as u32);
+        caps
+    }
+
     /// Match a JobOffer against this node's capabilities. Same contract as
     /// the pre-M2 `Discovery::handle_job_offer`, with the wasm-runtime
     /// string mapped through to `JobSpecKind::Wasm`.
@@ -1174,10 +2492,7 @@ impl Driver {
             };
         }
 
-        let estimated_start = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
+        let estimated_start = unix_secs_now();
 
         JobResponse::Accepted {
             job_id: offer.job_id,
@@ -1187,6 +2502,48 @@ impl Driver {
     }
 }
 
+fn retry_after_ms(wait: Duration) -> u64 {
+    u64::try_from(wait.as_millis()).unwrap_or(u64::MAX).max(1)
+}
+
+fn unix_secs_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Run a spawned request handler, turning a panic into `None` so the task
+/// still sends a reply and the driver releases the peer's in-flight slot.
+async fn catch_handler_panic<T>(handler: impl std::future::Future<Output = T>) -> Option<T> {
+    match std::panic::AssertUnwindSafe(handler).catch_unwind().await {
+        Ok(response) => Some(response),
+        Err(_) => {
+            warn!("Inbound request handler panicked");
+            None
+        }
+    }
+}
+
+/// Free one in-flight slot for `peer`.
+fn release_slot(inflight: &mut HashMap<PeerId, usize>, peer: PeerId) {
+    if let Some(n) = inflight.get_mut(&peer) {
+        *n -= 1;
+        if *n == 0 {
+            inflight.remove(&peer);
+        }
+    }
+}
+
+/// The trailing `/p2p/<peer-id>` of a multiaddr. For a circuit address that
+/// is the destination, not the relay.
+fn peer_id_of(addr: &Multiaddr) -> Option<PeerId> {
+    addr.iter().fold(None, |last, p| match p {
+        Protocol::P2p(peer_id) => Some(peer_id),
+        _ => last,
+    })
+}
+
 /// Map a wire `wasm_runtime` string onto a `JobSpecKind`. Conservative —
 /// anything we don't recognise comes back as `None`, which the caller
 /// treats as `RuntimeNotSupported`.
@@ -12,17 +12,28 @@
 //! The execution-side `Worker` trait lives in `phase-protocol`; the wasmtime
 //! implementation lives in `crates/plasm/` (after M7).
 
+pub mod access;
 pub mod discovery;
+pub mod limits;
 pub mod peer;
 pub mod protocol;
+pub mod record;
+pub mod store;
 
-pub use discovery::{Discovery, DiscoveryConfig, JobRelayHandler};
+pub use access::{NetworkKey, PeerFilter};
+pub use discovery::{
+    capability_record_key, BlobHandler, Discovery, DiscoveryConfig, JobRelayHandler, NetworkEvent,
+    Reachability,
+};
+pub use limits::{LimitsConfig, RateLimit};
 pub use peer::{
-    BandwidthBucket, LatencyBucket, PeerCapabilities, PeerInfo,
+    BandwidthBucket, LatencyBucket, PeerCapabilities, PeerInfo, PeerStats,
 };
+pub use record::{RecordError, RecordValidator, SignedRecord, SignedRecordValidator, ValidatorRegistry};
+pub use store::{state_dir_for_identity, StoreConfig};
 pub use protocol::{
-    JobOffer, JobRelayRequest, JobRelayResponse, JobRequest, JobRequirements, JobResponse,
-    JobResult, RejectionReason,
+    BlobPart, BlobRequest, BlobResponse, JobOffer, JobRelayRequest, JobRelayResponse, JobRequest, JobRequirements, JobResponse,
+    JobResult, RejectionReason, BLOB_MAX_RANGE_BYTES,
 };
 
 // Re-export libp2p's `PeerId` so downstream Phase crates (lucidd, future
@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Connection limits and per-peer rate limiting for the inbound job
+//! protocols.
+//!
+//! Payload sizes are capped at the codec (SEC-06); this module caps how
+//! *much* a peer can ask for. Three layers, all set through
+//! [`LimitsConfig`] on [`crate::DiscoveryConfig`]:
+//!
+//! - libp2p's connection-limits behaviour bounds established and pending
+//!   connections, in total and per peer.
+//! - A token bucket per peer and protocol (`/phase/job-offer/…`,
+//!   `/phase/job-relay/…`, `/phase/blob/…`) bounds the request rate. An empty bucket gets
+//!   [`crate::JobRelayResponse::RateLimited`] (or a `RateLimited` offer
+//!   rejection) with the time until the next token, before any handler
+//!   runs.
+//! - A per-peer cap on job relays (and blob reads) in flight, so a peer
+//!   can't queue unbounded work behind a handler even within its rate.
+//!   A request past the cap gets [`crate::JobRelayResponse::Busy`] (or
+//!   [`crate::BlobResponse::Busy`]).
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use libp2p::PeerId;
+
+/// Peers with a bucket at once. Past this, full (idle) buckets are
+/// dropped; a dropped peer starts over with a full bucket, which is what
+/// an idle peer would have anyway.
+const MAX_TRACKED_BUCKETS: usize = 4096;
+
+/// Token-bucket parameters: up to `burst` requests back to back, refilled
+/// at `per_minute`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+/// Every connection and request limit `Discovery` enforces. `None` means
+/// unlimited for the connection counts.
+#[derive(Debug, Clone)]
+pub struct LimitsConfig {
+    pub max_established_incoming: Option<u32>,
+    pub max_established_outgoing: Option<u32>,
+    pub max_established_per_peer: Option<u32>,
+    pub max_pending_incoming: Option<u32>,
+    /// Inbound job offers per peer.
+    pub job_offer_rate: RateLimit,
+    /// Inbound job relays per peer.
+    pub job_relay_rate: RateLimit,
+    /// Inbound job relays one peer may have running at once.
+    pub max_inflight_relays_per_peer: usize,
+    /// Inbound blob range requests per peer.
+    pub blob_rate: RateLimit,
+    /// Inbound blob range requests one peer may have being read at once.
+    pub max_inflight_blob_requests_per_peer: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_established_incoming: Some(256),
+            max_established_outgoing: Some(256),
+            // Direct, relayed and hole-punched connections to the same
+            // peer can coexist briefly.
+            max_established_per_peer: Some(4),
+            max_pending_incoming: Some(64),
+            // Offers are cheap to evaluate; relays run a job.
+            job_offer_rate: RateLimit {
+                burst: 20,
+                per_minute: 120,
+            },
+            job_relay_rate: RateLimit {
+                burst: 4,
+                per_minute: 30,
+            },
+            max_inflight_relays_per_peer: 2,
+            // One request per 1 MiB chunk: 3000/min is about 50 MiB/s per
+            // peer, enough for model weights without letting one peer
+            // saturate the disk.
+            blob_rate: RateLimit {
+                burst: 32,
+                per_minute: 3000,
+            },
+            max_inflight_blob_requests_per_peer: 8,
+        }
+    }
+}
+
+impl LimitsConfig {
+    pub(crate) fn connection_limits(&self) -> libp2p::connection_limits::ConnectionLimits {
+        libp2p::connection_limits::ConnectionLimits::default()
+            .with_max_established_incoming(self.max_established_incoming)
+            .with_max_established_outgoing(self.max_established_outgoing)
+            .with_max_established_per_peer(self.max_established_per_peer)
+            .with_max_pending_incoming(self.max_pending_incoming)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// One token bucket per peer for a single protocol.
+#[derive(Debug)]
+pub(crate) struct PeerRateLimiter {
+    limit: RateLimit,
+    buckets: HashMap<PeerId, TokenBucket>,
+}
+
+impl PeerRateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.limit.per_minute) / 60.0
+    }
+
+    /// Take a token for `peer`. On an empty bucket, returns how long until
+    /// the next token.
+    pub(crate) fn check(&mut self, peer: PeerId, now: Instant) -> Result<(), Duration> {
+        let burst = f64::from(self.limit.burst);
+        let rate = self.refill_per_sec();
+        if self.buckets.len() >= MAX_TRACKED_BUCKETS && !self.buckets.contains_key(&peer) {
+            self.buckets.retain(|_, b| {
+                b.tokens + now.saturating_duration_since(b.updated).as_secs_f64() * rate < burst
+            });
+        }
+        let bucket = self.buckets.entry(peer).or_insert(TokenBucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if rate > 0.0 {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        } else {
+            Err(Duration::MAX)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_burst_then_refills_at_rate() {
+        let mut limiter = PeerRateLimiter::new(RateLimit {
+            burst: 2,
+            per_minute: 60,
+        });
+        let peer = PeerId::random();
+        let t0 = Instant::now();
+
+        assert!(limiter.check(peer, t0).is_ok());
+        assert!(limiter.check(peer, t0).is_ok());
+        let wait = limiter.check(peer, t0).unwrap_err();
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1), "{wait:?}");
+
+        // Other peers have their own bucket.
+        assert!(limiter.check(PeerId::random(), t0).is_ok());
+
+        // One token back after a second, never more than the burst.
+        assert!(limiter.check(peer, t0 + Duration::from_secs(1)).is_ok());
+        assert!(limiter.check(peer, t0 + Duration::from_secs(1)).is_err());
+        let later = t0 + Duration::from_secs(600);
+        assert!(limiter.check(peer, later).is_ok());
+        assert!(limiter.check(peer, later).is_ok());
+        assert!(limiter.check(peer, later).is_err());
+    }
+}
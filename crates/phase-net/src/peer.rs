@@ -14,9 +14,13 @@
 //! settled on (see MISSION.md). Sharing exact mbps/ms over a public mesh is a
 //! privacy footprint; sharing "high / mid / low" is enough for routing.
 //!
-//! Measurement is a follow-up task. The new fields populate to `None` by
-//! default; an active probe job (or passive observation of completed jobs)
-//! will fill them in later.
+//! Measurement is passive. The discovery driver pings every connected peer
+//! and times every outbound job relay, keeping the precise figures in a
+//! local [`PeerStats`] table (see `Discovery::peer_stats`). Only the
+//! buckets leave the node: the summary over that table is what fills the
+//! `measured_*` fields of the capability record this node publishes.
+
+use std::time::Duration;
 
 use libp2p::PeerId;
 use phase_protocol::JobSpecKind;
@@ -115,6 +119,101 @@ pub enum BandwidthBucket {
     Unknown,
 }
 
+impl LatencyBucket {
+    /// Bucket a round-trip time using the thresholds documented on the
+    /// variants.
+    pub fn from_rtt(rtt: Duration) -> Self {
+        match rtt.as_millis() {
+            0..100 => LatencyBucket::Good,
+            100..=500 => LatencyBucket::Fair,
+            _ => LatencyBucket::Poor,
+        }
+    }
+}
+
+impl BandwidthBucket {
+    /// Bucket a throughput, in bytes per second, using the thresholds
+    /// documented on the variants.
+    pub fn from_bytes_per_sec(bytes_per_sec: u64) -> Self {
+        const MBIT: u64 = 1_000_000 / 8;
+        match bytes_per_sec {
+            b if b > 100 * MBIT => BandwidthBucket::HighBw,
+            b if b >= 10 * MBIT => BandwidthBucket::MidBw,
+            _ => BandwidthBucket::LowBw,
+        }
+    }
+}
+
+/// What this node has measured about one peer. Local only: the precise
+/// figures here are for this node's own router and scheduler, never
+/// gossiped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerStats {
+    pub peer_id: PeerId,
+
+    /// Smoothed ping round-trip time (TCP-style EWMA, gain 1/8).
+    pub rtt: Option<Duration>,
+
+    /// Best throughput seen on a blob read from this peer, in bytes per
+    /// second. Job relays don't count: their wall time is mostly the
+    /// serving peer's compute. Each sample still includes a round trip
+    /// and a disk read, so it is a lower bound on the link; keeping the
+    /// best one is the closest estimate.
+    pub throughput_bytes_per_sec: Option<u64>,
+
+    /// Completed outbound job relays to this peer.
+    pub relay_transfers: u64,
+
+    /// `agent_version` the peer reported over identify.
+    pub agent_version: Option<String>,
+
+    /// Unix timestamp (seconds) of the most recent RTT or throughput sample.
+    pub last_measured_at: Option<u64>,
+}
+
+impl PeerStats {
+    /// An empty entry for a peer nothing has been measured about yet.
+    pub fn new(peer_id: PeerId) -> Self {
+        Self {
+            peer_id,
+            rtt: None,
+            throughput_bytes_per_sec: None,
+            relay_transfers: 0,
+            agent_version: None,
+            last_measured_at: None,
+        }
+    }
+
+    /// Fold a ping round-trip into the smoothed RTT.
+    pub fn record_rtt(&mut self, sample: Duration, now_unix_secs: u64) {
+        self.rtt = Some(match self.rtt {
+            Some(srtt) => (srtt * 7 + sample) / 8,
+            None => sample,
+        });
+        self.last_measured_at = Some(now_unix_secs);
+    }
+
+    /// Record a completed blob transfer of `bytes` that took `elapsed`.
+    pub fn record_transfer(&mut self, bytes: u64, elapsed: Duration, now_unix_secs: u64) {
+        let micros = elapsed.as_micros().max(1);
+        let rate = (u128::from(bytes) * 1_000_000 / micros).min(u128::from(u64::MAX)) as u64;
+        self.throughput_bytes_per_sec = Some(self.throughput_bytes_per_sec.map_or(rate, |best| best.max(rate)));
+        self.last_measured_at = Some(now_unix_secs);
+    }
+
+    /// Latency bucket for the smoothed RTT; `Unknown` if never pinged.
+    pub fn latency_bucket(&self) -> LatencyBucket {
+        self.rtt.map_or(LatencyBucket::Unknown, LatencyBucket::from_rtt)
+    }
+
+    /// Bandwidth bucket for the best throughput; `Unknown` if no blob
+    /// transfer has completed.
+    pub fn bandwidth_bucket(&self) -> BandwidthBucket {
+        self.throughput_bytes_per_sec
+            .map_or(BandwidthBucket::Unknown, BandwidthBucket::from_bytes_per_sec)
+    }
+}
+
 impl Default for PeerCapabilities {
     fn default() -> Self {
         Self {
@@ -184,4 +283,37 @@ mod tests {
         assert_eq!(back.measured_latency_bucket, Some(LatencyBucket::Good));
         assert_eq!(back.current_concurrency, Some(2));
     }
+
+    #[test]
+    fn buckets_follow_documented_thresholds() {
+        assert_eq!(LatencyBucket::from_rtt(Duration::from_millis(99)), LatencyBucket::Good);
+        assert_eq!(LatencyBucket::from_rtt(Duration::from_millis(100)), LatencyBucket::Fair);
+        assert_eq!(LatencyBucket::from_rtt(Duration::from_millis(500)), LatencyBucket::Fair);
+        assert_eq!(LatencyBucket::from_rtt(Duration::from_millis(501)), LatencyBucket::Poor);
+
+        // 1 Mbit/s = 125_000 bytes/s.
+        assert_eq!(BandwidthBucket::from_bytes_per_sec(125_000), BandwidthBucket::LowBw);
+        assert_eq!(BandwidthBucket::from_bytes_per_sec(1_250_000), BandwidthBucket::MidBw);
+        assert_eq!(BandwidthBucket::from_bytes_per_sec(12_500_000), BandwidthBucket::MidBw);
+        assert_eq!(BandwidthBucket::from_bytes_per_sec(12_500_001), BandwidthBucket::HighBw);
+    }
+
+    #[test]
+    fn stats_smooth_rtt_and_keep_best_throughput() {
+        let mut stats = PeerStats::new(PeerId::random());
+        assert_eq!(stats.latency_bucket(), LatencyBucket::Unknown);
+        assert_eq!(stats.bandwidth_bucket(), BandwidthBucket::Unknown);
+
+        stats.record_rtt(Duration::from_millis(80), 10);
+        assert_eq!(stats.rtt, Some(Duration::from_millis(80)));
+        stats.record_rtt(Duration::from_millis(160), 11);
+        assert_eq!(stats.rtt, Some(Duration::from_millis(90)));
+        assert_eq!(stats.latency_bucket(), LatencyBucket::Good);
+
+        stats.record_transfer(1_000_000, Duration::from_secs(2), 12);
+        stats.record_transfer(1_000_000, Duration::from_secs(4), 13);
+        assert_eq!(stats.throughput_bytes_per_sec, Some(500_000));
+        assert_eq!(stats.bandwidth_bucket(), BandwidthBucket::LowBw);
+        assert_eq!(stats.last_measured_at, Some(13));
+    }
 }
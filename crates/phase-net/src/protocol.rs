@@ -64,6 +64,66 @@ pub enum JobRelayResponse {
     },
     /// Serving peer refused or hit an in-flight error.
     Err { reason: String },
+    /// Serving peer is rate limiting us (see [`crate::limits`]); the
+    /// request never reached its handler. Retry no sooner than
+    /// `retry_after_ms`.
+    RateLimited { retry_after_ms: u64 },
+    /// Serving peer already runs as many of our relays as it allows at
+    /// once; retry after one of them finishes.
+    Busy,
+}
+
+// ---------------------------------------------------------------------------
+// Blob — byte ranges of content-addressed blobs, for peers without a
+// reachable HTTP port.
+// ---------------------------------------------------------------------------
+
+/// Largest range one [`BlobRequest`] may ask for. Matches the artifact
+/// server's outboard chunk size, so one verified chunk is one round trip.
+pub const BLOB_MAX_RANGE_BYTES: u32 = 1024 * 1024;
+
+/// Which file of a blob a [`BlobRequest`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlobPart {
+    /// The blob itself (`blobs/<aa>/<hex>.bin`).
+    Content,
+    /// Its per-chunk hash outboard (`blobs/<aa>/<hex>.outboard`).
+    Outboard,
+}
+
+/// Ask a peer for `length` bytes at `offset` of a blob it stores. phase-net
+/// does not interpret `blob_id` (the SHA-256 hex the artifact server keys
+/// blobs by); the serving side's handler resolves it and the requesting
+/// side verifies what comes back.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRequest {
+    pub blob_id: String,
+    pub part: BlobPart,
+    pub offset: u64,
+    /// Capped at [`BLOB_MAX_RANGE_BYTES`]; the server may return less.
+    pub length: u32,
+}
+
+/// Serving peer's answer to a [`BlobRequest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlobResponse {
+    /// The requested range, shortened at end of file. `total_size` is the
+    /// length of the whole part, so a client can page through it.
+    Data {
+        total_size: u64,
+        #[serde(with = "serde_bytes")]
+        bytes: Vec<u8>,
+    },
+    /// The peer does not have this blob.
+    NotFound,
+    /// Serving peer refused or failed to read the range.
+    Err { reason: String },
+    /// Serving peer is rate limiting us; retry no sooner than
+    /// `retry_after_ms`.
+    RateLimited { retry_after_ms: u64 },
+    /// Serving peer already has as many of our reads in flight as it
+    /// allows; retry after one of them finishes.
+    Busy,
 }
 
 /// Job offer from client to node
@@ -143,6 +203,12 @@ pub enum RejectionReason {
 
     /// Malformed request
     InvalidRequest { details: String },
+
+    /// Sender is outside this node's peer allowlist (or on its denylist)
+    PeerNotPermitted,
+
+    /// Sender exceeded its offer rate; retry after `retry_after_ms`
+    RateLimited { retry_after_ms: u64 },
 }
 
 /// Complete job request with WASM payload
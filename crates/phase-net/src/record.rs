@@ -0,0 +1,360 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Signed DHT records and namespace-aware admission.
+//!
+//! The Kademlia DHT is untrusted — any peer can put any bytes under any
+//! key. Before this module each consumer verified (or didn't verify) what
+//! it read back on its own. Now every record passes a
+//! [`ValidatorRegistry`] before it is published, before a peer's PUT is
+//! admitted to the local store, and before a GET result reaches the
+//! caller. The validator is chosen by the longest registered key prefix;
+//! keys outside every namespace are only subject to the store's size
+//! limit.
+//!
+//! The default registry requires a [`SignedRecord`] envelope everywhere
+//! Phase publishes:
+//!
+//! | prefix                      | covers                                    |
+//! |-----------------------------|-------------------------------------------|
+//! | `/phase/`                   | capability, boot manifest and blob keys   |
+//! | `/phase/peer-capabilities/` | same, and the signer must be the key's peer |
+//!
+//! Consumers with their own signed format (lucidd's
+//! `SignedModelAdvertisement` under `phase/model/`) add a validator for
+//! their prefix with [`ValidatorRegistry::register`].
+
+use std::{collections::BTreeMap, fmt, sync::Arc, time::Duration};
+
+use ed25519_dalek::{Signature, Signer, Verifier, VerifyingKey};
+use libp2p::PeerId;
+use phase_identity::NodeIdentity;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::store::base64_bytes;
+
+/// Version tag of the [`SignedRecord`] envelope and its signed bytes.
+pub const RECORD_SCHEMA_VERSION: u8 = 1;
+
+/// Domain separator so a record signature can't be replayed as any other
+/// signature the node identity produces.
+const SIGNING_DOMAIN: &[u8] = b"phase/dht-record/v1";
+
+/// Size limit the default validators apply to a whole record (envelope
+/// included). Every Phase record is a small JSON or postcard document;
+/// this is headroom, not a target.
+pub const DEFAULT_MAX_RECORD_BYTES: usize = 16 * 1024;
+
+/// Why a record was refused.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RecordError {
+    #[error("record is {size} bytes, over the {limit}-byte limit")]
+    TooLarge { size: usize, limit: usize },
+    #[error("record is not a signed record envelope")]
+    Unsigned,
+    #[error("unsupported record schema version {0}")]
+    UnsupportedVersion(u8),
+    #[error("record was signed for a different key")]
+    KeyMismatch,
+    #[error("record signature does not verify")]
+    BadSignature,
+    #[error("record expired at {expires_unix_ms} (now {now_unix_ms})")]
+    Expired { expires_unix_ms: u64, now_unix_ms: u64 },
+    #[error("record signer is not the peer named in its key")]
+    WrongPublisher,
+    /// A namespace-specific validator's own reason.
+    #[error("{0}")]
+    Invalid(String),
+}
+
+/// A DHT value signed by a node identity, bound to its key and an expiry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedRecord {
+    /// See [`RECORD_SCHEMA_VERSION`].
+    pub version: u8,
+    /// The DHT key the record was signed for. A record copied under
+    /// another key fails verification.
+    #[serde(with = "base64_bytes")]
+    pub key: Vec<u8>,
+    /// The payload.
+    #[serde(with = "base64_bytes")]
+    pub value: Vec<u8>,
+    /// Signer's Ed25519 verifying key.
+    #[serde(with = "base64_bytes")]
+    pub public_key: Vec<u8>,
+    /// Unix milliseconds after which the record is refused.
+    pub expires_unix_ms: u64,
+    /// Ed25519 signature over the fields above.
+    #[serde(with = "base64_bytes")]
+    pub signature: Vec<u8>,
+}
+
+impl SignedRecord {
+    /// Sign `value` for publication under `key`, valid for `ttl` from now.
+    pub fn sign(identity: &NodeIdentity, key: &[u8], value: &[u8], ttl: Duration) -> Self {
+        let public_key = identity.verifying_key().to_bytes().to_vec();
+        let expires_unix_ms = unix_ms_now().saturating_add(ttl.as_millis() as u64);
+        let bytes = signed_bytes(RECORD_SCHEMA_VERSION, key, value, &public_key, expires_unix_ms);
+        Self {
+            version: RECORD_SCHEMA_VERSION,
+            key: key.to_vec(),
+            value: value.to_vec(),
+            public_key,
+            expires_unix_ms,
+            signature: identity.signing_key().sign(&bytes).to_bytes().to_vec(),
+        }
+    }
+
+    /// Check version, key binding, expiry and signature.
+    pub fn verify(&self, key: &[u8], now_unix_ms: u64) -> Result<(), RecordError> {
+        if self.version != RECORD_SCHEMA_VERSION {
+            return Err(RecordError::UnsupportedVersion(self.version));
+        }
+        if self.key != key {
+            return Err(RecordError::KeyMismatch);
+        }
+        if now_unix_ms >= self.expires_unix_ms {
+            return Err(RecordError::Expired {
+                expires_unix_ms: self.expires_unix_ms,
+                now_unix_ms,
+            });
+        }
+        let public_key: &[u8; 32] = self
+            .public_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| RecordError::BadSignature)?;
+        let signature: &[u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| RecordError::BadSignature)?;
+        let vk = VerifyingKey::from_bytes(public_key).map_err(|_| RecordError::BadSignature)?;
+        let bytes = signed_bytes(self.version, &self.key, &self.value, &self.public_key, self.expires_unix_ms);
+        vk.verify(&bytes, &Signature::from_bytes(signature))
+            .map_err(|_| RecordError::BadSignature)
+    }
+
+    /// The libp2p `PeerId` of the signer.
+    pub fn publisher(&self) -> Option<PeerId> {
+        let ed = libp2p::identity::ed25519::PublicKey::try_from_bytes(&self.public_key).ok()?;
+        Some(PeerId::from(libp2p::identity::PublicKey::from(ed)))
+    }
+
+    /// Wire form stored as the DHT record value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("SignedRecord serialization is infallible")
+    }
+
+    /// Parse the wire form. Does not verify; see [`SignedRecord::open`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RecordError> {
+        serde_json::from_slice(bytes).map_err(|_| RecordError::Unsigned)
+    }
+
+    /// Parse and verify a record read from the DHT under `key`.
+    pub fn open(key: &[u8], bytes: &[u8]) -> Result<Self, RecordError> {
+        let record = Self::from_bytes(bytes)?;
+        record.verify(key, unix_ms_now())?;
+        Ok(record)
+    }
+}
+
+/// `domain || version || len(key) || key || len(value) || value || pubkey
+/// || expires`, lengths and expiry big-endian.
+fn signed_bytes(version: u8, key: &[u8], value: &[u8], public_key: &[u8], expires_unix_ms: u64) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SIGNING_DOMAIN.len() + 1 + 8 + key.len() + value.len() + public_key.len() + 8);
+    out.extend_from_slice(SIGNING_DOMAIN);
+    out.push(version);
+    out.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    out.extend_from_slice(key);
+    out.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    out.extend_from_slice(value);
+    out.extend_from_slice(public_key);
+    out.extend_from_slice(&expires_unix_ms.to_be_bytes());
+    out
+}
+
+/// Admission check for one key namespace.
+pub trait RecordValidator: Send + Sync {
+    /// Accept or refuse `value` under `key`. On success, returns the
+    /// record's own expiry (Unix ms) if it has one, so the local store
+    /// drops it no later than that.
+    fn validate(&self, key: &[u8], value: &[u8], now_unix_ms: u64) -> Result<Option<u64>, RecordError>;
+}
+
+/// Requires a valid, unexpired [`SignedRecord`] within a size limit.
+#[derive(Debug, Clone)]
+pub struct SignedRecordValidator {
+    max_bytes: usize,
+    publisher_bound: bool,
+}
+
+impl SignedRecordValidator {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            publisher_bound: false,
+        }
+    }
+
+    /// Additionally require the key to end in the signer's `PeerId`, for
+    /// namespaces where a peer only speaks for itself.
+    pub fn publisher_bound(mut self) -> Self {
+        self.publisher_bound = true;
+        self
+    }
+}
+
+impl RecordValidator for SignedRecordValidator {
+    fn validate(&self, key: &[u8], value: &[u8], now_unix_ms: u64) -> Result<Option<u64>, RecordError> {
+        if value.len() > self.max_bytes {
+            return Err(RecordError::TooLarge {
+                size: value.len(),
+                limit: self.max_bytes,
+            });
+        }
+        let record = SignedRecord::from_bytes(value)?;
+        record.verify(key, now_unix_ms)?;
+        if self.publisher_bound {
+            let publisher = record.publisher().ok_or(RecordError::BadSignature)?;
+            if !key.ends_with(publisher.to_string().as_bytes()) {
+                return Err(RecordError::WrongPublisher);
+            }
+        }
+        Ok(Some(record.expires_unix_ms))
+    }
+}
+
+/// Validators keyed by key prefix. The longest matching prefix decides.
+#[derive(Clone)]
+pub struct ValidatorRegistry {
+    validators: BTreeMap<Vec<u8>, Arc<dyn RecordValidator>>,
+}
+
+impl ValidatorRegistry {
+    /// A registry with no namespaces: every record is admitted.
+    pub fn empty() -> Self {
+        Self {
+            validators: BTreeMap::new(),
+        }
+    }
+
+    /// Install (or replace) the validator for keys starting with `prefix`.
+    pub fn register(&mut self, prefix: impl Into<Vec<u8>>, validator: Arc<dyn RecordValidator>) -> &mut Self {
+        self.validators.insert(prefix.into(), validator);
+        self
+    }
+
+    /// Run the validator for `key`'s namespace. Keys outside every
+    /// registered namespace are admitted with no expiry of their own.
+    pub fn validate(&self, key: &[u8], value: &[u8], now_unix_ms: u64) -> Result<Option<u64>, RecordError> {
+        match self
+            .validators
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+        {
+            Some((_, validator)) => validator.validate(key, value, now_unix_ms),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Default for ValidatorRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register("/phase/", Arc::new(SignedRecordValidator::new(DEFAULT_MAX_RECORD_BYTES)))
+            .register(
+                "/phase/peer-capabilities/",
+                Arc::new(SignedRecordValidator::new(DEFAULT_MAX_RECORD_BYTES).publisher_bound()),
+            );
+        registry
+    }
+}
+
+impl fmt::Debug for ValidatorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.validators.keys().map(|k| String::from_utf8_lossy(k)))
+            .finish()
+    }
+}
+
+pub(crate) fn unix_ms_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn signed_record_verifies_only_for_its_key_and_window() {
+        let identity = NodeIdentity::generate();
+        let record = SignedRecord::sign(&identity, b"/phase/blob/ab", b"payload", HOUR);
+        let now = unix_ms_now();
+        record.verify(b"/phase/blob/ab", now).unwrap();
+        assert_eq!(record.verify(b"/phase/blob/cd", now), Err(RecordError::KeyMismatch));
+        assert!(matches!(
+            record.verify(b"/phase/blob/ab", record.expires_unix_ms),
+            Err(RecordError::Expired { .. })
+        ));
+
+        let mut tampered = record.clone();
+        tampered.value = b"other".to_vec();
+        assert_eq!(tampered.verify(b"/phase/blob/ab", now), Err(RecordError::BadSignature));
+        let mut extended = record.clone();
+        extended.expires_unix_ms += 1;
+        assert_eq!(extended.verify(b"/phase/blob/ab", now), Err(RecordError::BadSignature));
+
+        let reparsed = SignedRecord::open(b"/phase/blob/ab", &record.to_bytes()).unwrap();
+        assert_eq!(reparsed.value, b"payload");
+    }
+
+    #[test]
+    fn default_registry_requires_signatures_in_phase_namespaces() {
+        let registry = ValidatorRegistry::default();
+        let identity = NodeIdentity::generate();
+        let now = unix_ms_now();
+
+        for key in [&b"/phase/stable/arm64/manifest"[..], b"/phase/blob/ab"] {
+            assert_eq!(registry.validate(key, b"{\"plain\":true}", now), Err(RecordError::Unsigned));
+            let signed = SignedRecord::sign(&identity, key, b"ok", HOUR);
+            assert_eq!(registry.validate(key, &signed.to_bytes(), now), Ok(Some(signed.expires_unix_ms)));
+        }
+        // Outside every namespace: admitted as-is.
+        assert_eq!(registry.validate(b"/other/key", b"anything", now), Ok(None));
+
+        let big = SignedRecord::sign(&identity, b"/phase/blob/ab", &[0; DEFAULT_MAX_RECORD_BYTES], HOUR);
+        assert!(matches!(
+            registry.validate(b"/phase/blob/ab", &big.to_bytes(), now),
+            Err(RecordError::TooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn peer_capability_records_must_come_from_that_peer() {
+        let registry = ValidatorRegistry::default();
+        let identity = NodeIdentity::generate();
+        let other = NodeIdentity::generate();
+        let own_key = |id: &NodeIdentity| {
+            let record = SignedRecord::sign(id, b"", b"", HOUR);
+            format!("/phase/peer-capabilities/{}", record.publisher().unwrap()).into_bytes()
+        };
+        let key = own_key(&identity);
+
+        let own = SignedRecord::sign(&identity, &key, b"{}", HOUR);
+        assert!(registry.validate(&key, &own.to_bytes(), unix_ms_now()).is_ok());
+        let forged = SignedRecord::sign(&other, &key, b"{}", HOUR);
+        assert_eq!(
+            registry.validate(&key, &forged.to_bytes(), unix_ms_now()),
+            Err(RecordError::WrongPublisher)
+        );
+    }
+}
@@ -0,0 +1,584 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Disk-backed Kademlia state.
+//!
+//! With a plain `MemoryStore` every restart forgot the records and provider
+//! entries the node was holding for others, and the routing table that
+//! told it where anyone was. On a small private network with no fixed
+//! bootstrap node that meant a restarted node was alone until mDNS (LAN
+//! only) or an operator put it back in touch.
+//!
+//! Two files live in [`DiscoveryConfig::state_dir`]:
+//!
+//! - `kad-records.json` — [`PersistentStore`]: a `MemoryStore` with TTL
+//!   clamping that snapshots its records and provider entries to disk.
+//! - `peers.json` — a [`PeerSnapshot`] of the routing table, redialled on
+//!   start as implicit bootstrap peers.
+//!
+//! Both are written from the discovery driver (periodically, on
+//! [`Discovery::persist`], and when the driver exits) via write-to-temp
+//! then rename, so a crash mid-write leaves the previous snapshot intact.
+//! A missing or unreadable snapshot is logged and treated as empty — stale
+//! DHT state is never a reason to refuse to start.
+//!
+//! Kademlia expiries are monotonic `Instant`s, which mean nothing across a
+//! restart; on disk they become remaining-TTL wall-clock deadlines.
+//!
+//! [`DiscoveryConfig::state_dir`]: crate::DiscoveryConfig::state_dir
+//! [`Discovery::persist`]: crate::Discovery::persist
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use libp2p::{
+    kad::{
+        store::{self, MemoryStore, MemoryStoreConfig, RecordStore},
+        ProviderRecord, Record, RecordKey,
+    },
+    Multiaddr, PeerId,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::record::{unix_ms_now, ValidatorRegistry};
+
+/// Record-store snapshot file name inside the state dir.
+pub const RECORDS_FILE: &str = "kad-records.json";
+
+/// Routing-table snapshot file name inside the state dir.
+pub const PEERS_FILE: &str = "peers.json";
+
+/// Bumped if the snapshot layout changes; a mismatched file is ignored.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Most peers kept in the routing-table snapshot. Enough to find the mesh
+/// again; more just means a slower start dialling dead addresses.
+pub const MAX_SNAPSHOT_PEERS: usize = 64;
+
+/// Conventional state dir for a node whose identity lives at
+/// `identity_path`: a sibling directory named after the key file
+/// (`identity.key` → `identity.dht/`), so two nodes on one host with
+/// separate identities never share DHT state.
+pub fn state_dir_for_identity(identity_path: &Path) -> PathBuf {
+    identity_path.with_extension("dht")
+}
+
+/// Size and TTL limits for the Kademlia record store.
+#[derive(Debug, Clone)]
+pub struct StoreConfig {
+    /// Most records held (ours and other peers').
+    pub max_records: usize,
+    /// Largest record value accepted, in bytes.
+    pub max_value_bytes: usize,
+    /// Most providers kept per key.
+    pub max_providers_per_key: usize,
+    /// Most keys this node itself provides.
+    pub max_provided_keys: usize,
+    /// Longest a record or provider entry is kept, whatever expiry it
+    /// arrived with. Entries with no expiry get exactly this.
+    pub max_ttl: Duration,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        let memory = MemoryStoreConfig::default();
+        Self {
+            max_records: memory.max_records,
+            max_value_bytes: memory.max_value_bytes,
+            max_providers_per_key: memory.max_providers_per_key,
+            max_provided_keys: memory.max_provided_keys,
+            // libp2p-kad's default record TTL.
+            max_ttl: Duration::from_secs(48 * 60 * 60),
+        }
+    }
+}
+
+/// Kademlia record store: a `MemoryStore` that can snapshot to, and
+/// reload from, `kad-records.json`.
+pub struct PersistentStore {
+    inner: MemoryStore,
+    /// Snapshot file; `None` keeps the store memory-only.
+    path: Option<PathBuf>,
+    max_ttl: Duration,
+    /// Keys with provider entries. `MemoryStore` can list providers per key
+    /// and our own provided keys, but not every key it holds providers for.
+    provider_keys: HashSet<RecordKey>,
+    /// Changed since the last flush.
+    dirty: bool,
+}
+
+impl PersistentStore {
+    /// Build a store for `local_peer_id`, reloading `state_dir`'s snapshot
+    /// if there is one. Restored records go through `validators` like an
+    /// inbound PUT: the snapshot is only as trustworthy as the disk it
+    /// sits on, and a record may have outlived its signed expiry.
+    pub fn new(
+        local_peer_id: PeerId,
+        config: &StoreConfig,
+        state_dir: Option<&Path>,
+        validators: &ValidatorRegistry,
+    ) -> Self {
+        let memory_config = MemoryStoreConfig {
+            max_records: config.max_records,
+            max_value_bytes: config.max_value_bytes,
+            max_providers_per_key: config.max_providers_per_key,
+            max_provided_keys: config.max_provided_keys,
+        };
+        let mut store = Self {
+            inner: MemoryStore::with_config(local_peer_id, memory_config),
+            path: state_dir.map(|d| d.join(RECORDS_FILE)),
+            max_ttl: config.max_ttl,
+            provider_keys: HashSet::new(),
+            dirty: false,
+        };
+        if let Some(path) = store.path.clone() {
+            match read_json::<RecordSnapshot>(&path) {
+                Ok(Some(snapshot)) => store.restore(snapshot, validators),
+                Ok(None) => {}
+                Err(e) => warn!("Ignoring unreadable record snapshot {}: {:#}", path.display(), e),
+            }
+        }
+        store
+    }
+
+    fn restore(&mut self, snapshot: RecordSnapshot, validators: &ValidatorRegistry) {
+        if snapshot.version != SNAPSHOT_VERSION {
+            warn!("Ignoring record snapshot version {}", snapshot.version);
+            return;
+        }
+        let now = Instant::now();
+        let now_ms = unix_ms_now();
+        let (mut records, mut providers) = (0usize, 0usize);
+        for r in snapshot.records {
+            let expires_unix_ms = match validators.validate(&r.key, &r.value, now_ms) {
+                Ok(signed) => signed.map_or(r.expires_unix_ms, |e| e.min(r.expires_unix_ms)),
+                Err(e) => {
+                    debug!("Dropping restored record {}: {}", String::from_utf8_lossy(&r.key), e);
+                    continue;
+                }
+            };
+            let Some(expires) = instant_from_unix_ms(expires_unix_ms, now, now_ms) else {
+                continue;
+            };
+            let record = Record {
+                key: RecordKey::new(&r.key),
+                value: r.value,
+                publisher: r.publisher.and_then(|p| PeerId::from_str(&p).ok()),
+                expires: Some(expires),
+            };
+            if self.put(record).is_ok() {
+                records += 1;
+            }
+        }
+        for p in snapshot.providers {
+            let Some(expires) = instant_from_unix_ms(p.expires_unix_ms, now, now_ms) else {
+                continue;
+            };
+            let Ok(provider) = PeerId::from_str(&p.provider) else {
+                continue;
+            };
+            let record = ProviderRecord {
+                key: RecordKey::new(&p.key),
+                provider,
+                expires: Some(expires),
+                addresses: p.addresses.iter().filter_map(|a| a.parse().ok()).collect(),
+            };
+            if self.add_provider(record).is_ok() {
+                providers += 1;
+            }
+        }
+        self.dirty = false;
+        info!("Restored {} DHT records and {} provider entries", records, providers);
+    }
+
+    /// Clamp an expiry to at most `max_ttl` from now.
+    fn clamp(&self, expires: Option<Instant>) -> Option<Instant> {
+        let limit = Instant::now() + self.max_ttl;
+        Some(expires.map_or(limit, |e| e.min(limit)))
+    }
+
+    /// Write the snapshot if anything changed since the last flush. Expired
+    /// entries are left out.
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let now_ms = unix_ms_now();
+        let records = self
+            .inner
+            .records()
+            .filter_map(|r| {
+                Some(StoredRecord {
+                    key: r.key.to_vec(),
+                    value: r.value.clone(),
+                    publisher: r.publisher.map(|p| p.to_string()),
+                    expires_unix_ms: unix_ms_from_instant(r.expires, now, now_ms)?,
+                })
+            })
+            .collect();
+        let providers = self
+            .provider_keys
+            .iter()
+            .flat_map(|k| self.inner.providers(k))
+            .filter_map(|p| {
+                Some(StoredProvider {
+                    key: p.key.to_vec(),
+                    provider: p.provider.to_string(),
+                    addresses: p.addresses.iter().map(|a| a.to_string()).collect(),
+                    expires_unix_ms: unix_ms_from_instant(p.expires, now, now_ms)?,
+                })
+            })
+            .collect();
+        write_json_atomic(
+            &path,
+            &RecordSnapshot {
+                version: SNAPSHOT_VERSION,
+                records,
+                providers,
+            },
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl RecordStore for PersistentStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, k: &RecordKey) -> Option<Cow<'_, Record>> {
+        self.inner.get(k)
+    }
+
+    fn put(&mut self, mut r: Record) -> store::Result<()> {
+        r.expires = self.clamp(r.expires);
+        self.inner.put(r)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn remove(&mut self, k: &RecordKey) {
+        self.inner.remove(k);
+        self.dirty = true;
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.inner.records()
+    }
+
+    fn add_provider(&mut self, mut record: ProviderRecord) -> store::Result<()> {
+        record.expires = self.clamp(record.expires);
+        let key = record.key.clone();
+        self.inner.add_provider(record)?;
+        self.provider_keys.insert(key);
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+        self.inner.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.inner.provided()
+    }
+
+    fn remove_provider(&mut self, k: &RecordKey, p: &PeerId) {
+        self.inner.remove_provider(k, p);
+        if self.inner.providers(k).is_empty() {
+            self.provider_keys.remove(k);
+        }
+        self.dirty = true;
+    }
+}
+
+/// Routing-table snapshot: peers and the addresses they were reached at.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PeerSnapshot {
+    version: u32,
+    pub peers: Vec<SnapshotPeer>,
+}
+
+/// One routing-table entry in a [`PeerSnapshot`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotPeer {
+    pub peer_id: String,
+    pub addrs: Vec<String>,
+}
+
+impl PeerSnapshot {
+    /// Snapshot up to [`MAX_SNAPSHOT_PEERS`] of `peers`.
+    pub fn new(peers: impl IntoIterator<Item = (PeerId, Vec<Multiaddr>)>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            peers: peers
+                .into_iter()
+                .filter(|(_, addrs)| !addrs.is_empty())
+                .take(MAX_SNAPSHOT_PEERS)
+                .map(|(peer, addrs)| SnapshotPeer {
+                    peer_id: peer.to_string(),
+                    addrs: addrs.iter().map(|a| a.to_string()).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Load `state_dir`'s snapshot; empty if absent or unreadable.
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(PEERS_FILE);
+        match read_json::<PeerSnapshot>(&path) {
+            Ok(Some(s)) if s.version == SNAPSHOT_VERSION => s,
+            Ok(Some(s)) => {
+                warn!("Ignoring peer snapshot version {}", s.version);
+                Self::default()
+            }
+            Ok(None) => Self::default(),
+            Err(e) => {
+                warn!("Ignoring unreadable peer snapshot {}: {:#}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, state_dir: &Path) -> Result<()> {
+        write_json_atomic(&state_dir.join(PEERS_FILE), self)
+    }
+
+    /// Parsed entries; malformed ones are skipped.
+    pub fn entries(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        self.peers
+            .iter()
+            .filter_map(|p| {
+                let peer = PeerId::from_str(&p.peer_id).ok()?;
+                let addrs: Vec<Multiaddr> = p.addrs.iter().filter_map(|a| a.parse().ok()).collect();
+                (!addrs.is_empty()).then_some((peer, addrs))
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordSnapshot {
+    version: u32,
+    records: Vec<StoredRecord>,
+    providers: Vec<StoredProvider>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    #[serde(with = "base64_bytes")]
+    key: Vec<u8>,
+    #[serde(with = "base64_bytes")]
+    value: Vec<u8>,
+    publisher: Option<String>,
+    expires_unix_ms: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredProvider {
+    #[serde(with = "base64_bytes")]
+    key: Vec<u8>,
+    provider: String,
+    addresses: Vec<String>,
+    expires_unix_ms: u64,
+}
+
+/// Wall-clock deadline for a monotonic expiry; `None` if already expired.
+/// Entries always carry an expiry here — `clamp` fills in missing ones.
+fn unix_ms_from_instant(expires: Option<Instant>, now: Instant, now_ms: u64) -> Option<u64> {
+    let remaining = expires?.checked_duration_since(now)?;
+    Some(now_ms + remaining.as_millis() as u64)
+}
+
+/// Monotonic expiry for a wall-clock deadline; `None` if already past.
+pub(crate) fn instant_from_unix_ms(deadline_ms: u64, now: Instant, now_ms: u64) -> Option<Instant> {
+    let remaining = deadline_ms.checked_sub(now_ms).filter(|&ms| ms > 0)?;
+    Some(now + Duration::from_millis(remaining))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<Option<T>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes).context("Malformed snapshot")?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).context("Failed to read snapshot"),
+    }
+}
+
+fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let dir = path.parent().context("Snapshot path has no parent directory")?;
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create state dir {}", dir.display()))?;
+    let tmp = path.with_extension("json.tmp");
+    let mut file = fs::File::create(&tmp)
+        .with_context(|| format!("Failed to create {}", tmp.display()))?;
+    file.write_all(&serde_json::to_vec(value)?)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Base64 for binary record keys and values, so snapshots and signed
+/// records stay readable JSON instead of number arrays.
+pub(crate) mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        STANDARD.decode(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(key: &str, value: &[u8], ttl: Option<Duration>) -> Record {
+        Record {
+            key: RecordKey::new(&key),
+            value: value.to_vec(),
+            publisher: None,
+            expires: ttl.map(|t| Instant::now() + t),
+        }
+    }
+
+    #[test]
+    fn records_and_providers_survive_a_reload() {
+        let dir = TempDir::new().unwrap();
+        let local = PeerId::random();
+        let provider = PeerId::random();
+        let config = StoreConfig::default();
+
+        let mut store = PersistentStore::new(local, &config, Some(dir.path()), &ValidatorRegistry::empty());
+        store.put(record("/model/a", b"advert", Some(Duration::from_secs(60)))).unwrap();
+        store
+            .add_provider(ProviderRecord {
+                key: RecordKey::new(&"/phase/capability/x86_64/wasm"),
+                provider,
+                expires: None,
+                addresses: vec!["/ip4/10.0.0.2/tcp/4001".parse().unwrap()],
+            })
+            .unwrap();
+        store.flush().unwrap();
+
+        let reloaded = PersistentStore::new(local, &config, Some(dir.path()), &ValidatorRegistry::empty());
+        let got = reloaded.get(&RecordKey::new(&"/model/a")).unwrap();
+        assert_eq!(got.value, b"advert");
+        let remaining = got.expires.unwrap() - Instant::now();
+        assert!(remaining <= Duration::from_secs(60) && remaining > Duration::from_secs(50));
+        let providers = reloaded.providers(&RecordKey::new(&"/phase/capability/x86_64/wasm"));
+        assert_eq!(providers.len(), 1);
+        assert_eq!(providers[0].provider, provider);
+        assert_eq!(providers[0].addresses.len(), 1);
+    }
+
+    #[test]
+    fn ttl_is_clamped_and_expired_entries_are_dropped() {
+        let dir = TempDir::new().unwrap();
+        let local = PeerId::random();
+        let config = StoreConfig {
+            max_ttl: Duration::from_secs(30),
+            ..StoreConfig::default()
+        };
+
+        let mut store = PersistentStore::new(local, &config, Some(dir.path()), &ValidatorRegistry::empty());
+        store.put(record("/forever", b"x", None)).unwrap();
+        store.put(record("/long", b"y", Some(Duration::from_secs(3600)))).unwrap();
+        store.put(record("/gone", b"z", Some(Duration::ZERO))).unwrap();
+        for key in ["/forever", "/long"] {
+            let expires = store.get(&RecordKey::new(&key)).unwrap().expires.unwrap();
+            assert!(expires <= Instant::now() + Duration::from_secs(30), "{key}");
+        }
+        store.flush().unwrap();
+
+        let reloaded = PersistentStore::new(local, &config, Some(dir.path()), &ValidatorRegistry::empty());
+        assert!(reloaded.get(&RecordKey::new(&"/forever")).is_some());
+        assert!(reloaded.get(&RecordKey::new(&"/gone")).is_none());
+    }
+
+    #[test]
+    fn restored_records_are_revalidated() {
+        use crate::record::SignedRecord;
+        use phase_identity::NodeIdentity;
+
+        let dir = TempDir::new().unwrap();
+        let local = PeerId::random();
+        let config = StoreConfig::default();
+        let identity = NodeIdentity::generate();
+        let signed = |key: &str, ttl| SignedRecord::sign(&identity, key.as_bytes(), b"v", ttl).to_bytes();
+
+        // Written unchecked, as a tampered or stale snapshot would be.
+        let mut store = PersistentStore::new(local, &config, Some(dir.path()), &ValidatorRegistry::empty());
+        store.put(record("/phase/good", &signed("/phase/good", Duration::from_secs(60)), None)).unwrap();
+        store.put(record("/phase/moved", &signed("/phase/elsewhere", Duration::from_secs(60)), None)).unwrap();
+        store.put(record("/phase/stale", &signed("/phase/stale", Duration::ZERO), None)).unwrap();
+        store.put(record("/other", b"unsigned", None)).unwrap();
+        store.flush().unwrap();
+
+        let reloaded = PersistentStore::new(local, &config, Some(dir.path()), &ValidatorRegistry::default());
+        let good = reloaded.get(&RecordKey::new(&"/phase/good")).unwrap();
+        // The signed expiry wins over the store's longer clamp.
+        assert!(good.expires.unwrap() <= Instant::now() + Duration::from_secs(60));
+        assert!(reloaded.get(&RecordKey::new(&"/phase/moved")).is_none());
+        assert!(reloaded.get(&RecordKey::new(&"/phase/stale")).is_none());
+        assert!(reloaded.get(&RecordKey::new(&"/other")).is_some());
+    }
+
+    #[test]
+    fn size_limits_apply() {
+        let config = StoreConfig {
+            max_records: 1,
+            max_value_bytes: 4,
+            ..StoreConfig::default()
+        };
+        let mut store = PersistentStore::new(PeerId::random(), &config, None, &ValidatorRegistry::empty());
+        assert!(store.put(record("/big", b"too large", None)).is_err());
+        store.put(record("/a", b"ok", None)).unwrap();
+        assert!(store.put(record("/b", b"ok", None)).is_err());
+        // Memory-only stores flush to nowhere.
+        store.flush().unwrap();
+    }
+
+    #[test]
+    fn unreadable_snapshots_start_empty() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join(RECORDS_FILE), b"{not json").unwrap();
+        fs::write(dir.path().join(PEERS_FILE), b"[]").unwrap();
+        let store = PersistentStore::new(
+            PeerId::random(),
+            &StoreConfig::default(),
+            Some(dir.path()),
+            &ValidatorRegistry::empty(),
+        );
+        assert_eq!(store.records().count(), 0);
+        assert!(PeerSnapshot::load(dir.path()).entries().is_empty());
+    }
+
+    #[test]
+    fn peer_snapshot_round_trips() {
+        let dir = TempDir::new().unwrap();
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/192.168.1.9/tcp/4001".parse().unwrap();
+        PeerSnapshot::new([(peer, vec![addr.clone()]), (PeerId::random(), Vec::new())])
+            .save(dir.path())
+            .unwrap();
+        assert_eq!(PeerSnapshot::load(dir.path()).entries(), vec![(peer, vec![addr])]);
+    }
+}
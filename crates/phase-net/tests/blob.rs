@@ -0,0 +1,110 @@
+//! The blob range protocol between two in-process swarms on loopback.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_net::{
+    BlobHandler, BlobPart, BlobRequest, BlobResponse, Discovery, DiscoveryConfig,
+    BLOB_MAX_RANGE_BYTES,
+};
+
+use common::{listening_addr, node};
+
+fn config() -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        ..DiscoveryConfig::default()
+    }
+}
+
+/// A listening server and a client dialled into it.
+async fn setup() -> (Discovery, Discovery) {
+    let server = node(config());
+    server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+    let addr = listening_addr(&server).await;
+    let client = node(config());
+    client
+        .dial_peer(&format!("{addr}/p2p/{}", server.local_peer_id()))
+        .await
+        .unwrap();
+    (server, client)
+}
+
+/// Serves ranges of one in-memory blob called `"abc"`.
+fn handler(content: Arc<Vec<u8>>) -> BlobHandler {
+    Arc::new(move |_peer, request: BlobRequest| {
+        let content = content.clone();
+        Box::pin(async move {
+            if request.blob_id != "abc" || request.part != BlobPart::Content {
+                return BlobResponse::NotFound;
+            }
+            let start = (request.offset as usize).min(content.len());
+            let end = (start + request.length as usize).min(content.len());
+            BlobResponse::Data {
+                total_size: content.len() as u64,
+                bytes: content[start..end].to_vec(),
+            }
+        })
+    })
+}
+
+fn range(blob_id: &str, offset: u64, length: u32) -> BlobRequest {
+    BlobRequest {
+        blob_id: blob_id.to_string(),
+        part: BlobPart::Content,
+        offset,
+        length,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn ranges_are_served_by_the_installed_handler() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let (server, client) = setup().await;
+        let peer = *server.local_peer_id();
+        let content: Arc<Vec<u8>> =
+            Arc::new((0..BLOB_MAX_RANGE_BYTES as usize * 2).map(|i| (i % 251) as u8).collect());
+        server
+            .set_blob_handler(Some(handler(content.clone())))
+            .await
+            .unwrap();
+
+        match client.request_blob(peer, range("abc", 10, 100)).await.unwrap() {
+            BlobResponse::Data { total_size, bytes } => {
+                assert_eq!(total_size, content.len() as u64);
+                assert_eq!(bytes, content[10..110]);
+            }
+            other => panic!("expected Data, got {other:?}"),
+        }
+
+        // Oversized asks are clamped to one full range.
+        match client.request_blob(peer, range("abc", 0, u32::MAX)).await.unwrap() {
+            BlobResponse::Data { bytes, .. } => {
+                assert_eq!(bytes.len(), BLOB_MAX_RANGE_BYTES as usize);
+            }
+            other => panic!("expected Data, got {other:?}"),
+        }
+
+        let response = client.request_blob(peer, range("def", 0, 10)).await.unwrap();
+        assert_eq!(response, BlobResponse::NotFound);
+    })
+    .await
+    .expect("blob requests timed out");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn requests_are_refused_without_a_handler() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let (server, client) = setup().await;
+        let peer = *server.local_peer_id();
+        match client.request_blob(peer, range("abc", 0, 10)).await.unwrap() {
+            BlobResponse::Err { reason } => assert!(reason.contains("no blob handler"), "{reason}"),
+            other => panic!("expected Err, got {other:?}"),
+        }
+    })
+    .await
+    .expect("refused blob request timed out");
+}
@@ -0,0 +1,22 @@
+//! Helpers shared by the in-process swarm tests.
+
+use std::time::Duration;
+
+use libp2p::Multiaddr;
+use phase_net::{Discovery, DiscoveryConfig};
+
+/// Start a node. Tests run with mDNS off, so this can't fail for want of
+/// multicast permissions.
+pub fn node(config: DiscoveryConfig) -> Discovery {
+    Discovery::new(config).expect("discovery without mDNS needs no special permissions")
+}
+
+/// Poll `node` until it reports an address it listens on.
+pub async fn listening_addr(node: &Discovery) -> Multiaddr {
+    loop {
+        if let Some(a) = node.listen_addrs().await.unwrap().into_iter().next() {
+            return a.parse().expect("listen addresses are multiaddrs");
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
@@ -0,0 +1,100 @@
+//! The `Discovery::subscribe` event stream between two in-process swarms
+//! on loopback: connects, relays and disconnects show up in order.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_net::{
+    DiscoveryConfig, JobRelayHandler, JobRelayRequest, JobRelayResponse, NetworkEvent, PeerId,
+};
+use tokio::sync::broadcast;
+
+use common::{listening_addr, node};
+
+fn config() -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        ..DiscoveryConfig::default()
+    }
+}
+
+/// Skip events until one matches `pred`.
+async fn next_matching(
+    events: &mut broadcast::Receiver<NetworkEvent>,
+    pred: impl Fn(&NetworkEvent) -> bool,
+) -> NetworkEvent {
+    loop {
+        let event = events.recv().await.expect("event stream open");
+        if pred(&event) {
+            return event;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn subscribers_see_peers_come_and_go() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let server = node(config());
+        let mut events = server.subscribe();
+        server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let handler: JobRelayHandler = Arc::new(|_peer, payload| {
+            Box::pin(async move {
+                JobRelayResponse::Ok {
+                    events: payload,
+                    receipt: Vec::new(),
+                }
+            })
+        });
+        server.set_job_relay_handler(Some(handler)).await.unwrap();
+        let addr = listening_addr(&server).await;
+
+        let client = node(config());
+        let client_peer: PeerId = *client.local_peer_id();
+        client
+            .dial_peer(&format!("{addr}/p2p/{}", server.local_peer_id()))
+            .await
+            .unwrap();
+        let connected = next_matching(&mut events, |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        })
+        .await;
+        assert!(
+            matches!(connected, NetworkEvent::PeerConnected { peer, .. } if peer == client_peer),
+            "{connected:?}"
+        );
+
+        client
+            .send_job_relay(
+                *server.local_peer_id(),
+                JobRelayRequest {
+                    payload: b"ping".to_vec(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            next_matching(&mut events, |e| matches!(e, NetworkEvent::JobRelayStarted { .. })).await,
+            NetworkEvent::JobRelayStarted { peer: client_peer }
+        );
+        assert_eq!(
+            next_matching(&mut events, |e| matches!(e, NetworkEvent::JobRelayFinished { .. })).await,
+            NetworkEvent::JobRelayFinished {
+                peer: client_peer,
+                ok: true
+            }
+        );
+
+        drop(client);
+        let disconnected =
+            next_matching(&mut events, |e| matches!(e, NetworkEvent::PeerDisconnected { .. })).await;
+        assert!(
+            matches!(disconnected, NetworkEvent::PeerDisconnected { peer, .. } if peer == client_peer),
+            "{disconnected:?}"
+        );
+    })
+    .await
+    .expect("event stream exchange timed out");
+}
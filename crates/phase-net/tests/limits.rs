@@ -0,0 +1,149 @@
+//! Per-peer limits on the inbound job-relay protocol, between two
+//! in-process swarms on loopback.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_net::{
+    Discovery, DiscoveryConfig, JobRelayHandler, JobRelayRequest, JobRelayResponse, LimitsConfig,
+    RateLimit,
+};
+
+use common::{listening_addr, node};
+
+fn config(limits: LimitsConfig) -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        limits,
+        ..DiscoveryConfig::default()
+    }
+}
+
+/// Serve relays with a handler that takes `delay`; returns a connected
+/// client.
+async fn setup(limits: LimitsConfig, delay: Duration) -> (Discovery, Discovery) {
+    let server = node(config(limits));
+    server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+    let handler: JobRelayHandler = Arc::new(move |_peer, payload| {
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            JobRelayResponse::Ok {
+                events: payload,
+                receipt: Vec::new(),
+            }
+        })
+    });
+    server.set_job_relay_handler(Some(handler)).await.unwrap();
+    let addr = listening_addr(&server).await;
+    let client = node(config(LimitsConfig::default()));
+    client
+        .dial_peer(&format!("{addr}/p2p/{}", server.local_peer_id()))
+        .await
+        .unwrap();
+    (server, client)
+}
+
+fn request() -> JobRelayRequest {
+    JobRelayRequest {
+        payload: b"job".to_vec(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn relays_past_the_burst_are_rate_limited() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let limits = LimitsConfig {
+            job_relay_rate: RateLimit {
+                burst: 2,
+                per_minute: 1,
+            },
+            ..LimitsConfig::default()
+        };
+        let (server, client) = setup(limits, Duration::ZERO).await;
+        let peer = *server.local_peer_id();
+
+        for _ in 0..2 {
+            let response = client.send_job_relay(peer, request()).await.unwrap();
+            assert!(matches!(response, JobRelayResponse::Ok { .. }), "{response:?}");
+        }
+        match client.send_job_relay(peer, request()).await.unwrap() {
+            JobRelayResponse::RateLimited { retry_after_ms } => {
+                assert!(retry_after_ms > 30_000 && retry_after_ms <= 60_000, "{retry_after_ms}");
+            }
+            other => panic!("expected RateLimited, got {other:?}"),
+        }
+    })
+    .await
+    .expect("rate-limited relay timed out");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn concurrent_relays_per_peer_are_capped() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let limits = LimitsConfig {
+            max_inflight_relays_per_peer: 1,
+            ..LimitsConfig::default()
+        };
+        let (server, client) = setup(limits, Duration::from_millis(500)).await;
+        let peer = *server.local_peer_id();
+
+        let (a, b) = tokio::join!(
+            client.send_job_relay(peer, request()),
+            client.send_job_relay(peer, request()),
+        );
+        let responses = [a.unwrap(), b.unwrap()];
+        assert_eq!(
+            responses.iter().filter(|r| matches!(r, JobRelayResponse::Ok { .. })).count(),
+            1,
+            "{responses:?}"
+        );
+        assert!(responses.iter().any(|r| matches!(r, JobRelayResponse::Busy)));
+
+        // The slot frees once the first handler finishes.
+        let response = client.send_job_relay(peer, request()).await.unwrap();
+        assert!(matches!(response, JobRelayResponse::Ok { .. }), "{response:?}");
+    })
+    .await
+    .expect("concurrent relays timed out");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn a_panicking_handler_replies_and_frees_its_slot() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let server = node(config(LimitsConfig {
+            max_inflight_relays_per_peer: 1,
+            ..LimitsConfig::default()
+        }));
+        server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let handler: JobRelayHandler = Arc::new(|_peer, payload| {
+            Box::pin(async move {
+                assert_ne!(payload, b"panic", "handler blew up");
+                JobRelayResponse::Ok {
+                    events: payload,
+                    receipt: Vec::new(),
+                }
+            })
+        });
+        server.set_job_relay_handler(Some(handler)).await.unwrap();
+        let addr = listening_addr(&server).await;
+        let peer = *server.local_peer_id();
+        let client = node(config(LimitsConfig::default()));
+        client.dial_peer(&format!("{addr}/p2p/{peer}")).await.unwrap();
+
+        let panicked = JobRelayRequest {
+            payload: b"panic".to_vec(),
+        };
+        match client.send_job_relay(peer, panicked).await.unwrap() {
+            JobRelayResponse::Err { reason } => assert_eq!(reason, "relay handler failed"),
+            other => panic!("expected Err, got {other:?}"),
+        }
+        // The only slot is free again.
+        let response = client.send_job_relay(peer, request()).await.unwrap();
+        assert!(matches!(response, JobRelayResponse::Ok { .. }), "{response:?}");
+    })
+    .await
+    .expect("panicking relay timed out");
+}
@@ -0,0 +1,122 @@
+//! Passive peer measurement: ping RTT, blob throughput, relay counts and
+//! identify, between two in-process swarms on loopback, and the bucketed
+//! summary in the published capability record.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_net::{
+    capability_record_key, BlobHandler, BlobPart, BlobRequest, BlobResponse, Discovery,
+    DiscoveryConfig, JobRelayHandler, JobRelayRequest, JobRelayResponse, LatencyBucket,
+    PeerCapabilities, PeerStats, SignedRecord,
+};
+
+use common::{listening_addr, node};
+
+fn config() -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        ..DiscoveryConfig::default()
+    }
+}
+
+async fn stats_for(node: &Discovery, peer: phase_net::PeerId) -> Option<PeerStats> {
+    node.peer_stats()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|s| s.peer_id == peer)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn ping_identify_and_relay_fill_the_peer_table() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let server = node(config());
+        server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let handler: JobRelayHandler = Arc::new(|_peer, payload| {
+            Box::pin(async move {
+                JobRelayResponse::Ok {
+                    events: payload,
+                    receipt: Vec::new(),
+                }
+            })
+        });
+        server.set_job_relay_handler(Some(handler)).await.unwrap();
+        let blob_handler: BlobHandler = Arc::new(|_peer, request: BlobRequest| {
+            Box::pin(async move {
+                BlobResponse::Data {
+                    total_size: u64::from(request.length),
+                    bytes: vec![7; request.length as usize],
+                }
+            })
+        });
+        server.set_blob_handler(Some(blob_handler)).await.unwrap();
+        let addr = listening_addr(&server).await;
+        let server_peer = *server.local_peer_id();
+
+        let client = node(config());
+        // Advertised before anything is measured, as a daemon does at
+        // startup; the driver republishes once the first sample lands.
+        client.advertise_capabilities().await.unwrap();
+        client
+            .dial_peer(&format!("{addr}/p2p/{server_peer}"))
+            .await
+            .unwrap();
+        client
+            .send_job_relay(
+                server_peer,
+                JobRelayRequest {
+                    payload: vec![7; 64 * 1024],
+                },
+            )
+            .await
+            .unwrap();
+        client
+            .request_blob(
+                server_peer,
+                BlobRequest {
+                    blob_id: "abc".into(),
+                    part: BlobPart::Content,
+                    offset: 0,
+                    length: 64 * 1024,
+                },
+            )
+            .await
+            .unwrap();
+
+        let stats = loop {
+            match stats_for(&client, server_peer).await {
+                Some(s) if s.rtt.is_some() && s.agent_version.is_some() => break s,
+                _ => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        };
+        assert_eq!(stats.relay_transfers, 1);
+        assert!(stats.throughput_bytes_per_sec.unwrap() > 0);
+        assert_eq!(stats.latency_bucket(), LatencyBucket::Good);
+        assert!(stats.agent_version.unwrap().starts_with("phase-net/"));
+        assert!(stats.last_measured_at.is_some());
+
+        // Without another advertise call, the client's record now carries
+        // the buckets, and the server can read it off the DHT.
+        let key = capability_record_key(client.local_peer_id());
+        let caps = loop {
+            let found = server.get_kad_record(key.clone()).await.unwrap();
+            if let Some(r) = found.into_iter().next() {
+                let signed = SignedRecord::open(&key, &r).unwrap();
+                assert_eq!(signed.publisher().unwrap(), *client.local_peer_id());
+                let caps: PeerCapabilities = serde_json::from_slice(&signed.value).unwrap();
+                if caps.last_measured_at.is_some() {
+                    break caps;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+        assert_eq!(caps.measured_latency_bucket, Some(LatencyBucket::Good));
+        assert_eq!(caps.current_concurrency, Some(0));
+    })
+    .await
+    .expect("measurement never arrived");
+}
@@ -0,0 +1,76 @@
+//! DHT state across a restart: a node that knew a peer and held a record
+//! comes back with both, and reaches the peer again without being told
+//! where it is.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_identity::NodeIdentity;
+use phase_net::{DiscoveryConfig, JobRelayHandler, JobRelayRequest, JobRelayResponse};
+use tempfile::TempDir;
+
+use common::{listening_addr, node};
+
+fn config(identity: NodeIdentity, state_dir: Option<&TempDir>) -> DiscoveryConfig {
+    DiscoveryConfig {
+        identity: Some(identity),
+        mdns: false,
+        autonat: false,
+        state_dir: state_dir.map(|d| d.path().to_path_buf()),
+        ..DiscoveryConfig::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn restarted_node_keeps_records_and_redials_known_peers() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let server = node(config(NodeIdentity::generate(), None));
+        server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let handler: JobRelayHandler = Arc::new(|_peer, _payload| {
+            Box::pin(async {
+                JobRelayResponse::Ok {
+                    events: b"pong".to_vec(),
+                    receipt: Vec::new(),
+                }
+            })
+        });
+        server.set_job_relay_handler(Some(handler)).await.unwrap();
+        let addr = listening_addr(&server).await;
+        let server_peer = *server.local_peer_id();
+
+        let state = TempDir::new().unwrap();
+        let identity = NodeIdentity::generate();
+        let client = node(config(identity.clone(), Some(&state)));
+        client.dial_peer(&format!("{addr}/p2p/{server_peer}")).await.unwrap();
+        client
+            .publish_kad_record(b"/test/key".to_vec(), b"value".to_vec())
+            .await
+            .unwrap();
+        // Wait until identify has put the server in the routing table and
+        // the snapshot names it.
+        loop {
+            client.persist().await.unwrap();
+            let peers = std::fs::read_to_string(state.path().join(phase_net::store::PEERS_FILE))
+                .unwrap_or_default();
+            if peers.contains(&server_peer.to_string()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        drop(client);
+
+        let restarted = node(config(identity, Some(&state)));
+        let values = restarted.get_kad_record(b"/test/key".to_vec()).await.unwrap();
+        assert_eq!(values, vec![b"value".to_vec()]);
+        // No dial_peer: the snapshot is the only source of the address.
+        let response = restarted
+            .send_job_relay(server_peer, JobRelayRequest { payload: Vec::new() })
+            .await
+            .unwrap();
+        assert!(matches!(response, JobRelayResponse::Ok { ref events, .. } if events == b"pong"));
+    })
+    .await
+    .expect("restart recovery timed out");
+}
@@ -0,0 +1,96 @@
+//! Private-network mode between in-process swarms on loopback: the pnet
+//! key gates who can connect at all, and the peer allowlist gates who may
+//! send job relays.
+
+mod common;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_net::{
+    Discovery, DiscoveryConfig, JobRelayHandler, JobRelayRequest, JobRelayResponse, NetworkKey,
+    PeerFilter,
+};
+
+use common::{listening_addr, node};
+
+fn config(network_key: Option<NetworkKey>, peer_filter: PeerFilter) -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        network_key,
+        peer_filter,
+        ..DiscoveryConfig::default()
+    }
+}
+
+/// Listen on loopback TCP with an echo relay handler; returns the dialable
+/// `/p2p/` address.
+async fn serve(server: &Discovery) -> String {
+    server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+    let handler: JobRelayHandler = Arc::new(|_peer, payload| {
+        Box::pin(async move {
+            JobRelayResponse::Ok {
+                events: payload,
+                receipt: Vec::new(),
+            }
+        })
+    });
+    server.set_job_relay_handler(Some(handler)).await.unwrap();
+    let addr = listening_addr(server).await;
+    format!("{addr}/p2p/{}", server.local_peer_id())
+}
+
+async fn relay(client: &Discovery, server: &Discovery, addr: &str) -> anyhow::Result<JobRelayResponse> {
+    client.dial_peer(addr).await?;
+    client
+        .send_job_relay(
+            *server.local_peer_id(),
+            JobRelayRequest {
+                payload: b"ping".to_vec(),
+            },
+        )
+        .await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn only_peers_with_the_network_key_connect() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let key = NetworkKey::new([7; 32]);
+        let server = node(config(Some(key), PeerFilter::Open));
+        let addr = serve(&server).await;
+
+        let member = node(config(Some(key), PeerFilter::Open));
+        let response = relay(&member, &server, &addr).await.unwrap();
+        assert!(matches!(response, JobRelayResponse::Ok { ref events, .. } if events == b"ping"));
+
+        let wrong_key = node(config(Some(NetworkKey::new([8; 32])), PeerFilter::Open));
+        assert!(relay(&wrong_key, &server, &addr).await.is_err());
+        let no_key = node(config(None, PeerFilter::Open));
+        assert!(relay(&no_key, &server, &addr).await.is_err());
+    })
+    .await
+    .expect("private network exchange timed out");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn allowlist_refuses_unlisted_peers() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let friend = node(config(None, PeerFilter::Open));
+        let stranger = node(config(None, PeerFilter::Open));
+        let server = node(config(
+            None,
+            PeerFilter::Allow(HashSet::from([*friend.local_peer_id()])),
+        ));
+        let addr = serve(&server).await;
+
+        assert!(matches!(
+            relay(&friend, &server, &addr).await.unwrap(),
+            JobRelayResponse::Ok { .. }
+        ));
+        assert!(relay(&stranger, &server, &addr).await.is_err());
+    })
+    .await
+    .expect("allowlist exchange timed out");
+}
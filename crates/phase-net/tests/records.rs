@@ -0,0 +1,106 @@
+//! Namespace-aware record admission between two in-process swarms: signed
+//! records under `/phase/` travel, unsigned ones are refused at publish
+//! and dropped on the way back from a lookup. Provider sets collect
+//! every peer announcing a key.
+
+mod common;
+
+use std::time::Duration;
+
+use phase_net::{DiscoveryConfig, SignedRecord, ValidatorRegistry};
+
+use common::{listening_addr, node};
+
+fn config(validators: ValidatorRegistry) -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        validators,
+        ..DiscoveryConfig::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unsigned_records_never_cross_a_validating_node() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let strict = node(config(ValidatorRegistry::default()));
+        strict.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let addr = listening_addr(&strict).await;
+
+        // The validating node won't even publish an unsigned record.
+        let err = strict
+            .publish_kad_record(b"/phase/blob/aa".to_vec(), b"plain".to_vec())
+            .await
+            .expect_err("unsigned record under /phase/ is refused");
+        assert!(format!("{err:#}").contains("not a signed record"), "{err:#}");
+
+        // A permissive peer can, but the validating node drops it.
+        let loose = node(config(ValidatorRegistry::empty()));
+        // Listening lets identify hand the strict node a route back here.
+        loose.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        loose
+            .dial_peer(&format!("{addr}/p2p/{}", strict.local_peer_id()))
+            .await
+            .unwrap();
+        loose
+            .publish_kad_record(b"/phase/blob/aa".to_vec(), b"plain".to_vec())
+            .await
+            .unwrap();
+        loose
+            .publish_signed_record(b"/phase/blob/bb".to_vec(), b"signed".to_vec(), Duration::from_secs(60))
+            .await
+            .unwrap();
+
+        let record = loop {
+            let found = strict.get_kad_record(b"/phase/blob/bb".to_vec()).await.unwrap();
+            if let Some(r) = found.into_iter().next() {
+                break r;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+        let signed = SignedRecord::open(b"/phase/blob/bb", &record).unwrap();
+        assert_eq!(signed.value, b"signed");
+        assert_eq!(signed.publisher().unwrap(), *loose.local_peer_id());
+
+        assert!(strict
+            .get_kad_record(b"/phase/blob/aa".to_vec())
+            .await
+            .unwrap()
+            .is_empty());
+    })
+    .await
+    .expect("record exchange timed out");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn provider_sets_collect_every_announcing_peer() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let server = node(config(ValidatorRegistry::default()));
+        server.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let addr = listening_addr(&server).await;
+        let client = node(config(ValidatorRegistry::default()));
+        client.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        client
+            .dial_peer(&format!("{addr}/p2p/{}", server.local_peer_id()))
+            .await
+            .unwrap();
+
+        let key = b"/phase/blob/cc".to_vec();
+        server.start_providing(key.clone()).await.unwrap();
+        client.start_providing(key.clone()).await.unwrap();
+        loop {
+            let providers = client.get_providers(key.clone()).await.unwrap();
+            if providers.contains(server.local_peer_id()) {
+                assert!(providers.contains(client.local_peer_id()), "{providers:?}");
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // Nobody provides this one: a miss, not an error.
+        let providers = server.get_providers(b"/phase/blob/dd".to_vec()).await.unwrap();
+        assert!(providers.is_empty(), "{providers:?}");
+    })
+    .await
+    .expect("provider lookup timed out");
+}
@@ -0,0 +1,115 @@
+//! Circuit relay v2 end-to-end, with three in-process swarms on loopback.
+//!
+//! R runs the relay server, B reserves a slot on R, and A — which never
+//! learns a direct address for B — reaches B through
+//! `<R>/p2p-circuit/p2p/<B>` and round-trips a `JobRelayRequest`. mDNS is
+//! off so the relay is the only way A can find B.
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use phase_net::{
+    Discovery, DiscoveryConfig, JobRelayHandler, JobRelayRequest, JobRelayResponse,
+    Reachability,
+};
+
+use common::{listening_addr, node};
+
+fn config(relay_server: bool, relays: Vec<String>) -> DiscoveryConfig {
+    DiscoveryConfig {
+        mdns: false,
+        autonat: false,
+        relay_server,
+        relays,
+        ..DiscoveryConfig::default()
+    }
+}
+
+/// Poll `listen_addrs` until one satisfies `pred`.
+async fn wait_for_addr(node: &Discovery, pred: impl Fn(&str) -> bool) -> String {
+    loop {
+        let addrs = node.listen_addrs().await.unwrap();
+        if let Some(addr) = addrs.into_iter().find(|a| pred(a)) {
+            return addr;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+fn echo_handler() -> JobRelayHandler {
+    Arc::new(|_peer, payload| {
+        Box::pin(async move {
+            JobRelayResponse::Ok {
+                events: payload,
+                receipt: b"relayed".to_vec(),
+            }
+        })
+    })
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn job_relay_round_trips_over_a_circuit() {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        let relay = node(config(true, Vec::new()));
+        relay.listen("/ip4/127.0.0.1/tcp/0").await.unwrap();
+        let relay_addr = listening_addr(&relay).await;
+        // A relay only hands out reservations once it has an external
+        // address to put in them; on loopback that's the listen address.
+        relay.add_external_address(&relay_addr.to_string()).await.unwrap();
+        let relay_addr = format!("{relay_addr}/p2p/{}", relay.local_peer_id());
+
+        let server = node(config(false, vec![relay_addr.clone()]));
+        server.set_job_relay_handler(Some(echo_handler())).await.unwrap();
+        let circuit = wait_for_addr(&server, |a| a.contains("/p2p-circuit")).await;
+        assert!(circuit.starts_with(&relay_addr), "{circuit} not via {relay_addr}");
+
+        let server_peer = *server.local_peer_id();
+        let client = node(config(false, Vec::new()));
+        client
+            .dial_peer(&format!("{relay_addr}/p2p-circuit/p2p/{server_peer}"))
+            .await
+            .unwrap();
+        let response = client
+            .send_job_relay(
+                server_peer,
+                JobRelayRequest {
+                    payload: b"job".to_vec(),
+                },
+            )
+            .await
+            .unwrap();
+        match response {
+            JobRelayResponse::Ok { events, receipt } => {
+                assert_eq!(events, b"job");
+                assert_eq!(receipt, b"relayed");
+            }
+            other => panic!("relay refused: {other:?}"),
+        }
+    })
+    .await
+    .expect("relayed job timed out");
+}
+
+#[tokio::test]
+async fn reachability_is_unknown_until_autonat_reports() {
+    let node = Discovery::new(DiscoveryConfig {
+        mdns: false,
+        ..DiscoveryConfig::default()
+    })
+    .unwrap();
+    assert_eq!(node.reachability().await.unwrap(), Reachability::Unknown);
+}
+
+#[tokio::test]
+async fn relay_address_must_name_the_relay_peer() {
+    let err = Discovery::new(DiscoveryConfig {
+        mdns: false,
+        relays: vec!["/ip4/127.0.0.1/tcp/4001".into()],
+        ..DiscoveryConfig::default()
+    })
+    .err()
+    .expect("a relay without /p2p/ is rejected");
+    assert!(format!("{err}").contains("/p2p/"), "{err}");
+}
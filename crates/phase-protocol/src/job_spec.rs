@@ -126,6 +126,10 @@ pub struct InferenceJobSpec {
 
     /// Single-turn completion prompt. Mutually exclusive with `messages`
     /// in practice; workers MAY reject specs with both populated.
+    ///
+    /// Empty `messages` with an absent or empty `prompt` is a **load
+    /// request**: the worker makes the model resident, applies
+    /// `keep_alive`, and terminates with a `Final` carrying no output.
     #[serde(default)]
     pub prompt: Option<String>,
 
@@ -154,6 +158,54 @@ pub struct InferenceJobSpec {
     /// MUST emit only the terminal [`crate::JobEvent::Final`].
     #[serde(default = "default_true")]
     pub stream: bool,
+
+    /// Functions the model may call. Rendered into the prompt by the
+    /// worker's chat template; calls the model makes come back as
+    /// `"tool_call"` [`crate::OutputChunk`]s rather than plain tokens.
+    /// Empty = no tool use. Skipped on the wire when empty so manifests
+    /// signed before this field existed keep their canonical bytes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+
+    /// Constraint on the shape of the generated text. Part of the signed
+    /// manifest, so a relaying peer can't quietly drop it. Workers enforce
+    /// it during decoding where the backend supports that, and MUST check
+    /// the finished output before `Final` — a violation terminates with
+    /// [`Completion::ConstraintViolation`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_constraint: Option<OutputConstraint>,
+
+    /// How long the worker keeps the model resident once this job (and
+    /// any other job on the same model) has finished. `None` = the
+    /// worker's configured default. Workers MAY clamp this, and always
+    /// remain free to evict under their own capacity limits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<KeepAlive>,
+}
+
+/// Model residency after a job. Mirrors Ollama's `keep_alive` request
+/// field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeepAlive {
+    /// Keep the model loaded for `ms` milliseconds of idleness. `0`
+    /// unloads it as soon as it goes idle.
+    Duration { ms: u64 },
+    /// Keep the model loaded until the worker needs the room.
+    Forever,
+}
+
+/// Structured-output constraint for an [`InferenceJobSpec`]. Mirrors the two
+/// forms of Ollama's `format` field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputConstraint {
+    /// A well-formed JSON object (Ollama `format: "json"`). Top-level
+    /// arrays and scalars fail, matching the object grammar workers apply.
+    Json,
+    /// JSON conforming to `schema`, a JSON-encoded JSON Schema document —
+    /// string-encoded for the same reason as [`SamplingParams`] values.
+    JsonSchema { schema: String },
 }
 
 fn default_true() -> bool {
@@ -171,6 +223,27 @@ pub struct ChatMessage {
     /// supports them.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub images: Vec<String>,
+    /// Lowercase hex SHA-256 of each *decoded* image, index-aligned with
+    /// `images`. Signed with the rest of the manifest, so the receipt's
+    /// `job_spec_hash` binds the exact pixels independent of how the
+    /// base64 was padded or wrapped. Workers MUST refuse a message whose
+    /// images don't match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image_digests: Vec<String>,
+    /// Tool calls an assistant turn made. Carried so a follow-up turn can
+    /// replay the full exchange (assistant call → tool result) through the
+    /// template.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// For [`ChatRole::Tool`] messages: the name of the tool whose result
+    /// `content` holds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// For [`ChatRole::Tool`] messages: the [`ToolCall::id`] this result
+    /// answers, when the client knows it (OpenAI shape; Ollama clients
+    /// only send `tool_name`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -182,6 +255,37 @@ pub enum ChatRole {
     Tool,
 }
 
+/// A function the model may call. Mirrors the `function` object of an
+/// Ollama / OpenAI `tools[]` entry.
+///
+/// `parameters` is the JSON Schema for the arguments, carried as a
+/// JSON-encoded string for the same reason [`SamplingParams`] values are —
+/// the protocol crate stays free of a dynamic-JSON type in its wire schema.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON-encoded JSON Schema object describing the arguments.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<String>,
+}
+
+/// A structured tool invocation produced by the model.
+///
+/// Workers emit each call as one `OutputChunk { kind: "tool_call" }` whose
+/// `data` is this struct JSON-encoded, so the receipt commits to the call
+/// separately from surrounding `"token"` text.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Caller-visible call id (OpenAI shape). Ollama doesn't use one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    /// JSON-encoded arguments object.
+    pub arguments: String,
+}
+
 /// Open-ended sampling knobs. Workers extract what they understand and
 /// silently ignore the rest. Concrete keys consumers may set today:
 /// `temperature`, `top_p`, `top_k`, `min_p`, `repetition_penalty`, `seed`,
@@ -305,6 +409,11 @@ pub enum Completion {
     /// Worker-side error. The error message is in the receipt's signed
     /// envelope alongside this completion code.
     Error,
+    /// Generation finished but the output failed the job's
+    /// [`InferenceJobSpec::output_constraint`]. The chunks are still
+    /// committed and signed — the receipt attests to what was produced and
+    /// that it didn't conform.
+    ConstraintViolation,
 }
 
 /// Best-effort metrics. Not load-bearing for verification.
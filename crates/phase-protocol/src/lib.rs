@@ -33,7 +33,8 @@ mod worker;
 pub use commitment::CommitmentAccumulator;
 pub use job_spec::{
     ChatMessage, ChatRole, Completion, ConversationToken, InferenceJobSpec, JobMetrics, JobResult,
-    JobSpec, JobSpecKind, PeerId, SamplingParams, WasmJobSpec,
+    JobSpec, JobSpecKind, KeepAlive, OutputConstraint, PeerId, SamplingParams, ToolCall,
+    ToolDefinition, WasmJobSpec,
 };
 pub use worker::{
     should_resume_on_same_peer, DynWorker, JobEvent, JobHandle, JobHandleProducer, JobId,
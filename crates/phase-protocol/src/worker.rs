@@ -351,8 +351,9 @@ pub enum JobEvent {
 ///
 /// Workload-agnostic by design. The `kind` field carries a workload-specific
 /// discriminator (`"token"` for inference, `"image_tile"` for image gen,
-/// `"progress_log"` for fine-tuning, `"stdout"` for WASM streaming output);
-/// the `data` field is opaque bytes interpreted per-kind.
+/// `"progress_log"` for fine-tuning, `"stdout"` for WASM streaming output,
+/// `"tool_call"` for a structured inference tool invocation); the `data`
+/// field is opaque bytes interpreted per-kind.
 ///
 /// This is the "future workloads" extension point. Adding image generation
 /// or scientific compute means defining new chunk kinds + a new `JobSpec`
@@ -360,12 +361,13 @@ pub enum JobEvent {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputChunk {
     /// Workload-specific discriminator. Stable strings, lower_snake_case.
-    /// Reserved kinds: `"token"`, `"image_tile"`, `"progress_log"`,
-    /// `"stdout"`, `"stderr"`.
+    /// Reserved kinds: `"token"`, `"tool_call"`, `"image_tile"`,
+    /// `"progress_log"`, `"stdout"`, `"stderr"`.
     pub kind: String,
 
     /// Opaque payload. For `kind: "token"` this is the UTF-8 bytes of the
-    /// token text. For `kind: "image_tile"` this is PNG-encoded tile data
+    /// token text. For `kind: "tool_call"` it is a JSON-encoded
+    /// [`crate::ToolCall`]. For `kind: "image_tile"` this is PNG-encoded tile data
     /// preceded by a small header (defined per workload, not by the
     /// protocol). The protocol layer NEVER inspects these bytes.
     #[serde(with = "serde_bytes_field")]
@@ -4,9 +4,16 @@
 //! via the Phase network's Kademlia DHT. It reuses the plasm library's
 //! networking infrastructure.
 //!
+//! With `--mdns` it also browses the local network for
+//! `_phase-image._tcp` providers, then ranks LAN and DHT results together
+//! by the version of the manifest each one serves. A version only counts
+//! when the manifest is signed by the provider's key: the DHT record's
+//! publisher, or the peer ID a LAN provider advertises.
+//!
 //! Usage:
 //!   phase-discover --arch x86_64 --channel stable
 //!   phase-discover --arch arm64 --channel testing --ephemeral
+//!   phase-discover --arch x86_64 --channel stable --mdns
 
 // The event-loop `match` arms wrap their bodies in `if !args.quiet { ... }`
 // for legibility. Collapsing each into a match guard would scatter the
@@ -14,18 +21,28 @@
 // already long state machine. Keep the explicit nested form.
 #![allow(clippy::collapsible_match, clippy::collapsible_if)]
 
-use std::time::Duration;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use clap::Parser;
 use tracing::{info, warn, error, Level};
 use tracing_subscriber::FmtSubscriber;
 
 use libp2p::{
+    identity::{ed25519, PublicKey},
     kad::{self, store::MemoryStore, Mode, QueryResult, GetRecordOk, RecordKey},
     noise, yamux,
     swarm::{NetworkBehaviour, SwarmEvent},
     tcp, Multiaddr, PeerId,
 };
 use futures::StreamExt;
+use plasm::provider::{
+    pinned_http_client, verify_manifest_signature, BootManifest, ManifestRecord, MdnsBrowser,
+    MdnsEvent, MdnsProvider, TXT_PEER_ID,
+};
+
+/// Largest manifest body read while ranking candidates
+const MAX_MANIFEST_BYTES: usize = 1024 * 1024;
 
 /// Phase Discover - Boot-time manifest discovery
 #[derive(Parser, Debug)]
@@ -60,6 +77,15 @@ struct Args {
     /// Quiet mode (only output manifest URL)
     #[arg(short, long)]
     quiet: bool,
+
+    /// Also browse the LAN over mDNS, and rank LAN and DHT results by
+    /// manifest version
+    #[arg(long)]
+    mdns: bool,
+
+    /// How long to listen for LAN providers with --mdns, in seconds
+    #[arg(long, default_value = "3")]
+    mdns_wait: u64,
 }
 
 /// Combined network behaviour for discovery
@@ -75,6 +101,46 @@ struct DiscoveryResult {
     manifest_url: String,
     peer_id: String,
     provider_count: usize,
+    /// Every manifest found with --mdns, best first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    candidates: Vec<Candidate>,
+}
+
+/// Where a candidate manifest was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Source {
+    Mdns,
+    Dht,
+}
+
+impl Source {
+    fn as_str(self) -> &'static str {
+        match self {
+            Source::Mdns => "mdns",
+            Source::Dht => "dht",
+        }
+    }
+}
+
+/// A manifest found on the LAN or in the DHT
+#[derive(Debug, serde::Serialize)]
+struct Candidate {
+    manifest_url: String,
+    source: Source,
+    /// mDNS instance name or DHT record publisher
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<String>,
+    /// SPKI pin an HTTPS provider advertised over mDNS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_spki_sha256: Option<String>,
+    /// Peer whose key must sign the manifest
+    #[serde(skip)]
+    signer: Option<PeerId>,
+    /// `version` of the fetched manifest; absent if it couldn't be fetched
+    /// or isn't signed by `signer`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
 }
 
 #[tokio::main]
@@ -171,17 +237,43 @@ async fn main() -> anyhow::Result<()> {
         info!("Started DHT query: {:?}", query_id);
     }
 
+    // Browse the LAN alongside the DHT query
+    let mut browser = if args.mdns {
+        match MdnsBrowser::new() {
+            Ok(browser) => Some(browser),
+            Err(e) => {
+                if !args.quiet {
+                    warn!("mDNS browsing unavailable: {:#}", e);
+                }
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut lan: HashMap<String, MdnsProvider> = HashMap::new();
+    let mut dht: Vec<Candidate> = Vec::new();
+    let mut dht_done = false;
+
     // Run event loop with timeout
-    let timeout_duration = Duration::from_secs(args.timeout);
-    let start_time = std::time::Instant::now();
+    let start_time = Instant::now();
+    let deadline = start_time + Duration::from_secs(args.timeout);
+    let mdns_deadline = start_time + Duration::from_secs(args.mdns_wait.min(args.timeout));
 
     loop {
-        if start_time.elapsed() > timeout_duration {
+        let now = Instant::now();
+        if now > deadline {
+            if args.mdns {
+                break;
+            }
             if !args.quiet {
                 error!("Discovery timeout after {}s", args.timeout);
             }
             std::process::exit(1);
         }
+        if args.mdns && dht_done && now >= mdns_deadline {
+            break;
+        }
 
         tokio::select! {
             event = swarm.select_next_some() => {
@@ -191,33 +283,44 @@ async fn main() -> anyhow::Result<()> {
                             kad::Event::OutboundQueryProgressed { result, .. } => {
                                 match result {
                                     QueryResult::GetRecord(Ok(GetRecordOk::FoundRecord(record))) => {
+                                        let Some((manifest_url, publisher)) = decode_manifest_record(&record.record) else {
+                                            if !args.quiet {
+                                                warn!("Ignoring manifest record that failed verification");
+                                            }
+                                            continue;
+                                        };
+
+                                        if args.mdns {
+                                            if !args.quiet {
+                                                info!("Found manifest in DHT: {}", manifest_url);
+                                            }
+                                            dht.push(Candidate {
+                                                manifest_url,
+                                                source: Source::Dht,
+                                                provider: Some(publisher.to_string()),
+                                                tls_spki_sha256: None,
+                                                signer: Some(publisher),
+                                                version: None,
+                                            });
+                                            continue;
+                                        }
+
                                         // Found the manifest!
-                                        let value = String::from_utf8_lossy(&record.record.value);
-
-                                        if args.format == "json" {
-                                            let result = DiscoveryResult {
-                                                key: manifest_key.clone(),
-                                                manifest_url: value.to_string(),
-                                                peer_id: local_peer_id.to_string(),
-                                                provider_count: 1,
-                                            };
-                                            println!("{}", serde_json::to_string(&result)?);
-                                        } else if args.quiet {
-                                            println!("{}", value);
-                                        } else {
+                                        if !args.quiet && args.format != "json" {
                                             info!("Found manifest!");
-                                            println!("MANIFEST_URL={}", value);
                                         }
-
+                                        print_result(&args, &manifest_key, &local_peer_id, &manifest_url, Vec::new())?;
                                         return Ok(());
                                     }
                                     QueryResult::GetRecord(Ok(GetRecordOk::FinishedWithNoAdditionalRecord { .. })) => {
                                         // Query finished, no more records
+                                        dht_done = true;
                                     }
                                     QueryResult::GetRecord(Err(err)) => {
                                         if !args.quiet {
                                             warn!("DHT query error: {:?}", err);
                                         }
+                                        dht_done = true;
                                     }
                                     QueryResult::Bootstrap(Ok(_)) => {
                                         if !args.quiet {
@@ -253,8 +356,214 @@ async fn main() -> anyhow::Result<()> {
                     _ => {}
                 }
             }
+            Some(event) = next_lan_event(&mut browser) => {
+                match event {
+                    MdnsEvent::Resolved(provider) => {
+                        if provider.serves(&args.channel, &args.arch) {
+                            if !args.quiet {
+                                info!("Found LAN provider: {} ({})", provider.fullname, provider.host);
+                            }
+                            lan.insert(provider.fullname.clone(), provider);
+                        } else {
+                            lan.remove(&provider.fullname);
+                        }
+                    }
+                    MdnsEvent::Removed(fullname) => {
+                        lan.remove(&fullname);
+                    }
+                }
+            }
+            _ = tokio::time::sleep(Duration::from_millis(250)) => {}
+        }
+    }
+
+    // --mdns: merge what the LAN and the DHT turned up
+    if let Some(browser) = browser.take() {
+        let _ = browser.shutdown();
+    }
+    let mut candidates: Vec<Candidate> = lan
+        .values()
+        .filter_map(|provider| {
+            Some(Candidate {
+                manifest_url: provider.manifest_url(&args.channel, &args.arch)?,
+                source: Source::Mdns,
+                provider: Some(provider.fullname.clone()),
+                tls_spki_sha256: provider.tls_spki_sha256.clone(),
+                signer: provider.txt.get(TXT_PEER_ID).and_then(|p| p.parse().ok()),
+                version: None,
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.manifest_url.cmp(&b.manifest_url));
+    candidates.extend(dht);
+    let mut seen = std::collections::HashSet::new();
+    candidates.retain(|c| seen.insert(c.manifest_url.clone()));
+
+    if candidates.is_empty() {
+        if !args.quiet {
+            error!("No manifest found on the LAN or in the DHT");
+        }
+        std::process::exit(1);
+    }
+
+    rank_by_manifest_version(&mut candidates).await?;
+    if !args.quiet && args.format != "json" {
+        for c in &candidates {
+            info!(
+                "{:<4} {} version={}",
+                c.source.as_str(),
+                c.manifest_url,
+                c.version.as_deref().unwrap_or("unverified")
+            );
+        }
+    }
+    let best = candidates[0].manifest_url.clone();
+    print_result(&args, &manifest_key, &local_peer_id, &best, candidates)
+}
+
+/// Print the chosen manifest URL in the requested format.
+fn print_result(
+    args: &Args,
+    key: &str,
+    local_peer_id: &PeerId,
+    manifest_url: &str,
+    candidates: Vec<Candidate>,
+) -> anyhow::Result<()> {
+    if args.format == "json" {
+        let result = DiscoveryResult {
+            key: key.to_string(),
+            manifest_url: manifest_url.to_string(),
+            peer_id: local_peer_id.to_string(),
+            provider_count: candidates.len().max(1),
+            candidates,
+        };
+        println!("{}", serde_json::to_string(&result)?);
+    } else if args.quiet {
+        println!("{}", manifest_url);
+    } else {
+        println!("MANIFEST_URL={}", manifest_url);
+    }
+    Ok(())
+}
+
+/// The next LAN event, or never without a browser.
+async fn next_lan_event(browser: &mut Option<MdnsBrowser>) -> Option<MdnsEvent> {
+    match browser {
+        Some(browser) => browser.next().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Manifest URL and publisher of a DHT manifest record. `None` unless it
+/// is a signed `ManifestRecord` that verifies.
+fn decode_manifest_record(record: &kad::Record) -> Option<(String, PeerId)> {
+    let (manifest, publisher) = ManifestRecord::from_signed_bytes(&record.value).ok()?;
+    Some((manifest.manifest_url, publisher))
+}
+
+/// Fetch every candidate's manifest and order them newest version first.
+/// Manifests that are unreachable or not signed by the candidate's peer go
+/// last; ties keep LAN providers ahead. A candidate with a TLS pin is only
+/// trusted if its server holds that key.
+async fn rank_by_manifest_version(candidates: &mut [Candidate]) -> anyhow::Result<()> {
+    let client = reqwest::Client::new();
+    let versions = futures::future::join_all(candidates.iter().map(|c| {
+        let client = match &c.tls_spki_sha256 {
+            Some(pin) => pinned_http_client(pin).ok(),
+            None => Some(client.clone()),
+        };
+        async move {
+            let client = client?;
+            let fetch = fetch_manifest_version(&client, &c.manifest_url, c.signer.as_ref()?);
+            tokio::time::timeout(Duration::from_secs(5), fetch).await.ok()?
+        }
+    }))
+    .await;
+    for (candidate, version) in candidates.iter_mut().zip(versions) {
+        candidate.version = version;
+    }
+    candidates.sort_by(|a, b| match (&a.version, &b.version) {
+        (Some(a), Some(b)) => compare_versions(b, a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    Ok(())
+}
+
+/// The version of the manifest at `url`, if `signer` signed it. Bodies
+/// over [`MAX_MANIFEST_BYTES`] are refused.
+async fn fetch_manifest_version(client: &reqwest::Client, url: &str, signer: &PeerId) -> Option<String> {
+    let mut response = client.get(url).send().await.ok()?.error_for_status().ok()?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.ok()? {
+        if body.len() + chunk.len() > MAX_MANIFEST_BYTES {
+            return None;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let manifest: BootManifest = serde_json::from_slice(&body).ok()?;
+    signed_by(&manifest, signer).then_some(manifest.version)
+}
+
+/// Whether one of `manifest`'s signatures is by `signer`'s key and verifies.
+fn signed_by(manifest: &BootManifest, signer: &PeerId) -> bool {
+    manifest.signatures.iter().any(|sig| {
+        let Ok(bytes) = hex::decode(&sig.key_id) else {
+            return false;
+        };
+        let Ok(key) = ed25519::PublicKey::try_from_bytes(&bytes) else {
+            return false;
+        };
+        if PeerId::from(PublicKey::from(key)) != *signer {
+            return false;
+        }
+        let Ok(key) = ed25519_dalek::VerifyingKey::try_from(bytes.as_slice()) else {
+            return false;
+        };
+        verify_manifest_signature(manifest, &key).unwrap_or(false)
+    })
+}
+
+/// Compare versions by semver precedence: dotted release components
+/// numerically (so `0.10.0` > `0.9.1`), then a release above any of its
+/// pre-releases (`1.0.0` > `1.0.0-rc1`), whose dotted identifiers compare
+/// numerically when both are numbers and by text otherwise. Build
+/// metadata after `+` is ignored.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| {
+        let v = v.trim_start_matches('v');
+        let v = v.split_once('+').map_or(v, |(v, _build)| v);
+        match v.split_once('-') {
+            Some((release, pre)) => (release.to_string(), Some(pre.to_string())),
+            None => (v.to_string(), None),
+        }
+    };
+    let ((a_release, a_pre), (b_release, b_pre)) = (split(a), split(b));
+    compare_dotted(&a_release, &b_release).then_with(|| match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a), Some(b)) => compare_dotted(&a, &b),
+    })
+}
+
+/// Compare dot-separated identifiers one by one: numbers numerically and
+/// below text, text by bytes; a longer list wins a shared prefix.
+fn compare_dotted(a: &str, b: &str) -> Ordering {
+    let (a, b): (Vec<&str>, Vec<&str>) = (a.split('.').collect(), b.split('.').collect());
+    for (x, y) in a.iter().zip(&b) {
+        let order = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if order != Ordering::Equal {
+            return order;
         }
     }
+    a.len().cmp(&b.len())
 }
 
 /// Default bootstrap nodes for the Phase network
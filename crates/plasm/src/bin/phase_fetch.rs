@@ -197,6 +197,10 @@ fn fetch_artifact(
     let output_path = output_dir.join(name);
     let hash_path = output_dir.join(format!("{}.sha256", name));
 
+    // A copy from an earlier run is revalidated by ETag rather than fetched again
+    let have_current = output_path.exists()
+        && fs::read_to_string(&hash_path).is_ok_and(|h| h.trim() == artifact.hash);
+
     // Try each URL with retry logic
     let mut last_error = None;
 
@@ -206,7 +210,7 @@ fn fetch_artifact(
                 info!("  Retry {}/{} for URL {}", attempt + 1, retry_count, url_idx + 1);
             }
 
-            match download_and_verify(url, &artifact.hash, artifact.size, &output_path, timeout_secs, quiet) {
+            match download_and_verify(url, &artifact.hash, artifact.size, &output_path, have_current, timeout_secs, quiet) {
                 Ok(_) => {
                     // Write hash file
                     fs::write(&hash_path, &artifact.hash)
@@ -244,11 +248,15 @@ fn fetch_artifact(
 }
 
 /// Download file and verify hash
+///
+/// With `have_current`, `output_path` already holds the expected content and
+/// the server is asked (by `If-None-Match`) to skip sending it again.
 fn download_and_verify(
     url: &str,
     expected_hash: &str,
     expected_size: u64,
     output_path: &Path,
+    have_current: bool,
     timeout_secs: u64,
     quiet: bool,
 ) -> Result<()> {
@@ -265,11 +273,24 @@ fn download_and_verify(
         info!("  Downloading from {}...", url);
     }
 
-    let mut response = client
-        .get(url)
+    let mut request = client.get(url);
+    if have_current {
+        request = request.header(
+            reqwest::header::IF_NONE_MATCH,
+            format!("\"sha256:{}\"", expected_hash),
+        );
+    }
+    let mut response = request
         .send()
         .context("HTTP request failed")?;
 
+    if have_current && response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        if !quiet {
+            info!("  Not modified, keeping {}", output_path.display());
+        }
+        return Ok(());
+    }
+
     if !response.status().is_success() {
         return Err(anyhow!("HTTP error: {}", response.status()));
     }
@@ -19,6 +19,19 @@ pub struct Config {
     #[serde(default)]
     pub bootstrap_peers: Vec<String>,
 
+    /// Private-network key file (go-ipfs `swarm.key` format). Only peers
+    /// holding the same key can connect.
+    #[serde(default)]
+    pub network_key_file: Option<PathBuf>,
+
+    /// Only talk to these peer IDs (mutually exclusive with `deny_peers`)
+    #[serde(default)]
+    pub allow_peers: Vec<String>,
+
+    /// Refuse connections and job offers from these peer IDs
+    #[serde(default)]
+    pub deny_peers: Vec<String>,
+
     /// Maximum concurrent jobs
     #[serde(default = "default_max_concurrent_jobs")]
     pub max_concurrent_jobs: usize,
@@ -50,6 +63,9 @@ impl Default for Config {
             listen_addrs: vec![],  // Empty = use default /ip4/0.0.0.0/tcp/0
             peer_addrs: vec![],
             bootstrap_peers: vec![],
+            network_key_file: None,
+            allow_peers: vec![],
+            deny_peers: vec![],
             max_concurrent_jobs: 4,
             limits: ExecutionLimits::default(),
         }
@@ -185,6 +201,13 @@ mod tests {
         assert_eq!(config.limits.max_memory_bytes, 128 * 1024 * 1024);
     }
 
+    #[test]
+    fn test_private_network_fields_default_when_absent() {
+        let config: Config = serde_json::from_str(r#"{"listen_addrs": []}"#).unwrap();
+        assert!(config.network_key_file.is_none());
+        assert!(config.allow_peers.is_empty() && config.deny_peers.is_empty());
+    }
+
     #[test]
     fn test_save_load_config() {
         let temp_file = NamedTempFile::new().unwrap();
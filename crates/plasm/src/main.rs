@@ -7,12 +7,17 @@ use tracing::{info, warn};
 use plasm::{
     config::Config,
     network::{Discovery, DiscoveryConfig, ExecutionHandler, JobRequest, JobRequirements},
-    provider::{ProviderConfig, ProviderServer},
+    provider::{MdnsAdvertiser, MdnsConfig, ProviderConfig, ProviderServer, SelfSignedCert, TlsConfig},
     wasm::runtime::{WasmRuntime, Wasm3Runtime},
 };
 
 // Persistent Ed25519 node identity (phase-identity crate, M3 of phase-core).
-use phase_identity::{default_identity_path, NodeIdentity};
+use phase_identity::{
+    default_identity_path, rotate_identity, succession_path, NodeIdentity, Passphrase,
+    PASSPHRASE_ENV, PASSPHRASE_FD_ENV,
+};
+use phase_artifact_server::{ArtifactStore, BlobId};
+use phase_net::{state_dir_for_identity, NetworkKey, PeerFilter};
 
 #[derive(Parser)]
 #[command(name = "plasmd")]
@@ -99,16 +104,98 @@ enum Commands {
         /// Disable mDNS advertisement
         #[arg(long)]
         no_mdns: bool,
+
+        /// Hex public key allowed to upload and delete artifacts (repeatable).
+        /// Without one the provider is read-only.
+        #[arg(long = "operator", value_name = "PUBKEY_HEX")]
+        operators: Vec<String>,
+
+        /// Evict unpinned cached blobs, least recently used first, to keep
+        /// the blob bucket under this size (e.g. 500M, 20G)
+        #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+        blob_quota: Option<u64>,
+
+        /// Serve HTTPS with this PEM certificate chain (needs --tls-key).
+        /// Replacing the files on disk swaps the certificate in
+        #[arg(long, value_name = "PEM", requires = "tls_key", conflicts_with = "tls_self_signed")]
+        tls_cert: Option<PathBuf>,
+
+        /// Private key for --tls-cert
+        #[arg(long, value_name = "PEM", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+
+        /// Serve HTTPS with a certificate over a key derived from the node
+        /// identity. Manifests are then signed by the identity and pin the
+        /// certificate
+        #[arg(long)]
+        tls_self_signed: bool,
     },
     /// Provider management commands
     Provider {
         #[command(subcommand)]
         command: ProviderCommands,
     },
+    /// Node identity management commands
+    Identity {
+        #[command(subcommand)]
+        command: IdentityCommands,
+    },
     /// Show version information
     Version,
 }
 
+#[derive(Subcommand)]
+enum IdentityCommands {
+    /// Replace the node key, recording the old key's signed hand-over
+    Rotate {
+        /// Identity key file (default: the platform identity path)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Encrypt the key file under a passphrase
+    Encrypt {
+        /// Identity key file (default: the platform identity path)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+
+        /// Read the passphrase from this file (default: PHASE_IDENTITY_PASSPHRASE
+        /// or PHASE_IDENTITY_PASSPHRASE_FD)
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+    /// Decrypt the key file back to the raw format
+    Decrypt {
+        /// Identity key file (default: the platform identity path)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+
+        /// Read the passphrase from this file (default: PHASE_IDENTITY_PASSPHRASE
+        /// or PHASE_IDENTITY_PASSPHRASE_FD)
+        #[arg(long)]
+        passphrase_file: Option<PathBuf>,
+    },
+}
+
+/// `--path`, or the platform identity path.
+fn identity_path_or_default(path: Option<PathBuf>) -> Result<PathBuf> {
+    match path {
+        Some(p) => Ok(p),
+        None => Ok(default_identity_path()?),
+    }
+}
+
+/// `--passphrase-file`, or the passphrase from the environment.
+fn passphrase_from(file: Option<PathBuf>) -> Result<Passphrase> {
+    if let Some(file) = file {
+        return Ok(Passphrase::read_from(&file)?);
+    }
+    Passphrase::from_env()?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "No passphrase: pass --passphrase-file or set {PASSPHRASE_ENV} or {PASSPHRASE_FD_ENV}"
+        )
+    })
+}
+
 #[derive(Subcommand)]
 enum ProviderCommands {
     /// Show provider status
@@ -127,6 +214,69 @@ enum ProviderCommands {
         #[arg(short, long, default_value = "http://localhost:8080")]
         addr: String,
     },
+    /// Sweep orphaned outboards and evict unpinned blobs over a quota
+    Gc {
+        /// Artifacts directory (default: the platform artifacts path)
+        #[arg(short = 'a', long)]
+        artifacts: Option<PathBuf>,
+
+        /// Evict least recently used unpinned blobs until the blob bucket
+        /// fits this size (e.g. 500M, 20G). Without it nothing is evicted.
+        #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+        quota: Option<u64>,
+
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Keep a blob through garbage collection
+    Pin {
+        /// Blob id (SHA-256 hex)
+        blob_id: String,
+
+        /// Artifacts directory (default: the platform artifacts path)
+        #[arg(short = 'a', long)]
+        artifacts: Option<PathBuf>,
+    },
+    /// Let garbage collection evict a pinned blob again
+    Unpin {
+        /// Blob id (SHA-256 hex)
+        blob_id: String,
+
+        /// Artifacts directory (default: the platform artifacts path)
+        #[arg(short = 'a', long)]
+        artifacts: Option<PathBuf>,
+    },
+}
+
+/// Open the artifact store at `--artifacts`, or the platform default.
+fn open_store(artifacts: Option<PathBuf>) -> Result<ArtifactStore> {
+    ArtifactStore::new(artifacts.unwrap_or_else(|| ProviderConfig::default().artifacts_dir))
+}
+
+fn parse_blob_id(hex: &str) -> Result<BlobId> {
+    BlobId::from_hex(hex).ok_or_else(|| anyhow::anyhow!("not a SHA-256 hex blob id: {hex}"))
+}
+
+/// `1048576`, `512K`, `500M`, `20G`, `1T` (binary units; a trailing `B`
+/// or `iB` is accepted).
+fn parse_byte_size(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let upper = trimmed.to_ascii_uppercase();
+    let number = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (digits, shift) = match number.chars().last() {
+        Some('K') => (&number[..number.len() - 1], 10),
+        Some('M') => (&number[..number.len() - 1], 20),
+        Some('G') => (&number[..number.len() - 1], 30),
+        Some('T') => (&number[..number.len() - 1], 40),
+        _ => (number, 0),
+    };
+    digits
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1u64 << shift))
+        .ok_or_else(|| format!("invalid size {trimmed:?}; use bytes or a K/M/G/T suffix"))
 }
 
 #[tokio::main]
@@ -159,6 +309,8 @@ async fn main() -> Result<()> {
             println!("  - listen_addrs: Addresses to listen on");
             println!("  - peer_addrs: Peers to connect to on startup");
             println!("  - bootstrap_peers: DHT bootstrap nodes");
+            println!("  - network_key_file: Private-network key (swarm.key)");
+            println!("  - allow_peers / deny_peers: Peer ID allowlist or denylist");
 
             Ok(())
         }
@@ -199,6 +351,13 @@ async fn main() -> Result<()> {
             // Create discovery configuration
             let disc_config = DiscoveryConfig {
                 identity: Some(node_identity),
+                state_dir: Some(state_dir_for_identity(&identity_path)),
+                network_key: cfg
+                    .network_key_file
+                    .as_deref()
+                    .map(NetworkKey::load)
+                    .transpose()?,
+                peer_filter: PeerFilter::from_lists(&cfg.allow_peers, &cfg.deny_peers)?,
                 ..DiscoveryConfig::default()
             };
 
@@ -215,7 +374,8 @@ async fn main() -> Result<()> {
             // Bootstrap DHT
             discovery.bootstrap().await?;
 
-            // Advertise this node's capabilities
+            // Advertise this node's capabilities; the discovery driver
+            // republishes them as measurements come in.
             discovery.advertise_capabilities().await?;
 
             info!("Phase daemon started. Peer ID: {}", discovery.local_peer_id());
@@ -319,7 +479,36 @@ async fn main() -> Result<()> {
             bind,
             no_dht,
             no_mdns,
+            operators,
+            blob_quota,
+            tls_cert,
+            tls_key,
+            tls_self_signed,
         } => {
+            // A self-signed certificate is issued over a key derived from
+            // the node identity, which signs the manifests that pin it. The
+            // identity's own secret is never written here.
+            let (tls, identity) = if tls_self_signed {
+                let identity_path = default_identity_path()?;
+                let identity = NodeIdentity::load_or_create(&identity_path)?;
+                let dir = state_dir_for_identity(&identity_path).join("tls");
+                let tls = TlsConfig {
+                    cert_path: dir.join("cert.pem"),
+                    key_path: dir.join("key.pem"),
+                };
+                let mut names = vec!["localhost".to_string()];
+                if bind != "0.0.0.0" && bind != "::" {
+                    names.push(bind.clone());
+                }
+                SelfSignedCert::generate(&identity, names)?.write(&tls)?;
+                (Some(tls), Some(identity))
+            } else {
+                let tls = tls_cert
+                    .zip(tls_key)
+                    .map(|(cert_path, key_path)| TlsConfig { cert_path, key_path });
+                (tls, None)
+            };
+
             // Build provider config
             let config = ProviderConfig {
                 enabled: true,
@@ -372,24 +561,75 @@ async fn main() -> Result<()> {
                         std::env::consts::ARCH.to_string()
                     }
                 }),
+                operators,
+                operator_successions: Vec::new(),
+                blob_quota_bytes: blob_quota,
+                tls,
+            };
+
+            let server = ProviderServer::try_new(config.clone(), identity.as_ref())?;
+            let tls_pin = server.tls_spki_sha256();
+            let scheme = if config.tls.is_some() { "https" } else { "http" };
+
+            // Announce on the LAN for as long as the server runs
+            let mdns = if no_mdns {
+                None
+            } else {
+                let mut mdns_config = MdnsConfig::new(config.port, &config.channel, &config.arch);
+                if config.tls.is_some() {
+                    // Only a self-signed certificate's key stays put; a
+                    // CA-issued one may be renewed under a new key.
+                    let pin = tls_pin.clone().filter(|_| identity.is_some());
+                    mdns_config = mdns_config.with_tls(pin);
+                }
+                if let Some(peer_id) = server.peer_id() {
+                    mdns_config = mdns_config.with_peer_id(peer_id.to_string());
+                }
+                match MdnsAdvertiser::new(mdns_config) {
+                    Ok(advertiser) => Some(advertiser),
+                    Err(e) => {
+                        warn!("mDNS advertisement unavailable: {:#}", e);
+                        None
+                    }
+                }
             };
 
             // Display startup banner
             println!("╔══════════════════════════════════════════════╗");
             println!("║           Phase Boot Provider                ║");
             println!("╠══════════════════════════════════════════════╣");
-            println!("║ HTTP:     http://{}:{:<21} ║", config.bind_addr, config.port);
+            println!("║ HTTP:     {:<34} ║", format!("{}://{}:{}", scheme, config.bind_addr, config.port));
             println!("║ Artifacts: {:<33} ║", config.artifacts_dir.display().to_string().chars().take(33).collect::<String>());
             println!("║ Channel:  {:<34} ║", config.channel);
             println!("║ Arch:     {:<34} ║", config.arch);
             println!("║ DHT:      {:<34} ║", if no_dht { "disabled" } else { "enabled" });
-            println!("║ mDNS:     {:<34} ║", if no_mdns { "disabled" } else { "enabled" });
+            println!("║ mDNS:     {:<34} ║", match (&mdns, no_mdns) {
+                (Some(_), _) => "enabled",
+                (None, true) => "disabled",
+                (None, false) => "unavailable",
+            });
+            println!("║ Uploads:  {:<34} ║", match config.operators.len() {
+                0 => "disabled".to_string(),
+                n => format!("{} operator key(s)", n),
+            });
+            println!("║ Quota:    {:<34} ║", match config.blob_quota_bytes {
+                None => "unlimited".to_string(),
+                Some(bytes) => format!("{} bytes", bytes),
+            });
+            println!("║ TLS:      {:<34} ║", match (&tls_pin, identity.is_some()) {
+                (None, _) => "disabled",
+                (Some(_), true) => "self-signed (HTTP/2)",
+                (Some(_), false) => "certificate (HTTP/2)",
+            });
             println!("╚══════════════════════════════════════════════╝");
+            if let Some(pin) = &tls_pin {
+                println!("TLS SPKI SHA-256: {}", pin);
+            }
             println!();
 
-            // Create and run server
-            let server = ProviderServer::new(config);
+            // Run server
             server.run().await?;
+            drop(mdns);
 
             Ok(())
         }
@@ -424,6 +664,25 @@ async fn main() -> Result<()> {
                                     println!("Metrics:");
                                     println!("  Requests total:     {}", status["metrics"]["requests_total"].as_u64().unwrap_or(0));
                                     println!("  Bytes served total: {}", status["metrics"]["bytes_served_total"].as_u64().unwrap_or(0));
+                                    let storage = &status["storage"];
+                                    if let Some(buckets) = storage["buckets"].as_array() {
+                                        println!();
+                                        println!("Storage:");
+                                        for bucket in buckets {
+                                            println!(
+                                                "  {:<18} {} files, {} bytes",
+                                                format!("{}:", bucket["name"].as_str().unwrap_or("?")),
+                                                bucket["files"].as_u64().unwrap_or(0),
+                                                bucket["bytes"].as_u64().unwrap_or(0)
+                                            );
+                                        }
+                                        println!("  Blobs:             {} ({} bytes)", storage["blobs"].as_u64().unwrap_or(0), storage["blob_bytes"].as_u64().unwrap_or(0));
+                                        println!("  Pinned:            {} ({} bytes)", storage["pinned_blobs"].as_u64().unwrap_or(0), storage["pinned_bytes"].as_u64().unwrap_or(0));
+                                        match storage["quota_bytes"].as_u64() {
+                                            Some(quota) => println!("  Quota:             {} bytes", quota),
+                                            None => println!("  Quota:             unlimited"),
+                                        }
+                                    }
                                 }
                             } else {
                                 eprintln!("Error: Provider returned status {}", response.status());
@@ -487,8 +746,103 @@ async fn main() -> Result<()> {
 
                     Ok(())
                 }
+                ProviderCommands::Gc { artifacts, quota, json } => {
+                    let store = open_store(artifacts)?;
+                    let report = store.collect_garbage(quota)?;
+                    let usage = store.usage()?;
+
+                    if json {
+                        let output = serde_json::json!({ "gc": report, "usage": usage });
+                        println!("{}", serde_json::to_string_pretty(&output)?);
+                        return Ok(());
+                    }
+                    println!("Garbage collection in {}:", store.base_dir().display());
+                    println!("  Orphaned outboards removed: {}", report.orphans_removed);
+                    println!("  Blobs evicted:              {} ({} bytes)", report.evicted.len(), report.bytes_freed);
+                    for id in &report.evicted {
+                        println!("    {}", id);
+                    }
+                    if report.over_quota {
+                        println!("  Warning: pinned blobs alone exceed the quota");
+                    }
+                    println!();
+                    println!("Usage:");
+                    for bucket in &usage.buckets {
+                        println!("  {:<18} {} files, {} bytes", format!("{}:", bucket.name), bucket.files, bucket.bytes);
+                    }
+                    println!("  Blobs:             {} ({} bytes)", usage.blobs, usage.blob_bytes);
+                    println!("  Pinned:            {} ({} bytes)", usage.pinned_blobs, usage.pinned_bytes);
+                    Ok(())
+                }
+                ProviderCommands::Pin { blob_id, artifacts } => {
+                    let id = parse_blob_id(&blob_id)?;
+                    let store = open_store(artifacts)?;
+                    if store.pin_blob(&id)? {
+                        println!("Pinned {}", id);
+                    } else {
+                        println!("{} was already pinned", id);
+                    }
+                    if store.get_blob_path(&id).is_none() {
+                        println!("Note: the blob is not stored here yet; the pin keeps it once it is.");
+                    }
+                    Ok(())
+                }
+                ProviderCommands::Unpin { blob_id, artifacts } => {
+                    let id = parse_blob_id(&blob_id)?;
+                    let store = open_store(artifacts)?;
+                    if store.unpin_blob(&id)? {
+                        println!("Unpinned {}", id);
+                    } else {
+                        println!("{} was not pinned", id);
+                    }
+                    if store.pinned_blobs()?.contains(&id) {
+                        println!("Note: a channel manifest still refers to it, so it stays pinned.");
+                    }
+                    Ok(())
+                }
             }
         }
+        Commands::Identity { command } => match command {
+            IdentityCommands::Rotate { path } => {
+                let path = identity_path_or_default(path)?;
+                let previous = NodeIdentity::load(&path)?;
+                let (rotated, chain) = rotate_identity(&path)?;
+
+                println!("Rotated identity at {}", path.display());
+                println!("  Previous key: {}", hex::encode(previous.peer_id_bytes()));
+                println!("  New key:      {}", hex::encode(rotated.peer_id_bytes()));
+                println!("  Chain:        {} ({} rotations)", succession_path(&path).display(), chain.records().len());
+                println!();
+                println!("Peers that allowlist an earlier key keep trusting this node once they");
+                println!("add the chain below to submitter_successions:");
+                println!("{}", chain.to_hex());
+                println!();
+                println!("Restart the daemon to start using the new key.");
+                Ok(())
+            }
+            IdentityCommands::Encrypt { path, passphrase_file } => {
+                let path = identity_path_or_default(path)?;
+                if NodeIdentity::is_encrypted(&path)? {
+                    anyhow::bail!("{} is already encrypted", path.display());
+                }
+                let identity = NodeIdentity::load(&path)?;
+                identity.save_encrypted(&path, &passphrase_from(passphrase_file)?)?;
+                println!("Encrypted identity at {}", path.display());
+                println!("Daemons now need {PASSPHRASE_ENV} or {PASSPHRASE_FD_ENV} to start.");
+                Ok(())
+            }
+            IdentityCommands::Decrypt { path, passphrase_file } => {
+                let path = identity_path_or_default(path)?;
+                if !NodeIdentity::is_encrypted(&path)? {
+                    anyhow::bail!("{} is not encrypted", path.display());
+                }
+                let identity =
+                    NodeIdentity::load_with_passphrase(&path, &passphrase_from(passphrase_file)?)?;
+                identity.save(&path)?;
+                println!("Decrypted identity at {}", path.display());
+                Ok(())
+            }
+        },
         Commands::Version => {
             println!("plasmd version {}", env!("CARGO_PKG_VERSION"));
             println!("Phase Open MVP - Local WASM Execution");
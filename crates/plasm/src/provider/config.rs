@@ -1,3 +1,4 @@
+use phase_artifact_server::TlsConfig;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -27,6 +28,24 @@ pub struct ProviderConfig {
     /// Target architecture (e.g., "x86_64", "aarch64")
     #[serde(default = "default_arch")]
     pub arch: String,
+
+    /// Hex public keys allowed to upload and delete artifacts over HTTP.
+    /// Empty keeps the provider read-only.
+    #[serde(default)]
+    pub operators: Vec<String>,
+
+    /// Succession chains that extend `operators` to rotated keys.
+    #[serde(default)]
+    pub operator_successions: Vec<String>,
+
+    /// Byte budget for cached blobs; unpinned ones are evicted past it.
+    /// `None` never evicts.
+    #[serde(default)]
+    pub blob_quota_bytes: Option<u64>,
+
+    /// Certificate and key to serve HTTPS with. `None` serves plain HTTP.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
 }
 
 fn default_bind_addr() -> String {
@@ -103,6 +122,10 @@ impl Default for ProviderConfig {
             artifacts_dir: default_artifacts_dir(),
             channel: default_channel(),
             arch: default_arch(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         }
     }
 }
@@ -4,11 +4,13 @@
 //! in the artifact store, with optional signing support.
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use ed25519_dalek::SigningKey;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use phase_artifact_server::ArtifactStore;
-use super::manifest::{ArtifactInfo, BootManifest, ManifestBuilder};
+use phase_artifact_server::{ArtifactStore, ReloadingCert};
+use super::manifest::{ArtifactInfo, BootManifest, ManifestBuilder, ProviderInfo};
 use super::signing::sign_manifest;
 
 /// Generates boot manifests from available artifacts
@@ -17,6 +19,10 @@ pub struct ManifestGenerator {
     artifacts: Arc<ArtifactStore>,
     signing_key: Option<SigningKey>,
     default_version: String,
+    provider: Option<ProviderInfo>,
+    tls_cert: Option<Arc<ReloadingCert>>,
+    /// Last manifest handed out per (channel, arch), reused while current
+    issued: Mutex<HashMap<(String, String), BootManifest>>,
 }
 
 impl ManifestGenerator {
@@ -31,6 +37,9 @@ impl ManifestGenerator {
             artifacts,
             signing_key,
             default_version: env!("CARGO_PKG_VERSION").to_string(),
+            provider: None,
+            tls_cert: None,
+            issued: Mutex::new(HashMap::new()),
         }
     }
 
@@ -40,6 +49,21 @@ impl ManifestGenerator {
         self
     }
 
+    /// Name the serving provider in generated manifests
+    pub fn with_provider(mut self, provider: ProviderInfo) -> Self {
+        self.provider = Some(provider);
+        self
+    }
+
+    /// Pin `cert` in the provider information of generated manifests
+    ///
+    /// The pin is read at generation time, so it follows certificate
+    /// reloads. Has no effect without [`Self::with_provider`].
+    pub fn with_tls_pin(mut self, cert: Arc<ReloadingCert>) -> Self {
+        self.tls_cert = Some(cert);
+        self
+    }
+
     /// Generate manifest for channel/arch from available artifacts
     ///
     /// This scans the artifact store and creates a manifest listing all
@@ -77,6 +101,14 @@ impl ManifestGenerator {
             builder = builder.artifact(artifact_name, artifact_info);
         }
 
+        if let Some(provider) = &self.provider {
+            let mut provider = provider.clone();
+            if let Some(cert) = &self.tls_cert {
+                provider.tls_spki_sha256 = Some(cert.spki_sha256());
+            }
+            builder = builder.provider(provider);
+        }
+
         builder
             .build()
             .context("Failed to build manifest")
@@ -87,6 +119,12 @@ impl ManifestGenerator {
     /// This generates a manifest and signs it if a signing key is available.
     /// If no signing key is configured, returns an unsigned manifest.
     ///
+    /// The previous manifest for the same channel/arch is returned again,
+    /// timestamps and signature included, while its artifacts, version and
+    /// provider are unchanged and less than half its validity has passed.
+    /// Repeated requests then get identical bytes, which clients can
+    /// revalidate by ETag instead of downloading.
+    ///
     /// # Arguments
     ///
     /// * `channel` - Release channel
@@ -94,14 +132,35 @@ impl ManifestGenerator {
     pub fn generate_signed(&self, channel: &str, arch: &str) -> Result<BootManifest> {
         let mut manifest = self.generate(channel, arch)?;
 
+        let key = (channel.to_string(), arch.to_string());
+        let mut issued = self.issued.lock().expect("manifest cache poisoned");
+        if let Some(previous) = issued.get(&key) {
+            if Self::still_current(previous, &manifest) {
+                return Ok(previous.clone());
+            }
+        }
+
         if let Some(ref key) = self.signing_key {
             sign_manifest(&mut manifest, key)
                 .context("Failed to sign manifest")?;
         }
 
+        issued.insert(key, manifest.clone());
         Ok(manifest)
     }
 
+    /// Whether `previous` can stand in for the freshly generated `current`
+    fn still_current(previous: &BootManifest, current: &BootManifest) -> bool {
+        let parse = |t: &str| DateTime::parse_from_rfc3339(t).map(|t| t.with_timezone(&Utc));
+        let (Ok(created), Ok(expires)) = (parse(&previous.created_at), parse(&previous.expires_at)) else {
+            return false;
+        };
+        previous.version == current.version
+            && previous.artifacts == current.artifacts
+            && previous.provider == current.provider
+            && Utc::now() < created + (expires - created) / 2
+    }
+
     /// Normalize artifact filename to canonical name
     ///
     /// Maps common artifact filenames to standard names:
@@ -219,4 +278,56 @@ mod tests {
         let manifest = generator.generate("stable", "arm64").unwrap();
         assert_eq!(manifest.version, "1.2.3");
     }
+
+    #[test]
+    fn test_provider_tls_pin_is_signed() {
+        use crate::provider::signing::{generate_signing_key, verify_manifest_signature};
+        use phase_artifact_server::{SelfSignedCert, TlsConfig};
+        use phase_identity::NodeIdentity;
+
+        let (temp, store) = setup_test_artifacts();
+        let tls = TlsConfig {
+            cert_path: temp.path().join("cert.pem"),
+            key_path: temp.path().join("key.pem"),
+        };
+        SelfSignedCert::generate(&NodeIdentity::generate(), vec!["localhost".to_string()])
+            .unwrap()
+            .write(&tls)
+            .unwrap();
+        let cert = Arc::new(ReloadingCert::load(tls).unwrap());
+        let signing_key = generate_signing_key();
+        let generator = ManifestGenerator::new(store, Some(signing_key.clone()))
+            .with_provider(ProviderInfo {
+                peer_id: "12D3KooTest".to_string(),
+                addresses: Vec::new(),
+                tls_spki_sha256: None,
+            })
+            .with_tls_pin(Arc::clone(&cert));
+
+        let mut manifest = generator.generate_signed("stable", "arm64").unwrap();
+        let provider = manifest.provider.clone().unwrap();
+        assert_eq!(provider.tls_spki_sha256, Some(cert.spki_sha256()));
+        assert!(verify_manifest_signature(&manifest, &signing_key.verifying_key()).unwrap());
+
+        // Swapping the pin breaks the signature.
+        manifest.provider.as_mut().unwrap().tls_spki_sha256 = Some("00".repeat(32));
+        assert!(!verify_manifest_signature(&manifest, &signing_key.verifying_key()).unwrap());
+    }
+
+    #[test]
+    fn test_signed_manifest_is_reused_until_artifacts_change() {
+        use crate::provider::signing::generate_signing_key;
+
+        let (temp, store) = setup_test_artifacts();
+        let generator = ManifestGenerator::new(store, Some(generate_signing_key()));
+
+        let first = generator.generate_signed("stable", "arm64").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(generator.generate_signed("stable", "arm64").unwrap(), first);
+
+        fs::write(temp.path().join("stable/arm64/kernel"), b"a newer test kernel").unwrap();
+        let second = generator.generate_signed("stable", "arm64").unwrap();
+        assert_ne!(second.artifacts, first.artifacts);
+        assert_ne!(second.signatures, first.signatures);
+    }
 }
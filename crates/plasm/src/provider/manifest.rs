@@ -249,6 +249,13 @@ pub struct ProviderInfo {
 
     /// Multiaddresses where this provider can be reached
     pub addresses: Vec<String>,
+
+    /// Hex SHA-256 of the public key in the provider's TLS certificate.
+    /// Set when it serves HTTPS with a self-signed certificate: covered
+    /// by the manifest signature, it lets clients pin the certificate
+    /// instead of trusting a CA.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_spki_sha256: Option<String>,
 }
 
 /// Builder for creating BootManifest instances
@@ -526,6 +533,7 @@ mod tests {
         let provider = ProviderInfo {
             peer_id: "12D3KooTest".to_string(),
             addresses: vec!["/ip4/127.0.0.1/tcp/4001".to_string()],
+            tls_spki_sha256: None,
         };
 
         let manifest = ManifestBuilder::new("stable".to_string(), "arm64".to_string())
@@ -59,13 +59,21 @@ pub use phase_artifact_server::{
     ManifestProvider,
     ManifestRecord,
     MdnsAdvertiser,
+    MdnsBrowser,
     MdnsConfig,
+    MdnsEvent,
+    MdnsProvider,
     MetricsSnapshot,
     ProviderMetrics,
+    ReloadingCert,
+    SelfSignedCert,
     ServerHandle,
+    TlsConfig,
     DEFAULT_MANIFEST_TTL,
     MANIFEST_REFRESH_INTERVAL,
     MDNS_SERVICE_TYPE,
+    TXT_PEER_ID,
+    pinned_http_client,
 };
 
 mod server_adapter;
@@ -11,13 +11,17 @@
 //! boundary tests against `plasm::provider::ProviderServer` keep passing.
 
 use anyhow::Result;
+use libp2p::identity::{ed25519, PublicKey};
+use libp2p::PeerId;
 use phase_artifact_server::{
     ArtifactServer, ArtifactServerConfig, ManifestProvider,
 };
+use phase_identity::NodeIdentity;
 use std::sync::Arc;
 
 use super::config::ProviderConfig;
 use super::generator::ManifestGenerator;
+use super::manifest::ProviderInfo;
 
 /// Pre-M6-compatible HTTP provider server.
 ///
@@ -26,28 +30,57 @@ use super::generator::ManifestGenerator;
 /// `run` surface the daemon (and the boundary tests) historically used.
 pub struct ProviderServer {
     inner: ArtifactServer,
+    peer_id: Option<PeerId>,
 }
 
 impl ProviderServer {
     /// Build a new provider server from the daemon-side [`ProviderConfig`].
     /// The channel/arch defaults from the config become the default channel
     /// + default arch the inner artifact server reports on `/manifest.json`.
+    ///
+    /// Panics if the artifacts directory or TLS certificate can't be
+    /// loaded; see [`Self::try_new`].
     pub fn new(config: ProviderConfig) -> Self {
+        Self::try_new(config, None).expect("create artifact server")
+    }
+
+    /// Like [`Self::new`], but returns load errors. With an `identity`,
+    /// manifests are signed by the node key and name this node as their
+    /// provider, along with the TLS certificate's pin when serving HTTPS.
+    pub fn try_new(config: ProviderConfig, identity: Option<&NodeIdentity>) -> Result<Self> {
         let inner_config = ArtifactServerConfig {
             bind_addr: config.bind_addr.clone(),
             port: config.port,
             artifacts_dir: config.artifacts_dir.clone(),
+            operators: config.operators.clone(),
+            operator_successions: config.operator_successions.clone(),
+            blob_quota_bytes: config.blob_quota_bytes,
+            tls: config.tls.clone(),
         };
 
         // The artifact store inside ArtifactServer owns its own copy of the
         // root dir. We construct another ArtifactStore here so the manifest
         // generator can read it; both stores share the same on-disk layout
         // and hash cache eviction is acceptable across them.
-        let inner = ArtifactServer::new(inner_config).expect("create artifact server");
+        let inner = ArtifactServer::new(inner_config)?;
         let store = inner.store().clone();
 
+        let peer_id = identity.map(peer_id_of).transpose()?;
+        let mut generator =
+            ManifestGenerator::new(store, identity.map(|id| id.signing_key().clone()));
+        if let Some(peer_id) = peer_id {
+            generator = generator.with_provider(ProviderInfo {
+                peer_id: peer_id.to_string(),
+                addresses: Vec::new(),
+                tls_spki_sha256: None,
+            });
+            if let Some(cert) = inner.tls() {
+                generator = generator.with_tls_pin(Arc::clone(cert));
+            }
+        }
+
         let provider: Arc<dyn ManifestProvider> = Arc::new(BootManifestProvider {
-            generator: Arc::new(ManifestGenerator::new(store, None)),
+            generator: Arc::new(generator),
             default_channel: config.channel.clone(),
             default_arch: config.arch.clone(),
         });
@@ -57,7 +90,17 @@ impl ProviderServer {
             .with_info_version(env!("CARGO_PKG_VERSION").to_string())
             .with_manifest_provider(provider);
 
-        Self { inner }
+        Ok(Self { inner, peer_id })
+    }
+
+    /// Hex SHA-256 of the TLS certificate's public key, when serving HTTPS.
+    pub fn tls_spki_sha256(&self) -> Option<String> {
+        self.inner.tls().map(|cert| cert.spki_sha256())
+    }
+
+    /// Peer ID of the identity that signs the manifests, if any.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        self.peer_id
     }
 
     /// Run the underlying HTTP server. Blocks until the listener errors or
@@ -67,6 +110,12 @@ impl ProviderServer {
     }
 }
 
+/// The libp2p peer ID of `identity`'s key.
+fn peer_id_of(identity: &NodeIdentity) -> Result<PeerId> {
+    let key = ed25519::PublicKey::try_from_bytes(&identity.peer_id_bytes())?;
+    Ok(PeerId::from(PublicKey::from(key)))
+}
+
 /// Implementation of [`ManifestProvider`] backed by the BootManifest-
 /// specific [`ManifestGenerator`]. Bridges between the generic JSON Value
 /// the trait talks in and the typed `BootManifest` the generator returns.
@@ -64,6 +64,9 @@ const DEFAULT_MAX_MEMORY: u64 = 128 * 1024 * 1024;
 pub struct WorkerSecurityConfig {
     /// Lowercase-hex Ed25519 pubkeys authorized to submit WASM jobs.
     pub authorized_submitters: Vec<String>,
+    /// Hex succession chains for submitters that rotated their key; a
+    /// successor of an allowlisted key is authorized too.
+    pub submitter_successions: Vec<String>,
     /// INSECURE escape hatch: accept any verified manifest. Local dev only.
     pub allow_unauthenticated: bool,
     /// Hard server-side ceiling on `max_memory_bytes` (clamps the manifest).
@@ -76,6 +79,7 @@ impl Default for WorkerSecurityConfig {
     fn default() -> Self {
         Self {
             authorized_submitters: Vec::new(),
+            submitter_successions: Vec::new(),
             allow_unauthenticated: false,
             max_memory_bytes: DEFAULT_MAX_MEMORY,
             max_duration: DEFAULT_MAX_DURATION,
@@ -95,9 +99,11 @@ impl WorkerSecurityConfig {
         if self.allow_unauthenticated {
             return true;
         }
-        self.authorized_submitters
-            .iter()
-            .any(|k| k.eq_ignore_ascii_case(pubkey_hex))
+        phase_identity::allowlist_permits(
+            &self.authorized_submitters,
+            &self.submitter_successions,
+            pubkey_hex,
+        )
     }
 }
 
@@ -476,6 +482,30 @@ mod tests {
         assert!(stream.next().await.is_some(), "stream should produce events");
     }
 
+    #[tokio::test]
+    async fn sec01_rotated_submitter_is_accepted_via_succession() {
+        // The allowlist names the client's old key; after a rotation the
+        // chain carries that authorization to the new one.
+        let node = NodeIdentity::generate();
+        let old = NodeIdentity::generate();
+        let mut chain = phase_identity::SuccessionChain::default();
+        let rotated = chain.rotate(&old).unwrap();
+
+        let unaware = allowlisted_worker(&node, &old);
+        assert!(unaware.execute(build_job(&rotated, tiny_wasm())).await.is_err());
+
+        let worker = WasmtimeWorker::new(node).with_security(WorkerSecurityConfig {
+            authorized_submitters: vec![hex::encode(old.verifying_key().to_bytes())],
+            submitter_successions: vec![chain.to_hex()],
+            ..WorkerSecurityConfig::default()
+        });
+        let (_handle, mut stream) = worker
+            .execute(build_job(&rotated, tiny_wasm()))
+            .await
+            .expect("successor of an allowlisted signer must be accepted");
+        assert!(stream.next().await.is_some(), "stream should produce events");
+    }
+
     #[tokio::test]
     async fn sec01_max_memory_is_clamped_to_operator_ceiling() {
         // A manifest claiming max_memory_bytes = u64::MAX must NOT be honored;
@@ -487,6 +517,7 @@ mod tests {
         let ceiling = 32 * 1024 * 1024;
         let worker = WasmtimeWorker::new(node).with_security(WorkerSecurityConfig {
             authorized_submitters: vec![hex::encode(client.verifying_key().to_bytes())],
+            submitter_successions: Vec::new(),
             allow_unauthenticated: false,
             max_memory_bytes: ceiling,
             max_duration: Duration::from_secs(1),
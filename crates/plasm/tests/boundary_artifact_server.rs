@@ -45,6 +45,10 @@ async fn artifact_server_e2e_range() {
             artifacts_dir: temp.path().to_path_buf(),
             channel: "stable".to_string(),
             arch: "x86_64".to_string(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         };
 
         let server = ProviderServer::new(config);
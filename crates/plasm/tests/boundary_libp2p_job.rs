@@ -44,6 +44,7 @@ fn make_node(port_hint: u16) -> Option<Discovery> {
         // Ephemeral identity is fine here: this boundary test exercises
         // libp2p job dispatch, not identity persistence.
         identity: None,
+        ..DiscoveryConfig::default()
     };
     match Discovery::new(config) {
         Ok(d) => Some(d),
@@ -77,6 +77,10 @@ async fn manifest_sign_serialize_fetch_verify() {
             artifacts_dir: temp.path().to_path_buf(),
             channel: "stable".to_string(),
             arch: "x86_64".to_string(),
+            operators: Vec::new(),
+            operator_successions: Vec::new(),
+            blob_quota_bytes: None,
+            tls: None,
         };
         let server = ProviderServer::new(config);
         let server_handle = tokio::spawn(async move {